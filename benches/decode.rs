@@ -0,0 +1,149 @@
+// benches/decode.rs
+//! Frame decoding throughput during a gift storm
+//!
+//! Run with `cargo bench --bench decode`. Compares the previous copying decode
+//! path with `protocol::decode` and a reused, streaming `protocol::Decoder` on
+//! zlib bundles of SEND_GIFT messages. Full decoding is dominated by building the JSON values,
+//! so the framing section measures splitting a bundle into bodies on its own.
+
+use blivedm::protocol::{self, Decoder, HEADER_LEN, Operation};
+use serde_json::Value;
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const GIFTS_PER_BUNDLE: usize = 300;
+const FRAMES: usize = 2_000;
+
+fn server_packet(operation: u32, ver: u16, body: &[u8]) -> Vec<u8> {
+    let mut packet = protocol::make_raw_packet(body, Operation::HEARTBEAT);
+    packet[6..8].copy_from_slice(&ver.to_be_bytes());
+    packet[8..12].copy_from_slice(&operation.to_be_bytes());
+    packet
+}
+
+fn gift_storm_frame() -> Vec<u8> {
+    let mut bundle = Vec::new();
+    for i in 0..GIFTS_PER_BUNDLE {
+        let gift = serde_json::json!({
+            "cmd": "SEND_GIFT",
+            "data": {
+                "uname": format!("viewer{}", i),
+                "uid": 10_000 + i,
+                "giftName": "小心心",
+                "giftId": 30607,
+                "num": 1,
+                "coin_type": "silver",
+                "total_coin": 0,
+                "timestamp": 1_700_000_000 + i,
+            }
+        });
+        bundle.extend(server_packet(5, 0, gift.to_string().as_bytes()));
+    }
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+    encoder.write_all(&bundle).unwrap();
+    server_packet(5, 2, &encoder.finish().unwrap())
+}
+
+/// The decode path before the protocol core: owned frames, a fresh buffer per
+/// bundle, and each body copied into a String before parsing
+fn legacy_decode(resv: Vec<u8>, out: &mut Vec<Value>) {
+    let mut offset = 0;
+    while offset + HEADER_LEN <= resv.len() {
+        let head = protocol::get_msg_header(&resv[offset..offset + HEADER_LEN]);
+        let end = offset + head.pack_len as usize;
+        let body = &resv[offset + HEADER_LEN..end];
+        if head.ver == 2 {
+            legacy_decode(protocol::inflate(body).unwrap(), out);
+        } else {
+            let s = String::from_utf8(body.to_vec()).unwrap();
+            out.push(serde_json::from_str(s.as_str()).unwrap());
+        }
+        offset = end;
+    }
+}
+
+/// Previous framing: a fresh inflate buffer and an owned String per body
+fn legacy_bodies(frame: &[u8]) -> usize {
+    let mut total = 0;
+    for (_, body) in protocol::packets(frame) {
+        let inflated = protocol::inflate(body).unwrap();
+        for (_, inner) in protocol::packets(&inflated) {
+            total += String::from_utf8(inner.to_vec()).unwrap().len();
+        }
+    }
+    total
+}
+
+/// Borrowed framing over a reused inflate buffer
+fn borrowed_bodies(frame: &[u8], scratch: &mut Vec<u8>) -> usize {
+    let mut total = 0;
+    for (_, body) in protocol::packets(frame) {
+        scratch.clear();
+        protocol::inflate_into(body, scratch).unwrap();
+        for (_, inner) in protocol::packets(scratch) {
+            total += std::str::from_utf8(inner).unwrap().len();
+        }
+    }
+    total
+}
+
+fn report(name: &str, elapsed: Duration, messages: usize) {
+    let per_frame = elapsed / FRAMES as u32;
+    let rate = messages as f64 / elapsed.as_secs_f64();
+    println!(
+        "{:<16} {:>10.2?}/frame {:>12.0} msg/s",
+        name, per_frame, rate
+    );
+}
+
+fn main() {
+    let frame = gift_storm_frame();
+    println!(
+        "{} frames of {} gifts ({} bytes compressed), full decode",
+        FRAMES,
+        GIFTS_PER_BUNDLE,
+        frame.len()
+    );
+
+    let start = Instant::now();
+    let mut messages = 0;
+    for _ in 0..FRAMES {
+        let mut out = Vec::new();
+        legacy_decode(black_box(frame.clone()), &mut out);
+        messages += out.len();
+    }
+    report("legacy", start.elapsed(), messages);
+
+    let start = Instant::now();
+    let mut messages = 0;
+    for _ in 0..FRAMES {
+        messages += protocol::decode(black_box(&frame)).len();
+    }
+    report("decode", start.elapsed(), messages);
+
+    let start = Instant::now();
+    let mut messages = 0;
+    let mut decoder = Decoder::new();
+    let mut out = Vec::with_capacity(GIFTS_PER_BUNDLE);
+    for _ in 0..FRAMES {
+        out.clear();
+        decoder.decode_into(black_box(&frame), &mut out);
+        messages += out.len();
+    }
+    report("Decoder reused", start.elapsed(), messages);
+
+    println!("framing only (inflate and split into bodies)");
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        black_box(legacy_bodies(black_box(&frame)));
+    }
+    report("legacy", start.elapsed(), FRAMES * GIFTS_PER_BUNDLE);
+
+    let start = Instant::now();
+    let mut scratch = Vec::new();
+    for _ in 0..FRAMES {
+        black_box(borrowed_bodies(black_box(&frame), &mut scratch));
+    }
+    report("borrowed", start.elapsed(), FRAMES * GIFTS_PER_BUNDLE);
+}
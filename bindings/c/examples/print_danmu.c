@@ -0,0 +1,38 @@
+/* Print every message of a live room as JSON until Enter is pressed.
+ *
+ *   cargo build --release
+ *   cc examples/print_danmu.c -Iinclude -Ltarget/release -lblivedm_ffi -o print_danmu
+ *   LD_LIBRARY_PATH=target/release ./print_danmu 24779526
+ */
+#include <stdio.h>
+
+#include "blivedm.h"
+
+static void on_message(const char *json, void *user_data) {
+    (void)user_data;
+    printf("%s\n", json);
+    fflush(stdout);
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s <room_id> [cookies]\n", argv[0]);
+        return 1;
+    }
+
+    blivedm_client *client = blivedm_client_new(argv[1], argc > 2 ? argv[2] : NULL);
+    if (client == NULL) {
+        fprintf(stderr, "error: %s\n", blivedm_last_error());
+        return 1;
+    }
+    blivedm_client_set_callback(client, on_message, NULL);
+    if (blivedm_client_start(client) != 0) {
+        fprintf(stderr, "error: %s\n", blivedm_last_error());
+        blivedm_client_free(client);
+        return 1;
+    }
+
+    getchar();
+    blivedm_client_free(client);
+    return 0;
+}
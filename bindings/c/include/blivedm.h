@@ -0,0 +1,54 @@
+/* blivedm.h - C interface of the blivedm Bilibili live danmaku client
+ *
+ * Link against libblivedm_ffi (cdylib or staticlib) built from bindings/c.
+ * Messages are delivered as JSON objects with a "type" field naming the
+ * message kind ("danmu", "gift", ...; see "Message Types" in docs/client.md
+ * for the full list) and the fields of the matching message.
+ *
+ * Functions returning int return 0 on success and -1 on failure; call
+ * blivedm_last_error() on the same thread for a description.
+ */
+#ifndef BLIVEDM_H
+#define BLIVEDM_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct blivedm_client blivedm_client;
+
+/* Called on a background thread for every message. `json` is only valid
+ * during the call; copy it to keep it. */
+typedef void (*blivedm_message_cb)(const char *json, void *user_data);
+
+/* Create a client for `room_id`. `cookies` may be NULL to read them from
+ * the browser. Returns NULL on invalid arguments. */
+blivedm_client *blivedm_client_new(const char *room_id, const char *cookies);
+
+/* Set the message callback, replacing any previous one; NULL removes it.
+ * `user_data` is passed back to every call. */
+int blivedm_client_set_callback(blivedm_client *client, blivedm_message_cb callback,
+                                void *user_data);
+
+/* Connect and start delivering messages on background threads. */
+int blivedm_client_start(blivedm_client *client);
+
+/* Stop delivering messages, interrupting a receive blocked on the socket,
+ * and return once the background threads have exited. */
+void blivedm_client_stop(blivedm_client *client);
+
+/* Stop the client if needed and free it. NULL is ignored. */
+void blivedm_client_free(blivedm_client *client);
+
+/* Latest failure on this thread, or NULL. Valid until the next call into
+ * this library on the same thread. */
+const char *blivedm_last_error(void);
+
+/* Library version, e.g. "0.5.5". */
+const char *blivedm_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BLIVEDM_H */
@@ -0,0 +1,230 @@
+// bindings/c/src/lib.rs
+//! C ABI over the blivedm client, declared in `include/blivedm.h`
+//!
+//! Messages are handed to a C callback as NUL-terminated JSON strings in the
+//! form of `BiliMessage::to_json`. Functions returning `int` use 0 for success
+//! and -1 for failure; `blivedm_last_error` describes the latest failure on the
+//! calling thread.
+
+use blivedm::client::live_room::{Credentials, LiveRoom};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Callback receiving one message as JSON; the string is only valid during the call
+pub type MessageCallback = extern "C" fn(json: *const c_char, user_data: *mut c_void);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Registered callback and its opaque user data pointer
+#[derive(Clone, Copy)]
+struct Callback {
+    func: MessageCallback,
+    user_data: *mut c_void,
+}
+
+// The caller guarantees `user_data` may be used from the delivery thread
+unsafe impl Send for Callback {}
+
+/// Connected room of one `start` and the thread handing its messages to the callback
+struct Session {
+    room: LiveRoom,
+    running: Arc<AtomicBool>,
+    delivery: JoinHandle<()>,
+}
+
+/// Opaque client handle (`blivedm_client` in C)
+pub struct Client {
+    room_id: u64,
+    cookies: Option<String>,
+    callback: Arc<Mutex<Option<Callback>>>,
+    session: Option<Session>,
+}
+
+impl Client {
+    fn start(&mut self) -> Result<(), String> {
+        if self.session.is_some() {
+            return Ok(());
+        }
+        let credentials = match &self.cookies {
+            Some(cookies) => Credentials::Cookies(cookies.clone()),
+            None => Credentials::Browser,
+        };
+        let room = LiveRoom::open(self.room_id, credentials)?;
+
+        // Ends once the room is closed
+        let mut messages = room.subscribe("c callback");
+        let callback = Arc::clone(&self.callback);
+        let running = Arc::new(AtomicBool::new(true));
+        let delivering = Arc::clone(&running);
+        let delivery = thread::spawn(move || {
+            while let Some(batch) = messages.blocking_recv() {
+                for msg in batch.iter() {
+                    if !delivering.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let Some(callback) = callback.lock().ok().and_then(|callback| *callback) else {
+                        continue;
+                    };
+                    if let Ok(json) = CString::new(msg.to_json().to_string()) {
+                        (callback.func)(json.as_ptr(), callback.user_data);
+                    }
+                }
+            }
+        });
+
+        self.session = Some(Session {
+            room,
+            running,
+            delivery,
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        session.running.store(false, Ordering::SeqCst);
+        session.room.close();
+        // Stopping from inside the callback must not join the delivery thread itself
+        if session.delivery.thread().id() != thread::current().id() {
+            let _ = session.delivery.join();
+        }
+    }
+}
+
+/// Read an optional C string argument
+unsafe fn optional_str(value: *const c_char) -> Result<Option<String>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map(|s| (!s.is_empty()).then(|| s.to_string()))
+        .map_err(|_| "argument is not valid UTF-8".to_string())
+}
+
+/// Create a client for `room_id`; `cookies` may be NULL to read them from the browser.
+/// Returns NULL on invalid arguments.
+///
+/// # Safety
+/// `room_id` and `cookies` must be NULL or valid NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blivedm_client_new(
+    room_id: *const c_char,
+    cookies: *const c_char,
+) -> *mut Client {
+    let parsed = unsafe { optional_str(room_id) }
+        .and_then(|room_id| room_id.ok_or_else(|| "room_id is required".to_string()))
+        .and_then(|room_id| {
+            room_id
+                .parse::<u64>()
+                .map_err(|_| format!("room_id must be numeric, got {}", room_id))
+        })
+        .and_then(|room_id| Ok((room_id, unsafe { optional_str(cookies) }?)));
+    match parsed {
+        Ok((room_id, cookies)) => Box::into_raw(Box::new(Client {
+            room_id,
+            cookies,
+            callback: Arc::new(Mutex::new(None)),
+            session: None,
+        })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Set the message callback, replacing any previous one; NULL removes it.
+///
+/// # Safety
+/// `client` must come from `blivedm_client_new` and not be freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blivedm_client_set_callback(
+    client: *mut Client,
+    callback: Option<MessageCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(client) = (unsafe { client.as_mut() }) else {
+        set_last_error("client is NULL");
+        return -1;
+    };
+    match client.callback.lock() {
+        Ok(mut slot) => {
+            *slot = callback.map(|func| Callback { func, user_data });
+            0
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            -1
+        }
+    }
+}
+
+/// Connect and start delivering messages on background threads.
+///
+/// # Safety
+/// `client` must come from `blivedm_client_new` and not be freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blivedm_client_start(client: *mut Client) -> c_int {
+    let Some(client) = (unsafe { client.as_mut() }) else {
+        set_last_error("client is NULL");
+        return -1;
+    };
+    match client.start() {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
+}
+
+/// Stop delivering messages, interrupting a blocked receive, and return once
+/// the background threads have exited.
+///
+/// # Safety
+/// `client` must be NULL or come from `blivedm_client_new` and not be freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blivedm_client_stop(client: *mut Client) {
+    if let Some(client) = unsafe { client.as_mut() } {
+        client.stop();
+    }
+}
+
+/// Stop the client if needed and free it.
+///
+/// # Safety
+/// `client` must be NULL or come from `blivedm_client_new`; it must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blivedm_client_free(client: *mut Client) {
+    if !client.is_null() {
+        let mut client = unsafe { Box::from_raw(client) };
+        client.stop();
+    }
+}
+
+/// Description of the latest failure on this thread, or NULL. Valid until the next call
+/// into this library on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn blivedm_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Library version as a static string
+#[unsafe(no_mangle)]
+pub extern "C" fn blivedm_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
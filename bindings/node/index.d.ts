@@ -0,0 +1,229 @@
+// Type declarations for the blivedm Node.js addon
+
+export interface FanMedal {
+  name: string
+  level: number
+}
+
+export interface RankEntry {
+  rank: number
+  uid: number
+  name: string
+  score: number
+}
+
+export interface DanmuMessage {
+  type: 'danmu'
+  user: string
+  text: string
+  uid: number
+  guard_level: number
+  medal: FanMedal | null
+}
+
+export interface GiftMessage {
+  type: 'gift'
+  user: string
+  gift: string
+  num: string
+  gift_id: number
+  value: number
+  combo_id: string | null
+}
+
+export interface SuperChatMessage {
+  type: 'super_chat'
+  id: number
+  user: string
+  text: string
+  price: number
+  duration: number
+}
+
+export interface SuperChatDeleteMessage {
+  type: 'super_chat_delete'
+  ids: number[]
+}
+
+export interface GuardBuyMessage {
+  type: 'guard_buy'
+  user: string
+  guard_level: number
+  num: number
+  price: number
+}
+
+export interface OnlineRankCountMessage {
+  type: 'online_rank_count'
+  count: number
+  online_count: number
+}
+
+export interface OnlineRankMessage {
+  type: 'online_rank'
+  entries: RankEntry[]
+}
+
+export interface ComboSendMessage {
+  type: 'combo_send'
+  combo_id: string
+  user: string
+  uid: number
+  gift: string
+  gift_id: number
+  total_num: number
+  value: number
+}
+
+export interface DanmuAggregationMessage {
+  type: 'danmu_aggregation'
+  text: string
+  count: number
+}
+
+export interface LotteryWinner {
+  uid: number
+  name: string
+}
+
+export interface LotteryStartMessage {
+  type: 'lottery_start'
+  id: number
+  prize: string
+  prize_num: number
+  requirement: string
+  danmu: string
+  duration: number
+}
+
+export interface LotteryAwardMessage {
+  type: 'lottery_award'
+  id: number
+  prize: string
+  winners: LotteryWinner[]
+}
+
+export interface VoiceJoinListMessage {
+  type: 'voice_join_list'
+  apply_count: number
+}
+
+export interface VoiceJoinSwitchMessage {
+  type: 'voice_join_switch'
+  open: boolean
+}
+
+export interface VoiceJoinStatusMessage {
+  type: 'voice_join_status'
+  uid: number
+  user: string
+  connected: boolean
+}
+
+export interface EntryEffectMessage {
+  type: 'entry_effect'
+  uid: number
+  user: string
+  guard_level: number
+  text: string
+}
+
+export interface RoomChangeMessage {
+  type: 'room_change'
+  title: string
+  area_id: number
+  area_name: string
+  parent_area_name: string
+}
+
+export interface RoomInfoMessage {
+  type: 'room_info'
+  room_id: number
+  title: string
+  streamer: string
+  streamer_uid: number
+  area_id: number
+  area_name: string
+  parent_area_name: string
+  cover: string
+  live: boolean
+}
+
+export interface FollowerChangeMessage {
+  type: 'follower_change'
+  followers: number
+  followers_delta: number
+  fans_club: number
+  fans_club_delta: number
+}
+
+export interface StreamStartedMessage {
+  type: 'stream_started'
+  room_id: number
+  uid: number
+  streamer: string
+  title: string
+}
+
+export interface StreamEndedMessage {
+  type: 'stream_ended'
+  room_id: number
+  uid: number
+  streamer: string
+}
+
+export interface CustomMessage {
+  type: 'custom' | 'extension'
+  cmd: string
+  data: unknown
+}
+
+export interface RawMessage {
+  type: 'raw'
+  data: { cmd?: string; [key: string]: unknown }
+}
+
+export type LiveMessage =
+  | DanmuMessage
+  | GiftMessage
+  | SuperChatMessage
+  | SuperChatDeleteMessage
+  | GuardBuyMessage
+  | OnlineRankCountMessage
+  | OnlineRankMessage
+  | ComboSendMessage
+  | DanmuAggregationMessage
+  | LotteryStartMessage
+  | LotteryAwardMessage
+  | VoiceJoinListMessage
+  | VoiceJoinSwitchMessage
+  | VoiceJoinStatusMessage
+  | EntryEffectMessage
+  | RoomChangeMessage
+  | RoomInfoMessage
+  | FollowerChangeMessage
+  | StreamStartedMessage
+  | StreamEndedMessage
+  | CustomMessage
+  | RawMessage
+
+export class LiveClient implements AsyncIterable<LiveMessage> {
+  /** Client for `roomId`; without cookies they are read from the browser */
+  constructor(roomId: string, cookies?: string | null)
+  readonly roomId: string
+  readonly running: boolean
+  /** Connect and start receiving messages */
+  start(): Promise<void>
+  /** Next message, or null once the client is stopped */
+  next(): Promise<LiveMessage | null>
+  /**
+   * Stop receiving; pending and later `next()` calls resolve to null. Resolves
+   * once the background threads have exited, after which `start()` may be
+   * called again
+   */
+  stop(): Promise<void>
+  [Symbol.asyncIterator](): AsyncIterator<LiveMessage>
+}
+
+/** Parse a raw live message JSON string, or null if it is ignored */
+export function parseMessage(json: string): LiveMessage | null
@@ -0,0 +1,11 @@
+// Loads the native addon and makes LiveClient an async iterable of messages
+const native = require('./blivedm.node')
+
+native.LiveClient.prototype[Symbol.asyncIterator] = async function* () {
+  let message
+  while ((message = await this.next()) != null) {
+    yield message
+  }
+}
+
+module.exports = native
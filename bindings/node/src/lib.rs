@@ -0,0 +1,144 @@
+// bindings/node/src/lib.rs
+//! Node.js addon `blivedm`: the Rust client as an async iterable of messages
+//!
+//! ```js
+//! const { LiveClient } = require('blivedm')
+//!
+//! const client = new LiveClient('24779526')
+//! await client.start()
+//! for await (const msg of client) {
+//!   if (msg.type === 'danmu') console.log(`${msg.user}: ${msg.text}`)
+//! }
+//! ```
+//!
+//! `index.js` adds `Symbol.asyncIterator` on top of `next()`, and `index.d.ts`
+//! types the messages as a union discriminated by `type`.
+
+use blivedm::client::bus::MessageStream;
+use blivedm::client::live_room::{Credentials, LiveRoom};
+use futures::StreamExt;
+use futures::channel::oneshot;
+use futures::lock::Mutex as AsyncMutex;
+use napi::{Error, Result};
+use napi_derive::napi;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Client for one live room
+#[napi]
+pub struct LiveClient {
+    room_id: u64,
+    cookies: Option<String>,
+    messages: Arc<AsyncMutex<Option<MessageStream>>>,
+    /// Connected room of the current `start`; each start opens a new one
+    room: Mutex<Option<LiveRoom>>,
+}
+
+#[napi]
+impl LiveClient {
+    /// Create a client for `room_id`; without cookies they are read from the browser
+    #[napi(constructor)]
+    pub fn new(room_id: String, cookies: Option<String>) -> Result<Self> {
+        let room_id = room_id
+            .parse::<u64>()
+            .map_err(|_| Error::from_reason(format!("room_id must be numeric, got {}", room_id)))?;
+        Ok(Self {
+            room_id,
+            cookies,
+            messages: Arc::new(AsyncMutex::new(None)),
+            room: Mutex::new(None),
+        })
+    }
+
+    /// Connect and start receiving messages in background threads
+    #[napi]
+    pub async fn start(&self) -> Result<()> {
+        if self.running() {
+            return Ok(());
+        }
+
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let room_id = self.room_id;
+        let credentials = match &self.cookies {
+            Some(cookies) => Credentials::Cookies(cookies.clone()),
+            None => Credentials::Browser,
+        };
+        // Connecting does blocking HTTP and websocket setup; keep it off the event loop
+        thread::spawn(move || {
+            let _ = connected_tx.send(LiveRoom::open(room_id, credentials));
+        });
+        let opened = match connected_rx.await {
+            Ok(Ok(opened)) => opened,
+            Ok(Err(e)) => return Err(Error::from_reason(e)),
+            Err(_) => return Err(Error::from_reason("connect thread exited")),
+        };
+        // The guard is not `Send`, so it must be gone before the await below
+        let messages = {
+            let mut room = self
+                .room
+                .lock()
+                .map_err(|e| Error::from_reason(e.to_string()))?;
+            if room.is_some() {
+                // Started twice concurrently; drop this connection
+                return Ok(());
+            }
+            // Closing the room ends the stream, and with it iteration
+            let messages = opened.messages();
+            *room = Some(opened);
+            messages
+        };
+        *self.messages.lock().await = Some(messages);
+        Ok(())
+    }
+
+    /// Next message, or null once the client is stopped
+    #[napi(ts_return_type = "Promise<LiveMessage | null>")]
+    pub async fn next(&self) -> Result<Option<Value>> {
+        let mut messages = self.messages.lock().await;
+        let Some(stream) = messages.as_mut() else {
+            return Ok(None);
+        };
+        if !self.running() {
+            return Ok(None);
+        }
+        Ok(stream.next().await.map(|msg| msg.to_json()))
+    }
+
+    /// Stop receiving; resolves once the background threads have exited
+    #[napi]
+    pub async fn stop(&self) -> Result<()> {
+        let room = self.room.lock().ok().and_then(|mut room| room.take());
+        let Some(room) = room else {
+            return Ok(());
+        };
+        // Closing joins the room's threads; keep it off the event loop
+        let (stopped_tx, stopped_rx) = oneshot::channel();
+        thread::spawn(move || {
+            room.close();
+            let _ = stopped_tx.send(());
+        });
+        let _ = stopped_rx.await;
+        *self.messages.lock().await = None;
+        Ok(())
+    }
+
+    /// Whether the client has been started and not stopped
+    #[napi(getter)]
+    pub fn running(&self) -> bool {
+        self.room.lock().map(|room| room.is_some()).unwrap_or(false)
+    }
+
+    #[napi(getter)]
+    pub fn room_id(&self) -> String {
+        self.room_id.to_string()
+    }
+}
+
+/// Parse a raw live message JSON string, or null if it is ignored
+#[napi(ts_return_type = "LiveMessage | null")]
+pub fn parse_message(json: String) -> Result<Option<Value>> {
+    let value: Value =
+        serde_json::from_str(&json).map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(blivedm::protocol::handle(value).map(|msg| msg.to_json()))
+}
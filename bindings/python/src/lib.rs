@@ -0,0 +1,219 @@
+// bindings/python/src/lib.rs
+//! Python module `blivedm`: the Rust client with Python message callbacks
+//!
+//! ```python
+//! import blivedm
+//!
+//! client = blivedm.LiveClient("24779526")
+//! client.on_message(lambda msg: print(msg["type"], msg))
+//! client.start()
+//! ```
+
+use blivedm::client::live_room::{Credentials, LiveRoom};
+use blivedm::client::models::BiliMessage;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Convert a JSON value into the equivalent Python object
+fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => u.into_pyobject(py)?.into_any(),
+            (None, Some(i)) => i.into_pyobject(py)?.into_any(),
+            _ => n.as_f64().unwrap_or_default().into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => s.into_pyobject(py)?.into_any(),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| to_py(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, field) in fields {
+                dict.set_item(key, to_py(py, field)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+/// Message dict with a `type` key (`danmu`, `gift`, `super_chat`, ...) and the message fields
+fn message_to_py(py: Python<'_>, msg: &BiliMessage) -> PyResult<Py<PyAny>> {
+    to_py(py, &msg.to_json()).map(Bound::unbind)
+}
+
+/// Connected room of one `start` and the thread calling the callbacks, with
+/// its own stop flag so a restarted client never revives an earlier session
+struct Session {
+    room: LiveRoom,
+    running: Arc<AtomicBool>,
+    delivery: JoinHandle<()>,
+}
+
+impl Session {
+    fn stop(self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.room.close();
+        // Stopping from inside a callback must not join the delivery thread itself
+        if self.delivery.thread().id() != thread::current().id() {
+            let _ = self.delivery.join();
+        }
+    }
+}
+
+/// Client for one live room; callbacks run on a background thread holding the GIL
+#[pyclass]
+struct LiveClient {
+    room_id: u64,
+    cookies: Option<String>,
+    callbacks: Arc<Mutex<Vec<Py<PyAny>>>>,
+    session: Mutex<Option<Session>>,
+}
+
+#[pymethods]
+impl LiveClient {
+    /// Create a client for `room_id`; without cookies they are read from the browser
+    #[new]
+    #[pyo3(signature = (room_id, cookies = None))]
+    fn new(room_id: String, cookies: Option<String>) -> PyResult<Self> {
+        let room_id = room_id.parse::<u64>().map_err(|_| {
+            PyValueError::new_err(format!("room_id must be numeric, got {}", room_id))
+        })?;
+        Ok(Self {
+            room_id,
+            cookies,
+            callbacks: Arc::new(Mutex::new(Vec::new())),
+            session: Mutex::new(None),
+        })
+    }
+
+    /// Register a callable invoked with each message dict
+    fn on_message(&self, callback: Py<PyAny>) -> PyResult<()> {
+        self.callbacks
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?
+            .push(callback);
+        Ok(())
+    }
+
+    /// Connect and start receiving messages in background threads
+    fn start(&self, py: Python<'_>) -> PyResult<()> {
+        if self.running() {
+            return Ok(());
+        }
+
+        let credentials = match &self.cookies {
+            Some(cookies) => Credentials::Cookies(cookies.clone()),
+            None => Credentials::Browser,
+        };
+        let room_id = self.room_id;
+        // Connecting does blocking HTTP and websocket setup; let other Python threads run.
+        // The session lock is not held meanwhile, as another thread may need it to stop.
+        let room = py
+            .allow_threads(move || LiveRoom::open(room_id, credentials))
+            .map_err(PyRuntimeError::new_err)?;
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        if session.is_some() {
+            // Started concurrently from another thread; drop this connection
+            return Ok(());
+        }
+
+        // Ends once the room is closed
+        let mut messages = room.subscribe("python callbacks");
+        let callbacks = Arc::clone(&self.callbacks);
+        let running = Arc::new(AtomicBool::new(true));
+        let delivering = Arc::clone(&running);
+        let delivery = thread::spawn(move || {
+            while let Some(batch) = messages.blocking_recv() {
+                for msg in batch.iter() {
+                    if !delivering.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    Python::with_gil(|py| {
+                        let dict = match message_to_py(py, msg) {
+                            Ok(dict) => dict,
+                            Err(e) => return e.print(py),
+                        };
+                        let callbacks = match callbacks.lock() {
+                            Ok(callbacks) => callbacks
+                                .iter()
+                                .map(|callback| callback.clone_ref(py))
+                                .collect::<Vec<_>>(),
+                            Err(_) => return,
+                        };
+                        for callback in callbacks {
+                            if let Err(e) = callback.call1(py, (dict.clone_ref(py),)) {
+                                e.print(py);
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        *session = Some(Session {
+            room,
+            running,
+            delivery,
+        });
+        Ok(())
+    }
+
+    /// Stop receiving and wait for the background threads to exit
+    fn stop(&self, py: Python<'_>) {
+        let session = self
+            .session
+            .lock()
+            .ok()
+            .and_then(|mut session| session.take());
+        if let Some(session) = session {
+            // The delivery thread may be waiting for the GIL to run a callback
+            py.allow_threads(move || session.stop());
+        }
+    }
+
+    /// Whether the client has been started and not stopped
+    #[getter]
+    fn running(&self) -> bool {
+        self.session
+            .lock()
+            .map(|session| session.is_some())
+            .unwrap_or(false)
+    }
+
+    #[getter]
+    fn room_id(&self) -> String {
+        self.room_id.to_string()
+    }
+}
+
+/// Parse a raw live message JSON string into a message dict, or None if it is ignored
+#[pyfunction]
+fn parse_message(py: Python<'_>, json: &str) -> PyResult<Option<Py<PyAny>>> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    blivedm::client::websocket::handle(value)
+        .map(|msg| message_to_py(py, &msg))
+        .transpose()
+}
+
+#[pymodule]
+#[pyo3(name = "blivedm")]
+fn blivedm_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<LiveClient>()?;
+    m.add_function(wrap_pyfunction!(parse_message, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// bindings/wasm/src/lib.rs
+//! Browser build of the blivedm protocol core
+//!
+//! The packet framing and message parsing are the ones from `blivedm::protocol`;
+//! only the socket is the browser `WebSocket`. Fetching the danmaku server and
+//! auth token (`getDanmuInfo`) needs cookies and is blocked by CORS, so the page
+//! gets them from its own backend and passes them in.
+
+use blivedm::protocol::{self, Operation, Packet};
+use blivedm::session::Session;
+use serde_json::{Value, json};
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+/// Interval between heartbeats, in milliseconds
+const HEARTBEAT_INTERVAL_MS: i32 = 30_000;
+
+/// JSON form handed to JavaScript: a message object with a `type` field
+fn packet_to_json(packet: Packet) -> Option<Value> {
+    match packet {
+        Packet::Message(json) => protocol::handle(json).map(|msg| msg.to_json()),
+        Packet::HeartbeatReply { popularity } => Some(json!({
+            "type": "heartbeat_reply",
+            "popularity": popularity,
+        })),
+        Packet::AuthReply(reply) => Some(json!({
+            "type": "auth_reply",
+            "code": reply["code"],
+        })),
+    }
+}
+
+fn to_js(value: &Value) -> Result<JsValue, JsValue> {
+    js_sys::JSON::parse(&value.to_string())
+}
+
+/// Protocol session for an auth body, forcing zlib compression (protover 2),
+/// which the browser build can decode
+fn new_session(auth_body: &str) -> Result<Session, JsValue> {
+    let mut auth: Value = serde_json::from_str(auth_body)
+        .map_err(|e| JsValue::from_str(&format!("invalid auth body: {}", e)))?;
+    auth["protover"] = json!(2);
+    Ok(Session::new(auth.to_string()))
+}
+
+/// Encode the auth packet for `auth_body` (`{"uid", "roomid", "key", ...}`)
+#[wasm_bindgen(js_name = encodeAuth)]
+pub fn encode_auth(auth_body: &str) -> Result<Vec<u8>, JsValue> {
+    new_session(auth_body).map(|session| session.auth_frame())
+}
+
+/// Encode a heartbeat packet
+#[wasm_bindgen(js_name = encodeHeartbeat)]
+pub fn encode_heartbeat() -> Vec<u8> {
+    protocol::make_packet("{}", Operation::HEARTBEAT)
+}
+
+/// Decode one websocket frame into an array of message objects
+#[wasm_bindgen]
+pub fn decode(data: &[u8]) -> Result<js_sys::Array, JsValue> {
+    let messages = js_sys::Array::new();
+    for json in protocol::decode(data)
+        .into_iter()
+        .filter_map(packet_to_json)
+    {
+        messages.push(&to_js(&json)?);
+    }
+    Ok(messages)
+}
+
+/// Live room connection over the browser `WebSocket`
+#[wasm_bindgen]
+pub struct LiveConnection {
+    ws: WebSocket,
+    heartbeat: i32,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_heartbeat: Closure<dyn FnMut()>,
+}
+
+#[wasm_bindgen]
+impl LiveConnection {
+    /// Connect to `url` (e.g. `wss://host:443/sub`), authenticate with `auth_body`
+    /// and call `on_message` with each message object
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        url: &str,
+        auth_body: &str,
+        on_message: js_sys::Function,
+    ) -> Result<LiveConnection, JsValue> {
+        let mut session = new_session(auth_body)?;
+        let (auth, heartbeat_frame) = (session.auth_frame(), session.heartbeat_frame());
+        let ws = WebSocket::new(url)?;
+        ws.set_binary_type(BinaryType::Arraybuffer);
+
+        let open_ws = ws.clone();
+        let open_heartbeat = heartbeat_frame.clone();
+        let on_open = Closure::<dyn FnMut()>::new(move || {
+            let _ = open_ws.send_with_u8_array(&auth);
+            let _ = open_ws.send_with_u8_array(&open_heartbeat);
+        });
+        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() else {
+                return;
+            };
+            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+            // The session's decoder reuses its buffers across frames
+            for json in session.decode(&data).into_iter().filter_map(packet_to_json) {
+                if let Ok(message) = to_js(&json) {
+                    let _ = on_message.call1(&JsValue::NULL, &message);
+                }
+            }
+        });
+        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let heartbeat_ws = ws.clone();
+        let on_heartbeat = Closure::<dyn FnMut()>::new(move || {
+            if heartbeat_ws.ready_state() == WebSocket::OPEN {
+                let _ = heartbeat_ws.send_with_u8_array(&heartbeat_frame);
+            }
+        });
+        let heartbeat = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("no window"))?
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                on_heartbeat.as_ref().unchecked_ref(),
+                HEARTBEAT_INTERVAL_MS,
+            )?;
+
+        Ok(LiveConnection {
+            ws,
+            heartbeat,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_heartbeat: on_heartbeat,
+        })
+    }
+
+    /// Stop the heartbeat and close the socket
+    pub fn close(&self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.heartbeat);
+        }
+        self.ws.set_onmessage(None);
+        let _ = self.ws.close();
+    }
+}
+
+impl Drop for LiveConnection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
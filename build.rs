@@ -0,0 +1,13 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        // Use the bundled protoc so no system install is needed
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc");
+        // SAFETY: the build script is single-threaded
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/blivedm.proto"], &["proto"])
+            .expect("failed to compile proto/blivedm.proto");
+    }
+}
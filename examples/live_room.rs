@@ -0,0 +1,32 @@
+use blivedm::client::live_room::{Credentials, LiveRoom};
+use blivedm::client::models::BiliMessage;
+use futures::stream::StreamExt;
+use std::env;
+
+/// Answer `!ping` with `pong` in a room, using the browser login.
+///
+/// The room id is read from ROOM_ID, defaulting to 24779526.
+#[tokio::main]
+async fn main() {
+    let room_id = env::var("ROOM_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or(24779526);
+    let room = tokio::task::spawn_blocking(move || LiveRoom::open(room_id, Credentials::Browser))
+        .await
+        .unwrap()
+        .unwrap_or_else(|e| panic!("Failed to open room: {}", e));
+
+    let mut messages = room.messages();
+    while let Some(msg) = messages.next().await {
+        if let BiliMessage::Danmu { user, text, .. } = &msg {
+            println!("{}: {}", user, text);
+            if text == "!ping"
+                && let Err(e) = room.send_danmaku("pong").await
+            {
+                eprintln!("reply failed: {}", e);
+            }
+        }
+    }
+    room.close();
+}
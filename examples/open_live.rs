@@ -0,0 +1,77 @@
+use blivedm::client::models::BiliMessage;
+use blivedm::client::open_live::{OpenLiveClient, OpenLiveCredentials};
+use futures::channel::mpsc;
+use futures::stream::StreamExt;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Read messages through the open platform instead of browser cookies.
+///
+/// Requires BILI_ACCESS_KEY_ID, BILI_ACCESS_KEY_SECRET, BILI_APP_ID and the
+/// streamer identity code in BILI_CODE.
+#[tokio::main]
+async fn main() {
+    let var = |name: &str| env::var(name).unwrap_or_else(|_| panic!("{} is not set", name));
+    let credentials = OpenLiveCredentials {
+        access_key_id: var("BILI_ACCESS_KEY_ID"),
+        access_key_secret: var("BILI_ACCESS_KEY_SECRET"),
+        app_id: var("BILI_APP_ID")
+            .parse()
+            .expect("BILI_APP_ID must be a number"),
+        code: var("BILI_CODE"),
+    };
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let client = tokio::task::spawn_blocking(move || OpenLiveClient::connect(credentials, tx))
+        .await
+        .unwrap()
+        .unwrap_or_else(|e| panic!("Failed to start open live session: {}", e));
+    println!(
+        "Connected to room {} ({})",
+        client.session().room_id,
+        client.session().anchor_name
+    );
+
+    let client = Arc::new(Mutex::new(client));
+    let heartbeat_client = Arc::clone(&client);
+    thread::spawn(move || {
+        loop {
+            if let Ok(mut c) = heartbeat_client.lock() {
+                c.send_heart_beat();
+            }
+            thread::sleep(Duration::from_secs(20));
+        }
+    });
+
+    let recv_client = Arc::clone(&client);
+    thread::spawn(move || {
+        loop {
+            if let Ok(mut c) = recv_client.lock()
+                && let Err(e) = c.receive()
+            {
+                eprintln!("Receive error: {}", e);
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+
+    println!("Listening for messages...");
+    while let Some(msg) = rx.next().await {
+        match msg {
+            BiliMessage::Danmu { user, text, .. } => println!("Danmu: {}: {}", user, text),
+            BiliMessage::Gift {
+                user, gift, num, ..
+            } => {
+                println!("Gift: {} sent {} x{}", user, gift, num)
+            }
+            BiliMessage::SuperChat {
+                user, text, price, ..
+            } => println!("SuperChat: {} (¥{}): {}", user, price, text),
+            BiliMessage::Raw(json) => println!("Raw: {}", json),
+            _ => {}
+        }
+    }
+}
@@ -71,7 +71,7 @@ async fn main() {
     println!("Listening for messages...");
     while let Some(msg) = rx.next().await {
         match msg {
-            BiliMessage::Danmu { user, text } => {
+            BiliMessage::Danmu { user, text, .. } => {
                 println!("Danmu: {}: {}", user, text);
             }
             BiliMessage::Gift { user, gift, .. } => {
@@ -37,6 +37,7 @@ fn test_rest_api_mode() {
     let context = EventContext {
         cookies: None,
         room_id: 12345,
+        request_headers: None,
     };
     let mut scheduler = Scheduler::new(context);
 
@@ -51,10 +52,16 @@ fn test_rest_api_mode() {
         BiliMessage::Danmu {
             user: "观众1".to_string(),
             text: "REST API 模式测试".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         },
         BiliMessage::Danmu {
             user: "观众2".to_string(),
             text: "神经网络语音合成".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         },
     ];
 
@@ -72,6 +79,7 @@ fn test_command_mode() {
     let context = EventContext {
         cookies: None,
         room_id: 12345,
+        request_headers: None,
     };
     let mut scheduler = Scheduler::new(context);
 
@@ -98,10 +106,16 @@ fn test_command_mode() {
         BiliMessage::Danmu {
             user: "观众3".to_string(),
             text: "命令行模式测试".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         },
         BiliMessage::Danmu {
             user: "观众4".to_string(),
             text: "本地语音合成".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         },
     ];
 
@@ -0,0 +1,330 @@
+// src/client/accounts.rs
+//! Named credential profiles for switching between accounts
+//!
+//! Each profile keeps the cookies of one account under a name such as `work`
+//! or `alt`. The cookies live in the system keyring (Secret Service, macOS
+//! Keychain, Windows Credential Manager), one entry per profile; the profile
+//! file only lists the names. Files of earlier versions, which held the
+//! cookies themselves, are moved into the keyring when loaded.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the profile store in the configuration directory
+pub const ACCOUNTS_FILE: &str = "accounts.json";
+
+/// Keyring service the profiles are stored under, with the profile name as user
+pub const KEYRING_SERVICE: &str = "blivedm_rs";
+
+/// Stored login of one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    pub cookies: String,
+    /// Username at the time the profile was saved, for listing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u64>,
+}
+
+impl Account {
+    pub fn new(cookies: impl Into<String>) -> Self {
+        Self {
+            cookies: cookies.into(),
+            username: None,
+            uid: None,
+        }
+    }
+}
+
+/// Contents of the profile file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProfileFile {
+    Names(BTreeSet<String>),
+    /// Earlier format with the accounts in the file
+    Accounts(BTreeMap<String, Account>),
+}
+
+/// Where the accounts of the profiles are kept
+#[derive(Debug, Clone)]
+enum Secrets {
+    Keyring,
+    /// In memory, for tests without a keyring service
+    #[cfg(test)]
+    Memory(std::sync::Arc<std::sync::Mutex<BTreeMap<String, String>>>),
+}
+
+impl Secrets {
+    fn entry(name: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYRING_SERVICE, name)
+            .map_err(|e| format!("no keyring entry for account '{}': {}", name, e))
+    }
+
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        match self {
+            Secrets::Keyring => match Self::entry(name)?.get_password() {
+                Ok(secret) => Ok(Some(secret)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(format!(
+                    "failed to read account '{}' from the keyring: {}",
+                    name, e
+                )),
+            },
+            #[cfg(test)]
+            Secrets::Memory(secrets) => Ok(secrets.lock().unwrap().get(name).cloned()),
+        }
+    }
+
+    fn set(&self, name: &str, secret: &str) -> Result<(), String> {
+        match self {
+            Secrets::Keyring => Self::entry(name)?
+                .set_password(secret)
+                .map_err(|e| format!("failed to store account '{}' in the keyring: {}", name, e)),
+            #[cfg(test)]
+            Secrets::Memory(secrets) => {
+                secrets
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), secret.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        match self {
+            Secrets::Keyring => match Self::entry(name)?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(format!(
+                    "failed to delete account '{}' from the keyring: {}",
+                    name, e
+                )),
+            },
+            #[cfg(test)]
+            Secrets::Memory(secrets) => {
+                secrets.lock().unwrap().remove(name);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Profiles by name, listed in a file with their accounts in the keyring
+#[derive(Debug, Clone)]
+pub struct AccountStore {
+    path: PathBuf,
+    accounts: BTreeMap<String, Account>,
+    secrets: Secrets,
+    /// Profiles inserted or removed since loading, written by `save`
+    changed: BTreeSet<String>,
+}
+
+impl AccountStore {
+    /// Read the profiles listed in `path` from the keyring; a missing file is
+    /// an empty store
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        Self::load_with(path.into(), Secrets::Keyring)
+    }
+
+    fn load_with(path: PathBuf, secrets: Secrets) -> Result<Self, String> {
+        let profiles = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("invalid account file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                ProfileFile::Names(BTreeSet::new())
+            }
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        let mut store = Self {
+            path,
+            accounts: BTreeMap::new(),
+            secrets,
+            changed: BTreeSet::new(),
+        };
+        match profiles {
+            ProfileFile::Names(names) => {
+                for name in names {
+                    let Some(secret) = store.secrets.get(&name)? else {
+                        log::warn!("account '{}' is missing from the keyring", name);
+                        continue;
+                    };
+                    let account = serde_json::from_str(&secret)
+                        .map_err(|e| format!("invalid keyring entry of '{}': {}", name, e))?;
+                    store.accounts.insert(name, account);
+                }
+            }
+            ProfileFile::Accounts(accounts) => {
+                log::info!(
+                    "moving the cookies in {} into the system keyring",
+                    store.path.display()
+                );
+                store.changed.extend(accounts.keys().cloned());
+                store.accounts = accounts;
+                store.save()?;
+            }
+        }
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(name)
+    }
+
+    /// Profiles sorted by name
+    pub fn accounts(&self) -> impl Iterator<Item = (&str, &Account)> {
+        self.accounts
+            .iter()
+            .map(|(name, account)| (name.as_str(), account))
+    }
+
+    /// Add or replace the profile `name`; call `save` to keep it
+    pub fn insert(&mut self, name: &str, account: Account) {
+        self.accounts.insert(name.to_string(), account);
+        self.changed.insert(name.to_string());
+    }
+
+    /// Remove the profile `name`; call `save` to delete it from the keyring
+    pub fn remove(&mut self, name: &str) -> bool {
+        let removed = self.accounts.remove(name).is_some();
+        if removed {
+            self.changed.insert(name.to_string());
+        }
+        removed
+    }
+
+    /// Write the changed profiles to the keyring and the names to the file,
+    /// which is readable by its owner only
+    pub fn save(&mut self) -> Result<(), String> {
+        for name in std::mem::take(&mut self.changed) {
+            match self.accounts.get(&name) {
+                Some(account) => {
+                    let secret = serde_json::to_string(account)
+                        .map_err(|e| format!("failed to serialize account '{}': {}", name, e))?;
+                    self.secrets.set(&name, &secret)?;
+                }
+                None => self.secrets.delete(&name)?,
+            }
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        let names: Vec<&String> = self.accounts.keys().collect();
+        let text = serde_json::to_string_pretty(&names)
+            .map_err(|e| format!("failed to serialize accounts: {}", e))?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&self.path)
+            .map_err(|e| format!("failed to write {}: {}", self.path.display(), e))?;
+        // The mode above only applies when the file is created
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("failed to restrict {}: {}", self.path.display(), e))?;
+        }
+        file.write_all(text.as_bytes())
+            .map_err(|e| format!("failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "blivedm-accounts-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_account_store_round_trip() {
+        let path = temp_path("round-trip");
+        let keyring = Arc::new(Mutex::new(BTreeMap::new()));
+        let secrets = Secrets::Memory(Arc::clone(&keyring));
+
+        let mut store = AccountStore::load_with(path.clone(), secrets.clone()).unwrap();
+        assert_eq!(store.accounts().count(), 0);
+        store.insert("work", Account::new("SESSDATA=work"));
+        store.insert(
+            "alt",
+            Account {
+                cookies: "SESSDATA=alt".to_string(),
+                username: Some("alt user".to_string()),
+                uid: Some(2),
+            },
+        );
+        store.save().unwrap();
+
+        // Only the names reach the file
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("SESSDATA"));
+        assert_eq!(keyring.lock().unwrap().len(), 2);
+
+        let mut store = AccountStore::load_with(path.clone(), secrets.clone()).unwrap();
+        let names: Vec<_> = store.accounts().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["alt", "work"]);
+        assert_eq!(store.get("work").unwrap().cookies, "SESSDATA=work");
+        assert_eq!(store.get("alt").unwrap().uid, Some(2));
+
+        assert!(store.remove("work"));
+        assert!(!store.remove("work"));
+        store.save().unwrap();
+        assert!(!keyring.lock().unwrap().contains_key("work"));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(AccountStore::load_with(path.clone(), secrets).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_account_store_migrates_file_accounts() {
+        let path = temp_path("migrate");
+        fs::write(&path, r#"{"work": {"cookies": "SESSDATA=work"}}"#).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        let keyring = Arc::new(Mutex::new(BTreeMap::new()));
+
+        let store =
+            AccountStore::load_with(path.clone(), Secrets::Memory(Arc::clone(&keyring))).unwrap();
+        assert_eq!(store.get("work").unwrap().cookies, "SESSDATA=work");
+        assert!(keyring.lock().unwrap()["work"].contains("SESSDATA=work"));
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<String>>(&text).unwrap(),
+            vec!["work"]
+        );
+        // An existing file is restricted too
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
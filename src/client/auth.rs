@@ -1,16 +1,32 @@
 // src/client/auth.rs
 //! Authentication helpers for Bilibili live danmaku WebSocket client
 
+use crate::scheduler::EventContext;
 use md5;
 use reqwest::StatusCode;
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, REFERER};
 use serde::Deserialize;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Add browser cookie support
 #[cfg(feature = "browser_cookies")]
 use crate::browser_cookies;
 
+#[cfg(feature = "accounts")]
+pub use crate::accounts::{Account, AccountStore};
+pub use crate::login::{LoginStatus, SmsLogin, check_login, sms_login};
+
+/// Extract CSRF token from cookies string
+pub fn extract_csrf_token(cookies: &str) -> Option<String> {
+    for cookie in cookies.split(';') {
+        if let Some(token) = cookie.trim().strip_prefix("bili_jct=") {
+            return Some(token.to_string());
+        }
+    }
+    None
+}
+
 /// Get Bilibili cookies from browser (preferred, newest), then fallback to provided cookie string
 pub fn get_cookies_or_browser(provided_cookie: Option<&str>) -> Option<String> {
     #[cfg(feature = "browser_cookies")]
@@ -41,6 +57,82 @@ pub fn get_cookies_or_browser(provided_cookie: Option<&str>) -> Option<String> {
     None
 }
 
+/// Headers sent with the Bilibili HTTP API calls and the websocket upgrade
+#[derive(Debug, Clone, Default)]
+pub struct RequestHeaders {
+    /// Replaces the default `USER_AGENT`
+    pub user_agent: Option<String>,
+    /// Replaces the live room page sent as referer
+    pub referer: Option<String>,
+    /// Extra headers, overriding any header of the same name
+    pub extra: Vec<(String, String)>,
+}
+
+impl RequestHeaders {
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn with_referer(mut self, referer: impl Into<String>) -> Self {
+        self.referer = Some(referer.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra.push((name.into(), value.into()));
+        self
+    }
+
+    /// Validated header map, for `BiliLiveClientBuilder::request_headers` and
+    /// `EventContext::with_request_headers`
+    pub fn header_map(&self) -> Result<HeaderMap, String> {
+        let mut headers = HeaderMap::new();
+        let user_agent = self.user_agent.as_deref().unwrap_or(USER_AGENT);
+        let extra = self
+            .extra
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()));
+        let entries = std::iter::once(("user-agent", user_agent))
+            .chain(self.referer.as_deref().map(|referer| ("referer", referer)))
+            .chain(extra);
+        for (name, value) in entries {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("invalid header name {}: {}", name, e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| format!("invalid value for header {}: {}", name, e))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+}
+
+/// Set the default user agent unless the request already has one
+pub fn apply_request_headers(headers: &mut HeaderMap) {
+    headers
+        .entry(reqwest::header::USER_AGENT)
+        .or_insert(HeaderValue::from_static(USER_AGENT));
+}
+
+/// Set `custom` headers (from `RequestHeaders::header_map`) over a request's
+/// headers, then the default user agent if none was given
+pub fn apply_custom_headers(headers: &mut HeaderMap, custom: Option<&HeaderMap>) {
+    for (name, value) in custom.into_iter().flatten() {
+        headers.insert(name, value.clone());
+    }
+    apply_request_headers(headers);
+}
+
+/// Headers for a request on behalf of a live room page
+pub fn room_request_headers(room_id: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(referer) = HeaderValue::from_str(&format!("https://live.bilibili.com/{}", room_id)) {
+        headers.insert(REFERER, referer);
+    }
+    apply_request_headers(&mut headers);
+    headers
+}
+
 pub fn init_uid(headers: HeaderMap) -> (StatusCode, String) {
     let client = reqwest::blocking::Client::builder()
         .https_only(true)
@@ -48,7 +140,7 @@ pub fn init_uid(headers: HeaderMap) -> (StatusCode, String) {
         .unwrap();
 
     let mut request_headers = headers;
-    request_headers.insert("user-agent", USER_AGENT.parse().unwrap());
+    apply_request_headers(&mut request_headers);
 
     let response = client.get(UID_INIT_URL).headers(request_headers).send();
     log::debug!("init uid response: {:?}", response);
@@ -82,7 +174,7 @@ pub fn init_buvid(headers: HeaderMap) -> (StatusCode, String) {
         .unwrap();
 
     let mut request_headers = headers;
-    request_headers.insert("user-agent", USER_AGENT.parse().unwrap());
+    apply_request_headers(&mut request_headers);
 
     let response = client.get(BUVID_INIT_URL).headers(request_headers).send();
     let stat: StatusCode;
@@ -120,7 +212,7 @@ pub fn init_room(headers: HeaderMap, temp_room_id: &str) -> (StatusCode, String)
         .unwrap();
 
     let mut request_headers = headers;
-    request_headers.insert("user-agent", USER_AGENT.parse().unwrap());
+    apply_request_headers(&mut request_headers);
 
     let url = format!("{}?room_id={}", ROOM_INIT_URL, temp_room_id);
     let response = client.get(url).headers(request_headers).send();
@@ -146,7 +238,7 @@ pub fn init_host_server(headers: HeaderMap, room_id: u64) -> (StatusCode, String
         .unwrap();
 
     let mut request_headers = headers.clone();
-    request_headers.insert("user-agent", USER_AGENT.parse().unwrap());
+    apply_request_headers(&mut request_headers);
 
     // Get WBI keys for signing
     let wbi_keys = match get_wbi_keys(request_headers.clone()) {
@@ -188,6 +280,71 @@ pub fn init_host_server(headers: HeaderMap, room_id: u64) -> (StatusCode, String
     (stat, body)
 }
 
+/// POST a CSRF-protected form to a live API endpoint using the context cookies
+///
+/// Returns the `data` field of the response on success.
+pub(crate) async fn post_live_api(
+    url: &str,
+    mut form: Vec<(&str, String)>,
+    context: &EventContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let cookies = match &context.cookies {
+        Some(cookies) => cookies,
+        None => {
+            return Err("No cookies available for authenticated request".into());
+        }
+    };
+
+    let csrf_token = match extract_csrf_token(cookies) {
+        Some(token) => token,
+        None => {
+            return Err("Could not extract CSRF token from cookies".into());
+        }
+    };
+    form.push(("csrf_token", csrf_token.clone()));
+    form.push(("csrf", csrf_token));
+
+    let mut headers = room_request_headers(context.room_id);
+    apply_custom_headers(&mut headers, context.request_headers.as_ref());
+    headers.insert("Cookie", HeaderValue::from_str(cookies)?);
+
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    let response = http_client
+        .post(url)
+        .headers(headers)
+        .form(&form)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    log::debug!("{} response body: {}", url, body);
+    if !status.is_success() {
+        log::warn!("Live API request failed, status: {}", status);
+        return Err(format!("Request failed: {} - {}", status, body).into());
+    }
+
+    check_api_response(&body).map_err(|e| e.into())
+}
+
+/// Check the `code` field of a live API JSON response
+fn check_api_response(body: &str) -> Result<Value, String> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|e| format!("Invalid API response: {}", e))?;
+    match json["code"].as_i64() {
+        Some(0) => Ok(json["data"].clone()),
+        Some(code) => Err(format!(
+            "API error {}: {}",
+            code,
+            json["message"].as_str().unwrap_or("unknown error")
+        )),
+        None => Err("API response is missing the code field".to_string()),
+    }
+}
+
 // WBI signing constants and functions
 const MIXIN_KEY_ENC_TAB: [usize; 64] = [
     46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
@@ -277,7 +434,7 @@ fn get_wbi_keys(headers: HeaderMap) -> Result<(String, String), reqwest::Error>
         .unwrap();
 
     let mut request_headers = headers;
-    request_headers.insert("user-agent", USER_AGENT.parse().unwrap());
+    apply_request_headers(&mut request_headers);
 
     let response = client
         .get("https://api.bilibili.com/x/web-interface/nav")
@@ -315,6 +472,46 @@ mod tests {
         assert!(UID_INIT_URL.contains("bilibili.com"));
     }
 
+    #[test]
+    fn test_request_headers() {
+        let headers = RequestHeaders::default()
+            .with_referer("https://live.bilibili.com/")
+            .with_header("X-Extra", "1")
+            .with_header("User-Agent", "custom")
+            .header_map()
+            .unwrap();
+        assert_eq!(headers["user-agent"], "custom");
+        assert_eq!(headers["referer"], "https://live.bilibili.com/");
+        assert_eq!(headers["x-extra"], "1");
+
+        let invalid = RequestHeaders::default().with_header("bad name", "1");
+        assert!(invalid.header_map().is_err());
+
+        // Custom headers replace the room referer and the default user agent
+        let mut request = room_request_headers(1);
+        apply_custom_headers(&mut request, Some(&headers));
+        assert_eq!(request["user-agent"], "custom");
+        assert_eq!(request["referer"], "https://live.bilibili.com/");
+        let mut request = room_request_headers(1);
+        apply_custom_headers(&mut request, None);
+        assert_eq!(request["user-agent"], USER_AGENT);
+        assert_eq!(request["referer"], "https://live.bilibili.com/1");
+    }
+
+    #[test]
+    fn test_check_api_response() {
+        assert_eq!(
+            check_api_response(r#"{"code":0,"message":"0","data":{"id":1}}"#),
+            Ok(serde_json::json!({"id": 1}))
+        );
+
+        let err = check_api_response(r#"{"code":-403,"message":"permission denied"}"#);
+        assert_eq!(err, Err("API error -403: permission denied".to_string()));
+
+        assert!(check_api_response("not json").is_err());
+        assert!(check_api_response(r#"{"message":"0"}"#).is_err());
+    }
+
     #[test]
     fn test_take_filename() {
         assert_eq!(
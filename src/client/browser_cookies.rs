@@ -1,13 +1,55 @@
 // src/client/browser_cookies.rs
 //! Browser cookie reading functionality for automatic SESSDATA detection
-
+//!
+//! Chromium-based browsers encrypt cookie values. On Windows the AES-256-GCM
+//! key is kept in the `Local State` file of the browser, protected with DPAPI
+//! for the current user; values with the `v10` prefix are decrypted with it.
+//! The app-bound `v20` values of recent Chrome versions cannot be decrypted
+//! outside the browser.
+//!
+//! On macOS the values are AES-128-CBC encrypted with a key derived from the
+//! browser's `Safe Storage` password in the login Keychain. Reading it makes
+//! macOS ask whether to allow access, once per browser unless always allowed.
+
+use aes::cipher::{BlockDecryptMut, KeyIvInit, block_padding::Pkcs7};
+use base64::Engine;
 use chrono::{DateTime, TimeZone, Utc};
 use directories::UserDirs;
 use log::{debug, info, warn};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
 use sqlite::Connection;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Profile of Chromium-based browsers read unless another is chosen
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Cookie database inside a Chromium profile directory
+#[cfg(target_os = "windows")]
+const CHROMIUM_COOKIE_FILE: &str = "Network/Cookies";
+#[cfg(not(target_os = "windows"))]
+const CHROMIUM_COOKIE_FILE: &str = "Cookies";
+
+static CHROMIUM_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Read Chromium-based browsers from the profile directory `profile` (e.g.
+/// `Profile 1`, see `Browser::profiles`) from now on; `None` goes back to `Default`
+pub fn set_chromium_profile(profile: Option<&str>) {
+    if let Ok(mut current) = CHROMIUM_PROFILE.write() {
+        *current = profile.map(str::to_string);
+    }
+}
+
+/// Profile directory read from Chromium-based browsers
+pub fn chromium_profile() -> String {
+    CHROMIUM_PROFILE
+        .read()
+        .ok()
+        .and_then(|profile| profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
 
 #[derive(Debug, Clone)]
 pub struct Cookie {
@@ -35,22 +77,8 @@ impl Browser {
         let home_dir = user_dirs.home_dir();
 
         match self {
-            Browser::Chrome => {
-                #[cfg(target_os = "linux")]
-                {
-                    Some(home_dir.join(".config/google-chrome/Default/Cookies"))
-                }
-                #[cfg(target_os = "macos")]
-                {
-                    Some(home_dir.join("Library/Application Support/Google/Chrome/Default/Cookies"))
-                }
-                #[cfg(target_os = "windows")]
-                {
-                    Some(
-                        home_dir
-                            .join("AppData/Local/Google/Chrome/User Data/Default/Network/Cookies"),
-                    )
-                }
+            Browser::Chrome | Browser::Edge | Browser::Chromium => {
+                self.get_profile_cookie_db_path(&chromium_profile())
             }
             Browser::Firefox => {
                 #[cfg(target_os = "linux")]
@@ -77,39 +105,6 @@ impl Browser {
                     Self::find_firefox_profile_cookies(&firefox_dir)
                 }
             }
-            Browser::Edge => {
-                #[cfg(target_os = "linux")]
-                {
-                    Some(home_dir.join(".config/microsoft-edge/Default/Cookies"))
-                }
-                #[cfg(target_os = "macos")]
-                {
-                    Some(
-                        home_dir.join("Library/Application Support/Microsoft Edge/Default/Cookies"),
-                    )
-                }
-                #[cfg(target_os = "windows")]
-                {
-                    Some(
-                        home_dir
-                            .join("AppData/Local/Microsoft/Edge/User Data/Default/Network/Cookies"),
-                    )
-                }
-            }
-            Browser::Chromium => {
-                #[cfg(target_os = "linux")]
-                {
-                    Some(home_dir.join(".config/chromium/Default/Cookies"))
-                }
-                #[cfg(target_os = "macos")]
-                {
-                    Some(home_dir.join("Library/Application Support/Chromium/Default/Cookies"))
-                }
-                #[cfg(target_os = "windows")]
-                {
-                    Some(home_dir.join("AppData/Local/Chromium/User Data/Default/Network/Cookies"))
-                }
-            }
             Browser::Opera => {
                 #[cfg(target_os = "linux")]
                 {
@@ -132,28 +127,101 @@ impl Browser {
         }
     }
 
+    /// Directory holding the profiles of a Chromium-based browser, `None` for
+    /// browsers without Chromium profiles
+    pub fn user_data_dir(&self) -> Option<PathBuf> {
+        let user_dirs = UserDirs::new()?;
+        let home_dir = user_dirs.home_dir();
+
+        #[cfg(target_os = "linux")]
+        let dir = match self {
+            Browser::Chrome => ".config/google-chrome",
+            Browser::Edge => ".config/microsoft-edge",
+            Browser::Chromium => ".config/chromium",
+            Browser::Firefox | Browser::Opera => return None,
+        };
+        #[cfg(target_os = "macos")]
+        let dir = match self {
+            Browser::Chrome => "Library/Application Support/Google/Chrome",
+            Browser::Edge => "Library/Application Support/Microsoft Edge",
+            Browser::Chromium => "Library/Application Support/Chromium",
+            Browser::Firefox | Browser::Opera => return None,
+        };
+        #[cfg(target_os = "windows")]
+        let dir = match self {
+            Browser::Chrome => "AppData/Local/Google/Chrome/User Data",
+            Browser::Edge => "AppData/Local/Microsoft/Edge/User Data",
+            Browser::Chromium => "AppData/Local/Chromium/User Data",
+            Browser::Firefox | Browser::Opera => return None,
+        };
+        Some(home_dir.join(dir))
+    }
+
+    /// Cookie database of the profile directory `profile`; browsers without
+    /// Chromium profiles have only their usual database
+    pub fn get_profile_cookie_db_path(&self, profile: &str) -> Option<PathBuf> {
+        match self {
+            Browser::Chrome | Browser::Edge | Browser::Chromium => Some(
+                self.user_data_dir()?
+                    .join(profile)
+                    .join(CHROMIUM_COOKIE_FILE),
+            ),
+            Browser::Firefox | Browser::Opera => self.get_cookie_db_path(),
+        }
+    }
+
+    /// Profiles with a cookie database (`Default`, `Profile 1`, ...), empty for
+    /// browsers without Chromium profiles
+    pub fn profiles(&self) -> Vec<String> {
+        self.user_data_dir()
+            .map(|dir| profile_names(&dir))
+            .unwrap_or_default()
+    }
+
+    /// Cookie database of the default Firefox profile: the one `installs.ini`
+    /// or `profiles.ini` names, else a `*.default-release` and then a
+    /// `*.default` profile directory
     fn find_firefox_profile_cookies(firefox_dir: &Path) -> Option<PathBuf> {
         if !firefox_dir.exists() {
             return None;
         }
 
-        // Look for the default profile directory
-        let entries = fs::read_dir(firefox_dir).ok()?;
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_dir() {
-                    let dir_name = path.file_name()?.to_str()?;
-                    if dir_name.contains(".default") || dir_name.contains(".default-release") {
-                        let cookies_path = path.join("cookies.sqlite");
-                        if cookies_path.exists() {
-                            return Some(cookies_path);
-                        }
+        // The ini files sit next to the profiles on Linux, and next to the
+        // `Profiles` directory on macOS and Windows
+        for ini_dir in [Some(firefox_dir), firefox_dir.parent()]
+            .into_iter()
+            .flatten()
+        {
+            for ini in ["installs.ini", "profiles.ini"] {
+                if let Ok(text) = fs::read_to_string(ini_dir.join(ini))
+                    && let Some(profile) = default_firefox_profile(&text)
+                {
+                    let cookies_path = ini_dir.join(profile).join("cookies.sqlite");
+                    if cookies_path.exists() {
+                        return Some(cookies_path);
                     }
                 }
             }
         }
-        None
+
+        let rank = |path: &Path| {
+            let name = path.file_name()?.to_str()?;
+            if name.ends_with(".default-release") {
+                Some(0)
+            } else if name.contains(".default") {
+                Some(1)
+            } else {
+                None
+            }
+        };
+        fs::read_dir(firefox_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join("cookies.sqlite"))
+            .filter(|cookies_path| cookies_path.exists())
+            .filter_map(|cookies_path| Some((rank(cookies_path.parent()?)?, cookies_path)))
+            .min()
+            .map(|(_, cookies_path)| cookies_path)
     }
 
     pub fn get_all_supported() -> Vec<Browser> {
@@ -167,6 +235,51 @@ impl Browser {
     }
 }
 
+/// Path of the default profile in a Firefox `installs.ini` or `profiles.ini`:
+/// the `Default` of an install section, else the profile with `Default=1`
+fn default_firefox_profile(ini: &str) -> Option<String> {
+    let mut sections: Vec<(&str, HashMap<&str, &str>)> = Vec::new();
+    for line in ini.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name, HashMap::new()));
+        } else if let Some((key, value)) = line.split_once('=')
+            && let Some((_, entries)) = sections.last_mut()
+        {
+            entries.insert(key.trim(), value.trim());
+        }
+    }
+    let install = sections.iter().find_map(|(name, entries)| {
+        let is_install = !name.starts_with("Profile") && *name != "General";
+        entries.get("Default").filter(|_| is_install)
+    });
+    let profile = sections.iter().find_map(|(name, entries)| {
+        let is_default = name.starts_with("Profile") && entries.get("Default") == Some(&"1");
+        entries.get("Path").filter(|_| is_default)
+    });
+    install.or(profile).map(|path| path.to_string())
+}
+
+/// Profile directories in a Chromium user data directory that hold a cookie
+/// database, `Default` first and the others in the order they were created
+fn profile_names(user_data_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(user_data_dir) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(CHROMIUM_COOKIE_FILE).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name == DEFAULT_PROFILE || name.starts_with("Profile "))
+        .collect();
+    profiles.sort_by_key(|name| {
+        let number = name
+            .strip_prefix("Profile ")
+            .and_then(|number| number.parse::<u32>().ok());
+        (name != DEFAULT_PROFILE, number, name.clone())
+    });
+    profiles
+}
+
 /// Read cookies from a browser's cookie database
 pub fn read_cookies_from_browser(
     browser: &Browser,
@@ -182,32 +295,240 @@ pub fn read_cookies_from_browser(
 
     debug!("Reading cookies from: {:?}", db_path);
 
-    // Create a temporary copy of the database since browsers might have it locked
+    // Create a temporary copy of the database since browsers might have it locked,
+    // with its write-ahead log holding the latest changes of a running Firefox
     let temp_path = std::env::temp_dir().join(format!("temp_cookies_{}.db", std::process::id()));
+    let temp_wal = wal_path(&temp_path);
     if let Err(e) = fs::copy(&db_path, &temp_path) {
         return Err(format!("Failed to copy cookie database: {}", e));
     }
+    let _ = fs::remove_file(&temp_wal);
+    if wal_path(&db_path).exists()
+        && let Err(e) = fs::copy(wal_path(&db_path), &temp_wal)
+    {
+        debug!("Failed to copy the write-ahead log of {:?}: {}", db_path, e);
+    }
 
     let result = match browser {
         Browser::Firefox => read_firefox_cookies(&temp_path, domain_filter),
-        _ => read_chromium_cookies(&temp_path, domain_filter),
+        _ => read_chromium_cookies(&temp_path, domain_filter, chromium_key(browser).as_ref()),
     };
 
-    // Clean up temporary file
+    // Clean up temporary files
     let _ = fs::remove_file(&temp_path);
+    let _ = fs::remove_file(&temp_wal);
 
     result
 }
 
+/// `<database>-wal`, the write-ahead log of an SQLite database
+fn wal_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push("-wal");
+    PathBuf::from(path)
+}
+
+/// Key of the encrypted cookie values of a Chromium-based browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+enum ChromiumKey {
+    /// AES-256-GCM key, on Windows
+    Gcm(Vec<u8>),
+    /// AES-128-CBC key, on macOS
+    Cbc([u8; 16]),
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+impl ChromiumKey {
+    /// Key derived from a `Safe Storage` password
+    fn from_password(password: &[u8]) -> Self {
+        let mut key = [0_u8; 16];
+        ring::pbkdf2::derive(
+            ring::pbkdf2::PBKDF2_HMAC_SHA1,
+            std::num::NonZeroU32::new(1003).unwrap(),
+            b"saltysalt",
+            password,
+            &mut key,
+        );
+        ChromiumKey::Cbc(key)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn chromium_key(browser: &Browser) -> Option<ChromiumKey> {
+    let local_state = fs::read_to_string(browser.user_data_dir()?.join("Local State")).ok()?;
+    let key = key_from_local_state(&local_state)?;
+    let key = dpapi::unprotect(&key);
+    if key.is_none() {
+        warn!("Failed to decrypt the cookie key of {:?}", browser);
+    }
+    key.map(ChromiumKey::Gcm)
+}
+
+#[cfg(target_os = "macos")]
+fn chromium_key(browser: &Browser) -> Option<ChromiumKey> {
+    let (service, account) = match browser {
+        Browser::Chrome => ("Chrome Safe Storage", "Chrome"),
+        Browser::Edge => ("Microsoft Edge Safe Storage", "Microsoft Edge"),
+        Browser::Chromium => ("Chromium Safe Storage", "Chromium"),
+        Browser::Opera => ("Opera Safe Storage", "Opera"),
+        Browser::Firefox => return None,
+    };
+    info!(
+        "Reading \"{}\" from the Keychain to decrypt the {:?} cookies; macOS may ask to allow it",
+        service, browser
+    );
+    let output = std::process::Command::new("security")
+        .args(["find-generic-password", "-w", "-s", service, "-a", account])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        warn!(
+            "No access to \"{}\" in the Keychain: {}",
+            service,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return None;
+    }
+    let password = String::from_utf8_lossy(&output.stdout);
+    Some(ChromiumKey::from_password(password.trim_end().as_bytes()))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn chromium_key(_browser: &Browser) -> Option<ChromiumKey> {
+    None
+}
+
+/// DPAPI-protected cookie key in the `Local State` JSON, without its `DPAPI` prefix
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn key_from_local_state(local_state: &str) -> Option<Vec<u8>> {
+    let local_state: serde_json::Value = serde_json::from_str(local_state).ok()?;
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(local_state["os_crypt"]["encrypted_key"].as_str()?)
+        .ok()?;
+    key.strip_prefix(b"DPAPI").map(<[u8]>::to_vec)
+}
+
+#[cfg(target_os = "windows")]
+mod dpapi {
+    use std::ffi::c_void;
+    use std::ptr;
+
+    #[repr(C)]
+    struct DataBlob {
+        len: u32,
+        data: *mut u8,
+    }
+
+    #[link(name = "crypt32")]
+    unsafe extern "system" {
+        fn CryptUnprotectData(
+            data_in: *const DataBlob,
+            description: *mut *mut u16,
+            entropy: *const DataBlob,
+            reserved: *mut c_void,
+            prompt: *const c_void,
+            flags: u32,
+            data_out: *mut DataBlob,
+        ) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn LocalFree(mem: *mut c_void) -> *mut c_void;
+    }
+
+    /// Decrypt data protected by DPAPI for the current user
+    pub fn unprotect(data: &[u8]) -> Option<Vec<u8>> {
+        let input = DataBlob {
+            len: u32::try_from(data.len()).ok()?,
+            data: data.as_ptr().cast_mut(),
+        };
+        let mut output = DataBlob {
+            len: 0,
+            data: ptr::null_mut(),
+        };
+        // SAFETY: `input` points to `data`, which outlives the call, and the
+        // output buffer is allocated by the call and freed with LocalFree
+        unsafe {
+            if CryptUnprotectData(
+                &input,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null(),
+                0,
+                &mut output,
+            ) == 0
+            {
+                return None;
+            }
+            let plain = std::slice::from_raw_parts(output.data, output.len as usize).to_vec();
+            LocalFree(output.data.cast());
+            Some(plain)
+        }
+    }
+}
+
+/// Bytes of an uppercase or lowercase hex string
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Decrypt a `v10` cookie value: AES-256-GCM with the nonce ahead of the
+/// ciphertext, or AES-128-CBC with an IV of spaces. Databases from version 24
+/// on prefix the plaintext with the SHA-256 of the cookie domain.
+fn decrypt_cookie_value(encrypted: &[u8], key: &ChromiumKey, db_version: u32) -> Option<String> {
+    let data = encrypted.strip_prefix(b"v10")?;
+    let plain = match key {
+        ChromiumKey::Gcm(key) => {
+            if data.len() < NONCE_LEN {
+                return None;
+            }
+            let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+            let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).ok()?);
+            let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+            let mut in_out = ciphertext.to_vec();
+            key.open_in_place(nonce, Aad::empty(), &mut in_out)
+                .ok()?
+                .to_vec()
+        }
+        ChromiumKey::Cbc(key) => cbc::Decryptor::<aes::Aes128>::new(key.into(), &[b' '; 16].into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .ok()?,
+    };
+    let plain = if db_version >= 24 {
+        plain.get(32..)?
+    } else {
+        &plain
+    };
+    String::from_utf8(plain.to_vec()).ok()
+}
+
+/// `version` in the `meta` table of a Chromium cookie database
+fn chromium_db_version(connection: &Connection) -> u32 {
+    let mut version = 0;
+    let _ = connection.iterate("SELECT value FROM meta WHERE key = 'version'", |pairs| {
+        version = pairs[0].1.and_then(|v| v.parse().ok()).unwrap_or(0);
+        true
+    });
+    version
+}
+
 fn read_chromium_cookies(
     db_path: &Path,
     domain_filter: Option<&str>,
+    key: Option<&ChromiumKey>,
 ) -> Result<Vec<Cookie>, String> {
     let connection =
         Connection::open(db_path).map_err(|e| format!("Failed to open cookie database: {}", e))?;
+    let db_version = chromium_db_version(&connection);
 
     let mut query =
-        "SELECT name, value, host_key, path, expires_utc, is_secure, is_httponly FROM cookies"
+        "SELECT name, value, hex(encrypted_value) AS encrypted_value, host_key, path, expires_utc, is_secure, is_httponly FROM cookies"
             .to_string();
 
     if let Some(domain) = domain_filter {
@@ -237,9 +558,26 @@ fn read_chromium_cookies(
                 None
             };
 
+            let name = cookie_data.get("name").unwrap_or(&"").to_string();
+            let mut value = cookie_data.get("value").unwrap_or(&"").to_string();
+            if value.is_empty()
+                && let Some(encrypted) = cookie_data
+                    .get("encrypted_value")
+                    .and_then(|hex| decode_hex(hex))
+                    .filter(|encrypted| !encrypted.is_empty())
+            {
+                match key.and_then(|key| decrypt_cookie_value(&encrypted, key, db_version)) {
+                    Some(decrypted) => value = decrypted,
+                    None => {
+                        debug!("Skipping cookie {} that could not be decrypted", name);
+                        return true;
+                    }
+                }
+            }
+
             let cookie = Cookie {
-                name: cookie_data.get("name").unwrap_or(&"").to_string(),
-                value: cookie_data.get("value").unwrap_or(&"").to_string(),
+                name,
+                value,
                 domain: cookie_data.get("host_key").unwrap_or(&"").to_string(),
                 path: cookie_data.get("path").unwrap_or(&"").to_string(),
                 expires,
@@ -306,6 +644,37 @@ fn read_firefox_cookies(
     Ok(cookies)
 }
 
+/// Cookies needed to act as a logged-in user: the session, the CSRF token
+/// and the device id
+pub const LOGIN_COOKIES: [&str; 3] = ["SESSDATA", "bili_jct", "buvid3"];
+
+/// Cookie header of the unexpired `LOGIN_COOKIES` among `cookies`, the latest
+/// expiring of each; `None` without a `SESSDATA`
+fn login_cookie_header(cookies: &[Cookie]) -> Option<String> {
+    let now = Utc::now();
+    let header: Vec<String> = LOGIN_COOKIES
+        .iter()
+        .filter_map(|name| {
+            cookies
+                .iter()
+                .filter(|c| c.name == *name && c.expires.is_none_or(|expires| expires > now))
+                .max_by_key(|c| c.expires)
+                .map(|c| format!("{}={}", c.name, c.value))
+        })
+        .collect();
+    header
+        .first()
+        .is_some_and(|first| first.starts_with("SESSDATA="))
+        .then(|| header.join("; "))
+}
+
+/// Login cookies of bilibili (`LOGIN_COOKIES`) in the default Firefox profile,
+/// as a cookie header
+pub fn find_firefox_bilibili_cookies() -> Result<String, String> {
+    let cookies = read_cookies_from_browser(&Browser::Firefox, Some("bilibili.com"))?;
+    login_cookie_header(&cookies).ok_or_else(|| "No SESSDATA cookie found in Firefox".to_string())
+}
+
 /// Find SESSDATA cookie from all supported browsers
 pub fn find_bilibili_cookies_as_string() -> Option<String> {
     let browsers = Browser::get_all_supported();
@@ -403,6 +772,157 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_chromium_profiles() {
+        let dir = std::env::temp_dir().join(format!("blivedm-profiles-{}", std::process::id()));
+        for profile in [
+            "Profile 10",
+            "Default",
+            "Profile 2",
+            "System Profile",
+            "Guest Profile",
+        ] {
+            let db = dir.join(profile).join(CHROMIUM_COOKIE_FILE);
+            fs::create_dir_all(db.parent().unwrap()).unwrap();
+            fs::write(db, "").unwrap();
+        }
+        fs::create_dir_all(dir.join("Profile 3")).unwrap();
+        assert_eq!(
+            profile_names(&dir),
+            vec!["Default", "Profile 2", "Profile 10"]
+        );
+        assert!(profile_names(&dir.join("missing")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(chromium_profile(), DEFAULT_PROFILE);
+        assert!(Browser::Firefox.profiles().is_empty());
+    }
+
+    #[test]
+    fn test_decrypt_cookie_value() {
+        let key = ChromiumKey::Gcm(vec![7_u8; 32]);
+        let nonce = [1_u8; NONCE_LEN];
+        let encrypt = |plain: &[u8]| {
+            let sealing = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[7_u8; 32]).unwrap());
+            let mut in_out = plain.to_vec();
+            sealing
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::empty(),
+                    &mut in_out,
+                )
+                .unwrap();
+            [b"v10".as_slice(), &nonce, &in_out].concat()
+        };
+
+        let encrypted = encrypt(b"sessdata");
+        assert_eq!(
+            decrypt_cookie_value(&encrypted, &key, 0).as_deref(),
+            Some("sessdata")
+        );
+        let other_key = ChromiumKey::Gcm(vec![8_u8; 32]);
+        assert_eq!(decrypt_cookie_value(&encrypted, &other_key, 0), None);
+        let with_domain_hash = encrypt(&[[0_u8; 32].as_slice(), b"sessdata"].concat());
+        assert_eq!(
+            decrypt_cookie_value(&with_domain_hash, &key, 24).as_deref(),
+            Some("sessdata")
+        );
+        assert_eq!(decrypt_cookie_value(b"v20abc", &key, 24), None);
+
+        // `printf sessdata | openssl enc -aes-128-cbc` with the key of "password"
+        let key = ChromiumKey::from_password(b"password");
+        let encrypted = [
+            b"v10".as_slice(),
+            &decode_hex("c0629cb61c32b731be0bd10e4e62d242").unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            decrypt_cookie_value(&encrypted, &key, 0).as_deref(),
+            Some("sessdata")
+        );
+
+        assert_eq!(decode_hex("763130ff"), Some(b"v10\xff".to_vec()));
+        assert_eq!(decode_hex("7"), None);
+        let local_state = r#"{"os_crypt":{"encrypted_key":"RFBBUElrZXk="}}"#;
+        assert_eq!(key_from_local_state(local_state), Some(b"key".to_vec()));
+        assert_eq!(key_from_local_state("{}"), None);
+    }
+
+    #[test]
+    fn test_firefox_profile_and_login_cookies() {
+        let profiles_ini = "\
+            [Install4F96D1932A9F858E]
+            Default=Profiles/abc.default-release
+            Locked=1
+
+            [Profile1]
+            Name=default
+            IsRelative=1
+            Path=Profiles/xyz.default
+            Default=1
+
+            [General]
+            StartWithLastProfile=1
+        ";
+        assert_eq!(
+            default_firefox_profile(profiles_ini).as_deref(),
+            Some("Profiles/abc.default-release")
+        );
+        let old_ini =
+            "[General]\nVersion=2\n[Profile0]\nPath=a.dev\n[Profile1]\nPath=b.default\nDefault=1\n";
+        assert_eq!(
+            default_firefox_profile(old_ini).as_deref(),
+            Some("b.default")
+        );
+        assert_eq!(default_firefox_profile("[General]\nVersion=2\n"), None);
+
+        let dir = std::env::temp_dir().join(format!("blivedm-firefox-{}", std::process::id()));
+        for profile in ["a.default", "b.default-release", "c.dev-edition-default"] {
+            fs::create_dir_all(dir.join(profile)).unwrap();
+            fs::write(dir.join(profile).join("cookies.sqlite"), "").unwrap();
+        }
+        assert_eq!(
+            Browser::find_firefox_profile_cookies(&dir),
+            Some(dir.join("b.default-release").join("cookies.sqlite"))
+        );
+        fs::write(
+            dir.join("profiles.ini"),
+            "[Profile0]\nPath=a.default\nDefault=1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Browser::find_firefox_profile_cookies(&dir),
+            Some(dir.join("a.default").join("cookies.sqlite"))
+        );
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(
+            wal_path(Path::new("/tmp/cookies.sqlite")),
+            Path::new("/tmp/cookies.sqlite-wal")
+        );
+
+        let cookie = |name: &str, value: &str, days: i64| Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: ".bilibili.com".to_string(),
+            path: "/".to_string(),
+            expires: Some(Utc::now() + chrono::Duration::days(days)),
+            secure: true,
+            http_only: false,
+        };
+        let cookies = [
+            cookie("buvid3", "device", 300),
+            cookie("SESSDATA", "old", -1),
+            cookie("SESSDATA", "sess", 100),
+            cookie("bili_jct", "csrf", 100),
+            cookie("sid", "other", 100),
+        ];
+        assert_eq!(
+            login_cookie_header(&cookies).as_deref(),
+            Some("SESSDATA=sess; bili_jct=csrf; buvid3=device")
+        );
+        assert_eq!(login_cookie_header(&cookies[..2]), None);
+    }
+
     #[test]
     fn test_find_sessdata() {
         // This test will only work if you have bilibili cookies in your browser
@@ -0,0 +1,277 @@
+// src/client/bus.rs
+//! Fan-out of received messages to any number of consumers
+//!
+//! The receive loop publishes each batch once; every `Subscriber` (the
+//! scheduler, a recorder, a relay server, ...) gets its own copy of the stream
+//! and reads at its own pace. A subscriber that falls more than the bus
+//! capacity behind skips the oldest batches instead of slowing the others
+//! down, and counts what it missed:
+//!
+//! ```ignore
+//! let bus = MessageBus::new(DEFAULT_CAPACITY);
+//! let mut recorder = bus.subscribe("recorder");
+//! thread::spawn(move || {
+//!     while let Some(batch) = recorder.blocking_recv() {
+//!         write_lines(&batch);
+//!     }
+//! });
+//! let scheduler_feed = bus.subscribe("scheduler");
+//! thread::spawn(move || bus::dispatch(scheduler_feed, scheduler));
+//! ```
+//!
+//! Async code can also read one message at a time as a `Stream`, and use the
+//! usual combinators:
+//!
+//! ```ignore
+//! let mut gifts = bus
+//!     .stream("gifts")
+//!     .filter(|msg| future::ready(msg.kind() == "gift"));
+//! while let Some(gift) = gifts.next().await {
+//!     thank(&gift).await;
+//! }
+//! ```
+
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::models::BiliMessage;
+use crate::scheduler::Scheduler;
+
+/// Batches a subscriber may fall behind by default before it skips some
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Messages published together, shared by all subscribers
+pub type Batch = Arc<[BiliMessage]>;
+
+/// Messages of a subscriber one at a time, see `Subscriber::into_stream`
+pub type MessageStream = BoxStream<'static, BiliMessage>;
+
+/// Broadcast channel of message batches; clones publish to the same subscribers
+#[derive(Debug, Clone)]
+pub struct MessageBus {
+    sender: broadcast::Sender<Batch>,
+    capacity: usize,
+}
+
+impl Default for MessageBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl MessageBus {
+    pub fn new(capacity: usize) -> Self {
+        // The channel rounds its capacity up to a power of two
+        let capacity = capacity.max(1).next_power_of_two();
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender, capacity }
+    }
+
+    /// Send a batch to every current subscriber, returning how many there are.
+    /// Empty batches and batches without subscribers are dropped.
+    pub fn publish(&self, msgs: Vec<BiliMessage>) -> usize {
+        if msgs.is_empty() {
+            return 0;
+        }
+        self.sender.send(msgs.into()).unwrap_or(0)
+    }
+
+    /// New subscriber receiving the batches published from now on; `name`
+    /// identifies it in lag warnings
+    pub fn subscribe(&self, name: &str) -> Subscriber {
+        Subscriber {
+            name: name.to_string(),
+            receiver: self.sender.subscribe(),
+            lagged: 0,
+        }
+    }
+
+    /// `subscribe` as a stream of single messages
+    pub fn stream(&self, name: &str) -> MessageStream {
+        self.subscribe(name).into_stream()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Batches not yet received by every subscriber
+    pub fn pending(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Batches a subscriber may fall behind by before it skips some
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// One consumer of a `MessageBus`
+#[derive(Debug)]
+pub struct Subscriber {
+    name: String,
+    receiver: broadcast::Receiver<Batch>,
+    lagged: u64,
+}
+
+impl Subscriber {
+    /// Next batch, or `None` once every `MessageBus` clone is dropped.
+    /// Batches skipped for lagging behind are logged and counted in `lagged`.
+    pub async fn recv(&mut self) -> Option<Batch> {
+        loop {
+            let result = self.receiver.recv().await;
+            if let Some(batch) = self.accept(result) {
+                return batch;
+            }
+        }
+    }
+
+    /// `recv` for threads outside the async runtime; panics inside it
+    pub fn blocking_recv(&mut self) -> Option<Batch> {
+        loop {
+            let result = self.receiver.blocking_recv();
+            if let Some(batch) = self.accept(result) {
+                return batch;
+            }
+        }
+    }
+
+    /// The result of a receive, or `None` to try again after a lag
+    fn accept(&mut self, result: Result<Batch, RecvError>) -> Option<Option<Batch>> {
+        match result {
+            Ok(batch) => Some(Some(batch)),
+            Err(RecvError::Closed) => Some(None),
+            Err(RecvError::Lagged(skipped)) => {
+                self.lagged += skipped;
+                log::warn!(
+                    "{} fell behind and skipped {} message batches",
+                    self.name,
+                    skipped
+                );
+                None
+            }
+        }
+    }
+
+    /// Stream of the messages of each batch in order, ending when the bus
+    /// closes. Lagging skips whole batches as with `recv`.
+    pub fn into_stream(self) -> MessageStream {
+        stream::unfold(
+            (self, VecDeque::new()),
+            |(mut subscriber, mut pending): (Self, VecDeque<BiliMessage>)| async move {
+                loop {
+                    if let Some(msg) = pending.pop_front() {
+                        return Some((msg, (subscriber, pending)));
+                    }
+                    let batch = subscriber.recv().await?;
+                    pending.extend(batch.iter().cloned());
+                }
+            },
+        )
+        .boxed()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Batches skipped so far for lagging behind
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
+
+/// Feed every batch of `subscriber` to `scheduler` until the bus closes.
+/// Blocks while the handlers run (a staged scheduler waits for every stage),
+/// so call it from a dedicated thread rather than an async task.
+pub fn dispatch(mut subscriber: Subscriber, scheduler: Arc<Mutex<Scheduler>>) {
+    while let Some(batch) = subscriber.blocking_recv() {
+        if let Ok(scheduler) = scheduler.lock() {
+            scheduler.trigger_batch(batch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn danmu(text: &str) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: text.to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_bus_subscribers_lag_independently() {
+        let bus = MessageBus::new(2);
+        assert_eq!(bus.publish(vec![danmu("nobody listens")]), 0);
+
+        let mut fast = bus.subscribe("fast");
+        let mut slow = bus.subscribe("slow");
+        assert_eq!(bus.subscriber_count(), 2);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            for i in 0..4 {
+                assert_eq!(bus.publish(vec![danmu(&i.to_string())]), 2);
+                assert_eq!(fast.recv().await.unwrap()[0], danmu(&i.to_string()));
+            }
+            // Only the last two batches are still buffered for the slow subscriber
+            assert_eq!((bus.pending(), bus.capacity()), (2, 2));
+            assert_eq!(slow.recv().await.unwrap()[0], danmu("2"));
+            assert_eq!(slow.lagged(), 2);
+            assert_eq!(fast.lagged(), 0);
+
+            drop(bus);
+            assert_eq!(slow.recv().await.unwrap()[0], danmu("3"));
+            assert!(slow.recv().await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_bus_dispatch_feeds_scheduler() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut scheduler = Scheduler::new(crate::scheduler::EventContext::new(None, 1));
+        let recorded = Arc::clone(&seen);
+        scheduler.on(Default::default(), move |msg, _| {
+            recorded.lock().unwrap().push(msg.clone());
+        });
+
+        let bus = MessageBus::default();
+        let subscriber = bus.subscribe("scheduler");
+        let scheduler = Arc::new(Mutex::new(scheduler));
+        let feed = std::thread::spawn(move || dispatch(subscriber, scheduler));
+        bus.publish(vec![danmu("a"), danmu("b")]);
+        bus.publish(vec![danmu("c")]);
+        drop(bus);
+
+        // Staged handlers have finished once the dispatch thread returns
+        feed.join().unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![danmu("a"), danmu("b"), danmu("c")]
+        );
+    }
+
+    #[test]
+    fn test_bus_stream_flattens_batches() {
+        let bus = MessageBus::default();
+        let stream = bus.stream("test");
+        bus.publish(vec![danmu("a"), danmu("b")]);
+        bus.publish(vec![danmu("c")]);
+        drop(bus);
+
+        let msgs: Vec<_> = futures::executor::block_on(stream.collect());
+        assert_eq!(msgs, vec![danmu("a"), danmu("b"), danmu("c")]);
+    }
+}
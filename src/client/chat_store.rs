@@ -0,0 +1,497 @@
+// src/client/chat_store.rs
+//! SQLite store of the messages of each user
+//!
+//! `ChatStoreHandler` records danmaku, super chats, gifts and guard purchases
+//! in a SQLite database, indexed by uid and by user name, for questions such
+//! as "everything uid X said", "when was X first seen" or "what did X gift".
+//! Gift messages carry no uid; asking by uid also covers the names that uid
+//! chatted under.
+
+use serde::Serialize;
+use sqlite::{ConnectionThreadSafe, State, Statement};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::gift_catalog::rmb_to_coins;
+use crate::http_server::{Request, Response};
+use crate::models::BiliMessage;
+use crate::scheduler::{EventContext, EventHandler, Subscription};
+
+/// File name of the store in the data directory
+pub const CHAT_STORE_FILE: &str = "chat.sqlite";
+
+/// Path prefix of the user routes, see `routes`
+pub const API_PREFIX: &str = "/api/users/";
+
+const API_LIMIT: usize = 20;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    text TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_uid ON messages (uid, timestamp);
+CREATE INDEX IF NOT EXISTS messages_user ON messages (user, timestamp);
+";
+
+/// Condition selecting the messages of `?1` as a uid (with the names it used)
+/// or `?2` as a name
+const USER_CONDITION: &str = "(uid = ?1 OR (uid = 0 AND user IN
+    (SELECT DISTINCT user FROM messages WHERE uid = ?1 AND ?1 != 0)) OR user = ?2)";
+
+const GIFT_KINDS: &str = "('gift', 'guard_buy', 'super_chat')";
+
+/// User to look up, by uid or by name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UserRef {
+    Uid(u64),
+    Name(String),
+}
+
+impl FromStr for UserRef {
+    type Err = String;
+
+    /// A number is a uid, anything else a name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty user".to_string());
+        }
+        Ok(s.parse()
+            .map(UserRef::Uid)
+            .unwrap_or_else(|_| UserRef::Name(s.to_string())))
+    }
+}
+
+impl fmt::Display for UserRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserRef::Uid(uid) => write!(f, "{}", uid),
+            UserRef::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Message of a user as stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoredMessage {
+    pub room_id: u64,
+    /// Sender uid, 0 when unknown
+    pub uid: u64,
+    pub user: String,
+    /// Message kind, as `BiliMessage::kind`
+    pub kind: String,
+    /// Danmaku or super chat text, or the gift or guard bought
+    pub text: String,
+    /// Value in gold coins (1000 = 1 CNY), 0 for danmaku and free gifts
+    pub value: u64,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+}
+
+impl StoredMessage {
+    /// The stored form of `msg`, for the kinds kept
+    pub fn from_message(msg: &BiliMessage, room_id: u64, timestamp: u64) -> Option<Self> {
+        let (uid, user, text, value) = match msg {
+            BiliMessage::Danmu {
+                user, text, uid, ..
+            } => (*uid, user, text.clone(), 0),
+            BiliMessage::SuperChat {
+                user, text, price, ..
+            } => (0, user, text.clone(), rmb_to_coins(*price)),
+            BiliMessage::Gift {
+                user,
+                gift,
+                num,
+                value,
+                ..
+            } => (0, user, format!("{} x{}", gift, num), *value),
+            BiliMessage::GuardBuy {
+                user,
+                guard_level,
+                num,
+                price,
+            } => (
+                0,
+                user,
+                format!("{} x{}", crate::models::guard_level_name(*guard_level), num),
+                *price,
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            room_id,
+            uid,
+            user: user.clone(),
+            kind: msg.kind().to_string(),
+            text,
+            value,
+            timestamp,
+        })
+    }
+
+    fn read(statement: &Statement) -> sqlite::Result<Self> {
+        Ok(Self {
+            room_id: statement.read::<i64, _>("room_id")? as u64,
+            uid: statement.read::<i64, _>("uid")? as u64,
+            user: statement.read("user")?,
+            kind: statement.read("kind")?,
+            text: statement.read("text")?,
+            value: statement.read::<i64, _>("value")? as u64,
+            timestamp: statement.read::<i64, _>("timestamp")? as u64,
+        })
+    }
+}
+
+/// Totals of a user over all stored messages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UserSummary {
+    /// Unix timestamp of the first stored message, `None` if there is none
+    pub first_seen: Option<u64>,
+    pub last_seen: Option<u64>,
+    /// Danmaku sent
+    pub danmu_count: u64,
+    /// Gifts, guards and super chats sent
+    pub gift_count: u64,
+    /// Value of the gifts, guards and super chats in gold coins
+    pub gift_value: u64,
+}
+
+/// Summary and latest messages and gifts of a user
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UserHistory {
+    pub summary: UserSummary,
+    /// Newest first
+    pub messages: Vec<StoredMessage>,
+    /// Newest first
+    pub gifts: Vec<StoredMessage>,
+}
+
+fn error(e: sqlite::Error) -> String {
+    format!("chat store error: {}", e)
+}
+
+/// SQLite database of user messages, usable from several threads
+pub struct ChatStore {
+    connection: ConnectionThreadSafe,
+}
+
+impl ChatStore {
+    /// Open or create the store at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        let connection = sqlite::Connection::open_thread_safe(path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        Self::with_connection(connection)
+    }
+
+    /// Store kept in memory, lost when dropped
+    pub fn in_memory() -> Result<Self, String> {
+        Self::with_connection(sqlite::Connection::open_thread_safe(":memory:").map_err(error)?)
+    }
+
+    fn with_connection(connection: ConnectionThreadSafe) -> Result<Self, String> {
+        connection.execute(SCHEMA).map_err(error)?;
+        Ok(Self { connection })
+    }
+
+    pub fn insert(&self, message: &StoredMessage) -> Result<(), String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "INSERT INTO messages (room_id, uid, user, kind, text, value, timestamp)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .map_err(error)?;
+        for (index, value) in [message.room_id, message.uid].into_iter().enumerate() {
+            statement.bind((index + 1, value as i64)).map_err(error)?;
+        }
+        for (index, value) in [&message.user, &message.kind, &message.text]
+            .into_iter()
+            .enumerate()
+        {
+            statement.bind((index + 3, value.as_str())).map_err(error)?;
+        }
+        for (index, value) in [message.value, message.timestamp].into_iter().enumerate() {
+            statement.bind((index + 6, value as i64)).map_err(error)?;
+        }
+        statement.next().map_err(error)?;
+        Ok(())
+    }
+
+    /// Statement with the user of `?1`/`?2` bound
+    fn user_statement(&self, sql: &str, user: &UserRef) -> Result<Statement<'_>, String> {
+        let mut statement = self.connection.prepare(sql).map_err(error)?;
+        let (uid, name) = match user {
+            UserRef::Uid(uid) => (*uid as i64, None),
+            UserRef::Name(name) => (-1, Some(name.as_str())),
+        };
+        statement.bind((1, uid)).map_err(error)?;
+        statement.bind((2, name)).map_err(error)?;
+        Ok(statement)
+    }
+
+    fn query(&self, sql: &str, user: &UserRef) -> Result<Vec<StoredMessage>, String> {
+        let mut statement = self.user_statement(sql, user)?;
+        let mut messages = Vec::new();
+        while statement.next().map_err(error)? == State::Row {
+            messages.push(StoredMessage::read(&statement).map_err(error)?);
+        }
+        Ok(messages)
+    }
+
+    /// Latest `limit` danmaku and super chats of `user`, newest first
+    pub fn messages(&self, user: &UserRef, limit: usize) -> Result<Vec<StoredMessage>, String> {
+        self.query(
+            &format!(
+                "SELECT * FROM messages WHERE {} AND kind IN ('danmu', 'super_chat')
+                 ORDER BY timestamp DESC, id DESC LIMIT {}",
+                USER_CONDITION, limit
+            ),
+            user,
+        )
+    }
+
+    /// Latest `limit` gifts, guard purchases and super chats of `user`, newest first
+    pub fn gifts(&self, user: &UserRef, limit: usize) -> Result<Vec<StoredMessage>, String> {
+        self.query(
+            &format!(
+                "SELECT * FROM messages WHERE {} AND kind IN {}
+                 ORDER BY timestamp DESC, id DESC LIMIT {}",
+                USER_CONDITION, GIFT_KINDS, limit
+            ),
+            user,
+        )
+    }
+
+    /// Unix timestamp of the first stored message of `user`
+    pub fn first_seen(&self, user: &UserRef) -> Result<Option<u64>, String> {
+        Ok(self.summary(user)?.first_seen)
+    }
+
+    pub fn summary(&self, user: &UserRef) -> Result<UserSummary, String> {
+        let mut statement = self.user_statement(
+            &format!(
+                "SELECT MIN(timestamp), MAX(timestamp),
+                    COALESCE(SUM(kind = 'danmu'), 0),
+                    COALESCE(SUM(kind IN {kinds}), 0),
+                    COALESCE(SUM(CASE WHEN kind IN {kinds} THEN value ELSE 0 END), 0)
+                 FROM messages WHERE {condition}",
+                kinds = GIFT_KINDS,
+                condition = USER_CONDITION
+            ),
+            user,
+        )?;
+        statement.next().map_err(error)?;
+        let read = |index: usize| statement.read::<Option<i64>, _>(index).map_err(error);
+        Ok(UserSummary {
+            first_seen: read(0)?.map(|t| t as u64),
+            last_seen: read(1)?.map(|t| t as u64),
+            danmu_count: read(2)?.unwrap_or(0) as u64,
+            gift_count: read(3)?.unwrap_or(0) as u64,
+            gift_value: read(4)?.unwrap_or(0) as u64,
+        })
+    }
+
+    /// Summary of `user` with up to `limit` latest messages and gifts
+    pub fn history(&self, user: &UserRef, limit: usize) -> Result<UserHistory, String> {
+        Ok(UserHistory {
+            summary: self.summary(user)?,
+            messages: self.messages(user, limit)?,
+            gifts: self.gifts(user, limit)?,
+        })
+    }
+
+    /// Every user with a stored message: the uids of those who chatted, and
+    /// the names of everyone, as gifts are stored without a uid
+    pub fn known_users(&self) -> Result<Vec<UserRef>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT DISTINCT uid, NULL FROM messages WHERE uid != 0
+                 UNION SELECT DISTINCT -1, user FROM messages",
+            )
+            .map_err(error)?;
+        let mut users = Vec::new();
+        while statement.next().map_err(error)? == State::Row {
+            let user = match statement.read::<Option<String>, _>(1).map_err(error)? {
+                Some(name) => UserRef::Name(name),
+                None => UserRef::Uid(statement.read::<i64, _>(0).map_err(error)? as u64),
+            };
+            users.push(user);
+        }
+        Ok(users)
+    }
+}
+
+/// HTTP routes under `/api/users/<uid or name>`: the user's `UserHistory`,
+/// or with `/messages` or `/gifts` appended, just those; `?limit=` sets how
+/// many messages are listed (default 20)
+pub fn routes(
+    store: Arc<ChatStore>,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static {
+    move |request| {
+        let path = request.path.strip_prefix(API_PREFIX)?;
+        let (user, list) = path.split_once('/').unwrap_or((path, ""));
+        let user: UserRef = match user.parse() {
+            Ok(user) => user,
+            Err(e) => return Some(Response::error(400, &e)),
+        };
+        let limit = match request.query_param("limit").map(|limit| limit.parse()) {
+            None => API_LIMIT,
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return Some(Response::error(400, "invalid limit")),
+        };
+        let result = match list {
+            "" => store
+                .history(&user, limit)
+                .map(|history| Response::json(&history)),
+            "messages" => store
+                .messages(&user, limit)
+                .map(|list| Response::json(&list)),
+            "gifts" => store.gifts(&user, limit).map(|list| Response::json(&list)),
+            _ => return None,
+        };
+        Some(result.unwrap_or_else(|e| Response::error(500, &e)))
+    }
+}
+
+/// A plugin recording the messages of users in a `ChatStore`
+pub struct ChatStoreHandler {
+    store: Arc<ChatStore>,
+}
+
+impl ChatStoreHandler {
+    pub fn new(store: Arc<ChatStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl EventHandler for ChatStoreHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu", "super_chat", "gift", "guard_buy"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, context: &EventContext) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if let Some(message) = StoredMessage::from_message(msg, context.room_id, now)
+            && let Err(e) = self.store.insert(&message)
+        {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_store_queries() {
+        let store = ChatStore::in_memory().unwrap();
+        let danmu = |text: &str, uid: u64| BiliMessage::Danmu {
+            user: if uid == 2 { "viewer" } else { "other" }.to_string(),
+            text: text.to_string(),
+            uid,
+            guard_level: 0,
+            medal: None,
+        };
+        let gift = BiliMessage::Gift {
+            user: "viewer".to_string(),
+            gift: "小花花".to_string(),
+            num: "3".to_string(),
+            gift_id: 1,
+            value: 300,
+            combo_id: None,
+        };
+        let messages = [
+            (danmu("first", 2), 100),
+            (gift, 110),
+            (danmu("second", 2), 120),
+            (danmu("other", 3), 130),
+            (
+                BiliMessage::OnlineRankCount {
+                    count: 1,
+                    online_count: 1,
+                },
+                140,
+            ),
+        ];
+        for (msg, timestamp) in &messages {
+            if let Some(message) = StoredMessage::from_message(msg, 1, *timestamp) {
+                store.insert(&message).unwrap();
+            }
+        }
+
+        let by_uid = store.messages(&UserRef::Uid(2), 10).unwrap();
+        let texts: Vec<_> = by_uid.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["second", "first"]);
+        assert_eq!(store.messages(&UserRef::Uid(2), 1).unwrap().len(), 1);
+        // The gift has no uid and is found through the name uid 2 chatted under
+        let gifts = store.gifts(&UserRef::Uid(2), 10).unwrap();
+        assert_eq!(gifts.len(), 1);
+        assert_eq!((gifts[0].text.as_str(), gifts[0].value), ("小花花 x3", 300));
+        assert_eq!(store.first_seen(&UserRef::Uid(3)).unwrap(), Some(130));
+        assert_eq!(store.first_seen(&UserRef::Uid(4)).unwrap(), None);
+
+        let summary = store.summary(&"viewer".parse().unwrap()).unwrap();
+        assert_eq!(
+            summary,
+            UserSummary {
+                first_seen: Some(100),
+                last_seen: Some(120),
+                danmu_count: 2,
+                gift_count: 1,
+                gift_value: 300,
+            }
+        );
+        assert_eq!("12".parse(), Ok(UserRef::Uid(12)));
+        assert!("".parse::<UserRef>().is_err());
+
+        let mut known = store.known_users().unwrap();
+        known.sort_by_key(|user| format!("{:?}", user));
+        assert_eq!(
+            known,
+            vec![
+                UserRef::Name("other".to_string()),
+                UserRef::Name("viewer".to_string()),
+                UserRef::Uid(2),
+                UserRef::Uid(3),
+            ]
+        );
+
+        let routes = routes(Arc::new(store));
+        let get = |path: &str| {
+            routes(&Request::parse(&format!("GET {} HTTP/1.1\r\n\r\n", path)).unwrap())
+        };
+        let history: serde_json::Value =
+            serde_json::from_str(&get("/api/users/2?limit=1").unwrap().body).unwrap();
+        assert_eq!(history["summary"]["first_seen"], 100);
+        assert_eq!(history["messages"][0]["text"], "second");
+        assert_eq!(history["messages"].as_array().unwrap().len(), 1);
+        assert!(
+            get("/api/users/viewer/gifts")
+                .unwrap()
+                .body
+                .contains("小花花")
+        );
+        assert_eq!(get("/api/users/2?limit=x").unwrap().status, 400);
+        assert!(get("/api/users/2/other").is_none());
+        assert!("".parse::<UserRef>().is_err());
+    }
+}
@@ -0,0 +1,323 @@
+// src/client/danmaku.rs
+//! Sending danmaku to a live room with the cookies of a logged-in account
+
+use crate::auth::{apply_custom_headers, extract_csrf_token, room_request_headers};
+use crate::models::BiliMessage;
+use crate::scheduler::EventContext;
+use log::{debug, info, warn};
+use reqwest::header::HeaderValue;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+pub const SEND_DANMAKU_URL: &str = "https://api.live.bilibili.com/msg/send";
+
+/// API codes of danmaku turned away for being sent too fast
+pub const RATE_LIMITED_CODES: [i64; 2] = [10030, 10031];
+
+/// Why a danmaku was not sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// Sent too fast; worth retrying a little later
+    RateLimited,
+    /// Refused with an API error code, e.g. for a blocked word or being too long
+    Rejected { code: i64, message: String },
+    /// No usable cookies, or the request itself failed
+    Failed(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::RateLimited => write!(f, "danmaku rate limited"),
+            SendError::Rejected { code, message } => {
+                write!(f, "danmaku rejected with code {}: {}", code, message)
+            }
+            SendError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl SendError {
+    /// Error of a send API response body, `None` when the danmaku was accepted
+    fn from_response(body: &serde_json::Value) -> Option<Self> {
+        match body["code"].as_i64() {
+            Some(0) => None,
+            Some(code) if RATE_LIMITED_CODES.contains(&code) => Some(SendError::RateLimited),
+            code => Some(SendError::Rejected {
+                code: code.unwrap_or(-1),
+                message: body["message"].as_str().unwrap_or("").to_string(),
+            }),
+        }
+    }
+}
+
+/// Danmaku or user a sent danmaku replies to, shown as a threaded reply in the
+/// official client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyTo {
+    pub uid: u64,
+    pub uname: String,
+    /// Id of the danmaku replied to; without it the reply goes to the user
+    pub dmid: Option<String>,
+}
+
+impl ReplyTo {
+    /// Reply to the user `uid`
+    pub fn new(uid: u64, uname: impl Into<String>) -> Self {
+        Self {
+            uid,
+            uname: uname.into(),
+            dmid: None,
+        }
+    }
+
+    pub fn with_dmid(mut self, dmid: impl Into<String>) -> Self {
+        self.dmid = Some(dmid.into());
+        self
+    }
+
+    /// Reply to the sender of a `Danmu`; `None` for other messages and senders
+    /// whose uid is hidden. Parsed messages carry no danmaku id, see
+    /// `from_danmu_json` to reply to the danmaku itself.
+    pub fn from_message(message: &BiliMessage) -> Option<Self> {
+        match message {
+            BiliMessage::Danmu { user, uid, .. } if *uid != 0 => Some(Self::new(*uid, user)),
+            _ => None,
+        }
+    }
+
+    /// Reply to a raw `DANMU_MSG`, including its danmaku id when present
+    pub fn from_danmu_json(json: &serde_json::Value) -> Option<Self> {
+        let uid = json["info"][2][0].as_u64().filter(|&uid| uid != 0)?;
+        let uname = json["info"][2][1].as_str().unwrap_or("");
+        let reply = Self::new(uid, uname);
+        // `extra` is itself a JSON document
+        let extra: serde_json::Value = json["info"][0][15]["extra"]
+            .as_str()
+            .and_then(|extra| serde_json::from_str(extra).ok())
+            .unwrap_or_default();
+        Some(match extra["id_str"].as_str().filter(|id| !id.is_empty()) {
+            Some(dmid) => reply.with_dmid(dmid),
+            None => reply,
+        })
+    }
+}
+
+/// `text` addressed to `uname` with an @mention
+pub fn mention(uname: &str, text: &str) -> String {
+    format!("@{} {}", uname, text.trim())
+}
+
+/// Parameters for sending a danmaku message to Bilibili API
+#[derive(Serialize, Debug)]
+pub(crate) struct SendDanmakuRequest {
+    csrf: String,
+    roomid: u64,
+    msg: String,
+    rnd: u64,
+    fontsize: u32,
+    color: u32,
+    mode: u32,
+    bubble: u32,
+    room_type: u32,
+    jumpfrom: u32,
+    reply_mid: u64,
+    reply_attr: u32,
+    reply_uname: String,
+    replay_dmid: String,
+    statistics: String,
+    csrf_token: String,
+}
+
+impl SendDanmakuRequest {
+    /// White scrolling danmaku with `message` for `room_id`
+    pub(crate) fn new(csrf_token: String, room_id: u64, message: &str) -> Self {
+        // Current timestamp
+        let rnd = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Self {
+            csrf: csrf_token.clone(),
+            roomid: room_id,
+            msg: message.to_string(),
+            rnd,
+            fontsize: 25,
+            color: 16777215, // White color
+            mode: 1,         // Scroll mode
+            bubble: 0,
+            room_type: 0,
+            jumpfrom: 0,
+            reply_mid: 0,
+            reply_attr: 0,
+            reply_uname: String::new(),
+            replay_dmid: String::new(),
+            statistics: r#"{"appId":100,"platform":5}"#.to_string(),
+            csrf_token,
+        }
+    }
+
+    /// Thread the danmaku as a reply to `reply`
+    pub(crate) fn reply_to(mut self, reply: &ReplyTo) -> Self {
+        self.reply_mid = reply.uid;
+        self.reply_uname = reply.uname.clone();
+        self.replay_dmid = reply.dmid.clone().unwrap_or_default();
+        self
+    }
+}
+
+/// Send a danmaku message to the Bilibili live room
+///
+/// # Arguments
+/// * `message` - The text message to send
+/// * `context` - Event context containing cookies and room_id
+///
+/// # Returns
+/// Returns Ok(()) on success, or an error if the request fails or the API
+/// refuses the message
+pub async fn send_danmaku_message(
+    message: &str,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(send_danmaku(message, context).await?)
+}
+
+/// Send a danmaku message, telling a rate-limited message apart from other failures
+pub async fn send_danmaku(message: &str, context: &EventContext) -> Result<(), SendError> {
+    send(message, None, context).await
+}
+
+/// Send a danmaku message as a reply to another danmaku or user
+pub async fn send_reply(
+    message: &str,
+    reply: &ReplyTo,
+    context: &EventContext,
+) -> Result<(), SendError> {
+    send(message, Some(reply), context).await
+}
+
+async fn send(
+    message: &str,
+    reply: Option<&ReplyTo>,
+    context: &EventContext,
+) -> Result<(), SendError> {
+    let cookies = context
+        .cookies
+        .as_ref()
+        .ok_or_else(|| SendError::Failed("No cookies available for sending danmaku".to_string()))?;
+    let csrf_token = extract_csrf_token(cookies).ok_or_else(|| {
+        SendError::Failed("Could not extract CSRF token from cookies".to_string())
+    })?;
+
+    let mut request = SendDanmakuRequest::new(csrf_token, context.room_id, message);
+    if let Some(reply) = reply {
+        request = request.reply_to(reply);
+    }
+
+    // Set up headers
+    let mut headers = room_request_headers(context.room_id);
+    apply_custom_headers(&mut headers, context.request_headers.as_ref());
+    let cookie = HeaderValue::from_str(cookies)
+        .map_err(|e| SendError::Failed(format!("invalid cookies: {}", e)))?;
+    headers.insert("Cookie", cookie);
+
+    debug!("Sending danmaku: {}", message);
+
+    let failed = |e: reqwest::Error| SendError::Failed(format!("Failed to send danmaku: {}", e));
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(failed)?;
+
+    let response = http_client
+        .post(SEND_DANMAKU_URL)
+        .headers(headers)
+        .form(&request)
+        .send()
+        .await
+        .map_err(failed)?;
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        warn!("Failed to send danmaku, status: {}", status);
+        debug!("Response body: {}", body);
+        return Err(SendError::Failed(format!(
+            "Failed to send danmaku: {} - {}",
+            status, body
+        )));
+    }
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+    match SendError::from_response(&body) {
+        None => {
+            info!("Successfully sent danmaku: {}", message);
+            Ok(())
+        }
+        Some(e) => {
+            warn!("Danmaku '{}' not sent: {}", message, e);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_error_from_response() {
+        assert_eq!(
+            SendError::from_response(&serde_json::json!({"code": 0})),
+            None
+        );
+        assert_eq!(
+            SendError::from_response(&serde_json::json!({"code": 10030, "message": "频率过快"})),
+            Some(SendError::RateLimited)
+        );
+        assert_eq!(
+            SendError::from_response(
+                &serde_json::json!({"code": 1003212, "message": "超出限制长度"})
+            ),
+            Some(SendError::Rejected {
+                code: 1003212,
+                message: "超出限制长度".to_string(),
+            })
+        );
+        assert!(SendError::from_response(&serde_json::Value::Null).is_some());
+    }
+
+    #[test]
+    fn test_reply_to() {
+        let json = serde_json::json!({
+            "cmd": "DANMU_MSG",
+            "info": [
+                [0, 1, 25, 16777215, 0, 0, 0, "", 0, 0, 0, "", 0, "{}", "{}",
+                 {"extra": "{\"id_str\":\"abc123\",\"content\":\"hi\"}"}],
+                "hi",
+                [2, "viewer"]
+            ]
+        });
+        let reply = ReplyTo::from_danmu_json(&json).unwrap();
+        assert_eq!(reply, ReplyTo::new(2, "viewer").with_dmid("abc123"));
+        assert_eq!(
+            ReplyTo::from_danmu_json(&serde_json::json!({"info": [[], "hi", [0, "hidden"]]})),
+            None
+        );
+
+        let message = crate::protocol::handle(json).unwrap();
+        assert_eq!(
+            ReplyTo::from_message(&message),
+            Some(ReplyTo::new(2, "viewer"))
+        );
+
+        let request = SendDanmakuRequest::new("csrf".to_string(), 1, "ok").reply_to(&reply);
+        assert_eq!(request.reply_mid, 2);
+        assert_eq!(request.reply_uname, "viewer");
+        assert_eq!(request.replay_dmid, "abc123");
+        assert_eq!(mention("viewer", " thanks "), "@viewer thanks");
+    }
+}
@@ -0,0 +1,255 @@
+// src/client/extension.rs
+//! Parsers for commands this crate does not know yet
+//!
+//! A parser registered for a `cmd` in a `ParserRegistry` runs before the
+//! built-in parsing of `protocol::handle` and turns the message JSON into any
+//! `BiliMessage`: a `Custom` payload, or an `Extension` wrapping a type of your
+//! own. The registry is given to a client, which parses with it:
+//!
+//! ```ignore
+//! #[derive(Serialize)]
+//! struct Like { user: String }
+//!
+//! let parsers = ParserRegistry::new();
+//! parsers.register("LIKE_INFO_V3_CLICK", |json| {
+//!     let user = json["data"]["uname"].as_str()?.to_string();
+//!     Some(BiliMessage::Extension(Extension::new("LIKE_INFO_V3_CLICK", Like { user })))
+//! });
+//! let client = BiliLiveClient::builder("24779526").parsers(parsers).build(tx)?;
+//!
+//! if let BiliMessage::Extension(ext) = &msg
+//!     && let Some(like) = ext.downcast_ref::<Like>()
+//! {
+//!     println!("{} liked", like.user);
+//! }
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::models::BiliMessage;
+use crate::protocol;
+
+/// Parser for one command; `None` drops the message
+pub type CommandParser = Arc<dyn Fn(&Value) -> Option<BiliMessage> + Send + Sync>;
+
+/// Parsers by command for one client, see `BiliLiveClientBuilder::parsers`.
+/// Clones share their parsers, so parsers registered after the client was
+/// built apply to it too.
+#[derive(Clone, Default)]
+pub struct ParserRegistry {
+    parsers: Arc<RwLock<HashMap<String, CommandParser>>>,
+}
+
+impl ParserRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse messages of `cmd` with `parser` from now on, replacing any parser
+    /// registered before and the built-in parsing of `cmd`
+    pub fn register<F>(&self, cmd: &str, parser: F)
+    where
+        F: Fn(&Value) -> Option<BiliMessage> + Send + Sync + 'static,
+    {
+        if let Ok(mut parsers) = self.parsers.write() {
+            parsers.insert(cmd.to_string(), Arc::new(parser));
+        }
+    }
+
+    /// Remove the parser of `cmd`, returning whether one was registered
+    pub fn unregister(&self, cmd: &str) -> bool {
+        self.parsers
+            .write()
+            .is_ok_and(|mut parsers| parsers.remove(cmd).is_some())
+    }
+
+    /// Run the parser registered for `cmd` (compared without `:` suffixes), if any.
+    /// The outer `None` means no parser is registered.
+    pub fn parse(&self, cmd: &str, json: &Value) -> Option<Option<BiliMessage>> {
+        let cmd = cmd.split(':').next().unwrap_or(cmd);
+        // Clone the parser so it runs without the lock and may register parsers itself
+        let parser = self.parsers.read().ok()?.get(cmd).cloned()?;
+        Some(parser(json))
+    }
+
+    /// Parse a message with the parser of its command, or `protocol::handle`
+    pub fn handle(&self, json: Value) -> Option<BiliMessage> {
+        match self.parse(json["cmd"].as_str().unwrap_or(""), &json) {
+            Some(parsed) => parsed,
+            None => protocol::handle(json),
+        }
+    }
+}
+
+impl fmt::Debug for ParserRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commands: Vec<String> = self
+            .parsers
+            .read()
+            .map(|parsers| parsers.keys().cloned().collect())
+            .unwrap_or_default();
+        f.debug_struct("ParserRegistry")
+            .field("commands", &commands)
+            .finish()
+    }
+}
+
+/// Message of a type defined outside this crate, carrying the value and its
+/// JSON form. Serializing keeps only the JSON, so a deserialized extension
+/// has no value to downcast to.
+#[derive(Clone)]
+pub struct Extension {
+    cmd: String,
+    data: Value,
+    value: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extension {
+    pub fn new<T>(cmd: &str, value: T) -> Self
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        Self {
+            cmd: cmd.to_string(),
+            data: serde_json::to_value(&value).unwrap_or(Value::Null),
+            value: Some(Arc::new(value)),
+        }
+    }
+
+    /// Command the message was parsed from
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+
+    /// JSON form of the value
+    pub fn data(&self) -> &Value {
+        &self.data
+    }
+
+    /// The value, if it is a `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.as_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extension")
+            .field("cmd", &self.cmd)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl PartialEq for Extension {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmd == other.cmd && self.data == other.data
+    }
+}
+
+impl Eq for Extension {}
+
+/// Serialized form of `Extension`
+#[derive(Serialize, Deserialize)]
+struct ExtensionJson<'a> {
+    cmd: std::borrow::Cow<'a, str>,
+    data: std::borrow::Cow<'a, Value>,
+}
+
+impl Serialize for Extension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ExtensionJson {
+            cmd: (&self.cmd).into(),
+            data: std::borrow::Cow::Borrowed(&self.data),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Extension {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = ExtensionJson::deserialize(deserializer)?;
+        Ok(Self {
+            cmd: json.cmd.into_owned(),
+            data: json.data.into_owned(),
+            value: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct Like {
+        user: String,
+    }
+
+    #[test]
+    fn test_registered_parser() {
+        let cmd = "TEST_EXTENSION_LIKE";
+        let parsers = ParserRegistry::new();
+        let handle = |json| parsers.handle(json);
+        parsers.register(cmd, |json| {
+            let user = json["data"]["uname"].as_str()?.to_string();
+            Some(BiliMessage::Extension(Extension::new(
+                "TEST_EXTENSION_LIKE",
+                Like { user },
+            )))
+        });
+        parsers.register("TEST_EXTENSION_CUSTOM", |json| {
+            Some(BiliMessage::Custom {
+                cmd: "TEST_EXTENSION_CUSTOM".to_string(),
+                data: json["data"].clone(),
+            })
+        });
+
+        let msg = handle(serde_json::json!({"cmd": cmd, "data": {"uname": "fan"}})).unwrap();
+        let BiliMessage::Extension(ext) = &msg else {
+            panic!("unexpected message: {:?}", msg);
+        };
+        assert_eq!(
+            ext.downcast_ref::<Like>(),
+            Some(&Like {
+                user: "fan".to_string()
+            })
+        );
+        assert_eq!(
+            msg.to_json(),
+            serde_json::json!({"type": "extension", "cmd": cmd, "data": {"user": "fan"}})
+        );
+        // The JSON round trip keeps the data but not the value
+        let copy: BiliMessage =
+            serde_json::from_value(serde_json::to_value(&msg).unwrap()).unwrap();
+        assert_eq!(copy, msg);
+
+        // A parser returning None drops the message
+        assert_eq!(handle(serde_json::json!({"cmd": cmd, "data": {}})), None);
+        assert_eq!(
+            handle(serde_json::json!({"cmd": "TEST_EXTENSION_CUSTOM:1", "data": [1]})),
+            Some(BiliMessage::Custom {
+                cmd: "TEST_EXTENSION_CUSTOM".to_string(),
+                data: serde_json::json!([1]),
+            })
+        );
+
+        // Other registries, and so other clients, keep the built-in parsing
+        assert!(matches!(
+            ParserRegistry::new().handle(serde_json::json!({"cmd": "TEST_EXTENSION_CUSTOM"})),
+            Some(BiliMessage::Raw(_))
+        ));
+
+        assert!(parsers.unregister(cmd));
+        assert!(!parsers.unregister(cmd));
+        assert!(matches!(
+            handle(serde_json::json!({"cmd": cmd})),
+            Some(BiliMessage::Raw(_))
+        ));
+    }
+}
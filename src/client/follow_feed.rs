@@ -0,0 +1,197 @@
+// src/client/follow_feed.rs
+//! Follow feed: every live room of the streamers an account follows
+//!
+//! `watch_follow_feed` polls the live followings of the logged-in account,
+//! opens a `LiveRoom` when a streamer goes live and closes it when they go
+//! offline. The messages of all open rooms arrive on one channel, framed by a
+//! `StreamStarted` and a `StreamEnded` message per room.
+
+use futures::StreamExt;
+use futures_channel::mpsc::UnboundedSender;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::live_room::{Credentials, LiveRoom};
+use crate::models::BiliMessage;
+use crate::room_management::get_live_api_with_cookies;
+use crate::watchlist::RoomMessage;
+
+pub const FOLLOWING_URL: &str = "https://api.live.bilibili.com/xlive/web-ucenter/user/following";
+
+/// Default interval between polls of `watch_follow_feed`
+pub const FOLLOW_FEED_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Followings per page, as requested by the web page
+const PAGE_SIZE: u32 = 29;
+
+/// Pages fetched at most per poll
+const MAX_PAGES: u32 = 50;
+
+/// Followed streamer who is live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveFollowing {
+    pub room_id: u64,
+    pub uid: u64,
+    pub streamer: String,
+    pub title: String,
+}
+
+impl LiveFollowing {
+    fn started(&self) -> BiliMessage {
+        BiliMessage::StreamStarted {
+            room_id: self.room_id,
+            uid: self.uid,
+            streamer: self.streamer.clone(),
+            title: self.title.clone(),
+        }
+    }
+
+    fn ended(&self) -> BiliMessage {
+        BiliMessage::StreamEnded {
+            room_id: self.room_id,
+            uid: self.uid,
+            streamer: self.streamer.clone(),
+        }
+    }
+}
+
+/// Live followings of one page of the following list, and the page count
+fn live_from_page(data: &Value) -> (Vec<LiveFollowing>, u32) {
+    let live = data["list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry["live_status"].as_u64() == Some(1))
+        .filter_map(|entry| {
+            Some(LiveFollowing {
+                room_id: entry["roomid"].as_u64()?,
+                uid: entry["uid"].as_u64().unwrap_or(0),
+                streamer: entry["uname"].as_str().unwrap_or("").to_string(),
+                title: entry["title"].as_str().unwrap_or("").to_string(),
+            })
+        })
+        .collect();
+    let pages = data["totalPage"].as_u64().unwrap_or(1) as u32;
+    (live, pages)
+}
+
+/// Fetch the followed streamers of the account of `cookies` who are live now
+pub fn fetch_live_followings(cookies: &str) -> Result<Vec<LiveFollowing>, String> {
+    let mut live = Vec::new();
+    let mut page = 1;
+    loop {
+        let url = format!(
+            "{}?page={}&page_size={}&ignoreRecord=1",
+            FOLLOWING_URL, page, PAGE_SIZE
+        );
+        let data = get_live_api_with_cookies(&url, "following list", Some(cookies))?;
+        let (page_live, pages) = live_from_page(&data);
+        live.extend(page_live);
+        if page >= pages.min(MAX_PAGES) {
+            return Ok(live);
+        }
+        page += 1;
+    }
+}
+
+/// Poll the live followings of the account of `cookies` every `interval` from
+/// a background thread, keeping a room open for each and sending the messages
+/// of all of them to `tx`. Stops once `tx` is closed.
+pub fn watch_follow_feed(
+    cookies: String,
+    interval: Duration,
+    tx: UnboundedSender<RoomMessage>,
+) -> Result<JoinHandle<()>, String> {
+    thread::Builder::new()
+        .name("blivedm-follow-feed".to_string())
+        .spawn(move || {
+            let mut rooms: HashMap<u64, (LiveFollowing, LiveRoom)> = HashMap::new();
+            while !tx.is_closed() {
+                match fetch_live_followings(&cookies) {
+                    Ok(live) => {
+                        rooms.retain(|room_id, (following, _)| {
+                            let still_live = live.iter().any(|f| f.room_id == *room_id);
+                            if !still_live {
+                                send(&tx, *room_id, following.ended());
+                            }
+                            still_live
+                        });
+                        for following in live {
+                            if rooms.contains_key(&following.room_id) {
+                                continue;
+                            }
+                            let room_id = following.room_id;
+                            match LiveRoom::open(room_id, Credentials::Cookies(cookies.clone())) {
+                                Ok(room) => {
+                                    send(&tx, room_id, following.started());
+                                    if let Err(e) = forward(&room, room_id, tx.clone()) {
+                                        log::warn!("{}", e);
+                                    }
+                                    rooms.insert(room_id, (following, room));
+                                }
+                                Err(e) => log::warn!("failed to open room {}: {}", room_id, e),
+                            }
+                        }
+                    }
+                    Err(e) => log::warn!("follow feed poll failed: {}", e),
+                }
+                thread::sleep(interval);
+            }
+        })
+        .map_err(|e| format!("failed to start follow feed thread: {}", e))
+}
+
+fn send(tx: &UnboundedSender<RoomMessage>, room_id: u64, message: BiliMessage) {
+    let _ = tx.unbounded_send(RoomMessage { room_id, message });
+}
+
+/// Send the messages of `room` to `tx` from a thread ending with the room
+fn forward(room: &LiveRoom, room_id: u64, tx: UnboundedSender<RoomMessage>) -> Result<(), String> {
+    let mut messages = room.messages();
+    thread::Builder::new()
+        .name(format!("blivedm-follow-{}", room_id))
+        .spawn(move || {
+            futures::executor::block_on(async {
+                while let Some(message) = messages.next().await {
+                    if tx.unbounded_send(RoomMessage { room_id, message }).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .map(|_| ())
+        .map_err(|e| format!("failed to start forwarding thread: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_live_from_page() {
+        let data = serde_json::json!({
+            "list": [
+                {"roomid": 24779526, "uid": 2, "uname": "主播", "title": "晚间杂谈", "live_status": 1},
+                {"roomid": 100, "uid": 3, "uname": "offline", "title": "", "live_status": 0},
+                {"uid": 4, "uname": "no room", "live_status": 1}
+            ],
+            "totalPage": 3
+        });
+        let (live, pages) = live_from_page(&data);
+        assert_eq!(pages, 3);
+        assert_eq!(
+            live,
+            vec![LiveFollowing {
+                room_id: 24779526,
+                uid: 2,
+                streamer: "主播".to_string(),
+                title: "晚间杂谈".to_string(),
+            }]
+        );
+        assert_eq!(live[0].started().kind(), "stream_started");
+        assert_eq!(live[0].ended().kind(), "stream_ended");
+        assert_eq!(live_from_page(&Value::Null), (Vec::new(), 1));
+    }
+}
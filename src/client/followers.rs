@@ -0,0 +1,143 @@
+// src/client/followers.rs
+//! Follower and fan club count polling
+//!
+//! Neither count is pushed over the danmaku connection, so `watch_followers`
+//! polls the room info API and sends a `FollowerChange` message whenever one
+//! of them moves, for overlays celebrating follower milestones.
+
+use futures_channel::mpsc::Sender;
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::auth::ROOM_INIT_URL;
+use crate::models::BiliMessage;
+use crate::room_management::get_live_api;
+
+/// Default interval between polls of `watch_followers`
+pub const FOLLOWER_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Follower and fan club counts of a streamer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowerCounts {
+    pub followers: u64,
+    /// Members of the streamer's fan club
+    pub fans_club: u64,
+}
+
+impl FollowerCounts {
+    fn from_data(data: &Value) -> Option<Self> {
+        let anchor = &data["anchor_info"];
+        Some(Self {
+            followers: anchor["relation_info"]["attention"].as_u64()?,
+            fans_club: anchor["medal_info"]["fansclub"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// `FollowerChange` message from `previous` to these counts, `None` when
+    /// nothing changed
+    pub fn change_from(&self, previous: Option<FollowerCounts>) -> Option<BiliMessage> {
+        if previous == Some(*self) {
+            return None;
+        }
+        let previous = previous.unwrap_or(*self);
+        Some(BiliMessage::FollowerChange {
+            followers: self.followers,
+            followers_delta: self.followers as i64 - previous.followers as i64,
+            fans_club: self.fans_club,
+            fans_club_delta: self.fans_club as i64 - previous.fans_club as i64,
+        })
+    }
+}
+
+/// Fetch the follower and fan club counts of the streamer of a room
+pub fn fetch_follower_counts(room_id: u64) -> Result<FollowerCounts, String> {
+    let data = get_live_api(
+        &format!("{}?room_id={}", ROOM_INIT_URL, room_id),
+        "follower count",
+    )?;
+    FollowerCounts::from_data(&data).ok_or_else(|| "room info has no follower count".to_string())
+}
+
+/// Poll the counts of the room in `room` every `interval` from a background
+/// thread. The first poll of each room sends its counts with zero deltas; later
+/// ones send a `FollowerChange` to `tx` whenever a count moved. Stops once `tx`
+/// is closed.
+pub fn watch_followers(
+    room: Arc<AtomicU64>,
+    interval: Duration,
+    mut tx: Sender<BiliMessage>,
+) -> Result<JoinHandle<()>, String> {
+    thread::Builder::new()
+        .name("blivedm-followers".to_string())
+        .spawn(move || {
+            let mut last: Option<(u64, FollowerCounts)> = None;
+            while !tx.is_closed() {
+                let room_id = room.load(Ordering::Relaxed);
+                match fetch_follower_counts(room_id) {
+                    Ok(counts) => {
+                        let previous = last
+                            .filter(|(last_room, _)| *last_room == room_id)
+                            .map(|(_, counts)| counts);
+                        if let Some(msg) = counts.change_from(previous) {
+                            let _ = tx.try_send(msg);
+                        }
+                        last = Some((room_id, counts));
+                    }
+                    Err(e) => log::debug!("follower poll failed: {}", e),
+                }
+                thread::sleep(interval);
+            }
+        })
+        .map_err(|e| format!("failed to start follower thread: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follower_counts_and_deltas() {
+        let data = serde_json::json!({
+            "anchor_info": {
+                "relation_info": {"attention": 10_000},
+                "medal_info": {"fansclub": 321}
+            }
+        });
+        let counts = FollowerCounts::from_data(&data).unwrap();
+        assert_eq!(
+            counts,
+            FollowerCounts {
+                followers: 10_000,
+                fans_club: 321,
+            }
+        );
+        assert_eq!(FollowerCounts::from_data(&Value::Null), None);
+
+        // The first poll reports the counts without deltas
+        assert_eq!(
+            counts.change_from(None),
+            Some(BiliMessage::FollowerChange {
+                followers: 10_000,
+                followers_delta: 0,
+                fans_club: 321,
+                fans_club_delta: 0,
+            })
+        );
+        assert_eq!(counts.change_from(Some(counts)), None);
+        assert_eq!(
+            counts.change_from(Some(FollowerCounts {
+                followers: 9_998,
+                fans_club: 322,
+            })),
+            Some(BiliMessage::FollowerChange {
+                followers: 10_000,
+                followers_delta: 2,
+                fans_club: 321,
+                fans_club_delta: -1,
+            })
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// src/client/gift_catalog.rs
+//! Gift price catalog built from the room gift config
+
+use crate::auth::apply_custom_headers;
+use crate::models::BiliMessage;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const ROOM_GIFT_CONFIG_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-room/v1/giftPanel/roomGiftConfig";
+
+/// Gold coins per CNY
+const COINS_PER_RMB: u64 = 1000;
+/// Gold coins per battery
+const COINS_PER_BATTERY: u64 = 100;
+
+/// Convert a gold coin amount to CNY
+pub fn coins_to_rmb(coins: u64) -> f64 {
+    coins as f64 / COINS_PER_RMB as f64
+}
+
+/// Convert a CNY amount to gold coins
+pub fn rmb_to_coins(rmb: u64) -> u64 {
+    rmb * COINS_PER_RMB
+}
+
+/// Convert a gold coin amount to batteries
+pub fn coins_to_battery(coins: u64) -> u64 {
+    coins / COINS_PER_BATTERY
+}
+
+/// Price information for a single gift
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GiftInfo {
+    pub name: String,
+    /// Unit price in gold coins (0 for silver/free gifts)
+    pub price: u64,
+}
+
+/// Map of gift id to price, fetched once per room
+#[derive(Debug, Clone, Default)]
+pub struct GiftCatalog {
+    gifts: HashMap<u64, GiftInfo>,
+}
+
+impl GiftCatalog {
+    /// Fetch the gift config of a room
+    pub fn fetch(room_id: u64) -> Result<Self, String> {
+        Self::fetch_with_headers(room_id, None)
+    }
+
+    /// `fetch` with custom request headers, see `auth::RequestHeaders`
+    pub fn fetch_with_headers(room_id: u64, custom: Option<&HeaderMap>) -> Result<Self, String> {
+        let client = reqwest::blocking::Client::builder()
+            .https_only(true)
+            .build()
+            .map_err(|e| format!("http client init failed: {}", e))?;
+
+        let url = format!("{}?platform=pc&room_id={}", ROOM_GIFT_CONFIG_URL, room_id);
+        let mut headers = HeaderMap::new();
+        apply_custom_headers(&mut headers, custom);
+        let body: Value = client
+            .get(url)
+            .headers(headers)
+            .send()
+            .and_then(|resp| resp.json())
+            .map_err(|e| format!("fetch gift config failed: {}", e))?;
+
+        if body["code"].as_i64() != Some(0) {
+            return Err(format!(
+                "gift config API error: {}",
+                body["message"].as_str().unwrap_or("unknown error")
+            ));
+        }
+
+        let catalog = Self::from_config(&body["data"]);
+        log::info!("Loaded {} gift prices for room {}", catalog.len(), room_id);
+        Ok(catalog)
+    }
+
+    /// Build a catalog from the `data` field of a room gift config response
+    pub fn from_config(data: &Value) -> Self {
+        let gifts = data["global_gift"]["list"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|gift| {
+                let id = gift["id"].as_u64()?;
+                let price = if gift["coin_type"].as_str() == Some("gold") {
+                    gift["price"].as_u64().unwrap_or(0)
+                } else {
+                    0
+                };
+                let name = gift["name"].as_str().unwrap_or("").to_string();
+                Some((id, GiftInfo { name, price }))
+            })
+            .collect();
+        Self { gifts }
+    }
+
+    /// Look up a gift by id
+    pub fn get(&self, gift_id: u64) -> Option<&GiftInfo> {
+        self.gifts.get(&gift_id)
+    }
+
+    /// Number of gifts in the catalog
+    pub fn len(&self) -> usize {
+        self.gifts.len()
+    }
+
+    /// Whether the catalog is empty
+    pub fn is_empty(&self) -> bool {
+        self.gifts.is_empty()
+    }
+
+    /// Replace the value of a gift or combo message with the catalog price times the gift count
+    pub fn annotate(&self, msg: &mut BiliMessage) {
+        match msg {
+            BiliMessage::Gift {
+                gift_id,
+                num,
+                value,
+                ..
+            } => {
+                if let Some(info) = self.gifts.get(gift_id) {
+                    *value = info.price * num.parse::<u64>().unwrap_or(1);
+                }
+            }
+            BiliMessage::ComboSend {
+                gift_id,
+                total_num,
+                value,
+                ..
+            } => {
+                if let Some(info) = self.gifts.get(gift_id) {
+                    *value = info.price * *total_num;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_catalog() -> GiftCatalog {
+        GiftCatalog::from_config(&serde_json::json!({
+            "global_gift": {
+                "list": [
+                    {"id": 31036, "name": "小花花", "price": 100, "coin_type": "gold"},
+                    {"id": 1, "name": "辣条", "price": 100, "coin_type": "silver"},
+                    {"name": "missing id", "price": 100, "coin_type": "gold"}
+                ]
+            }
+        }))
+    }
+
+    #[test]
+    fn test_catalog_from_config() {
+        let catalog = sample_catalog();
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(catalog.get(31036).map(|g| g.price), Some(100));
+        assert_eq!(catalog.get(1).map(|g| g.price), Some(0));
+        assert!(GiftCatalog::from_config(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_annotate_gift_value() {
+        let catalog = sample_catalog();
+        let mut msg = BiliMessage::Gift {
+            user: "user".to_string(),
+            gift: "小花花".to_string(),
+            num: "5".to_string(),
+            gift_id: 31036,
+            value: 0,
+            combo_id: None,
+        };
+        catalog.annotate(&mut msg);
+        match msg {
+            BiliMessage::Gift { value, .. } => {
+                assert_eq!(value, 500);
+                assert_eq!(coins_to_rmb(value), 0.5);
+                assert_eq!(coins_to_battery(value), 5);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// src/client/grpc.rs
+//! gRPC service streaming received messages
+//!
+//! `LiveMessageService` implements `blivedm.v1.LiveMessages` from
+//! `proto/blivedm.proto`: each `Subscribe` call gets its own `MessageBus`
+//! subscription, filtered to the requested kinds, with typed fields for
+//! danmaku, gifts, super chats and guard purchases and JSON for every kind.
+//!
+//! ```ignore
+//! rt.spawn(grpc::serve(addr, bus.clone(), current_room));
+//! ```
+
+use futures::{StreamExt, future};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tonic::{Request, Response, Status};
+
+use crate::bus::MessageBus;
+use crate::models::BiliMessage;
+
+/// Types generated from `proto/blivedm.proto`
+pub mod proto {
+    tonic::include_proto!("blivedm.v1");
+}
+
+use proto::live_message::Payload;
+use proto::live_messages_server::{LiveMessages, LiveMessagesServer};
+use proto::{LiveMessage, SubscribeRequest};
+
+impl LiveMessage {
+    /// `msg` of room `room_id` in its gRPC form
+    pub fn from_message(msg: &BiliMessage, room_id: u64) -> Self {
+        let payload = match msg {
+            BiliMessage::Danmu {
+                user,
+                text,
+                uid,
+                guard_level,
+                medal,
+            } => Some(Payload::Danmu(proto::Danmu {
+                user: user.clone(),
+                text: text.clone(),
+                uid: *uid,
+                guard_level: (*guard_level).into(),
+                medal_name: medal.as_ref().map(|m| m.name.clone()).unwrap_or_default(),
+                medal_level: medal.as_ref().map_or(0, |m| m.level.into()),
+            })),
+            BiliMessage::Gift {
+                user,
+                gift,
+                num,
+                gift_id,
+                value,
+                ..
+            } => Some(Payload::Gift(proto::Gift {
+                user: user.clone(),
+                gift: gift.clone(),
+                num: num.clone(),
+                gift_id: *gift_id,
+                value: *value,
+            })),
+            BiliMessage::SuperChat {
+                id,
+                user,
+                text,
+                price,
+                duration,
+            } => Some(Payload::SuperChat(proto::SuperChat {
+                id: *id,
+                user: user.clone(),
+                text: text.clone(),
+                price: *price,
+                duration: *duration,
+            })),
+            BiliMessage::GuardBuy {
+                user,
+                guard_level,
+                num,
+                price,
+            } => Some(Payload::GuardBuy(proto::GuardBuy {
+                user: user.clone(),
+                guard_level: (*guard_level).into(),
+                num: *num,
+                price: *price,
+            })),
+            _ => None,
+        };
+        Self {
+            room_id,
+            r#type: msg.kind().to_string(),
+            json: msg.to_json().to_string(),
+            payload,
+        }
+    }
+}
+
+/// `LiveMessages` service over a message bus
+#[derive(Debug, Clone)]
+pub struct LiveMessageService {
+    bus: MessageBus,
+    /// Room the bus messages come from, followed across room switches
+    room_id: Arc<AtomicU64>,
+}
+
+impl LiveMessageService {
+    pub fn new(bus: MessageBus, room_id: Arc<AtomicU64>) -> Self {
+        Self { bus, room_id }
+    }
+
+    /// The service ready to add to a `tonic` server
+    pub fn into_server(self) -> LiveMessagesServer<Self> {
+        LiveMessagesServer::new(self)
+    }
+}
+
+type LiveMessageStream =
+    Pin<Box<dyn futures::Stream<Item = Result<LiveMessage, Status>> + Send + 'static>>;
+
+// `Status` is the error type tonic streams require
+#[allow(clippy::result_large_err)]
+#[tonic::async_trait]
+impl LiveMessages for LiveMessageService {
+    type SubscribeStream = LiveMessageStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let types: HashSet<String> = request.into_inner().types.into_iter().collect();
+        let room_id = Arc::clone(&self.room_id);
+        let stream = self
+            .bus
+            .stream("grpc")
+            .filter(move |msg| future::ready(types.is_empty() || types.contains(msg.kind())))
+            .map(move |msg| {
+                Ok(LiveMessage::from_message(
+                    &msg,
+                    room_id.load(Ordering::Relaxed),
+                ))
+            });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serve `LiveMessages` over the messages of `bus` on `addr` until the
+/// runtime shuts down
+pub async fn serve(addr: SocketAddr, bus: MessageBus, room_id: Arc<AtomicU64>) {
+    log::info!("serving gRPC on {}", addr);
+    if let Err(e) = tonic::transport::Server::builder()
+        .add_service(LiveMessageService::new(bus, room_id).into_server())
+        .serve(addr)
+        .await
+    {
+        log::warn!("gRPC server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_filters_kinds() {
+        let bus = MessageBus::default();
+        let service = LiveMessageService::new(bus.clone(), Arc::new(AtomicU64::new(7)));
+        let request = Request::new(SubscribeRequest {
+            types: vec!["gift".to_string()],
+        });
+        let mut stream = service.subscribe(request).await.unwrap().into_inner();
+
+        bus.publish(vec![
+            BiliMessage::Danmu {
+                user: "viewer".to_string(),
+                text: "hi".to_string(),
+                uid: 2,
+                guard_level: 0,
+                medal: None,
+            },
+            BiliMessage::Gift {
+                user: "viewer".to_string(),
+                gift: "小花花".to_string(),
+                num: "1".to_string(),
+                gift_id: 31036,
+                value: 100,
+                combo_id: None,
+            },
+        ]);
+        let message = stream.next().await.unwrap().unwrap();
+        assert_eq!((message.room_id, message.r#type.as_str()), (7, "gift"));
+        assert!(message.json.contains("小花花"));
+        match message.payload {
+            Some(Payload::Gift(gift)) => assert_eq!((gift.gift_id, gift.value), (31036, 100)),
+            other => panic!("unexpected payload {:?}", other),
+        }
+    }
+}
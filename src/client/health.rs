@@ -0,0 +1,248 @@
+// src/client/health.rs
+//! Liveness and readiness endpoints for container orchestrators
+//!
+//! `HealthProbe` reports the connection state, the age of the latest message
+//! and the pressure on the message bus. `/readyz` answers 200 while the
+//! client is connected and keeps up with its messages. `/healthz` answers
+//! 503 only when the client is wedged: reconnecting gave up, no message
+//! arrived for `stale_after` while connected, or the bus is full. Both return
+//! the report as JSON.
+//!
+//! ```ignore
+//! let probe = Arc::new(HealthProbe::new(status, bus.clone()));
+//! rt.spawn(health::record(bus.subscribe("health"), Arc::clone(&probe)));
+//! HttpServer::new().route("/", health::routes(probe)).serve(addr)?;
+//! ```
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::bus::{MessageBus, Subscriber};
+use crate::http_server::{Request, Response};
+use crate::status::{ConnectionState, ConnectionStatus};
+
+pub const HEALTH_PATH: &str = "/healthz";
+pub const READY_PATH: &str = "/readyz";
+
+/// Time without messages after which a connected client counts as wedged
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(300);
+
+/// Share of the bus capacity in use above which the client is not ready
+pub const DEFAULT_MAX_PRESSURE: f64 = 0.8;
+
+/// Health of the client at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// "connected", "reconnecting" or "disconnected"
+    pub state: &'static str,
+    pub reconnect_attempt: Option<u32>,
+    /// Seconds since the latest message, or since connecting if none came since
+    pub last_message_age_seconds: Option<f64>,
+    /// Batches on the bus not yet received by every subscriber
+    pub buffer_pending: usize,
+    pub buffer_capacity: usize,
+    /// `buffer_pending / buffer_capacity`
+    pub buffer_pressure: f64,
+    /// Why the client is not live, empty when it is
+    pub unhealthy: Vec<String>,
+    /// Why the client is not ready, empty when it is
+    pub unready: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn healthy(&self) -> bool {
+        self.unhealthy.is_empty()
+    }
+
+    pub fn ready(&self) -> bool {
+        self.unready.is_empty()
+    }
+}
+
+/// Source of health reports
+pub struct HealthProbe {
+    status: Arc<Mutex<ConnectionStatus>>,
+    bus: MessageBus,
+    last_message: Mutex<Option<Instant>>,
+    stale_after: Duration,
+    max_pressure: f64,
+}
+
+impl HealthProbe {
+    pub fn new(status: Arc<Mutex<ConnectionStatus>>, bus: MessageBus) -> Self {
+        Self {
+            status,
+            bus,
+            last_message: Mutex::new(None),
+            stale_after: DEFAULT_STALE_AFTER,
+            max_pressure: DEFAULT_MAX_PRESSURE,
+        }
+    }
+
+    /// Count a connected client as wedged after `stale_after` without messages
+    pub fn stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Report the client as not ready once the bus is fuller than `max_pressure`
+    pub fn max_pressure(mut self, max_pressure: f64) -> Self {
+        self.max_pressure = max_pressure;
+        self
+    }
+
+    /// Note a message received at `now`
+    pub fn message_at(&self, now: Instant) {
+        *self.last_message.lock().unwrap() = Some(now);
+    }
+
+    pub fn report(&self) -> HealthReport {
+        self.report_at(Instant::now())
+    }
+
+    /// Report as of `now`
+    pub fn report_at(&self, now: Instant) -> HealthReport {
+        let status = self.status.lock().unwrap().clone();
+        let last_message = *self.last_message.lock().unwrap();
+        let mut unhealthy = Vec::new();
+        let mut unready = Vec::new();
+
+        let (state, reconnect_attempt) = match status.state {
+            ConnectionState::Connected => ("connected", None),
+            ConnectionState::Reconnecting { attempt, .. } => ("reconnecting", Some(attempt)),
+            ConnectionState::Disconnected => ("disconnected", None),
+        };
+        match status.state {
+            ConnectionState::Connected => {}
+            ConnectionState::Reconnecting { .. } => unready.push("reconnecting".to_string()),
+            ConnectionState::Disconnected => {
+                let reason = match &status.last_error {
+                    Some(error) => format!("disconnected: {}", error),
+                    None => "disconnected".to_string(),
+                };
+                unhealthy.push(reason.clone());
+                unready.push(reason);
+            }
+        }
+
+        // A connection that got no message since connecting is as old as the connection
+        let since = match (last_message, status.connected_at) {
+            (Some(message), Some(connected)) => Some(message.max(connected)),
+            (message, connected) => message.or(connected),
+        };
+        let age = since.map(|since| now.saturating_duration_since(since));
+        if status.state == ConnectionState::Connected
+            && let Some(age) = age
+            && age > self.stale_after
+        {
+            let reason = format!("no message for {}s", age.as_secs());
+            unhealthy.push(reason.clone());
+            unready.push(reason);
+        }
+
+        let buffer_pending = self.bus.pending();
+        let buffer_capacity = self.bus.capacity();
+        let buffer_pressure = buffer_pending as f64 / buffer_capacity as f64;
+        if buffer_pending >= buffer_capacity {
+            unhealthy.push("message bus full".to_string());
+        }
+        if buffer_pressure > self.max_pressure {
+            unready.push(format!("message bus {:.0}% full", buffer_pressure * 100.0));
+        }
+
+        HealthReport {
+            state,
+            reconnect_attempt,
+            last_message_age_seconds: age.map(|age| age.as_secs_f64()),
+            buffer_pending,
+            buffer_capacity,
+            buffer_pressure,
+            unhealthy,
+            unready,
+        }
+    }
+}
+
+/// Note the arrival of every batch of `subscriber` in `probe` until the bus
+/// closes
+pub async fn record(mut subscriber: Subscriber, probe: Arc<HealthProbe>) {
+    while subscriber.recv().await.is_some() {
+        probe.message_at(Instant::now());
+    }
+}
+
+/// Routes answering `/healthz` and `/readyz` from `probe`
+pub fn routes(
+    probe: Arc<HealthProbe>,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static {
+    move |request| {
+        let report = probe.report();
+        let ok = match request.path.as_str() {
+            HEALTH_PATH => report.healthy(),
+            READY_PATH => report.ready(),
+            _ => return None,
+        };
+        let mut response = Response::json(&report);
+        if !ok {
+            response.status = 503;
+        }
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BiliMessage;
+    use crate::status::ConnectionEvent;
+
+    #[test]
+    fn test_health_and_readiness() {
+        let status = Arc::new(Mutex::new(ConnectionStatus::new()));
+        let bus = MessageBus::new(4);
+        let probe = Arc::new(
+            HealthProbe::new(Arc::clone(&status), bus.clone()).stale_after(Duration::from_secs(60)),
+        );
+        let routes = routes(Arc::clone(&probe));
+        let get = |path: &str| {
+            routes(&Request::parse(&format!("GET {} HTTP/1.1\r\n\r\n", path)).unwrap())
+                .map(|response| response.status)
+        };
+        // Not connected yet
+        assert_eq!((get(HEALTH_PATH), get(READY_PATH)), (Some(503), Some(503)));
+        assert_eq!(get("/metrics"), None);
+
+        let start = Instant::now();
+        status.lock().unwrap().apply_at(
+            ConnectionEvent::Connected {
+                room_id: "1".to_string(),
+                uid: 0,
+            },
+            start,
+        );
+        assert_eq!((get(HEALTH_PATH), get(READY_PATH)), (Some(200), Some(200)));
+
+        // Quiet for too long
+        let report = probe.report_at(start + Duration::from_secs(90));
+        assert_eq!(report.last_message_age_seconds, Some(90.0));
+        assert!(!report.healthy());
+        probe.message_at(start + Duration::from_secs(80));
+        assert!(probe.report_at(start + Duration::from_secs(90)).healthy());
+
+        // A subscriber that stopped reading fills the bus
+        let _stuck = bus.subscribe("stuck");
+        for _ in 0..4 {
+            bus.publish(vec![BiliMessage::Danmu {
+                user: "viewer".to_string(),
+                text: "hi".to_string(),
+                uid: 2,
+                guard_level: 0,
+                medal: None,
+            }]);
+        }
+        let report = probe.report_at(start + Duration::from_secs(90));
+        assert_eq!((report.buffer_pending, report.buffer_pressure), (4, 1.0));
+        assert!(!report.healthy() && !report.ready());
+    }
+}
@@ -0,0 +1,152 @@
+// src/client/history.rs
+//! Viewer count history of the current session, for drawing viewer graphs
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Samples kept by default: one heartbeat reply every 30 seconds for a day
+pub const DEFAULT_CAPACITY: usize = 2880;
+
+/// Viewer counts at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OnlineSample {
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    /// Popularity from the latest heartbeat reply
+    pub popularity: u64,
+    /// Watched count from the latest `WATCHED_CHANGE` message
+    pub watched: u64,
+}
+
+/// Ring buffer of viewer count samples; the oldest samples are dropped once full.
+///
+/// Each sample carries the latest value of both counts, so a graph can be drawn
+/// from either column alone.
+#[derive(Debug, Clone)]
+pub struct OnlineHistory {
+    samples: VecDeque<OnlineSample>,
+    capacity: usize,
+}
+
+impl Default for OnlineHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl OnlineHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record_popularity(&mut self, popularity: u64) {
+        self.record_popularity_at(unix_now(), popularity);
+    }
+
+    pub fn record_watched(&mut self, watched: u64) {
+        self.record_watched_at(unix_now(), watched);
+    }
+
+    pub fn record_popularity_at(&mut self, timestamp: u64, popularity: u64) {
+        let watched = self.latest().map_or(0, |sample| sample.watched);
+        self.push(OnlineSample {
+            timestamp,
+            popularity,
+            watched,
+        });
+    }
+
+    pub fn record_watched_at(&mut self, timestamp: u64, watched: u64) {
+        let popularity = self.latest().map_or(0, |sample| sample.popularity);
+        self.push(OnlineSample {
+            timestamp,
+            popularity,
+            watched,
+        });
+    }
+
+    fn push(&mut self, sample: OnlineSample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn latest(&self) -> Option<OnlineSample> {
+        self.samples.back().copied()
+    }
+
+    /// All samples, oldest first
+    pub fn samples(&self) -> Vec<OnlineSample> {
+        self.samples.iter().copied().collect()
+    }
+
+    /// Samples taken at or after the unix timestamp `timestamp`, oldest first
+    pub fn since(&self, timestamp: u64) -> Vec<OnlineSample> {
+        let start = self
+            .samples
+            .partition_point(|sample| sample.timestamp < timestamp);
+        self.samples.range(start..).copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_online_history_ring_buffer() {
+        let mut history = OnlineHistory::new(3);
+        history.record_popularity_at(10, 100);
+        history.record_watched_at(20, 7);
+        history.record_popularity_at(30, 120);
+        history.record_watched_at(40, 9);
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            history.samples(),
+            vec![
+                OnlineSample {
+                    timestamp: 20,
+                    popularity: 100,
+                    watched: 7,
+                },
+                OnlineSample {
+                    timestamp: 30,
+                    popularity: 120,
+                    watched: 7,
+                },
+                OnlineSample {
+                    timestamp: 40,
+                    popularity: 120,
+                    watched: 9,
+                },
+            ]
+        );
+        assert_eq!(history.since(30).len(), 2);
+        assert!(history.since(41).is_empty());
+        assert_eq!(history.latest().map(|sample| sample.watched), Some(9));
+    }
+}
@@ -0,0 +1,323 @@
+// src/client/http_server.rs
+//! Minimal HTTP server for the metrics and the JSON API
+//!
+//! Routes are matched by path prefix in the order added; a request no route
+//! answers goes to the fallback, or gets a 404. Stream routes take over the
+//! connection instead, for long-lived responses such as server-sent events.
+//! Each connection is served on its own thread.
+
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Largest request body read
+const MAX_BODY: usize = 1 << 20;
+
+/// HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Percent-decoded path, without the query
+    pub path: String,
+    /// Raw query string, without the `?`
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Parse a request, `None` if malformed
+    pub fn parse(text: &str) -> Option<Self> {
+        let (head, body) = text.split_once("\r\n\r\n").unwrap_or((text, ""));
+        let mut lines = head.split("\r\n");
+        let mut parts = lines.next()?.split(' ');
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Some(Self {
+            method,
+            path: percent_decode(path),
+            query: query.to_string(),
+            headers,
+            body: body.to_string(),
+        })
+    }
+
+    /// Read a request from `stream`, with its body up to `Content-Length`
+    fn read(stream: &mut TcpStream) -> Option<Self> {
+        let mut data = Vec::new();
+        let mut buf = [0_u8; 4096];
+        loop {
+            let len = stream.read(&mut buf).ok()?;
+            data.extend_from_slice(&buf[..len]);
+            let head_end = data.windows(4).position(|w| w == b"\r\n\r\n");
+            let complete = match head_end {
+                Some(end) => {
+                    let head = String::from_utf8_lossy(&data[..end]);
+                    let body_len = head
+                        .split("\r\n")
+                        .filter_map(|line| line.split_once(':'))
+                        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+                        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if body_len > MAX_BODY {
+                        return None;
+                    }
+                    data.len() >= end + 4 + body_len
+                }
+                None => data.len() > MAX_BODY,
+            };
+            if complete || len == 0 {
+                return Self::parse(&String::from_utf8_lossy(&data));
+            }
+        }
+    }
+
+    /// Value of a header, matched case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Decoded value of a query parameter
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(self.query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Response with a complete body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: String) -> Self {
+        Self {
+            status,
+            content_type: content_type.to_string(),
+            body,
+        }
+    }
+
+    /// 200 response with `value` as JSON
+    pub fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self::new(200, "application/json", body),
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    /// Error response with a JSON `{"error": message}` body
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::new(
+            status,
+            "application/json",
+            serde_json::json!({ "error": message }).to_string(),
+        )
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            415 => "Unsupported Media Type",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+/// Answer to a request, `None` to leave it to the next route
+pub type Handler = Arc<dyn Fn(&Request) -> Option<Response> + Send + Sync>;
+
+/// Writer of the whole response to a request, returning when done with the
+/// connection
+pub type StreamHandler = Arc<dyn Fn(&Request, TcpStream) + Send + Sync>;
+
+/// HTTP server dispatching requests to handlers by path prefix
+///
+/// ```ignore
+/// HttpServer::new()
+///     .route("/api/users/", chat_store::routes(store))
+///     .fallback(metrics::handler(status))
+///     .serve("127.0.0.1:9184")?;
+/// ```
+#[derive(Default)]
+pub struct HttpServer {
+    routes: Vec<(String, Handler)>,
+    streams: Vec<(String, StreamHandler)>,
+    fallback: Option<Handler>,
+}
+
+impl HttpServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests whose path starts with `prefix`
+    pub fn route<F>(mut self, prefix: &str, handler: F) -> Self
+    where
+        F: Fn(&Request) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.routes.push((prefix.to_string(), Arc::new(handler)));
+        self
+    }
+
+    /// Hand the connections of requests whose path starts with `prefix` to
+    /// `handler`; stream routes are checked before the other routes
+    pub fn stream<F>(mut self, prefix: &str, handler: F) -> Self
+    where
+        F: Fn(&Request, TcpStream) + Send + Sync + 'static,
+    {
+        self.streams.push((prefix.to_string(), Arc::new(handler)));
+        self
+    }
+
+    /// Answer requests no route answered
+    pub fn fallback<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Request) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    /// Response to `request`
+    pub fn dispatch(&self, request: &Request) -> Response {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| request.path.starts_with(prefix.as_str()))
+            .map(|(_, handler)| handler)
+            .chain(&self.fallback)
+            .find_map(|handler| handler(request))
+            .unwrap_or_else(|| Response::error(404, "not found"))
+    }
+
+    /// Serve on `addr` from a background thread, returning the bound address
+    pub fn serve(self, addr: &str) -> Result<SocketAddr, String> {
+        let listener =
+            TcpListener::bind(addr).map_err(|e| format!("http bind {} failed: {}", addr, e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("http address unavailable: {}", e))?;
+        let server = Arc::new(self);
+        thread::Builder::new()
+            .name("blivedm-http".to_string())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let server = Arc::clone(&server);
+                    let spawned = thread::Builder::new()
+                        .name("blivedm-http-conn".to_string())
+                        .spawn(move || server.handle(stream));
+                    if let Err(e) = spawned {
+                        log::warn!("failed to start http connection thread: {}", e);
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start http thread: {}", e))?;
+        Ok(local_addr)
+    }
+
+    fn handle(&self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+        let response = match Request::read(&mut stream) {
+            Some(request) => {
+                if let Some((_, handler)) = self
+                    .streams
+                    .iter()
+                    .find(|(prefix, _)| request.path.starts_with(prefix.as_str()))
+                {
+                    let _ = stream.set_read_timeout(None);
+                    return handler(&request, stream);
+                }
+                self.dispatch(&request)
+            }
+            None => Response::error(400, "malformed request"),
+        };
+        if let Err(e) = response.write_to(&mut stream) {
+            log::debug!("http response failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_server_dispatch() {
+        let request =
+            Request::parse("GET /api/users/%E5%B0%8F%E6%98%8E?limit=5&x=a%20b HTTP/1.1\r\nLast-Event-ID: 7\r\n\r\n")
+                .unwrap();
+        assert_eq!(request.path, "/api/users/小明");
+        assert_eq!(request.query_param("limit").as_deref(), Some("5"));
+        assert_eq!(request.query_param("x").as_deref(), Some("a b"));
+        assert_eq!(request.header("last-event-id"), Some("7"));
+        let post = Request::parse("POST /graphql HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}").unwrap();
+        assert_eq!(post.body, "{}");
+
+        let server = HttpServer::new()
+            .route("/api/", |request| {
+                (request.path == "/api/ping").then(|| Response::json(&"pong"))
+            })
+            .fallback(|_| Some(Response::new(200, "text/plain", "metrics".to_string())));
+        let get = |path: &str| Request::parse(&format!("GET {} HTTP/1.1\r\n\r\n", path)).unwrap();
+        assert_eq!(server.dispatch(&get("/api/ping")).body, "\"pong\"");
+        assert_eq!(server.dispatch(&get("/api/other")).body, "metrics");
+        assert_eq!(HttpServer::new().dispatch(&get("/")).status, 404);
+    }
+}
@@ -0,0 +1,165 @@
+// src/client/images.rs
+//! Room emotes and user avatars, for terminals that can show images
+//!
+//! `EmoteCatalog` maps the text of each emote usable in a room, such as
+//! `[dog]` or the name of a room sticker, to its image. `fetch_avatar_url`
+//! looks up the avatar of a user and `fetch_image` downloads either as a small
+//! PNG, converted by the image CDN.
+
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::auth::apply_request_headers;
+use crate::room_management::{get_live_api, get_live_api_with_cookies};
+
+pub const EMOTICONS_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v2/emoticon/GetEmoticons";
+pub const USER_INFO_URL: &str = "https://api.live.bilibili.com/live_user/v1/Master/info";
+
+/// Emote text to image URL, fetched once per room
+#[derive(Debug, Clone, Default)]
+pub struct EmoteCatalog {
+    emotes: HashMap<String, String>,
+}
+
+impl EmoteCatalog {
+    /// Fetch the emotes of a room; room stickers need the cookies of a login
+    pub fn fetch(room_id: u64, cookies: Option<&str>) -> Result<Self, String> {
+        let data = get_live_api_with_cookies(
+            &format!("{}?platform=pc&room_id={}", EMOTICONS_URL, room_id),
+            "emoticons",
+            cookies,
+        )?;
+        let catalog = Self::from_data(&data);
+        log::info!("Loaded {} emotes for room {}", catalog.len(), room_id);
+        Ok(catalog)
+    }
+
+    /// Build a catalog from the `data` field of an emoticons response
+    pub fn from_data(data: &Value) -> Self {
+        let emotes = data["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|package| package["emoticons"].as_array().into_iter().flatten())
+            .filter_map(|emote| {
+                let text = emote["emoji"].as_str().filter(|text| !text.is_empty())?;
+                let url = emote["url"].as_str().filter(|url| !url.is_empty())?;
+                Some((text.to_string(), url.to_string()))
+            })
+            .collect();
+        Self { emotes }
+    }
+
+    /// Image URL of the emote written as `text`
+    pub fn url(&self, text: &str) -> Option<&str> {
+        self.emotes.get(text).map(String::as_str)
+    }
+
+    /// Emotes in a danmaku: the whole text if it is a sticker, otherwise each
+    /// `[name]` token that names an emote, as byte ranges and image URLs
+    pub fn find(&self, text: &str) -> Vec<(Range<usize>, &str)> {
+        if let Some(url) = self.url(text) {
+            return vec![(0..text.len(), url)];
+        }
+        let mut found = Vec::new();
+        let mut rest = 0;
+        while let Some(start) = text[rest..].find('[').map(|start| rest + start) {
+            let Some(end) = text[start..].find(']').map(|end| start + end + 1) else {
+                break;
+            };
+            // A `[` inside the brackets starts the next candidate instead
+            let next = text[start + 1..end].rfind('[').map(|next| start + 1 + next);
+            let start = next.unwrap_or(start);
+            if let Some(url) = self.url(&text[start..end]) {
+                found.push((start..end, url));
+            }
+            rest = end;
+        }
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.emotes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emotes.is_empty()
+    }
+}
+
+/// Fetch the avatar URL of a user
+pub fn fetch_avatar_url(uid: u64) -> Result<String, String> {
+    let data = get_live_api(&format!("{}?uid={}", USER_INFO_URL, uid), "user info")?;
+    data["info"]["face"]
+        .as_str()
+        .filter(|face| !face.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| format!("user {} has no avatar", uid))
+}
+
+/// URL of the image at `url` converted to a `size` × `size` PNG by the image CDN
+pub fn png_url(url: &str, size: u32) -> String {
+    let url = match url.strip_prefix("http://") {
+        Some(rest) => format!("https://{}", rest),
+        None if url.starts_with("//") => format!("https:{}", url),
+        None => url.to_string(),
+    };
+    format!("{}@{}w_{}h.png", url, size, size)
+}
+
+/// Download the image at `url` as a `size` × `size` PNG
+pub fn fetch_image(url: &str, size: u32) -> Result<Vec<u8>, String> {
+    let client = reqwest::blocking::Client::builder()
+        .https_only(true)
+        .build()
+        .map_err(|e| format!("http client init failed: {}", e))?;
+    let mut headers = HeaderMap::new();
+    apply_request_headers(&mut headers);
+    let bytes = client
+        .get(png_url(url, size))
+        .headers(headers)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.bytes())
+        .map_err(|e| format!("fetch image {} failed: {}", url, e))?;
+    Ok(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_emote_catalog() {
+        let catalog = EmoteCatalog::from_data(&json!({
+            "data": [
+                { "pkg_name": "通用表情", "emoticons": [
+                    { "emoji": "[dog]", "url": "https://i0.hdslb.com/dog.png" },
+                    { "emoji": "[笑]", "url": "https://i0.hdslb.com/laugh.png" },
+                ] },
+                { "pkg_name": "房间专属表情", "emoticons": [
+                    { "emoji": "赞", "url": "https://i0.hdslb.com/like.png" },
+                    { "emoji": "", "url": "https://i0.hdslb.com/none.png" },
+                ] },
+            ]
+        }));
+        assert_eq!(catalog.len(), 3);
+        assert_eq!(
+            catalog.find("赞"),
+            vec![(0..3, "https://i0.hdslb.com/like.png")]
+        );
+        let found = catalog.find("[x[dog]] hi [笑][nope]");
+        let ranges: Vec<_> = found.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, vec![2..7, 12..17]);
+        assert!(catalog.find("[unclosed").is_empty());
+
+        assert_eq!(
+            png_url("http://i0.hdslb.com/dog.png", 48),
+            "https://i0.hdslb.com/dog.png@48w_48h.png"
+        );
+    }
+}
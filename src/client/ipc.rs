@@ -0,0 +1,121 @@
+// src/client/ipc.rs
+//! Local IPC output of received messages
+//!
+//! `serve` listens on a Unix domain socket (a named pipe such as
+//! `\\.\pipe\blivedm` on Windows) and writes every message of a `MessageBus`
+//! to each connected process as a JSON line (`BiliMessage::to_json`), so
+//! companion processes get the feed without a TCP port:
+//!
+//! ```ignore
+//! rt.spawn(ipc::serve("/tmp/blivedm.sock".to_string(), bus.clone()));
+//! // socat - UNIX-CONNECT:/tmp/blivedm.sock | jq .
+//! ```
+
+use futures::StreamExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::bus::{MessageBus, MessageStream};
+
+/// Write the messages of `messages` as JSON lines to `writer` until the bus
+/// closes or the reader goes away
+async fn feed<W: AsyncWrite + Unpin>(mut writer: W, mut messages: MessageStream) {
+    while let Some(msg) = messages.next().await {
+        let mut line = msg.to_json().to_string();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Serve the messages of `bus` on the Unix socket `path` until the runtime
+/// shuts down. A socket file left by a previous run is replaced.
+#[cfg(unix)]
+pub async fn serve(path: String, bus: MessageBus) {
+    if let Err(e) = serve_unix(&path, bus).await {
+        log::warn!("IPC output on {} failed: {}", path, e);
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(path: &str, bus: MessageBus) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    log::info!("serving messages as JSON lines on {}", path);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(feed(stream, bus.stream("ipc")));
+    }
+}
+
+/// Serve the messages of `bus` on the named pipe `path` until the runtime
+/// shuts down
+#[cfg(windows)]
+pub async fn serve(path: String, bus: MessageBus) {
+    if let Err(e) = serve_pipe(&path, bus).await {
+        log::warn!("IPC output on {} failed: {}", path, e);
+    }
+}
+
+#[cfg(windows)]
+async fn serve_pipe(path: &str, bus: MessageBus) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(path)?;
+    log::info!("serving messages as JSON lines on {}", path);
+    loop {
+        server.connect().await?;
+        // Open the next instance before handing this one over, so a client
+        // connecting meanwhile finds the pipe
+        let connected = std::mem::replace(&mut server, ServerOptions::new().create(path)?);
+        tokio::spawn(feed(connected, bus.stream("ipc")));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::models::BiliMessage;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[tokio::test]
+    async fn test_ipc_writes_json_lines() {
+        let path = std::env::temp_dir().join(format!("blivedm-ipc-{}.sock", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let bus = MessageBus::default();
+        tokio::spawn(serve(path.clone(), bus.clone()));
+
+        let mut stream = None;
+        for _ in 0..100 {
+            if let Ok(connected) = tokio::net::UnixStream::connect(&path).await {
+                stream = Some(connected);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        while bus.subscriber_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        bus.publish(vec![BiliMessage::Danmu {
+            user: "viewer".to_string(),
+            text: "hi".to_string(),
+            uid: 2,
+            guard_level: 0,
+            medal: None,
+        }]);
+
+        let mut lines = BufReader::new(stream.unwrap()).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["type"], "danmu");
+        assert_eq!(json["text"], "hi");
+        let _ = std::fs::remove_file(&path);
+    }
+}
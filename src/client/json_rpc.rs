@@ -0,0 +1,287 @@
+// src/client/json_rpc.rs
+//! JSON-RPC 2.0 over stdin/stdout, for hosts embedding the client as a child
+//! process
+//!
+//! Each line on stdin is a request and each line on stdout a response or a
+//! notification. The methods are:
+//!
+//! - `subscribe`, with an optional `types` list of message kinds: messages
+//!   from then on are sent as `message` notifications with
+//!   `BiliMessage::to_json` as params; `unsubscribe` stops them
+//! - `send_danmaku`, with a `text`: sends a danmaku to the room (needs a login)
+//! - `stats`: connection state, viewer counts and messages received by kind
+//!
+//! ```text
+//! → {"jsonrpc":"2.0","id":1,"method":"subscribe","params":{"types":["danmu"]}}
+//! ← {"jsonrpc":"2.0","id":1,"result":true}
+//! ← {"jsonrpc":"2.0","method":"message","params":{"type":"danmu","user":"...","text":"..."}}
+//! ```
+
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::live_room::LiveRoom;
+use crate::models::BiliMessage;
+use crate::runtime::AsyncRuntime;
+use crate::status::ConnectionState;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// A method that failed, such as a danmaku the API refused
+pub const SERVER_ERROR: i64 = -32000;
+
+type SendFn = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+type StatsFn = Box<dyn Fn() -> Value + Send + Sync>;
+
+/// State of one JSON-RPC connection: the subscription and the message counts
+pub struct JsonRpcSession {
+    send: SendFn,
+    stats: StatsFn,
+    /// Kinds sent as notifications, `None` before `subscribe`, empty for all
+    subscription: Mutex<Option<HashSet<String>>>,
+    /// Messages received by kind
+    counts: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl JsonRpcSession {
+    /// Session sending danmaku with `send` and answering `stats` with
+    /// `stats`, to which the message counts are added
+    pub fn new<S, T>(send: S, stats: T) -> Self
+    where
+        S: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+        T: Fn() -> Value + Send + Sync + 'static,
+    {
+        Self {
+            send: Box::new(send),
+            stats: Box::new(stats),
+            subscription: Mutex::new(None),
+            counts: Mutex::default(),
+        }
+    }
+
+    /// Session over `room`, sending danmaku on `rt`
+    pub fn for_room(room: Arc<LiveRoom>, rt: AsyncRuntime) -> Self {
+        let sender = Arc::clone(&room);
+        Self::new(
+            move |text| rt.block_on(sender.send_danmaku(text)),
+            move || {
+                let stats = room.stats();
+                let connection = &stats.connection;
+                json!({
+                    "room_id": room.context().room_id,
+                    "connected": connection.state == ConnectionState::Connected,
+                    "uptime_seconds": connection.uptime().as_secs_f64(),
+                    "heartbeat_rtt_ms": connection.heartbeat_rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+                    "last_error": connection.last_error,
+                    "online": stats.online,
+                    "suppressed": stats.suppressed,
+                })
+            },
+        )
+    }
+
+    /// Response to a request line, `None` for a notification
+    pub fn handle_line(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request["method"].as_str() else {
+            return Some(error(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "missing method",
+            ));
+        };
+        let result = self.call(method, &request["params"]);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error(id, code, &message),
+        })
+    }
+
+    fn call(&self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "subscribe" => {
+                let types = match &params["types"] {
+                    Value::Null => HashSet::new(),
+                    Value::Array(types) => types
+                        .iter()
+                        .map(|kind| kind.as_str().map(str::to_string))
+                        .collect::<Option<_>>()
+                        .ok_or((INVALID_PARAMS, "types must be strings".to_string()))?,
+                    _ => return Err((INVALID_PARAMS, "types must be a list".to_string())),
+                };
+                *self.subscription.lock().unwrap() = Some(types);
+                Ok(Value::Bool(true))
+            }
+            "unsubscribe" => {
+                *self.subscription.lock().unwrap() = None;
+                Ok(Value::Bool(true))
+            }
+            "send_danmaku" => {
+                let text = params["text"]
+                    .as_str()
+                    .filter(|text| !text.trim().is_empty())
+                    .ok_or((INVALID_PARAMS, "missing text".to_string()))?;
+                (self.send)(text).map_err(|e| (SERVER_ERROR, e))?;
+                Ok(Value::Bool(true))
+            }
+            "stats" => {
+                let mut stats = (self.stats)();
+                stats["messages"] = json!(*self.counts.lock().unwrap());
+                Ok(stats)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        }
+    }
+
+    /// Count `msg`, returning its notification if subscribed to its kind
+    pub fn message(&self, msg: &BiliMessage) -> Option<Value> {
+        *self.counts.lock().unwrap().entry(msg.kind()).or_default() += 1;
+        let subscription = self.subscription.lock().unwrap();
+        let types = subscription.as_ref()?;
+        (types.is_empty() || types.contains(msg.kind()))
+            .then(|| json!({ "jsonrpc": "2.0", "method": "message", "params": msg.to_json() }))
+    }
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Write `value` as one line to stdout, `false` once stdout is closed
+fn write_line(value: &Value) -> bool {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", value)
+        .and_then(|_| stdout.flush())
+        .is_ok()
+}
+
+/// Answer requests from stdin about `room` until stdin is closed
+pub fn serve_stdio(room: LiveRoom, rt: AsyncRuntime) -> Result<(), String> {
+    let room = Arc::new(room);
+    let mut messages = room.messages();
+    let session = Arc::new(JsonRpcSession::for_room(room, rt));
+
+    let notifier = Arc::clone(&session);
+    thread::Builder::new()
+        .name("blivedm-json-rpc".to_string())
+        .spawn(move || {
+            futures::executor::block_on(async {
+                while let Some(msg) = messages.next().await {
+                    if let Some(notification) = notifier.message(&msg)
+                        && !write_line(&notification)
+                    {
+                        break;
+                    }
+                }
+            })
+        })
+        .map_err(|e| format!("failed to start message thread: {}", e))?;
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("failed to read stdin: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = session.handle_line(&line)
+            && !write_line(&response)
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_rpc_session() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&sent);
+        let session = JsonRpcSession::new(
+            move |text| {
+                log.lock().unwrap().push(text.to_string());
+                Ok(())
+            },
+            || json!({ "connected": true }),
+        );
+        let danmu = BiliMessage::Danmu {
+            user: "viewer".to_string(),
+            text: "hi".to_string(),
+            uid: 2,
+            guard_level: 0,
+            medal: None,
+        };
+        let rank = BiliMessage::OnlineRankCount {
+            count: 1,
+            online_count: 2,
+        };
+
+        // Counted but not sent before subscribing
+        assert_eq!(session.message(&danmu), None);
+        let response = session
+            .handle_line(
+                r#"{"jsonrpc":"2.0","id":1,"method":"subscribe","params":{"types":["danmu"]}}"#,
+            )
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": true })
+        );
+        let notification = session.message(&danmu).unwrap();
+        assert_eq!(notification["method"], "message");
+        assert_eq!(notification["params"]["text"], "hi");
+        assert_eq!(session.message(&rank), None);
+
+        let response = session
+            .handle_line(
+                r#"{"jsonrpc":"2.0","id":"a","method":"send_danmaku","params":{"text":"hello"}}"#,
+            )
+            .unwrap();
+        assert_eq!(response["result"], true);
+        assert_eq!(*sent.lock().unwrap(), vec!["hello".to_string()]);
+
+        let stats = session
+            .handle_line(r#"{"jsonrpc":"2.0","id":2,"method":"stats"}"#)
+            .unwrap();
+        assert_eq!(stats["result"]["connected"], true);
+        assert_eq!(stats["result"]["messages"]["danmu"], 2);
+
+        let errors = [
+            ("not json", PARSE_ERROR),
+            (
+                r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#,
+                METHOD_NOT_FOUND,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","id":4,"method":"send_danmaku"}"#,
+                INVALID_PARAMS,
+            ),
+        ];
+        for (line, code) in errors {
+            assert_eq!(session.handle_line(line).unwrap()["error"]["code"], code);
+        }
+        // Notifications get no response
+        assert_eq!(
+            session.handle_line(r#"{"jsonrpc":"2.0","method":"unsubscribe"}"#),
+            None
+        );
+        assert_eq!(session.message(&danmu), None);
+    }
+}
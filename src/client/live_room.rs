@@ -0,0 +1,276 @@
+// src/client/live_room.rs
+//! Batteries-included room connection for simple bots
+//!
+//! `LiveRoom::open` connects, keeps the heartbeat and receive loops running on
+//! background threads and publishes the messages on a `MessageBus`. It is the
+//! one implementation of these loops; the C, Python and Node bindings wrap it.
+//!
+//! ```ignore
+//! let room = LiveRoom::open(24779526, Credentials::Browser)?;
+//! let mut messages = room.messages();
+//! while let Some(msg) = messages.next().await {
+//!     if let BiliMessage::Danmu { text, .. } = &msg
+//!         && text == "!ping"
+//!     {
+//!         room.send_danmaku("pong").await?;
+//!     }
+//! }
+//! ```
+
+use futures::channel::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::bus::{MessageBus, MessageStream, Subscriber};
+use crate::history::{OnlineHistory, OnlineSample};
+use crate::models::BiliMessage;
+use crate::protocol::CommandFilter;
+use crate::runtime::{AsyncRuntime, RuntimeFlavor};
+use crate::scheduler::{EventContext, MAX_BATCH_SIZE};
+use crate::send_queue::{SendLimits, SendQueue};
+use crate::status::{ConnectionControl, ConnectionRequest, ConnectionStatus};
+use crate::websocket::{BiliLiveClient, BiliLiveClientBuilder};
+
+/// Messages a single receive may queue before they are published
+const CHANNEL_SIZE: usize = 256;
+
+/// Step of the heartbeat thread's sleep, so closing does not wait a whole interval
+const HEARTBEAT_TICK: Duration = Duration::from_millis(100);
+
+/// How a `LiveRoom` logs in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Credentials {
+    /// Cookies of a browser logged into bilibili.com
+    #[default]
+    Browser,
+    /// A cookie header such as `SESSDATA=...; bili_jct=...`
+    Cookies(String),
+    /// No login; messages can be read but not sent
+    Guest,
+}
+
+/// Snapshot of a room connection
+#[derive(Debug, Clone)]
+pub struct RoomStats {
+    pub connection: ConnectionStatus,
+    /// Latest popularity and watched count
+    pub online: Option<OnlineSample>,
+    /// Noisy broadcast commands dropped so far
+    pub suppressed: u64,
+}
+
+/// Connected live room; dropping it (or `close`) stops the background threads
+pub struct LiveRoom {
+    client: Arc<Mutex<BiliLiveClient>>,
+    context: EventContext,
+    bus: MessageBus,
+    status: Arc<Mutex<ConnectionStatus>>,
+    online_history: Arc<Mutex<OnlineHistory>>,
+    command_filter: Arc<CommandFilter>,
+    control: Arc<ConnectionControl>,
+    /// Runtime given to the builder, for the send queue
+    runtime: Option<AsyncRuntime>,
+    /// Created on the first send
+    send_queue: Mutex<Option<SendQueue>>,
+    running: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl LiveRoom {
+    /// Connect to `room_id`; blocks until connected
+    pub fn open(room_id: u64, credentials: Credentials) -> Result<Self, String> {
+        Self::open_with(BiliLiveClient::builder(room_id.to_string()), credentials)
+    }
+
+    /// Connect with the other options of `builder`, e.g. a proxy
+    pub fn open_with(
+        builder: BiliLiveClientBuilder,
+        credentials: Credentials,
+    ) -> Result<Self, String> {
+        let builder = match credentials {
+            Credentials::Browser => builder.browser_cookies(true),
+            Credentials::Cookies(cookies) => builder.cookies(cookies).browser_cookies(false),
+            Credentials::Guest => builder.browser_cookies(false),
+        };
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let mut client = builder.build(tx)?;
+        client.send_auth();
+        client.send_heart_beat();
+
+        let cookies = Some(client.cookies().to_string()).filter(|cookies| !cookies.is_empty());
+        let room_id = client.room_id().parse::<u64>().unwrap_or(0);
+        let heartbeat_interval = client.heartbeat_interval();
+        let mut room = Self {
+            context: EventContext::new(cookies, room_id)
+                .with_request_headers(client.request_headers()),
+            bus: MessageBus::default(),
+            status: client.connection_status(),
+            online_history: client.online_history(),
+            command_filter: client.command_filter(),
+            control: client.connection_control(),
+            runtime: client.runtime().cloned(),
+            send_queue: Mutex::new(None),
+            client: Arc::new(Mutex::new(client)),
+            running: Arc::new(AtomicBool::new(true)),
+            threads: Vec::new(),
+        };
+        // On failure, dropping the room stops the threads already started
+        let heartbeat = room.spawn_heartbeat(heartbeat_interval)?;
+        room.threads.push(heartbeat);
+        let receiver = room.spawn_receiver(rx)?;
+        room.threads.push(receiver);
+        Ok(room)
+    }
+
+    fn spawn_heartbeat(&self, interval: Duration) -> Result<JoinHandle<()>, String> {
+        let client = Arc::clone(&self.client);
+        let running = Arc::clone(&self.running);
+        spawn("blivedm-heartbeat", move || {
+            let mut elapsed = Duration::ZERO;
+            while running.load(Ordering::Relaxed) {
+                thread::sleep(HEARTBEAT_TICK);
+                elapsed += HEARTBEAT_TICK;
+                if elapsed < interval {
+                    continue;
+                }
+                elapsed = Duration::ZERO;
+                match client.lock() {
+                    Ok(mut client) => client.send_heart_beat(),
+                    Err(_) => break,
+                }
+            }
+        })
+    }
+
+    /// Receive and publish the messages of each read on the bus
+    fn spawn_receiver(
+        &self,
+        mut rx: mpsc::Receiver<BiliMessage>,
+    ) -> Result<JoinHandle<()>, String> {
+        let client = Arc::clone(&self.client);
+        let running = Arc::clone(&self.running);
+        let bus = self.bus.clone();
+        spawn("blivedm-receive", move || {
+            while running.load(Ordering::Relaxed) {
+                match client.lock() {
+                    Ok(mut client) => {
+                        if let Err(e) = client.receive() {
+                            log::error!("{}", e);
+                        }
+                    }
+                    Err(_) => break,
+                }
+                let mut batch = Vec::new();
+                while batch.len() < MAX_BATCH_SIZE
+                    && let Ok(msg) = rx.try_recv()
+                {
+                    batch.push(msg);
+                }
+                if batch.is_empty() {
+                    thread::sleep(Duration::from_millis(10));
+                } else {
+                    bus.publish(batch);
+                }
+            }
+        })
+    }
+
+    /// Messages received from now on; each call is an independent stream,
+    /// ending once the room is closed
+    pub fn messages(&self) -> MessageStream {
+        self.bus.stream("live room")
+    }
+
+    /// `messages` as batches, for threads outside async code (see
+    /// `Subscriber::blocking_recv`); `name` identifies it in lag warnings
+    pub fn subscribe(&self, name: &str) -> Subscriber {
+        self.bus.subscribe(name)
+    }
+
+    /// Send a danmaku to the room through the `send_queue`; needs a login
+    pub async fn send_danmaku(&self, text: &str) -> Result<(), String> {
+        self.send_queue()?
+            .send(text)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Queue of the danmaku sent to the room, also for replies and mentions.
+    /// Its task runs on the runtime given to the builder, else on the calling
+    /// one, else on a runtime of its own.
+    pub fn send_queue(&self) -> Result<SendQueue, String> {
+        let mut queue = self
+            .send_queue
+            .lock()
+            .map_err(|_| "send queue lock poisoned".to_string())?;
+        if let Some(queue) = queue.as_ref() {
+            return Ok(queue.clone());
+        }
+        let runtime = match self.runtime.clone().or_else(AsyncRuntime::current) {
+            Some(runtime) => runtime,
+            None => AsyncRuntime::new(RuntimeFlavor::CurrentThread)?,
+        };
+        let created = SendQueue::new(self.context.clone(), SendLimits::default(), &runtime);
+        *queue = Some(created.clone());
+        Ok(created)
+    }
+
+    pub fn stats(&self) -> RoomStats {
+        RoomStats {
+            connection: self
+                .status
+                .lock()
+                .map(|status| status.clone())
+                .unwrap_or_else(|_| ConnectionStatus::new()),
+            online: self
+                .online_history
+                .lock()
+                .ok()
+                .and_then(|history| history.latest()),
+            suppressed: self.command_filter.suppressed(),
+        }
+    }
+
+    /// Context with the room id and cookies, for scheduler handlers or the
+    /// moderation and room management APIs
+    pub fn context(&self) -> &EventContext {
+        &self.context
+    }
+
+    /// The underlying client, e.g. to switch rooms or subscribe to connection events
+    pub fn client(&self) -> Arc<Mutex<BiliLiveClient>> {
+        Arc::clone(&self.client)
+    }
+
+    /// Disconnect and wait for the background threads to exit, interrupting a
+    /// blocked receive; message streams end once the room is dropped
+    pub fn close(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if self.threads.is_empty() {
+            return;
+        }
+        self.running.store(false, Ordering::Relaxed);
+        self.control.request(ConnectionRequest::Disconnect);
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for LiveRoom {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn spawn<F: FnOnce() + Send + 'static>(name: &str, f: F) -> Result<JoinHandle<()>, String> {
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(f)
+        .map_err(|e| format!("failed to start {} thread: {}", name, e))
+}
@@ -0,0 +1,328 @@
+// src/client/login.rs
+//! Logging in with an SMS code and checking stored logins
+//!
+//! `check_login` asks the nav API whether cookies are still logged in, and
+//! reads the expiry time carried in the `SESSDATA` cookie.
+//!
+//! Sending an SMS code is guarded by a geetest captcha, which has to be solved in a
+//! browser; the challenge is handed out and the solution passed back in:
+//!
+//! ```ignore
+//! let mut login = auth::sms_login("13800000000");
+//! let captcha = login.captcha()?;
+//! // solve captcha.gt / captcha.challenge, e.g. on a geetest test page
+//! login.send_code(&captcha, &GeetestResult::new(&validate))?;
+//! let cookies = login.verify(&code_from_sms)?;
+//! ```
+
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, SET_COOKIE};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::auth::{UID_INIT_URL, apply_request_headers};
+
+pub const CAPTCHA_URL: &str = "https://passport.bilibili.com/x/passport-login/captcha";
+pub const SMS_SEND_URL: &str = "https://passport.bilibili.com/x/passport-login/web/sms/send";
+pub const SMS_LOGIN_URL: &str = "https://passport.bilibili.com/x/passport-login/web/login/sms";
+
+/// Country calling code of mainland China
+pub const DEFAULT_COUNTRY_CODE: u32 = 86;
+
+const SOURCE: &str = "main_web";
+
+/// Logins expiring sooner than this deserve a warning
+pub const EXPIRY_WARNING: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Account of logged-in cookies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginStatus {
+    pub uid: u64,
+    pub username: String,
+    /// Unix timestamp at which the session expires, if the cookies carry it
+    pub expires_at: Option<u64>,
+}
+
+impl LoginStatus {
+    fn from_nav_data(data: &Value, expires_at: Option<u64>) -> Option<Self> {
+        if data["isLogin"].as_bool() != Some(true) {
+            return None;
+        }
+        Some(Self {
+            uid: data["mid"].as_u64().unwrap_or(0),
+            username: data["uname"].as_str().unwrap_or("").to_string(),
+            expires_at,
+        })
+    }
+
+    /// Time left until the session expires, zero once expired
+    pub fn expires_in(&self) -> Option<Duration> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        self.expires_at
+            .map(|expires_at| Duration::from_secs(expires_at.saturating_sub(now)))
+    }
+}
+
+/// Expiry unix timestamp in a `SESSDATA` cookie (`<token>%2C<expiry>%2C<...>`)
+pub fn sessdata_expiry(cookies: &str) -> Option<u64> {
+    let sessdata = cookies
+        .split(';')
+        .find_map(|cookie| cookie.trim().strip_prefix("SESSDATA="))?;
+    let sessdata = sessdata.replace("%2C", ",").replace("%2c", ",");
+    sessdata.split(',').nth(1)?.parse().ok()
+}
+
+/// Check `cookies` against the nav API: the account when logged in, `None`
+/// when the cookies are invalid or expired
+pub fn check_login(cookies: &str) -> Result<Option<LoginStatus>, String> {
+    let mut headers = headers();
+    let cookie = HeaderValue::from_str(cookies).map_err(|e| format!("invalid cookies: {}", e))?;
+    headers.insert(COOKIE, cookie);
+    let body: Value = Client::new()
+        .get(UID_INIT_URL)
+        .headers(headers)
+        .send()
+        .and_then(|resp| resp.json())
+        .map_err(|e| format!("login check failed: {}", e))?;
+    // Not being logged in is code -101 with isLogin false in the data
+    match body["code"].as_i64() {
+        Some(0) | Some(-101) => Ok(LoginStatus::from_nav_data(
+            &body["data"],
+            sessdata_expiry(cookies),
+        )),
+        code => Err(format!(
+            "login check error {}: {}",
+            code.unwrap_or(-1),
+            body["message"].as_str().unwrap_or("unknown error")
+        )),
+    }
+}
+
+/// Geetest captcha to solve before a code is sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeetestChallenge {
+    /// Captcha token, sent back with the solution
+    pub token: String,
+    pub gt: String,
+    pub challenge: String,
+}
+
+impl GeetestChallenge {
+    fn from_data(data: &Value) -> Option<Self> {
+        Some(Self {
+            token: data["token"].as_str()?.to_string(),
+            gt: data["geetest"]["gt"].as_str()?.to_string(),
+            challenge: data["geetest"]["challenge"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Solution of a `GeetestChallenge`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeetestResult {
+    pub validate: String,
+    pub seccode: String,
+}
+
+impl GeetestResult {
+    /// Result with the usual `<validate>|jordan` seccode
+    pub fn new(validate: &str) -> Self {
+        Self {
+            validate: validate.to_string(),
+            seccode: format!("{}|jordan", validate),
+        }
+    }
+}
+
+/// SMS login of one phone number, see `sms_login`
+#[derive(Debug, Clone)]
+pub struct SmsLogin {
+    client: Client,
+    phone: String,
+    country_code: u32,
+    /// Key of the sent code, needed to verify it
+    captcha_key: Option<String>,
+}
+
+/// Start an SMS login for a mainland China phone number
+pub fn sms_login(phone: &str) -> SmsLogin {
+    SmsLogin::new(phone)
+}
+
+impl SmsLogin {
+    pub fn new(phone: &str) -> Self {
+        Self {
+            client: Client::new(),
+            phone: phone.trim().to_string(),
+            country_code: DEFAULT_COUNTRY_CODE,
+            captcha_key: None,
+        }
+    }
+
+    /// Phone number of another country, e.g. 852 for Hong Kong
+    pub fn with_country_code(mut self, country_code: u32) -> Self {
+        self.country_code = country_code;
+        self
+    }
+
+    /// Fetch a geetest captcha guarding `send_code`
+    pub fn captcha(&self) -> Result<GeetestChallenge, String> {
+        let response = self
+            .client
+            .get(format!("{}?source={}", CAPTCHA_URL, SOURCE))
+            .headers(headers())
+            .send()
+            .map_err(|e| format!("fetch captcha failed: {}", e))?;
+        let data = api_data(response, "captcha")?;
+        GeetestChallenge::from_data(&data)
+            .ok_or_else(|| "captcha has no geetest challenge".to_string())
+    }
+
+    /// Send a login code to the phone once `captcha` is solved
+    pub fn send_code(
+        &mut self,
+        captcha: &GeetestChallenge,
+        result: &GeetestResult,
+    ) -> Result<(), String> {
+        let form = [
+            ("cid", self.country_code.to_string()),
+            ("tel", self.phone.clone()),
+            ("source", SOURCE.to_string()),
+            ("token", captcha.token.clone()),
+            ("challenge", captcha.challenge.clone()),
+            ("validate", result.validate.clone()),
+            ("seccode", result.seccode.clone()),
+        ];
+        let response = self
+            .client
+            .post(SMS_SEND_URL)
+            .headers(headers())
+            .form(&form)
+            .send()
+            .map_err(|e| format!("send SMS code failed: {}", e))?;
+        let data = api_data(response, "send SMS code")?;
+        let captcha_key = data["captcha_key"]
+            .as_str()
+            .ok_or("send SMS code returned no captcha key")?;
+        self.captcha_key = Some(captcha_key.to_string());
+        Ok(())
+    }
+
+    /// Log in with the code received by SMS, returning the session cookies as a
+    /// cookie header (`SESSDATA=...; bili_jct=...`)
+    pub fn verify(&self, code: &str) -> Result<String, String> {
+        let captcha_key = self
+            .captcha_key
+            .as_ref()
+            .ok_or("no SMS code was sent; call send_code first")?;
+        let form = [
+            ("cid", self.country_code.to_string()),
+            ("tel", self.phone.clone()),
+            ("code", code.trim().to_string()),
+            ("source", SOURCE.to_string()),
+            ("captcha_key", captcha_key.clone()),
+            ("keep", "0".to_string()),
+        ];
+        let response = self
+            .client
+            .post(SMS_LOGIN_URL)
+            .headers(headers())
+            .form(&form)
+            .send()
+            .map_err(|e| format!("SMS login failed: {}", e))?;
+        let cookies = cookie_header(
+            response
+                .headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok()),
+        );
+        api_data(response, "SMS login")?;
+        if !cookies.contains("SESSDATA=") {
+            return Err("SMS login returned no session cookie".to_string());
+        }
+        Ok(cookies)
+    }
+}
+
+fn headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    apply_request_headers(&mut headers);
+    headers
+}
+
+/// The `data` field of a passport API response
+fn api_data(response: Response, what: &str) -> Result<Value, String> {
+    let mut body: Value = response
+        .json()
+        .map_err(|e| format!("{} returned invalid JSON: {}", what, e))?;
+    match body["code"].as_i64() {
+        Some(0) => Ok(body["data"].take()),
+        code => Err(format!(
+            "{} error {}: {}",
+            what,
+            code.unwrap_or(-1),
+            body["message"].as_str().unwrap_or("unknown error")
+        )),
+    }
+}
+
+/// Cookie header of the `name=value` pairs of `Set-Cookie` values
+fn cookie_header<'a>(set_cookies: impl Iterator<Item = &'a str>) -> String {
+    set_cookies
+        .filter_map(|set_cookie| set_cookie.split(';').next())
+        .map(str::trim)
+        .filter(|pair| pair.contains('='))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sms_login_parsing() {
+        let data = serde_json::json!({
+            "type": "geetest",
+            "token": "token",
+            "geetest": {"gt": "gt", "challenge": "challenge"}
+        });
+        assert_eq!(
+            GeetestChallenge::from_data(&data),
+            Some(GeetestChallenge {
+                token: "token".to_string(),
+                gt: "gt".to_string(),
+                challenge: "challenge".to_string(),
+            })
+        );
+        assert_eq!(GeetestResult::new("abc").seccode, "abc|jordan");
+
+        let set_cookies = [
+            "SESSDATA=sess%2C1; Path=/; Domain=bilibili.com; HttpOnly; Secure",
+            "bili_jct=csrf; Path=/; Domain=bilibili.com",
+            "DedeUserID=2; Path=/",
+        ];
+        assert_eq!(
+            cookie_header(set_cookies.into_iter()),
+            "SESSDATA=sess%2C1; bili_jct=csrf; DedeUserID=2"
+        );
+        assert!(sms_login("138").verify("123456").is_err());
+    }
+
+    #[test]
+    fn test_login_status() {
+        let cookies = "buvid3=x; SESSDATA=abc%2C1893456000%2Cdef*11; bili_jct=csrf";
+        assert_eq!(sessdata_expiry(cookies), Some(1893456000));
+        assert_eq!(sessdata_expiry("SESSDATA=abc"), None);
+
+        let data = serde_json::json!({"isLogin": true, "mid": 2, "uname": "user"});
+        let status = LoginStatus::from_nav_data(&data, Some(0)).unwrap();
+        assert_eq!(status.username, "user");
+        assert_eq!(status.expires_in(), Some(Duration::ZERO));
+        assert_eq!(
+            LoginStatus::from_nav_data(&serde_json::json!({"isLogin": false}), None),
+            None
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// src/client/metrics.rs
+//! Prometheus metrics for the connection status
+//!
+//! `render` writes the text exposition format; `serve` answers every HTTP
+//! request on an address with it, for scraping at e.g. `http://host:port/metrics`.
+//! `serve_with` does the same for the requests other routes leave.
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::http_server::{HttpServer, Request, Response};
+use crate::status::{ConnectionState, ConnectionStatus};
+
+/// Escape a label value for the exposition format
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Metrics of one connection in the Prometheus text format
+pub fn render(status: &ConnectionStatus) -> String {
+    let room = format!("room_id=\"{}\"", label(&status.room_id));
+    let connected = u8::from(status.state == ConnectionState::Connected);
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{}{{{}}} {}", name, room, value);
+    };
+
+    metric(
+        "blivedm_connected",
+        "gauge",
+        "Whether the danmaku websocket is connected",
+        connected.to_string(),
+    );
+    metric(
+        "blivedm_uptime_seconds",
+        "gauge",
+        "Time since the session started",
+        status.uptime().as_secs_f64().to_string(),
+    );
+    metric(
+        "blivedm_heartbeats_sent_total",
+        "counter",
+        "Heartbeats sent to the danmaku server",
+        status.heartbeats_sent.to_string(),
+    );
+    if let Some(rtt) = status.heartbeat_rtt {
+        metric(
+            "blivedm_heartbeat_last_rtt_seconds",
+            "gauge",
+            "Round-trip time of the latest answered heartbeat",
+            rtt.as_secs_f64().to_string(),
+        );
+    }
+
+    let name = "blivedm_heartbeat_rtt_seconds";
+    let _ = writeln!(out, "# HELP {} Heartbeat round-trip times", name);
+    let _ = writeln!(out, "# TYPE {} summary", name);
+    let _ = writeln!(
+        out,
+        "{}_sum{{{}}} {}",
+        name,
+        room,
+        status.heartbeat_rtt_total.as_secs_f64()
+    );
+    let _ = writeln!(
+        out,
+        "{}_count{{{}}} {}",
+        name, room, status.heartbeat_rtt_count
+    );
+    out
+}
+
+/// Route answering requests with the metrics of `status`
+pub fn handler(
+    status: Arc<Mutex<ConnectionStatus>>,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static {
+    move |_| {
+        let status = status.lock().ok()?;
+        Some(Response::new(
+            200,
+            "text/plain; version=0.0.4",
+            render(&status),
+        ))
+    }
+}
+
+/// Serve the metrics of `status` on `addr` from a background thread,
+/// returning the bound address
+pub fn serve(addr: &str, status: Arc<Mutex<ConnectionStatus>>) -> Result<SocketAddr, String> {
+    serve_with(addr, status, HttpServer::new())
+}
+
+/// Like `serve`, with the metrics as the fallback of `server`'s routes
+pub fn serve_with(
+    addr: &str,
+    status: Arc<Mutex<ConnectionStatus>>,
+    server: HttpServer,
+) -> Result<SocketAddr, String> {
+    let local_addr = server.fallback(handler(status)).serve(addr)?;
+    log::info!("serving metrics on http://{}/metrics", local_addr);
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::ConnectionEvent;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    #[test]
+    fn test_metrics_render_and_serve() {
+        let mut status = ConnectionStatus::new();
+        status.apply(ConnectionEvent::Connected {
+            room_id: "24779526".to_string(),
+            uid: 0,
+        });
+        status.apply(ConnectionEvent::HeartbeatSent);
+        status.apply(ConnectionEvent::HeartbeatReply {
+            rtt: Some(Duration::from_millis(250)),
+        });
+
+        let text = render(&status);
+        assert!(text.contains("blivedm_connected{room_id=\"24779526\"} 1\n"));
+        assert!(text.contains("blivedm_heartbeats_sent_total{room_id=\"24779526\"} 1\n"));
+        assert!(text.contains("blivedm_heartbeat_last_rtt_seconds{room_id=\"24779526\"} 0.25\n"));
+        assert!(text.contains("blivedm_heartbeat_rtt_seconds_count{room_id=\"24779526\"} 1\n"));
+
+        let addr = serve("127.0.0.1:0", Arc::new(Mutex::new(status))).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("blivedm_heartbeat_rtt_seconds_sum"));
+    }
+}
@@ -1,12 +1,72 @@
 // src/client/lib.rs
 //! Library entry for the client package
 
+#[cfg(feature = "accounts")]
+pub mod accounts;
+#[cfg(feature = "client")]
 pub mod auth;
 #[cfg(feature = "browser_cookies")]
 pub mod browser_cookies;
+#[cfg(feature = "client")]
+pub mod bus;
+#[cfg(feature = "chat_store")]
+pub mod chat_store;
+#[cfg(feature = "client")]
+pub mod danmaku;
+pub mod extension;
+#[cfg(feature = "client")]
+pub mod follow_feed;
+#[cfg(feature = "client")]
+pub mod followers;
+#[cfg(feature = "client")]
+pub mod gift_catalog;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "client")]
+pub mod health;
+pub mod history;
+#[cfg(feature = "client")]
+pub mod http_server;
+#[cfg(feature = "client")]
+pub mod images;
+#[cfg(feature = "client")]
+pub mod ipc;
+#[cfg(feature = "client")]
+pub mod json_rpc;
+#[cfg(feature = "client")]
+pub mod live_room;
+#[cfg(feature = "client")]
+pub mod login;
+#[cfg(feature = "client")]
+pub mod metrics;
 pub mod models;
+#[cfg(feature = "client")]
+pub mod moderation;
+#[cfg(feature = "client")]
+pub mod open_live;
+pub mod payloads;
+pub mod protocol;
+#[cfg(feature = "client")]
+pub mod room_management;
+#[cfg(feature = "client")]
+pub mod runtime;
+#[cfg(feature = "client")]
 pub mod scheduler;
+#[cfg(feature = "client")]
+pub mod send_queue;
+pub mod session;
+#[cfg(feature = "client")]
+pub mod sse;
+pub mod status;
+#[cfg(feature = "client")]
+pub mod systemd;
+#[cfg(feature = "client")]
+pub mod tls;
+#[cfg(feature = "client")]
+pub mod watchlist;
+#[cfg(feature = "client")]
 pub mod websocket;
 
 // Re-export commonly used functions
+#[cfg(feature = "client")]
 pub use auth::get_cookies_or_browser;
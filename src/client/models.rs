@@ -55,16 +55,79 @@ impl AuthMessage {
     }
 }
 
+/// Fan medal worn by a user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FanMedal {
+    pub name: String,
+    pub level: u8,
+}
+
+/// Entry of the online contribution rank
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RankEntry {
+    /// Position in the rank, starting from 1
+    pub rank: u32,
+    pub uid: u64,
+    pub name: String,
+    /// Contribution score
+    pub score: u64,
+}
+
+/// Winner of an anchor lottery
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotteryWinner {
+    pub uid: u64,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum BiliMessage {
     Danmu {
         user: String,
         text: String,
+        /// Sender uid, 0 when hidden
+        uid: u64,
+        /// Sender guard level (0 = none, 1 = governor, 2 = admiral, 3 = captain)
+        guard_level: u8,
+        /// Fan medal worn by the sender
+        medal: Option<FanMedal>,
     },
     Gift {
         user: String,
         gift: String,
         num: String,
+        /// Gift id, used to look up the price in the gift catalog
+        gift_id: u64,
+        /// Total value in gold coins (1000 = 1 CNY), 0 for free gifts
+        value: u64,
+        /// Batch combo id shared with the matching `ComboSend`, if sent as part of a combo
+        combo_id: Option<String>,
+    },
+    /// Paid super chat message (SUPER_CHAT_MESSAGE)
+    SuperChat {
+        /// Super chat id
+        id: u64,
+        user: String,
+        text: String,
+        /// Price in CNY
+        price: u64,
+        /// How long the super chat stays pinned, in seconds
+        duration: u64,
+    },
+    /// Super chats withdrawn or removed by moderation (SUPER_CHAT_MESSAGE_DELETE)
+    SuperChatDelete {
+        /// Ids of the deleted super chats, as in `SuperChat::id`
+        ids: Vec<u64>,
+    },
+    /// Guard (captain/admiral/governor) purchase (GUARD_BUY)
+    GuardBuy {
+        user: String,
+        /// 1 = governor, 2 = admiral, 3 = captain
+        guard_level: u8,
+        /// Number of months bought
+        num: u64,
+        /// Total price in gold coins (1000 = 1 CNY)
+        price: u64,
     },
     /// Online rank count message (ONLINE_RANK_COUNT)
     OnlineRankCount {
@@ -73,12 +136,336 @@ pub enum BiliMessage {
         /// Number of online users in the live room
         online_count: u64,
     },
+    /// Top contributors of the online rank (ONLINE_RANK_V2)
+    OnlineRank {
+        entries: Vec<RankEntry>,
+    },
+    /// Summary of a gift combo (COMBO_SEND); the gifts of the combo also arrive
+    /// as individual `Gift` messages with the same `combo_id`
+    ComboSend {
+        /// Batch combo id
+        combo_id: String,
+        user: String,
+        uid: u64,
+        gift: String,
+        gift_id: u64,
+        /// Number of gifts sent in the combo so far
+        total_num: u64,
+        /// Total value of the combo in gold coins (1000 = 1 CNY)
+        value: u64,
+    },
+    /// Aggregated repeated danmaku, shown as "N人都在说…" (DANMU_AGGREGATION)
+    DanmuAggregation {
+        text: String,
+        /// Number of users who sent the text
+        count: u64,
+    },
+    /// Anchor lottery started (ANCHOR_LOT_START)
+    LotteryStart {
+        /// Lottery id, shared with the matching `LotteryAward`
+        id: u64,
+        prize: String,
+        /// Number of prizes
+        prize_num: u64,
+        /// Participation requirement as shown in the room, e.g. "至少成为粉丝团成员"
+        requirement: String,
+        /// Danmaku text to send for taking part, if any
+        danmu: String,
+        /// Running time in seconds
+        duration: u64,
+    },
+    /// Winners of an anchor lottery (ANCHOR_LOT_AWARD)
+    LotteryAward {
+        id: u64,
+        prize: String,
+        winners: Vec<LotteryWinner>,
+    },
+    /// Pending voice chat (connect-mic) applications (VOICE_JOIN_LIST,
+    /// VOICE_JOIN_ROOM_COUNT_INFO)
+    VoiceJoinList {
+        /// Number of viewers applying to join voice chat
+        apply_count: u64,
+    },
+    /// Voice chat applications opened or closed by the streamer (VOICE_JOIN_SWITCH)
+    VoiceJoinSwitch {
+        open: bool,
+    },
+    /// A viewer joined or left voice chat (VOICE_JOIN_STATUS)
+    VoiceJoinStatus {
+        uid: u64,
+        user: String,
+        /// Whether the viewer is now connected
+        connected: bool,
+    },
+    /// Entrance effect of a guard or other privileged viewer (ENTRY_EFFECT),
+    /// sent in addition to the ordinary INTERACT_WORD entry
+    EntryEffect {
+        uid: u64,
+        user: String,
+        /// Guard level of the viewer (0 = none, 1 = governor, 2 = admiral, 3 = captain)
+        guard_level: u8,
+        /// Entrance copy text, e.g. "欢迎舰长 name 进入直播间"
+        text: String,
+    },
+    /// Title or area of the room changed (ROOM_CHANGE, or a room info refresh)
+    RoomChange {
+        title: String,
+        /// Live area id (`area_v2`)
+        area_id: u64,
+        area_name: String,
+        parent_area_name: String,
+    },
+    /// Room metadata fetched when connecting to a room (room info API)
+    RoomInfo {
+        room_id: u64,
+        title: String,
+        /// Streamer name
+        streamer: String,
+        streamer_uid: u64,
+        /// Live area id (`area_v2`)
+        area_id: u64,
+        area_name: String,
+        parent_area_name: String,
+        /// Cover image url
+        cover: String,
+        /// Whether the room is currently live
+        live: bool,
+    },
+    /// Follower or fan club count of the streamer changed (polled, see
+    /// `followers::watch_followers`)
+    FollowerChange {
+        followers: u64,
+        /// Change since the previous poll, 0 on the first one
+        followers_delta: i64,
+        /// Members of the streamer's fan club
+        fans_club: u64,
+        fans_club_delta: i64,
+    },
+    /// A followed streamer went live (follow feed, see `follow_feed`)
+    StreamStarted {
+        room_id: u64,
+        uid: u64,
+        streamer: String,
+        title: String,
+    },
+    /// A followed streamer went offline; their room is closed
+    StreamEnded {
+        room_id: u64,
+        uid: u64,
+        streamer: String,
+    },
+    /// Structured payload of a command handled by a registered parser
+    Custom {
+        cmd: String,
+        data: serde_json::Value,
+    },
+    /// Message of a type defined outside this crate, from a registered parser
+    Extension(crate::extension::Extension),
     // Add more variants as needed
     Raw(serde_json::Value),
     #[deprecated(note = "Use Raw variant instead")]
     Unsupported,
 }
 
+/// Every `BiliMessage::kind` except the deprecated `unsupported`, in
+/// declaration order. The message types table of `docs/client.md` and the
+/// typings of the Node binding are tested against this list.
+pub const MESSAGE_KINDS: [&str; 23] = [
+    "danmu",
+    "gift",
+    "super_chat",
+    "super_chat_delete",
+    "guard_buy",
+    "online_rank_count",
+    "online_rank",
+    "combo_send",
+    "danmu_aggregation",
+    "lottery_start",
+    "lottery_award",
+    "voice_join_list",
+    "voice_join_switch",
+    "voice_join_status",
+    "entry_effect",
+    "room_change",
+    "room_info",
+    "follower_change",
+    "stream_started",
+    "stream_ended",
+    "custom",
+    "extension",
+    "raw",
+];
+
+impl BiliMessage {
+    /// Snake case name of the message kind, e.g. `danmu` or `super_chat`;
+    /// add new kinds to `MESSAGE_KINDS` as well
+    pub fn kind(&self) -> &'static str {
+        #[allow(deprecated)]
+        match self {
+            BiliMessage::Danmu { .. } => "danmu",
+            BiliMessage::Gift { .. } => "gift",
+            BiliMessage::SuperChat { .. } => "super_chat",
+            BiliMessage::SuperChatDelete { .. } => "super_chat_delete",
+            BiliMessage::GuardBuy { .. } => "guard_buy",
+            BiliMessage::OnlineRankCount { .. } => "online_rank_count",
+            BiliMessage::OnlineRank { .. } => "online_rank",
+            BiliMessage::ComboSend { .. } => "combo_send",
+            BiliMessage::DanmuAggregation { .. } => "danmu_aggregation",
+            BiliMessage::LotteryStart { .. } => "lottery_start",
+            BiliMessage::LotteryAward { .. } => "lottery_award",
+            BiliMessage::VoiceJoinList { .. } => "voice_join_list",
+            BiliMessage::VoiceJoinSwitch { .. } => "voice_join_switch",
+            BiliMessage::VoiceJoinStatus { .. } => "voice_join_status",
+            BiliMessage::EntryEffect { .. } => "entry_effect",
+            BiliMessage::RoomChange { .. } => "room_change",
+            BiliMessage::RoomInfo { .. } => "room_info",
+            BiliMessage::FollowerChange { .. } => "follower_change",
+            BiliMessage::StreamStarted { .. } => "stream_started",
+            BiliMessage::StreamEnded { .. } => "stream_ended",
+            BiliMessage::Custom { .. } => "custom",
+            BiliMessage::Extension(_) => "extension",
+            BiliMessage::Raw(_) => "raw",
+            BiliMessage::Unsupported => "unsupported",
+        }
+    }
+
+    /// Flat JSON object of the message fields plus a `type` field naming the kind,
+    /// as handed to language bindings; `Raw` messages keep their JSON under `data`
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut object = match (self, serde_json::to_value(self)) {
+            (BiliMessage::Raw(data), _) => {
+                serde_json::Map::from_iter([("data".to_string(), data.clone())])
+            }
+            (_, Ok(serde_json::Value::Object(variant))) => match variant.into_iter().next() {
+                Some((_, serde_json::Value::Object(fields))) => fields,
+                _ => serde_json::Map::new(),
+            },
+            _ => serde_json::Map::new(),
+        };
+        object.insert("type".to_string(), self.kind().into());
+        serde_json::Value::Object(object)
+    }
+}
+
+/// Borrowed fields of a `BiliMessage::Danmu`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Danmaku<'a> {
+    pub user: &'a str,
+    pub text: &'a str,
+    pub uid: u64,
+    pub guard_level: u8,
+    pub medal: Option<&'a FanMedal>,
+}
+
+/// Borrowed fields of a `BiliMessage::Gift`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gift<'a> {
+    pub user: &'a str,
+    pub gift: &'a str,
+    pub num: &'a str,
+    pub gift_id: u64,
+    /// Total value in gold coins (1000 = 1 CNY)
+    pub value: u64,
+    pub combo_id: Option<&'a str>,
+}
+
+/// Borrowed fields of a `BiliMessage::SuperChat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperChat<'a> {
+    pub id: u64,
+    pub user: &'a str,
+    pub text: &'a str,
+    /// Price in CNY
+    pub price: u64,
+    /// Pinned time in seconds
+    pub duration: u64,
+}
+
+impl BiliMessage {
+    pub fn as_danmaku(&self) -> Option<Danmaku<'_>> {
+        match self {
+            BiliMessage::Danmu {
+                user,
+                text,
+                uid,
+                guard_level,
+                medal,
+            } => Some(Danmaku {
+                user,
+                text,
+                uid: *uid,
+                guard_level: *guard_level,
+                medal: medal.as_ref(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn as_gift(&self) -> Option<Gift<'_>> {
+        match self {
+            BiliMessage::Gift {
+                user,
+                gift,
+                num,
+                gift_id,
+                value,
+                combo_id,
+            } => Some(Gift {
+                user,
+                gift,
+                num,
+                gift_id: *gift_id,
+                value: *value,
+                combo_id: combo_id.as_deref(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn as_super_chat(&self) -> Option<SuperChat<'_>> {
+        match self {
+            BiliMessage::SuperChat {
+                id,
+                user,
+                text,
+                price,
+                duration,
+            } => Some(SuperChat {
+                id: *id,
+                user,
+                text,
+                price: *price,
+                duration: *duration,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Name and uid of the user a message is from, uid 0 when it has none
+    pub fn sender(&self) -> Option<(&str, u64)> {
+        match self {
+            BiliMessage::Danmu { user, uid, .. }
+            | BiliMessage::ComboSend { user, uid, .. }
+            | BiliMessage::VoiceJoinStatus { user, uid, .. }
+            | BiliMessage::EntryEffect { user, uid, .. } => Some((user, *uid)),
+            BiliMessage::Gift { user, .. }
+            | BiliMessage::SuperChat { user, .. }
+            | BiliMessage::GuardBuy { user, .. } => Some((user, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Display name of a guard level (1 = governor, 2 = admiral, 3 = captain)
+pub fn guard_level_name(guard_level: u8) -> &'static str {
+    match guard_level {
+        1 => "总督",
+        2 => "提督",
+        3 => "舰长",
+        _ => "",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +481,46 @@ mod tests {
         assert_eq!(auth.roomid, 67890);
         assert_eq!(auth.key, "test_token");
     }
+
+    #[test]
+    fn test_message_kinds_documented() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let docs = std::fs::read_to_string(root.join("docs/client.md")).unwrap();
+        // Types are only checked where the bindings are part of the checkout
+        let typings = std::fs::read_to_string(root.join("bindings/node/index.d.ts")).ok();
+        for kind in MESSAGE_KINDS {
+            assert!(
+                docs.contains(&format!("| `{}` |", kind)),
+                "{} not in docs",
+                kind
+            );
+            if let Some(typings) = &typings {
+                let literal = format!("'{}'", kind);
+                assert!(typings.contains(&literal), "{} not in index.d.ts", kind);
+            }
+        }
+        let unique: std::collections::HashSet<_> = MESSAGE_KINDS.iter().collect();
+        assert_eq!(unique.len(), MESSAGE_KINDS.len());
+    }
+
+    #[test]
+    fn test_bili_message_to_json() {
+        let msg = BiliMessage::Danmu {
+            user: "viewer".to_string(),
+            text: "hello".to_string(),
+            uid: 1,
+            guard_level: 0,
+            medal: None,
+        };
+        let json = msg.to_json();
+        assert_eq!(json["type"], "danmu");
+        assert_eq!(json["user"], "viewer");
+        assert_eq!(json["medal"], serde_json::Value::Null);
+
+        let raw = BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"}));
+        assert_eq!(
+            raw.to_json(),
+            serde_json::json!({"type": "raw", "data": {"cmd": "LIKE"}})
+        );
+    }
 }
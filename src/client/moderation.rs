@@ -0,0 +1,77 @@
+// src/client/moderation.rs
+//! Room moderation helpers (silence and block users) for authenticated room managers
+
+use crate::auth::post_live_api;
+use crate::scheduler::EventContext;
+use log::info;
+
+pub const ADD_SILENT_USER_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/AddSilentUser";
+
+/// `hour` value understood by the silent-user API as a permanent block
+const PERMANENT_BLOCK_HOURS: i64 = -1;
+
+/// Silence a user in the room for the given number of hours
+///
+/// # Arguments
+/// * `uid` - The uid of the user to silence
+/// * `hours` - How long the user stays silenced
+/// * `context` - Event context containing cookies and room_id of a room manager
+pub async fn silence_user(
+    uid: u64,
+    hours: u32,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    add_silent_user(uid, hours as i64, context).await?;
+    info!("Silenced uid {} for {} hour(s)", uid, hours);
+    Ok(())
+}
+
+/// Add a user to the room block list permanently
+pub async fn block_user(
+    uid: u64,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    add_silent_user(uid, PERMANENT_BLOCK_HOURS, context).await?;
+    info!("Added uid {} to the block list", uid);
+    Ok(())
+}
+
+async fn add_silent_user(
+    uid: u64,
+    hour: i64,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let form = vec![
+        ("room_id", context.room_id.to_string()),
+        ("tuid", uid.to_string()),
+        ("msg", String::new()),
+        ("mobile_app", "web".to_string()),
+        ("hour", hour.to_string()),
+        ("visit_id", String::new()),
+    ];
+    post_live_api(ADD_SILENT_USER_URL, form, context).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_silence_user_requires_cookies() {
+        let context = EventContext {
+            cookies: None,
+            room_id: 12345,
+            request_headers: None,
+        };
+        assert!(silence_user(1, 1, &context).await.is_err());
+
+        let context = EventContext {
+            cookies: Some("SESSDATA=abc123".to_string()),
+            room_id: 12345,
+            request_headers: None,
+        };
+        assert!(block_user(1, &context).await.is_err());
+    }
+}
@@ -0,0 +1,542 @@
+// src/client/open_live.rs
+//! Client for the Bilibili Open Live (open platform) protocol
+//!
+//! Instead of browser cookies, the open platform authenticates with developer
+//! credentials (access key id/secret and app id) and the streamer's identity code.
+//! A session is started with `/v2/app/start`, kept alive with `/v2/app/heartbeat`
+//! and released with `/v2/app/end`; messages arrive over a websocket authenticated
+//! with the `auth_body` returned by the start call.
+
+use crate::models::{BiliMessage, FanMedal};
+use crate::protocol::Packet;
+use crate::session::Session;
+use crate::status::{ConnectionEvent, ConnectionStatus, HeartbeatClock};
+use crate::tls::TlsStream;
+use crate::websocket::connect_url;
+use futures_channel::mpsc::Sender;
+use ring::hmac;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tungstenite::{Message, WebSocket};
+
+pub const OPEN_LIVE_API: &str = "https://live-open.biliapi.com";
+
+/// How often the open platform expects a game heartbeat
+pub const GAME_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Open platform developer credentials and the streamer identity code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenLiveCredentials {
+    pub access_key_id: String,
+    pub access_key_secret: String,
+    /// Project id of the developer application
+    pub app_id: u64,
+    /// Identity code (身份码) of the streamer whose room is read
+    pub code: String,
+}
+
+/// A started open platform session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenLiveSession {
+    /// Id of the session, used for heartbeats and to end it
+    pub game_id: String,
+    /// Body of the websocket auth packet
+    pub auth_body: String,
+    /// Websocket servers, in order of preference
+    pub wss_links: Vec<String>,
+    /// Room of the streamer
+    pub room_id: u64,
+    /// Name of the streamer
+    pub anchor_name: String,
+}
+
+impl OpenLiveSession {
+    /// Build a session from the `data` field of an app start response
+    pub fn from_start_data(data: &Value) -> Result<Self, String> {
+        let game_id = data["game_info"]["game_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let auth_body = data["websocket_info"]["auth_body"]
+            .as_str()
+            .ok_or("app start response has no websocket auth body")?
+            .to_string();
+        let wss_links: Vec<String> = data["websocket_info"]["wss_link"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|link| link.as_str().map(str::to_string))
+            .collect();
+        if wss_links.is_empty() {
+            return Err("app start response has no websocket servers".to_string());
+        }
+        Ok(Self {
+            game_id,
+            auth_body,
+            wss_links,
+            room_id: data["anchor_info"]["room_id"].as_u64().unwrap_or(0),
+            anchor_name: data["anchor_info"]["uname"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+}
+
+/// Signed headers of an open platform request, including `Authorization`
+pub fn sign_headers(
+    credentials: &OpenLiveCredentials,
+    body: &str,
+    timestamp: u64,
+    nonce: &str,
+) -> Vec<(&'static str, String)> {
+    // Sorted by name, as required for the string to sign
+    let signed = [
+        ("x-bili-accesskeyid", credentials.access_key_id.clone()),
+        ("x-bili-content-md5", format!("{:x}", md5::compute(body))),
+        ("x-bili-signature-method", "HMAC-SHA256".to_string()),
+        ("x-bili-signature-nonce", nonce.to_string()),
+        ("x-bili-signature-version", "1.0".to_string()),
+        ("x-bili-timestamp", timestamp.to_string()),
+    ];
+    let to_sign = signed
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, credentials.access_key_secret.as_bytes());
+    let signature = hmac::sign(&key, to_sign.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    let mut headers = signed.to_vec();
+    headers.push(("Authorization", signature));
+    headers
+}
+
+/// Blocking client for the open platform HTTP APIs
+pub struct OpenLiveApi {
+    credentials: OpenLiveCredentials,
+    http: reqwest::blocking::Client,
+}
+
+impl OpenLiveApi {
+    pub fn new(credentials: OpenLiveCredentials) -> Result<Self, String> {
+        let http = reqwest::blocking::Client::builder()
+            .https_only(true)
+            .build()
+            .map_err(|e| format!("http client init failed: {}", e))?;
+        Ok(Self { credentials, http })
+    }
+
+    /// Start a session for the streamer identified by the credentials' code
+    pub fn start(&self) -> Result<OpenLiveSession, String> {
+        let data = self.post(
+            "/v2/app/start",
+            json!({ "code": self.credentials.code, "app_id": self.credentials.app_id }),
+        )?;
+        OpenLiveSession::from_start_data(&data)
+    }
+
+    /// Keep a session alive; sessions without a heartbeat for a minute are closed
+    pub fn heartbeat(&self, game_id: &str) -> Result<(), String> {
+        self.post("/v2/app/heartbeat", json!({ "game_id": game_id }))
+            .map(|_| ())
+    }
+
+    /// End a session
+    pub fn end(&self, game_id: &str) -> Result<(), String> {
+        self.post(
+            "/v2/app/end",
+            json!({ "game_id": game_id, "app_id": self.credentials.app_id }),
+        )
+        .map(|_| ())
+    }
+
+    fn post(&self, path: &str, body: Value) -> Result<Value, String> {
+        let body = body.to_string();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let nonce = format!("{}{:09}", now.as_secs(), now.subsec_nanos());
+
+        let mut request = self
+            .http
+            .post(format!("{}{}", OPEN_LIVE_API, path))
+            .header("accept", "application/json")
+            .header("content-type", "application/json");
+        for (name, value) in sign_headers(&self.credentials, &body, now.as_secs(), &nonce) {
+            request = request.header(name, value);
+        }
+        let response: Value = request
+            .body(body)
+            .send()
+            .and_then(|resp| resp.json())
+            .map_err(|e| format!("open live request {} failed: {}", path, e))?;
+
+        if response["code"].as_i64() != Some(0) {
+            return Err(format!(
+                "open live API {} error {}: {}",
+                path,
+                response["code"],
+                response["message"].as_str().unwrap_or("unknown error")
+            ));
+        }
+        Ok(response["data"].clone())
+    }
+}
+
+/// Open platform counterpart of `BiliLiveClient`: same receive/heartbeat loop,
+/// same `BiliMessage` output
+pub struct OpenLiveClient {
+    api: OpenLiveApi,
+    session: OpenLiveSession,
+    ws: WebSocket<TlsStream>,
+    ss: Sender<BiliMessage>,
+    status: Arc<Mutex<ConnectionStatus>>,
+    last_game_heartbeat: Instant,
+    ended: bool,
+    /// Frame encoding and decoding for the session's auth body
+    protocol: Session,
+    heartbeat_clock: HeartbeatClock,
+}
+
+impl OpenLiveClient {
+    /// Start a session and connect to its websocket
+    pub fn connect(
+        credentials: OpenLiveCredentials,
+        r: Sender<BiliMessage>,
+    ) -> Result<Self, String> {
+        let api = OpenLiveApi::new(credentials)?;
+        let session = api.start()?;
+        let protocol = Session::new(session.auth_body.clone());
+        let ws = match Self::connect_ws(&session, &protocol) {
+            Ok(ws) => ws,
+            Err(e) => {
+                // Release the session so the next start is not rejected as a duplicate
+                let _ = api.end(&session.game_id);
+                return Err(e);
+            }
+        };
+        log::info!(
+            "open live session {} started for room {} ({})",
+            session.game_id,
+            session.room_id,
+            session.anchor_name
+        );
+
+        let client = Self {
+            api,
+            session,
+            ws,
+            ss: r,
+            status: Arc::new(Mutex::new(ConnectionStatus::new())),
+            last_game_heartbeat: Instant::now(),
+            ended: false,
+            protocol,
+            heartbeat_clock: HeartbeatClock::default(),
+        };
+        client.emit(ConnectionEvent::Connected {
+            room_id: client.session.room_id.to_string(),
+            uid: 0,
+        });
+        Ok(client)
+    }
+
+    /// The started session
+    pub fn session(&self) -> &OpenLiveSession {
+        &self.session
+    }
+
+    /// Shared connection status, updated on connection lifecycle events
+    pub fn connection_status(&self) -> Arc<Mutex<ConnectionStatus>> {
+        Arc::clone(&self.status)
+    }
+
+    /// Send a websocket heartbeat, and a game heartbeat when one is due
+    pub fn send_heart_beat(&mut self) {
+        match self
+            .ws
+            .send(Message::Binary(self.protocol.heartbeat_frame()))
+        {
+            Ok(()) => {
+                self.heartbeat_clock.sent();
+                self.emit(ConnectionEvent::HeartbeatSent);
+            }
+            Err(e) => log::error!("failed to send heartbeat: {}", e),
+        }
+
+        if self.last_game_heartbeat.elapsed() >= GAME_HEARTBEAT_INTERVAL {
+            if let Err(e) = self.api.heartbeat(&self.session.game_id) {
+                log::warn!("open live game heartbeat failed: {}", e);
+            }
+            self.last_game_heartbeat = Instant::now();
+        }
+    }
+
+    /// Read one websocket message and forward the messages it contains
+    pub fn receive(&mut self) -> Result<(), String> {
+        if !self.ws.can_read() {
+            return Ok(());
+        }
+        match self.ws.read() {
+            Ok(m) => {
+                let data = m.into_data();
+                if data.len() >= 16 {
+                    self.parse_ws_message(&data);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                let msg = format!("read msg error: {}", e);
+                log::warn!("{}", msg);
+                self.reconnect().map_err(|reconnect_err| {
+                    format!("{}; reconnect failed: {}", msg, reconnect_err)
+                })
+            }
+        }
+    }
+
+    /// End the session; also done when the client is dropped
+    pub fn end(&mut self) -> Result<(), String> {
+        if self.ended {
+            return Ok(());
+        }
+        self.ended = true;
+        let _ = self.ws.close(None);
+        self.emit(ConnectionEvent::Disconnected {
+            reason: "open live session ended".to_string(),
+        });
+        self.api.end(&self.session.game_id)
+    }
+
+    fn connect_ws(
+        session: &OpenLiveSession,
+        protocol: &Session,
+    ) -> Result<WebSocket<TlsStream>, String> {
+        let mut last_err = None;
+        for link in &session.wss_links {
+            match connect_url(link) {
+                Ok((mut ws, _)) => {
+                    ws.send(Message::Binary(protocol.auth_frame()))
+                        .map_err(|e| format!("send auth error: {}", e))?;
+                    return Ok(ws);
+                }
+                Err(e) => {
+                    log::warn!("open live websocket {} failed: {}", link, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| "no websocket servers".to_string()))
+    }
+
+    fn reconnect(&mut self) -> Result<(), String> {
+        self.emit(ConnectionEvent::Reconnecting {
+            attempt: 1,
+            retry_at: Instant::now(),
+        });
+        match Self::connect_ws(&self.session, &self.protocol) {
+            Ok(ws) => {
+                self.ws = ws;
+                self.heartbeat_clock.reset();
+                log::info!("open live websocket reconnected");
+                self.emit(ConnectionEvent::Connected {
+                    room_id: self.session.room_id.to_string(),
+                    uid: 0,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.emit(ConnectionEvent::Disconnected { reason: e.clone() });
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_ws_message(&mut self, data: &[u8]) {
+        for packet in self.protocol.decode(data) {
+            match packet {
+                Packet::Message(json) => {
+                    if let Some(msg) = handle_open_live(json) {
+                        let _ = self.ss.try_send(msg);
+                    }
+                }
+                Packet::HeartbeatReply { .. } => {
+                    let rtt = self.heartbeat_clock.reply();
+                    self.emit(ConnectionEvent::HeartbeatReply { rtt });
+                }
+                Packet::AuthReply(_) => log::debug!("open live websocket authenticated"),
+            }
+        }
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        if let Ok(mut status) = self.status.lock() {
+            status.apply(event);
+        }
+    }
+}
+
+impl Drop for OpenLiveClient {
+    fn drop(&mut self) {
+        if let Err(e) = self.end() {
+            log::warn!("failed to end open live session: {}", e);
+        }
+    }
+}
+
+/// Convert an open platform message into a `BiliMessage`; unknown commands become `Raw`
+pub fn handle_open_live(json: Value) -> Option<BiliMessage> {
+    let data = &json["data"];
+    let text = |key: &str| data[key].as_str().unwrap_or_default().to_string();
+    let user = |value: &Value| value.as_str().unwrap_or("<unknown>").to_string();
+    match json["cmd"].as_str().unwrap_or_default() {
+        "LIVE_OPEN_PLATFORM_DM" => Some(BiliMessage::Danmu {
+            user: user(&data["uname"]),
+            text: text("msg"),
+            uid: data["uid"].as_u64().unwrap_or(0),
+            guard_level: data["guard_level"].as_u64().unwrap_or(0) as u8,
+            medal: match (
+                data["fans_medal_name"].as_str(),
+                data["fans_medal_level"].as_u64(),
+            ) {
+                (Some(name), Some(level)) if !name.is_empty() && level > 0 => Some(FanMedal {
+                    name: name.to_string(),
+                    level: level as u8,
+                }),
+                _ => None,
+            },
+        }),
+        "LIVE_OPEN_PLATFORM_SEND_GIFT" => {
+            let num = data["gift_num"].as_u64().unwrap_or(1);
+            // `price` is the unit price in gold coins; free gifts are not paid
+            let value = if data["paid"].as_bool().unwrap_or(false) {
+                data["price"].as_u64().unwrap_or(0) * num
+            } else {
+                0
+            };
+            Some(BiliMessage::Gift {
+                user: user(&data["uname"]),
+                gift: text("gift_name"),
+                num: num.to_string(),
+                gift_id: data["gift_id"].as_u64().unwrap_or(0),
+                value,
+                combo_id: None,
+            })
+        }
+        "LIVE_OPEN_PLATFORM_SUPER_CHAT" => {
+            let start = data["start_time"].as_u64().unwrap_or(0);
+            let end = data["end_time"].as_u64().unwrap_or(start);
+            Some(BiliMessage::SuperChat {
+                id: data["message_id"].as_u64().unwrap_or(0),
+                user: user(&data["uname"]),
+                text: text("message"),
+                price: data["rmb"].as_u64().unwrap_or(0),
+                duration: end.saturating_sub(start),
+            })
+        }
+        "LIVE_OPEN_PLATFORM_GUARD" => {
+            let num = data["guard_num"].as_u64().unwrap_or(1);
+            Some(BiliMessage::GuardBuy {
+                user: user(&data["user_info"]["uname"]),
+                guard_level: data["guard_level"].as_u64().unwrap_or(0) as u8,
+                num,
+                // `price` is the unit price in gold coins
+                price: data["price"].as_u64().unwrap_or(0) * num,
+            })
+        }
+        _ => Some(BiliMessage::Raw(json)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_headers() {
+        let credentials = OpenLiveCredentials {
+            access_key_id: "key".to_string(),
+            access_key_secret: "secret".to_string(),
+            app_id: 1,
+            code: "abc".to_string(),
+        };
+        let headers = sign_headers(
+            &credentials,
+            r#"{"app_id":1,"code":"abc"}"#,
+            1700000000,
+            "nonce",
+        );
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(
+            header("x-bili-content-md5"),
+            Some("cf479dc697f6a64899136f3b453bf24d")
+        );
+        assert_eq!(
+            header("Authorization"),
+            Some("70883f9203fa8ee9a28aa60386fb97f4f5dc30e2fac2c0eeb91ec7caf582ed33")
+        );
+    }
+
+    #[test]
+    fn test_handle_open_live_messages() {
+        let dm = json!({
+            "cmd": "LIVE_OPEN_PLATFORM_DM",
+            "data": {
+                "uname": "viewer",
+                "msg": "hello",
+                "uid": 0,
+                "guard_level": 3,
+                "fans_medal_name": "粉丝团",
+                "fans_medal_level": 12
+            }
+        });
+        assert_eq!(
+            handle_open_live(dm),
+            Some(BiliMessage::Danmu {
+                user: "viewer".to_string(),
+                text: "hello".to_string(),
+                uid: 0,
+                guard_level: 3,
+                medal: Some(FanMedal {
+                    name: "粉丝团".to_string(),
+                    level: 12,
+                }),
+            })
+        );
+
+        let sc = json!({
+            "cmd": "LIVE_OPEN_PLATFORM_SUPER_CHAT",
+            "data": {
+                "message_id": 7,
+                "uname": "fan",
+                "message": "hi",
+                "rmb": 30,
+                "start_time": 1700000000,
+                "end_time": 1700000060
+            }
+        });
+        assert_eq!(
+            handle_open_live(sc),
+            Some(BiliMessage::SuperChat {
+                id: 7,
+                user: "fan".to_string(),
+                text: "hi".to_string(),
+                price: 30,
+                duration: 60,
+            })
+        );
+
+        let like = json!({ "cmd": "LIVE_OPEN_PLATFORM_LIKE", "data": {} });
+        assert!(matches!(handle_open_live(like), Some(BiliMessage::Raw(_))));
+    }
+}
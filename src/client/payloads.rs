@@ -0,0 +1,101 @@
+// src/client/payloads.rs
+//! Original server JSON of recently delivered messages
+//!
+//! Parsing keeps only the fields of a `BiliMessage`. A client given a
+//! `PayloadLog` also records the JSON each message was parsed from, so a
+//! consumer such as the TUI inspector can look it up when the message reaches
+//! it. Messages are consumed in the order they were sent, so looking one up
+//! drops the entries recorded before it.
+
+use crate::models::BiliMessage;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Entries kept by default, enough for the batches between two TUI frames
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Bounded log of messages and the JSON they were parsed from
+#[derive(Debug)]
+pub struct PayloadLog {
+    entries: Mutex<VecDeque<(BiliMessage, Value)>>,
+    capacity: usize,
+}
+
+impl Default for PayloadLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl PayloadLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record the JSON `msg` was parsed from, dropping the oldest entry when full
+    pub fn record(&self, msg: &BiliMessage, json: &Value) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back((msg.clone(), json.clone()));
+        }
+    }
+
+    /// Take the JSON of the oldest entry equal to `msg`, and drop the entries
+    /// before it, which were never looked up
+    pub fn take(&self, msg: &BiliMessage) -> Option<Value> {
+        let mut entries = self.entries.lock().ok()?;
+        let index = entries.iter().position(|(recorded, _)| recorded == msg)?;
+        entries.drain(..index);
+        entries.pop_front().map(|(_, json)| json)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .map(|entries| entries.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_payload_log_take_in_order() {
+        let danmu = |text: &str| BiliMessage::Danmu {
+            user: "alice".to_string(),
+            text: text.to_string(),
+            uid: 1,
+            guard_level: 0,
+            medal: None,
+        };
+        let log = PayloadLog::new(3);
+        log.record(&danmu("a"), &json!({"cmd": "DANMU_MSG", "n": 1}));
+        log.record(&danmu("b"), &json!({"cmd": "DANMU_MSG", "n": 2}));
+        log.record(&danmu("b"), &json!({"cmd": "DANMU_MSG", "n": 3}));
+
+        // Equal messages are taken oldest first; "a" was skipped and is dropped
+        assert_eq!(log.take(&danmu("b")).unwrap()["n"], 2);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.take(&danmu("a")), None);
+        assert_eq!(log.take(&danmu("b")).unwrap()["n"], 3);
+
+        for n in 0..4 {
+            log.record(&danmu("c"), &json!({ "n": n }));
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.take(&danmu("c")).unwrap()["n"], 1);
+    }
+}
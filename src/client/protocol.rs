@@ -0,0 +1,964 @@
+// src/client/protocol.rs
+//! Live danmaku protocol core: packet framing, decompression and message parsing
+//!
+//! Nothing here does I/O, so the same code backs the native clients and the
+//! WebAssembly build (`--no-default-features`). See the
+//! [message stream docs](https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/live/message_stream.md)
+//! for the packet format.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::models::{BiliMessage, FanMedal, LotteryWinner, MsgHead, RankEntry};
+
+/// Size of the packet header
+pub const HEADER_LEN: usize = 16;
+
+pub enum Operation {
+    AUTH,
+    HEARTBEAT,
+}
+
+impl Operation {
+    fn code(&self) -> u32 {
+        match self {
+            Operation::AUTH => 7,
+            Operation::HEARTBEAT => 2,
+        }
+    }
+}
+
+/// A decoded server packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    /// Heartbeat reply (operation 3) carrying the room popularity
+    HeartbeatReply { popularity: u32 },
+    /// Reply to the auth packet (operation 8)
+    AuthReply(Value),
+    /// Business message (operation 5), still as JSON
+    Message(Value),
+}
+
+pub fn make_packet(body: &str, ops: Operation) -> Vec<u8> {
+    let json: Value = serde_json::from_str(body).unwrap();
+    make_raw_packet(json.to_string().as_bytes(), ops)
+}
+
+/// Build a packet around an already serialized body, sent as is
+pub fn make_raw_packet(body: &[u8], ops: Operation) -> Vec<u8> {
+    let mut res = Vec::with_capacity(HEADER_LEN + body.len());
+    res.extend_from_slice(&((HEADER_LEN + body.len()) as u32).to_be_bytes());
+    res.extend_from_slice(&(HEADER_LEN as u16).to_be_bytes());
+    res.extend_from_slice(&1_u16.to_be_bytes());
+    res.extend_from_slice(&ops.code().to_be_bytes());
+    res.extend_from_slice(&1_u32.to_be_bytes());
+    res.extend_from_slice(body);
+    res
+}
+
+/// Read a packet header from the first 16 bytes of `v_s`; missing bytes read as 0
+pub fn get_msg_header(v_s: &[u8]) -> MsgHead {
+    let mut header = [0_u8; HEADER_LEN];
+    let len = v_s.len().min(HEADER_LEN);
+    header[..len].copy_from_slice(&v_s[..len]);
+    MsgHead {
+        pack_len: u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+        raw_header_size: u16::from_be_bytes([header[4], header[5]]),
+        ver: u16::from_be_bytes([header[6], header[7]]),
+        operation: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+        seq_id: u32::from_be_bytes([header[12], header[13], header[14], header[15]]),
+    }
+}
+
+/// Decompress a brotli body (protocol version 3)
+pub fn decompress(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoded_input = Vec::new();
+    decompress_into(body, &mut decoded_input)?;
+    Ok(decoded_input)
+}
+
+/// Decompress a brotli body, appending to `out` so its allocation can be reused
+#[cfg(feature = "brotli")]
+pub fn decompress_into(body: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+    use brotlic::DecompressorReader;
+    let mut decompressed_reader: DecompressorReader<&[u8]> = DecompressorReader::new(body);
+    decompressed_reader.read_to_end(out)?;
+    Ok(())
+}
+
+/// Decompress a brotli body; needs the `brotli` feature
+#[cfg(not(feature = "brotli"))]
+pub fn decompress_into(_body: &[u8], _out: &mut Vec<u8>) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "brotli support is not enabled; authenticate with protover 2 instead",
+    ))
+}
+
+/// Decompress a zlib body (protocol version 2)
+pub fn inflate(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    inflate_into(body, &mut inflated)?;
+    Ok(inflated)
+}
+
+/// Decompress a zlib body, appending to `out` so its allocation can be reused
+pub fn inflate_into(body: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+    flate2::read::ZlibDecoder::new(body).read_to_end(out)?;
+    Ok(())
+}
+
+/// Iterator over the packets of a frame, yielding each header and a body
+/// borrowed from the frame
+pub struct Packets<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = (MsgHead, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.data.get(self.offset..)?;
+        if rest.len() < HEADER_LEN {
+            return None;
+        }
+        let head = get_msg_header(rest);
+        if (head.pack_len as usize) < HEADER_LEN {
+            log::error!("invalid packet length {}", head.pack_len);
+            self.offset = self.data.len();
+            return None;
+        }
+        let len = (head.pack_len as usize).min(rest.len());
+        let body = &rest[(head.raw_header_size as usize).min(len)..len];
+        self.offset += len;
+        Some((head, body))
+    }
+}
+
+/// Packets of a frame, without copying or decompressing anything
+pub fn packets(data: &[u8]) -> Packets<'_> {
+    Packets { data, offset: 0 }
+}
+
+/// Pool of byte buffers for packet bodies, shared by decoders so bursts of
+/// bundles reuse a few allocations instead of making new ones
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+    max_capacity: usize,
+}
+
+impl BufferPool {
+    /// Pool keeping up to `max_buffers` idle buffers; buffers that grew beyond
+    /// `max_capacity` bytes are freed instead of kept
+    pub fn new(max_buffers: usize, max_capacity: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+            max_capacity,
+        }
+    }
+
+    /// Take an empty buffer, reusing an idle one when available
+    pub fn get(self: &Arc<Self>) -> PooledBuffer {
+        let buf = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop())
+            .unwrap_or_default();
+        PooledBuffer {
+            buf,
+            pool: Arc::clone(self),
+        }
+    }
+
+    /// Number of idle buffers
+    pub fn idle(&self) -> usize {
+        self.buffers.lock().map_or(0, |buffers| buffers.len())
+    }
+
+    fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() > self.max_capacity {
+            return;
+        }
+        if let Ok(mut buffers) = self.buffers.lock()
+            && buffers.len() < self.max_buffers
+        {
+            buf.clear();
+            buffers.push(buf);
+        }
+    }
+}
+
+impl Default for BufferPool {
+    /// Eight idle buffers of up to 64 KiB
+    fn default() -> Self {
+        Self::new(8, 64 * 1024)
+    }
+}
+
+/// Buffer borrowed from a [`BufferPool`], returned to it on drop
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buf: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+/// Frame decoder. Compressed bundles are decompressed as a stream, one packet
+/// at a time into a pooled buffer, so a large bundle never has to be inflated
+/// in full before its messages are parsed.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    pool: Arc<BufferPool>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decoder taking its buffers from a shared pool
+    pub fn with_pool(pool: Arc<BufferPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Split a websocket frame into packets, unpacking compressed bundles.
+    ///
+    /// Packets that fail to parse are logged and skipped so one bad message does
+    /// not drop the rest of a bundle.
+    pub fn decode(&mut self, data: &[u8]) -> Vec<Packet> {
+        let mut out = Vec::new();
+        self.decode_into(data, &mut out);
+        out
+    }
+
+    /// Like [`Decoder::decode`], appending to `out`
+    pub fn decode_into(&mut self, data: &[u8], out: &mut Vec<Packet>) {
+        for (head, body) in packets(data) {
+            self.decode_packet(head, body, out);
+        }
+    }
+
+    fn decode_packet(&mut self, head: MsgHead, body: &[u8], out: &mut Vec<Packet>) {
+        match (head.operation, head.ver) {
+            (5, 0) => match serde_json::from_slice(body) {
+                Ok(json) => out.push(Packet::Message(json)),
+                Err(e) => log::error!("invalid message json: {}", e),
+            },
+            (5, 2) => {
+                if let Err(e) = self.decode_stream(flate2::read::ZlibDecoder::new(body), out) {
+                    log::error!("zlib decompress failed: {}", e);
+                }
+            }
+            #[cfg(feature = "brotli")]
+            (5, 3) => {
+                let reader = brotlic::DecompressorReader::new(body);
+                if let Err(e) = self.decode_stream(reader, out) {
+                    log::error!("brotli decompress failed: {}", e);
+                }
+            }
+            #[cfg(not(feature = "brotli"))]
+            (5, 3) => log::error!("brotli support is not enabled; authenticate with protover 2"),
+            (3, _) => {
+                let mut popularity = [0_u8; 4];
+                let len = body.len().min(4);
+                popularity[..len].copy_from_slice(&body[..len]);
+                out.push(Packet::HeartbeatReply {
+                    popularity: u32::from_be_bytes(popularity),
+                });
+            }
+            (8, _) => out.push(Packet::AuthReply(
+                serde_json::from_slice(body).unwrap_or(Value::Null),
+            )),
+            (operation, ver) => {
+                log::error!("unknown message operation={} ver={}", operation, ver)
+            }
+        }
+    }
+
+    /// Decode the packets of a decompressing reader as they come out of it.
+    /// Packets decoded before an error are kept.
+    fn decode_stream(&mut self, mut reader: impl Read, out: &mut Vec<Packet>) -> io::Result<()> {
+        let mut header = [0_u8; HEADER_LEN];
+        let mut body = self.pool.get();
+        while read_header(&mut reader, &mut header)? {
+            let head = get_msg_header(&header);
+            let Some(body_len) = (head.pack_len as usize).checked_sub(HEADER_LEN) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid packet length {}", head.pack_len),
+                ));
+            };
+            body.clear();
+            (&mut reader).take(body_len as u64).read_to_end(&mut body)?;
+            if body.len() < body_len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let skip = (head.raw_header_size as usize)
+                .saturating_sub(HEADER_LEN)
+                .min(body.len());
+            self.decode_packet(head, &body[skip..], out);
+        }
+        Ok(())
+    }
+}
+
+/// Fill `header` from `reader`; false on a clean end of stream before it
+fn read_header(reader: &mut impl Read, header: &mut [u8; HEADER_LEN]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < HEADER_LEN {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Split a websocket frame into packets, unpacking compressed bundles.
+///
+/// Uses a fresh buffer pool; long-running readers should keep a [`Decoder`]
+/// instead.
+pub fn decode(data: &[u8]) -> Vec<Packet> {
+    Decoder::new().decode(data)
+}
+
+/// Commands ignored by default: rank, banner and notice broadcasts that say
+/// nothing about the connected room
+pub const DEFAULT_IGNORED_COMMANDS: &[&str] = &[
+    "STOP_LIVE_ROOM_LIST",
+    "WIDGET_BANNER",
+    "WIDGET_GIFT_STAR_PROCESS",
+    "HOT_RANK_CHANGED",
+    "HOT_RANK_CHANGED_V2",
+    "HOT_ROOM_NOTIFY",
+    "AREA_RANK_CHANGED",
+    "NOTICE_MSG",
+    "ROOM_REAL_TIME_MESSAGE_UPDATE",
+    "LIKE_INFO_V3_UPDATE",
+];
+
+/// Ignore list of message commands dropped before they reach any handler,
+/// counting the dropped messages. Shared between threads; the list can be
+/// replaced at any time.
+#[derive(Debug)]
+pub struct CommandFilter {
+    ignored: RwLock<HashSet<String>>,
+    suppressed: AtomicU64,
+}
+
+impl Default for CommandFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_IGNORED_COMMANDS.iter().copied())
+    }
+}
+
+impl CommandFilter {
+    pub fn new<I, S>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            ignored: RwLock::new(commands.into_iter().map(Into::into).collect()),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Replace the ignored commands
+    pub fn set_ignored<I, S>(&self, commands: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Ok(mut ignored) = self.ignored.write() {
+            *ignored = commands.into_iter().map(Into::into).collect();
+        }
+    }
+
+    /// Whether `cmd` is ignored; suffixes such as `DANMU_MSG:4:0:2:2:2:0` are not compared
+    pub fn is_ignored(&self, cmd: &str) -> bool {
+        let cmd = cmd.split(':').next().unwrap_or(cmd);
+        self.ignored
+            .read()
+            .is_ok_and(|ignored| ignored.contains(cmd))
+    }
+
+    /// Whether the message `json` should be dropped, counting it if so
+    pub fn suppress(&self, json: &Value) -> bool {
+        let ignored = json["cmd"].as_str().is_some_and(|cmd| self.is_ignored(cmd));
+        if ignored {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+        }
+        ignored
+    }
+
+    /// Number of messages dropped so far
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+/// here we detail [info format is online](https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/live/message_stream.md)
+/// .
+pub fn handle(json: Value) -> Option<BiliMessage> {
+    let category = json["cmd"].as_str().unwrap_or("");
+    let data = json.get("data").unwrap_or(&json);
+    match category {
+        "DANMU_MSG" => Some(BiliMessage::Danmu {
+            user: json["info"][2][1]
+                .as_str()
+                .unwrap_or("<unknown>")
+                .to_string(),
+            text: json["info"][1].as_str().unwrap_or("").to_string(),
+            uid: json["info"][2][0].as_u64().unwrap_or(0),
+            guard_level: json["info"][7].as_u64().unwrap_or(0) as u8,
+            medal: parse_danmu_medal(&json["info"][3]),
+        }),
+        "SEND_GIFT" => Some(BiliMessage::Gift {
+            user: data["uname"]
+                .as_str()
+                .or_else(|| data["sender_uinfo"]["base"]["name"].as_str())
+                .unwrap_or("<unknown>")
+                .to_string(),
+            gift: data["giftName"].as_str().unwrap_or("").to_string(),
+            num: data["num"].as_u64().unwrap_or(1).to_string(),
+            gift_id: data["giftId"].as_u64().unwrap_or(0),
+            // Fallback until the gift catalog provides the configured price
+            value: if data["coin_type"].as_str() == Some("gold") {
+                data["total_coin"].as_u64().unwrap_or(0)
+            } else {
+                0
+            },
+            combo_id: data["batch_combo_id"]
+                .as_str()
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+        }),
+        "COMBO_SEND" => Some(BiliMessage::ComboSend {
+            combo_id: data["batch_combo_id"].as_str().unwrap_or("").to_string(),
+            user: data["uname"].as_str().unwrap_or("<unknown>").to_string(),
+            uid: data["uid"].as_u64().unwrap_or(0),
+            gift: data["gift_name"].as_str().unwrap_or("").to_string(),
+            gift_id: data["gift_id"].as_u64().unwrap_or(0),
+            total_num: data["total_num"]
+                .as_u64()
+                .or_else(|| data["batch_combo_num"].as_u64())
+                .unwrap_or(0),
+            value: data["combo_total_coin"].as_u64().unwrap_or(0),
+        }),
+        "SUPER_CHAT_MESSAGE" => Some(BiliMessage::SuperChat {
+            id: data["id"].as_u64().unwrap_or(0),
+            user: data["user_info"]["uname"]
+                .as_str()
+                .unwrap_or("<unknown>")
+                .to_string(),
+            text: data["message"].as_str().unwrap_or("").to_string(),
+            price: data["price"].as_u64().unwrap_or(0),
+            duration: data["time"].as_u64().unwrap_or(0),
+        }),
+        "SUPER_CHAT_MESSAGE_DELETE" => Some(BiliMessage::SuperChatDelete {
+            ids: data["ids"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|id| id.as_u64().or_else(|| id.as_str()?.parse().ok()))
+                .collect(),
+        }),
+        "GUARD_BUY" => {
+            let num = data["num"].as_u64().unwrap_or(1);
+            Some(BiliMessage::GuardBuy {
+                user: data["username"].as_str().unwrap_or("<unknown>").to_string(),
+                guard_level: data["guard_level"].as_u64().unwrap_or(0) as u8,
+                num,
+                // `price` is the unit price in gold coins
+                price: data["price"].as_u64().unwrap_or(0) * num,
+            })
+        }
+        "ONLINE_RANK_COUNT" => Some(BiliMessage::OnlineRankCount {
+            count: json["data"]["count"].as_u64().unwrap_or(0),
+            online_count: json["data"]["online_count"].as_u64().unwrap_or(0),
+        }),
+        "ONLINE_RANK_V2" => Some(BiliMessage::OnlineRank {
+            entries: parse_online_rank(data),
+        }),
+        "DANMU_AGGREGATION" => Some(BiliMessage::DanmuAggregation {
+            text: data["msg"].as_str().unwrap_or("").to_string(),
+            count: data["aggregation_num"].as_u64().unwrap_or(0),
+        }),
+        "ANCHOR_LOT_START" => Some(BiliMessage::LotteryStart {
+            id: data["id"].as_u64().unwrap_or(0),
+            prize: data["award_name"].as_str().unwrap_or("").to_string(),
+            prize_num: data["award_num"].as_u64().unwrap_or(1),
+            requirement: data["require_text"].as_str().unwrap_or("").to_string(),
+            danmu: data["danmu"].as_str().unwrap_or("").to_string(),
+            duration: data["time"].as_u64().unwrap_or(0),
+        }),
+        "ANCHOR_LOT_AWARD" => Some(BiliMessage::LotteryAward {
+            id: data["id"].as_u64().unwrap_or(0),
+            prize: data["award_name"].as_str().unwrap_or("").to_string(),
+            winners: data["award_users"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|user| LotteryWinner {
+                    uid: user["uid"].as_u64().unwrap_or(0),
+                    name: user["uname"].as_str().unwrap_or("<unknown>").to_string(),
+                })
+                .collect(),
+        }),
+        "VOICE_JOIN_LIST" | "VOICE_JOIN_ROOM_COUNT_INFO" => Some(BiliMessage::VoiceJoinList {
+            apply_count: data["apply_count"].as_u64().unwrap_or(0),
+        }),
+        "VOICE_JOIN_SWITCH" => Some(BiliMessage::VoiceJoinSwitch {
+            open: data["room_status"].as_u64() == Some(1),
+        }),
+        "VOICE_JOIN_STATUS" => Some(BiliMessage::VoiceJoinStatus {
+            uid: data["uid"].as_u64().unwrap_or(0),
+            user: data["user_name"]
+                .as_str()
+                .unwrap_or("<unknown>")
+                .to_string(),
+            connected: data["status"].as_u64() == Some(1),
+        }),
+        "ENTRY_EFFECT" | "ENTRY_EFFECT_MUST_RECEIVE" => {
+            // The copy text marks the user name as <%name%>
+            let text = data["copy_writing"].as_str().unwrap_or("");
+            let marked = text
+                .split_once("<%")
+                .and_then(|(_, rest)| rest.split_once("%>"))
+                .map(|(name, _)| name);
+            Some(BiliMessage::EntryEffect {
+                uid: data["uid"].as_u64().unwrap_or(0),
+                user: data["uinfo"]["base"]["name"]
+                    .as_str()
+                    .or(marked)
+                    .unwrap_or("<unknown>")
+                    .to_string(),
+                guard_level: data["privilege_type"].as_u64().unwrap_or(0) as u8,
+                text: text.replace("<%", "").replace("%>", ""),
+            })
+        }
+        "ROOM_CHANGE" => Some(BiliMessage::RoomChange {
+            title: data["title"].as_str().unwrap_or("").to_string(),
+            area_id: data["area_id"].as_u64().unwrap_or(0),
+            area_name: data["area_name"].as_str().unwrap_or("").to_string(),
+            parent_area_name: data["parent_area_name"].as_str().unwrap_or("").to_string(),
+        }),
+        // Add more cases for other types as needed
+        _ => Some(BiliMessage::Raw(json)),
+    }
+}
+
+/// Parse the medal array of a DANMU_MSG (`info[3]`: level, name, anchor, room, ...)
+fn parse_danmu_medal(medal: &Value) -> Option<FanMedal> {
+    let level = medal[0].as_u64()?;
+    let name = medal[1].as_str().filter(|name| !name.is_empty())?;
+    Some(FanMedal {
+        name: name.to_string(),
+        level: level as u8,
+    })
+}
+
+/// Parse the contributor list of an ONLINE_RANK_V2 message
+fn parse_online_rank(data: &Value) -> Vec<RankEntry> {
+    // Newer payloads use `online_list`, older ones `list`
+    let list = data["online_list"]
+        .as_array()
+        .or_else(|| data["list"].as_array());
+    list.into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(idx, entry)| RankEntry {
+            rank: entry["rank"].as_u64().unwrap_or(idx as u64 + 1) as u32,
+            uid: entry["uid"].as_u64().unwrap_or(0),
+            name: entry["uname"].as_str().unwrap_or("<unknown>").to_string(),
+            // The score is sent as a string
+            score: entry["score"]
+                .as_u64()
+                .or_else(|| entry["score"].as_str().and_then(|s| s.parse().ok()))
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Server packet with the given operation and protocol version
+    fn server_packet(operation: u32, ver: u16, body: &[u8]) -> Vec<u8> {
+        let mut packet = make_raw_packet(body, Operation::HEARTBEAT);
+        packet[6..8].copy_from_slice(&ver.to_be_bytes());
+        packet[8..12].copy_from_slice(&operation.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_make_packet_header() {
+        let packet = make_packet(r#"{"roomid": 1}"#, Operation::AUTH);
+        let head = get_msg_header(&packet);
+        assert_eq!(head.pack_len as usize, packet.len());
+        assert_eq!(head.raw_header_size, 16);
+        assert_eq!(head.operation, 7);
+        assert_eq!(&packet[HEADER_LEN..], br#"{"roomid":1}"#);
+    }
+
+    #[test]
+    fn test_decode_zlib_bundle() {
+        let mut bundle = server_packet(5, 0, br#"{"cmd":"DANMU_MSG"}"#);
+        bundle.extend(server_packet(5, 0, br#"{"cmd":"LIKE"}"#));
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(&bundle).unwrap();
+        let mut frame = server_packet(5, 2, &encoder.finish().unwrap());
+        frame.extend(server_packet(3, 1, &1234_u32.to_be_bytes()));
+
+        assert_eq!(
+            decode(&frame),
+            vec![
+                Packet::Message(serde_json::json!({"cmd": "DANMU_MSG"})),
+                Packet::Message(serde_json::json!({"cmd": "LIKE"})),
+                Packet::HeartbeatReply { popularity: 1234 },
+            ]
+        );
+        // Truncated frames decode what is complete instead of panicking
+        assert_eq!(decode(&frame[..10]), vec![]);
+    }
+
+    #[test]
+    fn test_packets_borrow_frame() {
+        let mut frame = server_packet(5, 0, br#"{"cmd":"A"}"#);
+        frame.extend(server_packet(5, 0, br#"{"cmd":"B"}"#));
+
+        let bodies: Vec<&[u8]> = packets(&frame).map(|(_, body)| body).collect();
+        assert_eq!(bodies, vec![&br#"{"cmd":"A"}"#[..], &br#"{"cmd":"B"}"#[..]]);
+        let offset = bodies[1].as_ptr() as usize - frame.as_ptr() as usize;
+        assert_eq!(offset, 2 * HEADER_LEN + bodies[0].len());
+    }
+
+    #[test]
+    fn test_decoder_streams_through_pool() {
+        let mut bundle = server_packet(5, 0, br#"{"cmd":"A"}"#);
+        bundle.extend(server_packet(5, 0, br#"{"cmd":"B"}"#));
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(&bundle).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let frame = server_packet(5, 2, &compressed);
+
+        let pool = Arc::new(BufferPool::new(2, 1024));
+        let mut decoder = Decoder::with_pool(Arc::clone(&pool));
+        assert_eq!(decoder.decode(&frame).len(), 2);
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(decoder.decode(&frame).len(), 2);
+        assert_eq!(pool.idle(), 1);
+
+        // A bundle cut short keeps the packets that were complete
+        let truncated = server_packet(5, 2, &compressed[..compressed.len() - 4]);
+        let decoded = decoder.decode(&truncated);
+        assert!(decoded.len() <= 2);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn test_handle_danmu_user_info() {
+        let json = serde_json::json!({
+            "cmd": "DANMU_MSG",
+            "info": [
+                [0],
+                "hello",
+                [12345, "viewer", 0],
+                [21, "粉丝团", "streamer", 24779526],
+                [],
+                "",
+                0,
+                3
+            ]
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::Danmu {
+                user: "viewer".to_string(),
+                text: "hello".to_string(),
+                uid: 12345,
+                guard_level: 3,
+                medal: Some(FanMedal {
+                    name: "粉丝团".to_string(),
+                    level: 21,
+                }),
+            })
+        );
+        assert_eq!(parse_danmu_medal(&serde_json::json!([])), None);
+    }
+
+    #[test]
+    fn test_handle_online_rank() {
+        let json = serde_json::json!({
+            "cmd": "ONLINE_RANK_V2",
+            "data": {
+                "online_list": [
+                    {"uid": 1, "uname": "first", "score": "520", "rank": 1},
+                    {"uid": 2, "uname": "second", "score": 100}
+                ],
+                "rank_type": "gold-rank"
+            }
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::OnlineRank {
+                entries: vec![
+                    RankEntry {
+                        rank: 1,
+                        uid: 1,
+                        name: "first".to_string(),
+                        score: 520,
+                    },
+                    RankEntry {
+                        rank: 2,
+                        uid: 2,
+                        name: "second".to_string(),
+                        score: 100,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_super_chat_delete() {
+        let json = serde_json::json!({
+            "cmd": "SUPER_CHAT_MESSAGE_DELETE",
+            "data": {"ids": [123, "456"]},
+            "roomid": 1
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::SuperChatDelete {
+                ids: vec![123, 456],
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_guard_buy_total_price() {
+        let json = serde_json::json!({
+            "cmd": "GUARD_BUY",
+            "data": {"username": "viewer", "guard_level": 3, "num": 3, "price": 198000}
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::GuardBuy {
+                user: "viewer".to_string(),
+                guard_level: 3,
+                num: 3,
+                price: 594000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_combo_send() {
+        let combo_id = "batch:gift:combo_id:1:2:31036:1700000000.1";
+        let gift = serde_json::json!({
+            "cmd": "SEND_GIFT",
+            "data": {"uname": "viewer", "giftName": "小花花", "num": 1, "giftId": 31036,
+                     "coin_type": "gold", "total_coin": 100, "batch_combo_id": combo_id}
+        });
+        let combo = serde_json::json!({
+            "cmd": "COMBO_SEND",
+            "data": {"uname": "viewer", "uid": 1, "gift_name": "小花花", "gift_id": 31036,
+                     "batch_combo_id": combo_id, "batch_combo_num": 10, "total_num": 10,
+                     "combo_total_coin": 1000}
+        });
+        match handle(gift) {
+            Some(BiliMessage::Gift { combo_id: id, .. }) => {
+                assert_eq!(id.as_deref(), Some(combo_id))
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert_eq!(
+            handle(combo),
+            Some(BiliMessage::ComboSend {
+                combo_id: combo_id.to_string(),
+                user: "viewer".to_string(),
+                uid: 1,
+                gift: "小花花".to_string(),
+                gift_id: 31036,
+                total_num: 10,
+                value: 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_anchor_lottery() {
+        let start = serde_json::json!({
+            "cmd": "ANCHOR_LOT_START",
+            "data": {"id": 42, "award_name": "周边", "award_num": 2, "danmu": "来了",
+                     "require_text": "当前主播粉丝勋章至少1级", "time": 600}
+        });
+        let award = serde_json::json!({
+            "cmd": "ANCHOR_LOT_AWARD",
+            "data": {"id": 42, "award_name": "周边", "award_num": 2,
+                     "award_users": [{"uid": 1, "uname": "lucky", "face": ""}]}
+        });
+        assert_eq!(
+            handle(start),
+            Some(BiliMessage::LotteryStart {
+                id: 42,
+                prize: "周边".to_string(),
+                prize_num: 2,
+                requirement: "当前主播粉丝勋章至少1级".to_string(),
+                danmu: "来了".to_string(),
+                duration: 600,
+            })
+        );
+        assert_eq!(
+            handle(award),
+            Some(BiliMessage::LotteryAward {
+                id: 42,
+                prize: "周边".to_string(),
+                winners: vec![LotteryWinner {
+                    uid: 1,
+                    name: "lucky".to_string(),
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_voice_join() {
+        let list = serde_json::json!({
+            "cmd": "VOICE_JOIN_LIST",
+            "data": {"room_id": 1, "category": 1, "apply_count": 3, "red_point": 1}
+        });
+        let switch = serde_json::json!({
+            "cmd": "VOICE_JOIN_SWITCH",
+            "data": {"room_id": 1, "root_status": 1, "room_status": 1}
+        });
+        let status = serde_json::json!({
+            "cmd": "VOICE_JOIN_STATUS",
+            "data": {"room_id": 1, "status": 1, "uid": 7, "user_name": "guest", "guard": 0}
+        });
+        assert_eq!(
+            handle(list),
+            Some(BiliMessage::VoiceJoinList { apply_count: 3 })
+        );
+        assert_eq!(
+            handle(switch),
+            Some(BiliMessage::VoiceJoinSwitch { open: true })
+        );
+        assert_eq!(
+            handle(status),
+            Some(BiliMessage::VoiceJoinStatus {
+                uid: 7,
+                user: "guest".to_string(),
+                connected: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_entry_effect() {
+        let json = serde_json::json!({
+            "cmd": "ENTRY_EFFECT",
+            "data": {"id": 4, "uid": 9, "privilege_type": 3,
+                     "copy_writing": "欢迎舰长 <%captain%> 进入直播间"}
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::EntryEffect {
+                uid: 9,
+                user: "captain".to_string(),
+                guard_level: 3,
+                text: "欢迎舰长 captain 进入直播间".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_command_filter() {
+        let filter = CommandFilter::default();
+        assert!(filter.suppress(&serde_json::json!({"cmd": "STOP_LIVE_ROOM_LIST"})));
+        assert!(!filter.suppress(&serde_json::json!({"cmd": "DANMU_MSG:4:0:2:2:2:0"})));
+        assert!(!filter.suppress(&serde_json::json!({"data": {}})));
+        assert_eq!(filter.suppressed(), 1);
+
+        filter.set_ignored(["DANMU_MSG"]);
+        assert!(filter.suppress(&serde_json::json!({"cmd": "DANMU_MSG:4:0:2:2:2:0"})));
+        assert!(!filter.is_ignored("STOP_LIVE_ROOM_LIST"));
+        assert_eq!(filter.suppressed(), 2);
+    }
+
+    #[test]
+    fn test_handle_room_change() {
+        let json = serde_json::json!({
+            "cmd": "ROOM_CHANGE",
+            "data": {"title": "新标题", "area_id": 745, "parent_area_id": 9,
+                     "area_name": "虚拟Gamer", "parent_area_name": "虚拟主播",
+                     "live_key": "0", "sub_session_key": ""}
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::RoomChange {
+                title: "新标题".to_string(),
+                area_id: 745,
+                area_name: "虚拟Gamer".to_string(),
+                parent_area_name: "虚拟主播".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_handle_danmu_aggregation() {
+        let json = serde_json::json!({
+            "cmd": "DANMU_AGGREGATION",
+            "data": {
+                "activity_identity": "1",
+                "activity_source": 2,
+                "aggregation_cycle": 1,
+                "aggregation_num": 41,
+                "msg": "老板大气！",
+                "show_rows": 1,
+                "show_time": 2
+            }
+        });
+        assert_eq!(
+            handle(json),
+            Some(BiliMessage::DanmuAggregation {
+                text: "老板大气！".to_string(),
+                count: 41,
+            })
+        );
+    }
+}
@@ -0,0 +1,595 @@
+// src/client/room_management.rs
+//! Room management helpers for streamers: title, area, and start/stop live
+
+use crate::auth::{ROOM_INIT_URL, apply_request_headers, post_live_api};
+use crate::http_server::{Request, Response};
+use crate::models::BiliMessage;
+use crate::runtime::AsyncRuntime;
+use crate::scheduler::EventContext;
+use futures_channel::mpsc::Sender;
+use log::info;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const ROOM_UPDATE_URL: &str = "https://api.live.bilibili.com/room/v1/Room/update";
+pub const START_LIVE_URL: &str = "https://api.live.bilibili.com/room/v1/Room/startLive";
+pub const STOP_LIVE_URL: &str = "https://api.live.bilibili.com/room/v1/Room/stopLive";
+pub const ROOM_INFO_URL: &str = "https://api.live.bilibili.com/room/v1/Room/get_info";
+
+/// Path prefix of the HTTP control routes
+pub const API_PREFIX: &str = "/api/room/";
+
+/// Default interval between room info refreshes of `watch_room_info`
+pub const ROOM_INFO_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Platform identifier sent with start/stop live requests
+const LIVE_PLATFORM: &str = "pc_link";
+
+/// RTMP push target returned when a live is started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RtmpInfo {
+    /// RTMP server address
+    pub addr: String,
+    /// Stream key (keep this secret)
+    pub code: String,
+}
+
+impl RtmpInfo {
+    /// Stream key with all but its last four characters hidden, for display
+    pub fn masked_code(&self) -> String {
+        let shown = self.code.chars().count().saturating_sub(4);
+        self.code
+            .chars()
+            .enumerate()
+            .map(|(i, c)| if i < shown { '*' } else { c })
+            .collect()
+    }
+
+    fn from_start_live_data(data: &Value) -> Option<Self> {
+        Some(Self {
+            addr: data["rtmp"]["addr"].as_str()?.to_string(),
+            code: data["rtmp"]["code"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Title and area of a room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomInfo {
+    pub title: String,
+    /// Live area id (`area_v2`)
+    pub area_id: u64,
+    pub area_name: String,
+    pub parent_area_name: String,
+    /// Whether the room is currently live
+    pub live: bool,
+}
+
+impl RoomInfo {
+    fn from_data(data: &Value) -> Option<Self> {
+        Some(Self {
+            title: data["title"].as_str()?.to_string(),
+            area_id: data["area_id"].as_u64().unwrap_or(0),
+            area_name: data["area_name"].as_str().unwrap_or("").to_string(),
+            parent_area_name: data["parent_area_name"].as_str().unwrap_or("").to_string(),
+            live: data["live_status"].as_u64() == Some(1),
+        })
+    }
+
+    /// The same update as a ROOM_CHANGE message
+    pub fn to_message(&self) -> BiliMessage {
+        BiliMessage::RoomChange {
+            title: self.title.clone(),
+            area_id: self.area_id,
+            area_name: self.area_name.clone(),
+            parent_area_name: self.parent_area_name.clone(),
+        }
+    }
+}
+
+/// Title, streamer, area and cover of a room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomMetadata {
+    /// Long room id
+    pub room_id: u64,
+    pub title: String,
+    pub streamer: String,
+    pub streamer_uid: u64,
+    /// Live area id (`area_v2`)
+    pub area_id: u64,
+    pub area_name: String,
+    pub parent_area_name: String,
+    /// Cover image url
+    pub cover: String,
+    /// Whether the room is currently live
+    pub live: bool,
+}
+
+impl RoomMetadata {
+    fn from_data(data: &Value) -> Option<Self> {
+        let room = &data["room_info"];
+        Some(Self {
+            room_id: room["room_id"].as_u64()?,
+            title: room["title"].as_str()?.to_string(),
+            streamer: data["anchor_info"]["base_info"]["uname"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            streamer_uid: room["uid"].as_u64().unwrap_or(0),
+            area_id: room["area_id"].as_u64().unwrap_or(0),
+            area_name: room["area_name"].as_str().unwrap_or("").to_string(),
+            parent_area_name: room["parent_area_name"].as_str().unwrap_or("").to_string(),
+            cover: room["cover"].as_str().unwrap_or("").to_string(),
+            live: room["live_status"].as_u64() == Some(1),
+        })
+    }
+
+    /// The metadata as a `RoomInfo` message
+    pub fn to_message(&self) -> BiliMessage {
+        BiliMessage::RoomInfo {
+            room_id: self.room_id,
+            title: self.title.clone(),
+            streamer: self.streamer.clone(),
+            streamer_uid: self.streamer_uid,
+            area_id: self.area_id,
+            area_name: self.area_name.clone(),
+            parent_area_name: self.parent_area_name.clone(),
+            cover: self.cover.clone(),
+            live: self.live,
+        }
+    }
+
+    /// Title and area alone, as compared by `watch_room_info`
+    fn to_room_change(&self) -> BiliMessage {
+        BiliMessage::RoomChange {
+            title: self.title.clone(),
+            area_id: self.area_id,
+            area_name: self.area_name.clone(),
+            parent_area_name: self.parent_area_name.clone(),
+        }
+    }
+}
+
+/// GET a public live API endpoint, returning the `data` field
+pub(crate) fn get_live_api(url: &str, what: &str) -> Result<Value, String> {
+    get_live_api_with_cookies(url, what, None)
+}
+
+/// `get_live_api` for endpoints about the logged-in account
+pub(crate) fn get_live_api_with_cookies(
+    url: &str,
+    what: &str,
+    cookies: Option<&str>,
+) -> Result<Value, String> {
+    let client = reqwest::blocking::Client::builder()
+        .https_only(true)
+        .build()
+        .map_err(|e| format!("http client init failed: {}", e))?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(cookies) = cookies {
+        let cookie =
+            HeaderValue::from_str(cookies).map_err(|e| format!("invalid cookies: {}", e))?;
+        headers.insert(COOKIE, cookie);
+    }
+    apply_request_headers(&mut headers);
+    let mut body: Value = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .and_then(|resp| resp.json())
+        .map_err(|e| format!("fetch {} failed: {}", what, e))?;
+
+    if body["code"].as_i64() != Some(0) {
+        return Err(format!(
+            "{} API error: {}",
+            what,
+            body["message"].as_str().unwrap_or("unknown error")
+        ));
+    }
+    Ok(body["data"].take())
+}
+
+/// Fetch the title and area of a room
+pub fn fetch_room_info(room_id: u64) -> Result<RoomInfo, String> {
+    let data = get_live_api(
+        &format!("{}?room_id={}", ROOM_INFO_URL, room_id),
+        "room info",
+    )?;
+    RoomInfo::from_data(&data).ok_or_else(|| "room info has no title".to_string())
+}
+
+/// Fetch the title, streamer name, area and cover of a room
+pub fn fetch_room_metadata(room_id: u64) -> Result<RoomMetadata, String> {
+    let data = get_live_api(
+        &format!("{}?room_id={}", ROOM_INIT_URL, room_id),
+        "room metadata",
+    )?;
+    RoomMetadata::from_data(&data).ok_or_else(|| "room metadata has no title".to_string())
+}
+
+/// Refresh the info of the room in `room` every `interval` from a background
+/// thread. The first refresh of each room sends its `RoomInfo`; later ones send
+/// a `RoomChange` message to `tx` whenever the title or area differs from the
+/// last one sent. Stops once `tx` is closed.
+pub fn watch_room_info(
+    room: Arc<AtomicU64>,
+    interval: Duration,
+    mut tx: Sender<BiliMessage>,
+) -> Result<JoinHandle<()>, String> {
+    thread::Builder::new()
+        .name("blivedm-room-info".to_string())
+        .spawn(move || {
+            let mut last: Option<(u64, BiliMessage)> = None;
+            while !tx.is_closed() {
+                let room_id = room.load(Ordering::Relaxed);
+                if last
+                    .as_ref()
+                    .is_none_or(|(last_room, _)| *last_room != room_id)
+                {
+                    match fetch_room_metadata(room_id) {
+                        Ok(metadata) => {
+                            let _ = tx.try_send(metadata.to_message());
+                            last = Some((room_id, metadata.to_room_change()));
+                            thread::sleep(interval);
+                            continue;
+                        }
+                        Err(e) => log::debug!("room metadata unavailable: {}", e),
+                    }
+                }
+                match fetch_room_info(room_id) {
+                    Ok(info) => {
+                        let msg = info.to_message();
+                        if last.as_ref() != Some(&(room_id, msg.clone())) {
+                            let _ = tx.try_send(msg.clone());
+                            last = Some((room_id, msg));
+                        }
+                    }
+                    Err(e) => log::debug!("room info refresh failed: {}", e),
+                }
+                thread::sleep(interval);
+            }
+        })
+        .map_err(|e| format!("failed to start room info thread: {}", e))
+}
+
+/// Update the title of the room in `context`
+pub async fn update_title(
+    title: &str,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let form = vec![
+        ("room_id", context.room_id.to_string()),
+        ("title", title.to_string()),
+    ];
+    post_live_api(ROOM_UPDATE_URL, form, context).await?;
+    info!("Updated room title to '{}'", title);
+    Ok(())
+}
+
+/// Move the room in `context` to another live area (`area_v2` id)
+pub async fn update_area(
+    area_id: u32,
+    context: &EventContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let form = vec![
+        ("room_id", context.room_id.to_string()),
+        ("area_id", area_id.to_string()),
+    ];
+    post_live_api(ROOM_UPDATE_URL, form, context).await?;
+    info!("Updated room area to {}", area_id);
+    Ok(())
+}
+
+/// Start the live in the given area and return the RTMP push target
+pub async fn start_live(
+    area_id: u32,
+    context: &EventContext,
+) -> Result<RtmpInfo, Box<dyn std::error::Error>> {
+    let form = vec![
+        ("room_id", context.room_id.to_string()),
+        ("area_v2", area_id.to_string()),
+        ("platform", LIVE_PLATFORM.to_string()),
+    ];
+    let data = post_live_api(START_LIVE_URL, form, context).await?;
+    let rtmp = RtmpInfo::from_start_live_data(&data)
+        .ok_or("Start live response is missing the RTMP address")?;
+    info!("Started live in area {}", area_id);
+    Ok(rtmp)
+}
+
+/// Stop the live in the room in `context`
+pub async fn stop_live(context: &EventContext) -> Result<(), Box<dyn std::error::Error>> {
+    let form = vec![
+        ("room_id", context.room_id.to_string()),
+        ("platform", LIVE_PLATFORM.to_string()),
+    ];
+    post_live_api(STOP_LIVE_URL, form, context).await?;
+    info!("Stopped live in room {}", context.room_id);
+    Ok(())
+}
+
+/// A control action of the HTTP API
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ControlAction {
+    Title(String),
+    Area(u32),
+    StartLive(u32),
+    StopLive,
+}
+
+impl ControlAction {
+    /// Action of a POST to `/api/room/<action>` with a JSON body
+    fn parse(request: &Request) -> Option<Result<Self, String>> {
+        let action = request.path.strip_prefix(API_PREFIX)?;
+        let body: Value = serde_json::from_str(&request.body).unwrap_or(Value::Null);
+        let area_id = || {
+            body["area_id"]
+                .as_u64()
+                .and_then(|area_id| u32::try_from(area_id).ok())
+                .ok_or_else(|| "expected {\"area_id\": <id>}".to_string())
+        };
+        Some(match action {
+            "title" => body["title"]
+                .as_str()
+                .filter(|title| !title.trim().is_empty())
+                .map(|title| ControlAction::Title(title.to_string()))
+                .ok_or_else(|| "expected {\"title\": <text>}".to_string()),
+            "area" => area_id().map(ControlAction::Area),
+            "live/start" => area_id().map(ControlAction::StartLive),
+            "live/stop" => Ok(ControlAction::StopLive),
+            _ => return None,
+        })
+    }
+}
+
+/// Whether `host`, a `Host` header or the authority of an `Origin`, names
+/// this machine
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "[::1]" || name.starts_with("127.")
+}
+
+/// Reject requests a web page in the browser could make: cross-site forms
+/// cannot send JSON, and pages of other sites, or of a name rebound to this
+/// address, carry a foreign `Origin` or `Host`
+fn check_local_json(request: &Request) -> Result<(), Response> {
+    let json = request
+        .header("Content-Type")
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return Err(Response::error(
+            415,
+            "expected Content-Type: application/json",
+        ));
+    }
+    if let Some(origin) = request.header("Origin")
+        && !origin
+            .split_once("://")
+            .is_some_and(|(_, host)| is_local_host(host))
+    {
+        return Err(Response::error(
+            403,
+            "cross-origin requests are not allowed",
+        ));
+    }
+    if !request.header("Host").is_some_and(is_local_host) {
+        return Err(Response::error(
+            403,
+            "only requests to localhost are allowed",
+        ));
+    }
+    Ok(())
+}
+
+/// HTTP routes for the room control actions, POSTed with a JSON body:
+/// `/api/room/title` with `{"title": ...}`, `/api/room/area` with
+/// `{"area_id": ...}`, `/api/room/live/start` with `{"area_id": ...}`, which
+/// answers with the RTMP `addr` and `code`, and `/api/room/live/stop`.
+/// They act on the logged-in account, so only requests with
+/// `Content-Type: application/json` to `localhost` or `127.0.0.1`, and from no
+/// other origin, are served.
+pub fn routes<F>(
+    rt: AsyncRuntime,
+    context: F,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static
+where
+    F: Fn() -> EventContext + Send + Sync + 'static,
+{
+    move |request| {
+        let action = match ControlAction::parse(request)? {
+            Ok(_) if request.method != "POST" => {
+                return Some(Response::error(405, "use POST"));
+            }
+            Ok(action) => action,
+            Err(e) => return Some(Response::error(400, &e)),
+        };
+        if let Err(response) = check_local_json(request) {
+            return Some(response);
+        }
+        let context = context();
+        let result = rt.block_on(async {
+            match action {
+                ControlAction::Title(title) => update_title(&title, &context)
+                    .await
+                    .map(|()| Response::json(&serde_json::json!({ "title": title }))),
+                ControlAction::Area(area_id) => update_area(area_id, &context)
+                    .await
+                    .map(|()| Response::json(&serde_json::json!({ "area_id": area_id }))),
+                ControlAction::StartLive(area_id) => start_live(area_id, &context)
+                    .await
+                    .map(|rtmp| Response::json(&rtmp)),
+                ControlAction::StopLive => stop_live(&context)
+                    .await
+                    .map(|()| Response::json(&serde_json::json!({ "live": false }))),
+            }
+            .map_err(|e| e.to_string())
+        });
+        Some(result.unwrap_or_else(|e| Response::error(502, &e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtmp_info_from_start_live_data() {
+        let data = serde_json::json!({
+            "change": 1,
+            "status": "LIVE",
+            "rtmp": {
+                "addr": "rtmp://live-push.bilivideo.com/live-bvc/",
+                "code": "?streamname=live_1_2&key=secret"
+            }
+        });
+        assert_eq!(
+            RtmpInfo::from_start_live_data(&data),
+            Some(RtmpInfo {
+                addr: "rtmp://live-push.bilivideo.com/live-bvc/".to_string(),
+                code: "?streamname=live_1_2&key=secret".to_string(),
+            })
+        );
+        assert_eq!(RtmpInfo::from_start_live_data(&Value::Null), None);
+        let rtmp = RtmpInfo::from_start_live_data(&data).unwrap();
+        assert_eq!(rtmp.masked_code(), format!("{}cret", "*".repeat(27)));
+    }
+
+    #[test]
+    fn test_control_action_parse() {
+        let post = |path: &str, body: &str| {
+            Request::parse(&format!("POST {} HTTP/1.1\r\n\r\n{}", path, body)).unwrap()
+        };
+        assert_eq!(
+            ControlAction::parse(&post("/api/room/title", r#"{"title": "晚间杂谈"}"#)),
+            Some(Ok(ControlAction::Title("晚间杂谈".to_string())))
+        );
+        assert_eq!(
+            ControlAction::parse(&post("/api/room/live/start", r#"{"area_id": 744}"#)),
+            Some(Ok(ControlAction::StartLive(744)))
+        );
+        assert_eq!(
+            ControlAction::parse(&post("/api/room/live/stop", "")),
+            Some(Ok(ControlAction::StopLive))
+        );
+        assert!(matches!(
+            ControlAction::parse(&post("/api/room/area", r#"{"area_id": "x"}"#)),
+            Some(Err(_))
+        ));
+        assert_eq!(ControlAction::parse(&post("/api/room/other", "")), None);
+        assert_eq!(ControlAction::parse(&post("/api/users/1", "")), None);
+    }
+
+    #[test]
+    fn test_control_requests_must_be_local_json() {
+        let status = |headers: &str| {
+            let request = Request::parse(&format!(
+                "POST /api/room/live/stop HTTP/1.1\r\n{}\r\n",
+                headers
+            ))
+            .unwrap();
+            check_local_json(&request)
+                .err()
+                .map(|response| response.status)
+        };
+        let json = "Content-Type: application/json; charset=utf-8\r\n";
+        assert_eq!(status(&format!("Host: 127.0.0.1:9184\r\n{}", json)), None);
+        assert_eq!(
+            status(&format!(
+                "Host: localhost:9184\r\nOrigin: http://localhost:3000\r\n{}",
+                json
+            )),
+            None
+        );
+        assert_eq!(status(&format!("Host: [::1]:9184\r\n{}", json)), None);
+        // A cross-site form
+        assert_eq!(
+            status("Host: 127.0.0.1:9184\r\nContent-Type: application/x-www-form-urlencoded\r\n"),
+            Some(415)
+        );
+        assert_eq!(
+            status(&format!(
+                "Host: 127.0.0.1:9184\r\nOrigin: https://example.com\r\n{}",
+                json
+            )),
+            Some(403)
+        );
+        assert_eq!(
+            status(&format!("Host: 127.0.0.1:9184\r\nOrigin: null\r\n{}", json)),
+            Some(403)
+        );
+        // DNS rebinding: a foreign name resolving to this machine
+        assert_eq!(
+            status(&format!("Host: evil.example:9184\r\n{}", json)),
+            Some(403)
+        );
+        assert_eq!(status(json), Some(403));
+    }
+
+    #[test]
+    fn test_room_info_from_data() {
+        let data = serde_json::json!({
+            "room_id": 24779526,
+            "live_status": 1,
+            "title": "晚间杂谈",
+            "area_id": 744,
+            "area_name": "虚拟Singer",
+            "parent_area_name": "虚拟主播"
+        });
+        let info = RoomInfo::from_data(&data).unwrap();
+        assert!(info.live);
+        assert_eq!(
+            info.to_message(),
+            BiliMessage::RoomChange {
+                title: "晚间杂谈".to_string(),
+                area_id: 744,
+                area_name: "虚拟Singer".to_string(),
+                parent_area_name: "虚拟主播".to_string(),
+            }
+        );
+        assert_eq!(RoomInfo::from_data(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_room_metadata_from_data() {
+        let data = serde_json::json!({
+            "room_info": {
+                "uid": 2,
+                "room_id": 24779526,
+                "title": "晚间杂谈",
+                "cover": "https://i0.hdslb.com/bfs/live/cover.jpg",
+                "live_status": 0,
+                "area_id": 744,
+                "area_name": "虚拟Singer",
+                "parent_area_name": "虚拟主播"
+            },
+            "anchor_info": {"base_info": {"uname": "主播"}}
+        });
+        let metadata = RoomMetadata::from_data(&data).unwrap();
+        assert_eq!(
+            metadata.to_message(),
+            BiliMessage::RoomInfo {
+                room_id: 24779526,
+                title: "晚间杂谈".to_string(),
+                streamer: "主播".to_string(),
+                streamer_uid: 2,
+                area_id: 744,
+                area_name: "虚拟Singer".to_string(),
+                parent_area_name: "虚拟主播".to_string(),
+                cover: "https://i0.hdslb.com/bfs/live/cover.jpg".to_string(),
+                live: false,
+            }
+        );
+        assert_eq!(metadata.to_message().kind(), "room_info");
+        assert_eq!(RoomMetadata::from_data(&Value::Null), None);
+    }
+}
@@ -0,0 +1,165 @@
+// src/client/runtime.rs
+//! Tokio runtime used for async work (HTTP requests of plugins and commands)
+//!
+//! Handlers take an `AsyncRuntime` instead of each building their own
+//! multi-threaded runtime, so an embedding application can pass its existing
+//! runtime or choose a single-threaded one.
+
+use futures::channel::oneshot;
+use std::future::Future;
+use std::sync::Arc;
+use std::thread;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::task::JoinHandle;
+
+/// Kind of runtime to build
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RuntimeFlavor {
+    /// One background thread runs all tasks
+    CurrentThread,
+    /// A worker pool with one worker per CPU core
+    #[default]
+    MultiThread,
+    /// A worker pool of the given size
+    Workers(usize),
+}
+
+impl std::str::FromStr for RuntimeFlavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "current_thread" => Ok(RuntimeFlavor::CurrentThread),
+            "multi_thread" => Ok(RuntimeFlavor::MultiThread),
+            other => other
+                .parse::<usize>()
+                .ok()
+                .filter(|workers| *workers > 0)
+                .map(RuntimeFlavor::Workers)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown runtime '{}' (expected current_thread, multi_thread or a worker count)",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// Keeps an owned runtime alive; shuts it down when the last clone is dropped
+struct Owned {
+    runtime: Option<Runtime>,
+    /// Stops the thread driving a current-thread runtime when dropped
+    _stop: Option<oneshot::Sender<()>>,
+}
+
+/// Handle to the runtime async work is spawned on, cheap to clone
+#[derive(Clone)]
+pub struct AsyncRuntime {
+    handle: Handle,
+    _owned: Option<Arc<Owned>>,
+}
+
+impl AsyncRuntime {
+    /// Build a runtime of the given flavor, owned by the returned value and its clones
+    pub fn new(flavor: RuntimeFlavor) -> Result<Self, String> {
+        let mut builder = match flavor {
+            RuntimeFlavor::CurrentThread => Builder::new_current_thread(),
+            RuntimeFlavor::MultiThread => Builder::new_multi_thread(),
+            RuntimeFlavor::Workers(workers) => {
+                let mut builder = Builder::new_multi_thread();
+                builder.worker_threads(workers);
+                builder
+            }
+        };
+        let runtime = builder
+            .enable_all()
+            .thread_name("blivedm-runtime")
+            .build()
+            .map_err(|e| format!("failed to build tokio runtime: {}", e))?;
+        let handle = runtime.handle().clone();
+
+        let owned = match flavor {
+            // A current-thread runtime only runs tasks while something blocks on it
+            RuntimeFlavor::CurrentThread => {
+                let (stop, stopped) = oneshot::channel::<()>();
+                thread::Builder::new()
+                    .name("blivedm-runtime".to_string())
+                    .spawn(move || {
+                        let _ = runtime.block_on(stopped);
+                    })
+                    .map_err(|e| format!("failed to start runtime thread: {}", e))?;
+                Owned {
+                    runtime: None,
+                    _stop: Some(stop),
+                }
+            }
+            _ => Owned {
+                runtime: Some(runtime),
+                _stop: None,
+            },
+        };
+        Ok(Self {
+            handle,
+            _owned: Some(Arc::new(owned)),
+        })
+    }
+
+    /// Use a runtime owned by the caller; it must outlive the handlers using it
+    pub fn from_handle(handle: Handle) -> Self {
+        Self {
+            handle,
+            _owned: None,
+        }
+    }
+
+    /// The runtime of the calling context, if called from inside one
+    pub fn current() -> Option<Self> {
+        Handle::try_current().ok().map(Self::from_handle)
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Spawn a task on the runtime
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    /// Run a future to completion from a thread outside the runtime
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle.block_on(future)
+    }
+}
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        // Dropping a runtime from async context panics; this never blocks
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_flavors() {
+        assert_eq!("current-thread".parse(), Ok(RuntimeFlavor::CurrentThread));
+        assert_eq!("4".parse(), Ok(RuntimeFlavor::Workers(4)));
+        assert!("0".parse::<RuntimeFlavor>().is_err());
+
+        for flavor in [RuntimeFlavor::CurrentThread, RuntimeFlavor::Workers(1)] {
+            let runtime = AsyncRuntime::new(flavor).unwrap();
+            let task = runtime.spawn(async { 40 + 2 });
+            assert_eq!(runtime.block_on(task).unwrap(), 42);
+        }
+    }
+}
@@ -1,8 +1,14 @@
 // In Cargo.toml, ensure you have: client = { path = "../client" }
-use models::BiliMessage;
-use std::sync::Arc;
+use models::{BiliMessage, Danmaku, Gift, SuperChat};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::models;
+use reqwest::header::HeaderMap;
 
 /// Context information passed to event handlers
 #[derive(Debug, Clone)]
@@ -11,24 +17,157 @@ pub struct EventContext {
     pub cookies: Option<String>,
     /// Room ID where the event occurred
     pub room_id: u64,
+    /// Headers set on the API calls made with this context, see `auth::RequestHeaders`
+    pub request_headers: Option<HeaderMap>,
 }
 
 impl EventContext {
     /// Create a new EventContext with automatic cookie detection
     pub fn new_with_auto_cookies(room_id: u64) -> Self {
         let cookies = crate::auth::get_cookies_or_browser(None);
-        Self { cookies, room_id }
+        Self::new(cookies, room_id)
     }
 
     /// Create a new EventContext with provided cookies
     pub fn new(cookies: Option<String>, room_id: u64) -> Self {
-        Self { cookies, room_id }
+        Self {
+            cookies,
+            room_id,
+            request_headers: None,
+        }
+    }
+
+    pub fn with_request_headers(mut self, headers: Option<HeaderMap>) -> Self {
+        self.request_headers = headers;
+        self
+    }
+}
+
+/// Message kinds a handler is dispatched, by `BiliMessage::kind`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Subscription {
+    #[default]
+    All,
+    Kinds(HashSet<&'static str>),
+}
+
+impl Subscription {
+    /// Only messages of the given kinds, e.g. `Subscription::kinds(["danmu", "super_chat"])`
+    pub fn kinds<I: IntoIterator<Item = &'static str>>(kinds: I) -> Self {
+        Subscription::Kinds(kinds.into_iter().collect())
+    }
+
+    pub fn accepts(&self, msg: &BiliMessage) -> bool {
+        match self {
+            Subscription::All => true,
+            Subscription::Kinds(kinds) => kinds.contains(msg.kind()),
+        }
+    }
+}
+
+/// How long a handler may take for one batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandlerTimeout {
+    pub limit: Duration,
+    /// Stop dispatching to the handler after this many timeouts
+    pub disable_after: Option<u32>,
+}
+
+impl HandlerTimeout {
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit,
+            disable_after: None,
+        }
+    }
+
+    pub fn disable_after(mut self, violations: u32) -> Self {
+        self.disable_after = Some(violations.max(1));
+        self
     }
 }
 
 /// Trait for event handlers (plugins) that process BiliMessage.
 pub trait EventHandler: Send + Sync {
     fn handle(&self, msg: &BiliMessage, context: &EventContext);
+
+    /// Name used in scheduler logs
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Timeout of this handler, read once when it is added to a scheduler;
+    /// `None` uses the scheduler's default timeout
+    fn timeout(&self) -> Option<HandlerTimeout> {
+        None
+    }
+
+    /// Called when a batch took longer than the timeout. A handler thread
+    /// cannot be killed, so the scheduler only stops waiting for it; handlers
+    /// running async tasks should abort them here.
+    fn on_timeout(&self) {}
+
+    /// Message kinds this handler wants, read once when it is added to a
+    /// scheduler; other messages are never dispatched to it
+    fn subscription(&self) -> Subscription {
+        Subscription::All
+    }
+
+    /// Handle messages that arrived together, such as one aggregated server packet.
+    /// Recorders and exporters can override this to write in bulk; by default each
+    /// message goes to `handle` in order.
+    fn handle_batch(&self, msgs: &[BiliMessage], context: &EventContext) {
+        for msg in msgs {
+            self.handle(msg, context);
+        }
+    }
+}
+
+/// Handler calling a closure, see `Scheduler::on`
+pub struct FnHandler<F> {
+    name: String,
+    f: F,
+}
+
+impl<F> FnHandler<F>
+where
+    F: Fn(&BiliMessage, &EventContext) + Send + Sync + 'static,
+{
+    pub fn new(name: &str, f: F) -> Self {
+        Self {
+            name: name.to_string(),
+            f,
+        }
+    }
+}
+
+impl<F> EventHandler for FnHandler<F>
+where
+    F: Fn(&BiliMessage, &EventContext) + Send + Sync + 'static,
+{
+    fn handle(&self, msg: &BiliMessage, context: &EventContext) {
+        (self.f)(msg, context);
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Largest batch the receive loop hands to `Scheduler::trigger_batch` at once
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// Generations of derived messages an enricher chain may produce from one
+/// received message; deeper ones are dropped, so enrichers reacting to each
+/// other's output cannot loop forever
+pub const MAX_ENRICH_DEPTH: usize = 4;
+
+/// Middleware deriving extra messages from the ones received, such as "user X
+/// sent their 100th message" milestones or alerts, usually as
+/// `BiliMessage::Custom`. Derived messages are dispatched to the handlers after
+/// the batch they came from and are enriched in turn, up to `MAX_ENRICH_DEPTH`.
+pub trait Enricher: Send + Sync {
+    fn enrich(&self, msg: &BiliMessage, context: &EventContext) -> Vec<BiliMessage>;
 }
 
 /// Scheduling mode: Parallel or Sequential.
@@ -37,12 +176,294 @@ pub enum ScheduleMode {
     Sequential,
 }
 
+/// How the scheduler runs handlers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Stages run one after another, each handler of a stage on its own
+    /// thread; `trigger_batch` returns once every stage is done. The priority
+    /// messages of a batch go through the stages before the ordinary ones.
+    #[default]
+    Staged,
+    /// Every handler runs independently on a pool of `workers` threads, in the
+    /// order messages arrived for that handler except that priority messages
+    /// overtake queued ordinary ones; stage order is not kept and
+    /// `trigger_batch` returns without waiting
+    Pooled { workers: usize },
+}
+
+/// What a paused scheduler does with incoming messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PausePolicy {
+    /// Keep up to `capacity` messages, dropping the oldest beyond that, and
+    /// dispatch them on resume
+    Buffer { capacity: usize },
+    /// Drop every message until resumed
+    Drop,
+}
+
+/// Messages held back while paused
+#[derive(Debug)]
+struct Paused {
+    policy: PausePolicy,
+    buffered: VecDeque<BiliMessage>,
+    dropped: u64,
+}
+
+impl Paused {
+    fn hold(&mut self, msgs: &[BiliMessage]) {
+        let capacity = match self.policy {
+            PausePolicy::Buffer { capacity } => capacity,
+            PausePolicy::Drop => 0,
+        };
+        self.buffered.extend(msgs.iter().cloned());
+        let excess = self.buffered.len().saturating_sub(capacity);
+        self.buffered.drain(..excess);
+        self.dropped += excess as u64;
+    }
+}
+
+/// Message kinds that jump ahead of queued ordinary messages by default, so
+/// paid messages are not delayed behind chat spam
+pub const DEFAULT_PRIORITY_KINDS: [&str; 4] = [
+    "super_chat",
+    "super_chat_delete",
+    "guard_buy",
+    "room_change",
+];
+
+type Queued = (Arc<[BiliMessage]>, EventContext);
+
+/// Batches waiting for a handler in pooled mode, in two lanes
+#[derive(Default)]
+struct Pending {
+    priority: VecDeque<Queued>,
+    ordinary: VecDeque<Queued>,
+    /// Whether the handler is queued on or running in the pool
+    scheduled: bool,
+}
+
+/// Handle of a handler added to a scheduler, for removing it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HandlerId(u64);
+
+impl fmt::Display for HandlerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Handler added to a scheduler, with the kinds it is dispatched, its
+/// timeout and the batches waiting for it in pooled mode
+struct Registered {
+    id: HandlerId,
+    handler: Arc<dyn EventHandler>,
+    subscription: Subscription,
+    timeout: Option<HandlerTimeout>,
+    violations: AtomicU32,
+    disabled: AtomicBool,
+    pending: Mutex<Pending>,
+}
+
+impl Registered {
+    fn new(id: HandlerId, handler: Arc<dyn EventHandler>) -> Arc<Self> {
+        let subscription = handler.subscription();
+        Self::with_subscription(id, handler, subscription)
+    }
+
+    fn with_subscription(
+        id: HandlerId,
+        handler: Arc<dyn EventHandler>,
+        subscription: Subscription,
+    ) -> Arc<Self> {
+        let timeout = handler.timeout();
+        Arc::new(Self {
+            id,
+            handler,
+            subscription,
+            timeout,
+            violations: AtomicU32::new(0),
+            disabled: AtomicBool::new(false),
+            pending: Mutex::new(Pending::default()),
+        })
+    }
+
+    /// The messages of `msgs` this handler subscribed to; none once it is disabled
+    fn select(&self, msgs: &Arc<[BiliMessage]>) -> Arc<[BiliMessage]> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return Arc::new([]);
+        }
+        match &self.subscription {
+            Subscription::All => Arc::clone(msgs),
+            subscription => msgs
+                .iter()
+                .filter(|msg| subscription.accepts(msg))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Queue a batch in the priority or ordinary lane, returning whether the
+    /// handler has to be scheduled on the pool
+    fn enqueue(&self, batch: Queued, priority: bool) -> bool {
+        let Ok(mut pending) = self.pending.lock() else {
+            return false;
+        };
+        if priority {
+            pending.priority.push_back(batch);
+        } else {
+            pending.ordinary.push_back(batch);
+        }
+        !std::mem::replace(&mut pending.scheduled, true)
+    }
+
+    /// Count a batch that took longer than `timeout`, disabling the handler
+    /// once it did so too often
+    fn timed_out(&self, timeout: HandlerTimeout) {
+        let violations = self.violations.fetch_add(1, Ordering::Relaxed) + 1;
+        log::warn!(
+            "handler {} exceeded its {:?} timeout ({} times)",
+            self.handler.name(),
+            timeout.limit,
+            violations
+        );
+        self.handler.on_timeout();
+        if timeout
+            .disable_after
+            .is_some_and(|limit| violations >= limit)
+            && !self.disabled.swap(true, Ordering::Relaxed)
+        {
+            log::error!(
+                "handler {} disabled after {} timeouts",
+                self.handler.name(),
+                violations
+            );
+        }
+    }
+
+    /// Handle queued batches until none is left; only one thread runs this at
+    /// a time. With a timeout each batch runs on its own thread: once it is
+    /// late the handler is marked as timed out and the worker goes back to the
+    /// pool, leaving that thread to drain the rest of the queue if it returns.
+    fn drain(self: Arc<Self>, default_timeout: Option<HandlerTimeout>) {
+        loop {
+            let next = match self.pending.lock() {
+                Ok(mut pending) => {
+                    let next = match pending.priority.pop_front() {
+                        Some(next) => Some(next),
+                        None => pending.ordinary.pop_front(),
+                    };
+                    match next {
+                        Some(next) => next,
+                        None => {
+                            pending.scheduled = false;
+                            return;
+                        }
+                    }
+                }
+                Err(_) => return,
+            };
+            let (msgs, context) = next;
+            let Some(timeout) = self.timeout.or(default_timeout) else {
+                self.handler.handle_batch(&msgs, &context);
+                continue;
+            };
+            // (batch done, worker stopped waiting), whichever is set second decides
+            // who drains the queue next
+            let handoff = Arc::new(Mutex::new((false, false)));
+            let (done_tx, done_rx) = mpsc::channel::<()>();
+            let registered = Arc::clone(&self);
+            let batch_handoff = Arc::clone(&handoff);
+            let spawned = thread::Builder::new()
+                .name("blivedm-handler-batch".to_string())
+                .spawn(move || {
+                    registered.handler.handle_batch(&msgs, &context);
+                    let abandoned = match batch_handoff.lock() {
+                        Ok(mut handoff) => {
+                            handoff.0 = true;
+                            handoff.1
+                        }
+                        Err(_) => false,
+                    };
+                    let _ = done_tx.send(());
+                    if abandoned {
+                        registered.drain(default_timeout);
+                    }
+                });
+            if let Err(e) = spawned {
+                log::error!("failed to start handler {}: {}", self.handler.name(), e);
+                continue;
+            }
+            if let Err(mpsc::RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout.limit) {
+                let abandoned = match handoff.lock() {
+                    Ok(mut handoff) => {
+                        handoff.1 = !handoff.0;
+                        handoff.1
+                    }
+                    Err(_) => true,
+                };
+                self.timed_out(timeout);
+                if abandoned {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+type Job = (Arc<Registered>, Option<HandlerTimeout>);
+
+/// Bounded pool of threads draining handler queues
+struct WorkerPool {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl WorkerPool {
+    fn new(workers: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for i in 0..workers.max(1) {
+            let queue = Arc::clone(&queue);
+            let spawned = thread::Builder::new()
+                .name(format!("blivedm-handler-{}", i))
+                .spawn(move || {
+                    // Ends once the scheduler, and with it the sender, is dropped
+                    loop {
+                        let job = match queue.lock() {
+                            Ok(queue) => queue.recv(),
+                            Err(_) => return,
+                        };
+                        match job {
+                            Ok((registered, timeout)) => registered.drain(timeout),
+                            Err(_) => return,
+                        }
+                    }
+                });
+            if let Err(e) = spawned {
+                log::error!("failed to start handler worker: {}", e);
+            }
+        }
+        Self { jobs }
+    }
+}
+
 /// Scheduler struct: manages event handlers and dispatches messages.
 pub struct Scheduler {
     /// Each stage is a Vec of handlers to run in parallel; stages run sequentially.
-    stages: Vec<Vec<Arc<dyn EventHandler>>>,
+    stages: Vec<Vec<Arc<Registered>>>,
     /// Context information for event handlers
     context: EventContext,
+    /// Set in `ExecutionMode::Pooled`
+    pool: Option<WorkerPool>,
+    /// Timeout of handlers that declare none
+    default_timeout: Option<HandlerTimeout>,
+    /// Set while paused
+    paused: Mutex<Option<Paused>>,
+    /// Id of the next handler added
+    next_id: u64,
+    /// Run on every batch before the handlers
+    enrichers: Vec<Arc<dyn Enricher>>,
+    /// Message kinds dispatched in the priority lane
+    priority: Subscription,
 }
 
 impl Scheduler {
@@ -50,34 +471,336 @@ impl Scheduler {
         Scheduler {
             stages: Vec::new(),
             context,
+            pool: None,
+            default_timeout: None,
+            paused: Mutex::new(None),
+            next_id: 0,
+            enrichers: Vec::new(),
+            priority: Subscription::kinds(DEFAULT_PRIORITY_KINDS),
+        }
+    }
+
+    /// Message kinds that overtake ordinary messages: queued ones in pooled
+    /// mode, the rest of their batch in staged mode.
+    /// `Subscription::kinds([])` turns the priority lane off
+    pub fn set_priority_kinds(&mut self, priority: Subscription) {
+        self.priority = priority;
+    }
+
+    /// Stop dispatching messages, holding or dropping them by `policy` until
+    /// `resume`. Pausing again only changes the policy for later messages.
+    pub fn pause(&self, policy: PausePolicy) {
+        if let Ok(mut paused) = self.paused.lock() {
+            paused
+                .get_or_insert_with(|| Paused {
+                    policy,
+                    buffered: VecDeque::new(),
+                    dropped: 0,
+                })
+                .policy = policy;
+        }
+    }
+
+    /// Dispatch the messages buffered while paused and carry on, returning how
+    /// many messages were dropped during the pause
+    pub fn resume(&self) -> u64 {
+        let Ok(mut paused) = self.paused.lock() else {
+            return 0;
+        };
+        let Some(state) = paused.take() else {
+            return 0;
+        };
+        // Still locked, so messages triggered meanwhile wait for the backlog
+        let buffered = Vec::from(state.buffered);
+        for chunk in buffered.chunks(MAX_BATCH_SIZE) {
+            self.dispatch(chunk.into());
+        }
+        if state.dropped > 0 {
+            log::info!("scheduler dropped {} messages while paused", state.dropped);
         }
+        state.dropped
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.lock().is_ok_and(|paused| paused.is_some())
+    }
+
+    /// Timeout of handlers that do not declare their own
+    pub fn set_default_timeout(&mut self, timeout: Option<HandlerTimeout>) {
+        self.default_timeout = timeout;
+    }
+
+    /// Names of the handlers disabled for timing out too often
+    pub fn disabled_handlers(&self) -> Vec<String> {
+        self.stages
+            .iter()
+            .flatten()
+            .filter(|registered| registered.disabled.load(Ordering::Relaxed))
+            .map(|registered| registered.handler.name().to_string())
+            .collect()
+    }
+
+    /// Replace the context passed to handlers, e.g. after switching rooms
+    pub fn set_context(&mut self, context: EventContext) {
+        self.context = context;
+    }
+
+    /// Choose how handlers run; batches already queued on a previous pool still finish
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.pool = match mode {
+            ExecutionMode::Staged => None,
+            ExecutionMode::Pooled { workers } => Some(WorkerPool::new(workers)),
+        };
+    }
+
+    fn next_id(&mut self) -> HandlerId {
+        self.next_id += 1;
+        HandlerId(self.next_id)
     }
 
     /// Add a new stage (group of handlers to run in parallel)
-    pub fn add_stage(&mut self, handlers: Vec<Arc<dyn EventHandler>>) {
-        self.stages.push(handlers);
+    pub fn add_stage(&mut self, handlers: Vec<Arc<dyn EventHandler>>) -> Vec<HandlerId> {
+        let stage: Vec<_> = handlers
+            .into_iter()
+            .map(|handler| Registered::new(self.next_id(), handler))
+            .collect();
+        let ids = stage.iter().map(|registered| registered.id).collect();
+        self.stages.push(stage);
+        ids
     }
 
     /// Add a single handler as a new sequential stage
-    pub fn add_sequential_handler(&mut self, handler: Arc<dyn EventHandler>) {
-        self.stages.push(vec![handler]);
+    pub fn add_sequential_handler(&mut self, handler: Arc<dyn EventHandler>) -> HandlerId {
+        let id = self.next_id();
+        self.stages.push(vec![Registered::new(id, handler)]);
+        id
+    }
+
+    /// Add a single handler as a new sequential stage, dispatching it only
+    /// `subscription` instead of the kinds it declares
+    pub fn add_subscribed_handler(
+        &mut self,
+        handler: Arc<dyn EventHandler>,
+        subscription: Subscription,
+    ) -> HandlerId {
+        let id = self.next_id();
+        self.stages.push(vec![Registered::with_subscription(
+            id,
+            handler,
+            subscription,
+        )]);
+        id
+    }
+
+    /// Call `f` for the messages of `subscription`, as a new sequential stage
+    pub fn on<F>(&mut self, subscription: Subscription, f: F) -> HandlerId
+    where
+        F: Fn(&BiliMessage, &EventContext) + Send + Sync + 'static,
+    {
+        self.add_subscribed_handler(Arc::new(FnHandler::new("closure", f)), subscription)
+    }
+
+    /// Call `f` for every danmaku, e.g. `scheduler.on_danmaku(|d| println!("{}: {}", d.user, d.text))`
+    pub fn on_danmaku<F>(&mut self, f: F) -> HandlerId
+    where
+        F: Fn(Danmaku<'_>) + Send + Sync + 'static,
+    {
+        let handler = FnHandler::new("on_danmaku", move |msg: &BiliMessage, _: &EventContext| {
+            if let Some(danmaku) = msg.as_danmaku() {
+                f(danmaku);
+            }
+        });
+        self.add_subscribed_handler(Arc::new(handler), Subscription::kinds(["danmu"]))
+    }
+
+    /// Call `f` for every gift
+    pub fn on_gift<F>(&mut self, f: F) -> HandlerId
+    where
+        F: Fn(Gift<'_>) + Send + Sync + 'static,
+    {
+        let handler = FnHandler::new("on_gift", move |msg: &BiliMessage, _: &EventContext| {
+            if let Some(gift) = msg.as_gift() {
+                f(gift);
+            }
+        });
+        self.add_subscribed_handler(Arc::new(handler), Subscription::kinds(["gift"]))
+    }
+
+    /// Call `f` for every super chat
+    pub fn on_super_chat<F>(&mut self, f: F) -> HandlerId
+    where
+        F: Fn(SuperChat<'_>) + Send + Sync + 'static,
+    {
+        let handler = FnHandler::new(
+            "on_super_chat",
+            move |msg: &BiliMessage, _: &EventContext| {
+                if let Some(super_chat) = msg.as_super_chat() {
+                    f(super_chat);
+                }
+            },
+        );
+        self.add_subscribed_handler(Arc::new(handler), Subscription::kinds(["super_chat"]))
+    }
+
+    /// Run `enricher` on every message before the handlers see it
+    pub fn add_enricher(&mut self, enricher: Arc<dyn Enricher>) {
+        self.enrichers.push(enricher);
+    }
+
+    /// Remove a handler, also while messages are being dispatched; batches
+    /// already queued for it in pooled mode still run. Returns whether it was found.
+    pub fn remove_handler(&mut self, id: HandlerId) -> bool {
+        let mut found = false;
+        for stage in &mut self.stages {
+            let before = stage.len();
+            stage.retain(|registered| registered.id != id);
+            found |= stage.len() != before;
+        }
+        self.stages.retain(|stage| !stage.is_empty());
+        found
+    }
+
+    /// Ids and names of the handlers, in dispatch order
+    pub fn handlers(&self) -> Vec<(HandlerId, String)> {
+        self.stages
+            .iter()
+            .flatten()
+            .map(|registered| (registered.id, registered.handler.name().to_string()))
+            .collect()
     }
 
     /// Trigger all stages with the given BiliMessage.
     pub fn trigger(&self, msg: BiliMessage) {
+        self.trigger_batch(vec![msg]);
+    }
+
+    /// Trigger all stages with a batch of messages. Each handler gets the
+    /// messages it subscribed to through `handle_batch`, so a stage costs one
+    /// thread per handler instead of one per handler and message; handlers
+    /// with nothing to handle are not woken. While paused the batch is held or
+    /// dropped instead.
+    pub fn trigger_batch(&self, msgs: impl Into<Arc<[BiliMessage]>>) {
+        let msgs: Arc<[BiliMessage]> = msgs.into();
+        if msgs.is_empty() {
+            return;
+        }
+        if let Ok(mut paused) = self.paused.lock()
+            && let Some(state) = paused.as_mut()
+        {
+            state.hold(&msgs);
+            return;
+        }
+        self.dispatch(msgs);
+    }
+
+    /// `msgs` followed by the messages the enrichers derived from them
+    fn enrich(&self, msgs: Arc<[BiliMessage]>) -> Arc<[BiliMessage]> {
+        if self.enrichers.is_empty() {
+            return msgs;
+        }
+        let mut all = msgs.to_vec();
+        let mut generation = 0..all.len();
+        for depth in 0..=MAX_ENRICH_DEPTH {
+            let derived: Vec<BiliMessage> = all[generation.clone()]
+                .iter()
+                .flat_map(|msg| {
+                    self.enrichers
+                        .iter()
+                        .flat_map(|enricher| enricher.enrich(msg, &self.context))
+                })
+                .collect();
+            if derived.is_empty() {
+                break;
+            }
+            if depth == MAX_ENRICH_DEPTH {
+                log::warn!(
+                    "dropping {} derived messages nested deeper than {} enrichments",
+                    derived.len(),
+                    MAX_ENRICH_DEPTH
+                );
+                break;
+            }
+            generation = all.len()..all.len() + derived.len();
+            all.extend(derived);
+        }
+        all.into()
+    }
+
+    /// The priority and the ordinary messages of `msgs`, flagged as priority
+    fn lanes(&self, msgs: Arc<[BiliMessage]>) -> [(Arc<[BiliMessage]>, bool); 2] {
+        if msgs.iter().any(|msg| self.priority.accepts(msg)) {
+            let (priority, ordinary): (Vec<_>, Vec<_>) = msgs
+                .iter()
+                .cloned()
+                .partition(|msg| self.priority.accepts(msg));
+            [(priority.into(), true), (ordinary.into(), false)]
+        } else {
+            [(Arc::new([]), true), (msgs, false)]
+        }
+    }
+
+    fn dispatch(&self, msgs: Arc<[BiliMessage]>) {
+        let lanes = self.lanes(self.enrich(msgs));
+        if let Some(pool) = &self.pool {
+            for registered in self.stages.iter().flatten() {
+                for (msgs, priority) in &lanes {
+                    let msgs = registered.select(msgs);
+                    if !msgs.is_empty()
+                        && registered.enqueue((msgs, self.context.clone()), *priority)
+                        && pool
+                            .jobs
+                            .send((Arc::clone(registered), self.default_timeout))
+                            .is_err()
+                    {
+                        log::error!("handler worker pool stopped");
+                    }
+                }
+            }
+            return;
+        }
+        for (msgs, _) in lanes {
+            if !msgs.is_empty() {
+                self.run_stages(&msgs);
+            }
+        }
+    }
+
+    /// Run the stages on `msgs`, waiting for each stage before the next
+    fn run_stages(&self, msgs: &Arc<[BiliMessage]>) {
         for stage in &self.stages {
-            let mut handles = vec![];
-            for handler in stage {
-                let msg = msg.clone();
+            let start = Instant::now();
+            let mut running = vec![];
+            for registered in stage {
+                let msgs = registered.select(msgs);
+                if msgs.is_empty() {
+                    continue;
+                }
                 let context = self.context.clone();
-                let handler = Arc::clone(handler);
-                handles.push(std::thread::spawn(move || {
-                    handler.handle(&msg, &context);
-                }));
+                let handler = Arc::clone(&registered.handler);
+                let (done_tx, done_rx) = mpsc::channel::<()>();
+                thread::spawn(move || {
+                    handler.handle_batch(&msgs, &context);
+                    let _ = done_tx.send(());
+                });
+                running.push((registered, done_rx));
             }
-            // Wait for all handlers in this stage to finish before next stage
-            for handle in handles {
-                let _ = handle.join();
+            // Wait for all handlers in this stage to finish before next stage,
+            // leaving those that time out running in the background
+            for (registered, done_rx) in running {
+                match registered.timeout.or(self.default_timeout) {
+                    Some(timeout) => {
+                        let remaining =
+                            (start + timeout.limit).saturating_duration_since(Instant::now());
+                        if let Err(mpsc::RecvTimeoutError::Timeout) =
+                            done_rx.recv_timeout(remaining)
+                        {
+                            registered.timed_out(timeout);
+                        }
+                    }
+                    None => {
+                        let _ = done_rx.recv();
+                    }
+                }
             }
         }
     }
@@ -117,6 +840,7 @@ mod tests {
         let context = super::EventContext {
             cookies: Some("test_cookies".to_string()),
             room_id: 12345,
+            request_headers: None,
         };
         let mut scheduler = super::Scheduler::new(context);
         scheduler.add_sequential_handler(Arc::new(handler));
@@ -125,6 +849,9 @@ mod tests {
         let test_msg = BiliMessage::Danmu {
             user: "user1".to_string(),
             text: "hello".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         tx.send(test_msg.clone()).unwrap();
 
@@ -170,6 +897,7 @@ mod tests {
         let context = super::EventContext {
             cookies: Some("test_cookies".to_string()),
             room_id: 12345,
+            request_headers: None,
         };
         let mut scheduler = super::Scheduler::new(context);
         // Add a parallel stage (handler1 and handler2)
@@ -180,6 +908,9 @@ mod tests {
         let test_msg = BiliMessage::Danmu {
             user: "user2".to_string(),
             text: "test".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         scheduler.trigger(test_msg);
 
@@ -189,4 +920,567 @@ mod tests {
         // handler3 should be called once (sequential stage)
         assert_eq!(counter3.load(Ordering::SeqCst), 1, "Handler3 not called");
     }
+
+    #[test]
+    fn test_scheduler_trigger_batch() {
+        struct BatchHandler {
+            batches: Arc<Mutex<Vec<usize>>>,
+        }
+        impl super::EventHandler for BatchHandler {
+            fn handle(&self, _msg: &BiliMessage, _context: &super::EventContext) {
+                self.batches.lock().unwrap().push(1);
+            }
+            fn handle_batch(&self, msgs: &[BiliMessage], _context: &super::EventContext) {
+                self.batches.lock().unwrap().push(msgs.len());
+            }
+        }
+        struct CounterHandler {
+            counter: Arc<AtomicUsize>,
+        }
+        impl super::EventHandler for CounterHandler {
+            fn handle(&self, _msg: &BiliMessage, _context: &super::EventContext) {
+                self.counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.add_stage(vec![
+            Arc::new(BatchHandler {
+                batches: Arc::clone(&batches),
+            }),
+            Arc::new(CounterHandler {
+                counter: Arc::clone(&counter),
+            }),
+        ]);
+
+        let msgs = vec![BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"})); 3];
+        scheduler.trigger_batch(msgs);
+        scheduler.trigger_batch(Vec::new());
+
+        // The batch handler saw one batch of three; the default fell back to `handle`
+        assert_eq!(*batches.lock().unwrap(), vec![3]);
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_scheduler_subscriptions() {
+        struct DanmuOnly {
+            counter: Arc<AtomicUsize>,
+        }
+        impl super::EventHandler for DanmuOnly {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                assert_eq!(msg.kind(), "danmu");
+                self.counter.fetch_add(1, Ordering::SeqCst);
+            }
+            fn subscription(&self) -> super::Subscription {
+                super::Subscription::kinds(["danmu"])
+            }
+        }
+
+        let declared = Arc::new(AtomicUsize::new(0));
+        let overridden = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.add_sequential_handler(Arc::new(DanmuOnly {
+            counter: Arc::clone(&declared),
+        }));
+        // Raw messages only: the handler is never woken
+        scheduler.add_subscribed_handler(
+            Arc::new(DanmuOnly {
+                counter: Arc::clone(&overridden),
+            }),
+            super::Subscription::kinds(["raw"]),
+        );
+
+        let danmu = BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: "hi".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        };
+        scheduler.trigger_batch(vec![
+            danmu.clone(),
+            BiliMessage::VoiceJoinList { apply_count: 1 },
+            danmu,
+        ]);
+
+        assert_eq!(declared.load(Ordering::SeqCst), 2);
+        assert_eq!(overridden.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_scheduler_closure_handlers() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        // Keep the super chat in batch order
+        scheduler.set_priority_kinds(super::Subscription::kinds([]));
+        let danmaku = Arc::clone(&seen);
+        scheduler.on_danmaku(move |d| {
+            danmaku
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", d.user, d.text))
+        });
+        let gifts = Arc::clone(&seen);
+        scheduler.on_gift(move |g| gifts.lock().unwrap().push(format!("{} x{}", g.gift, g.num)));
+        let super_chats = Arc::clone(&seen);
+        scheduler
+            .on_super_chat(move |sc| super_chats.lock().unwrap().push(format!("SC {}", sc.price)));
+        let all = Arc::clone(&seen);
+        let id = scheduler.on(super::Subscription::All, move |msg, context| {
+            all.lock()
+                .unwrap()
+                .push(format!("{} in {}", msg.kind(), context.room_id))
+        });
+        assert!(
+            scheduler
+                .handlers()
+                .iter()
+                .any(|(handler, name)| *handler == id && name == "closure")
+        );
+
+        scheduler.trigger_batch(vec![
+            BiliMessage::Danmu {
+                user: "user".to_string(),
+                text: "hi".to_string(),
+                uid: 0,
+                guard_level: 0,
+                medal: None,
+            },
+            BiliMessage::Gift {
+                user: "user".to_string(),
+                gift: "辣条".to_string(),
+                num: "2".to_string(),
+                gift_id: 1,
+                value: 200,
+                combo_id: None,
+            },
+            BiliMessage::SuperChat {
+                id: 1,
+                user: "user".to_string(),
+                text: "hello".to_string(),
+                price: 30,
+                duration: 60,
+            },
+        ]);
+
+        // Each stage handles the whole batch before the next one
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "user: hi",
+                "辣条 x2",
+                "SC 30",
+                "danmu in 1",
+                "gift in 1",
+                "super_chat in 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scheduler_enrichers_with_loop_protection() {
+        /// Derives a message from every danmu, and then from its own output forever
+        struct Echo;
+        impl super::Enricher for Echo {
+            fn enrich(
+                &self,
+                msg: &BiliMessage,
+                _context: &super::EventContext,
+            ) -> Vec<BiliMessage> {
+                let depth = match msg {
+                    BiliMessage::Danmu { .. } => 0,
+                    BiliMessage::Custom { data, .. } => data.as_u64().unwrap_or(0),
+                    _ => return Vec::new(),
+                };
+                vec![BiliMessage::Custom {
+                    cmd: "ECHO".to_string(),
+                    data: serde_json::json!(depth + 1),
+                }]
+            }
+        }
+        struct Recorder {
+            seen: Arc<Mutex<Vec<BiliMessage>>>,
+        }
+        impl super::EventHandler for Recorder {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                self.seen.lock().unwrap().push(msg.clone());
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.add_enricher(Arc::new(Echo));
+        scheduler.add_sequential_handler(Arc::new(Recorder {
+            seen: Arc::clone(&seen),
+        }));
+
+        let danmu = BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: "hi".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        };
+        let raw = BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"}));
+        scheduler.trigger_batch(vec![danmu.clone(), raw.clone()]);
+
+        // The received messages first, then one derived message per generation
+        let mut expected = vec![danmu, raw];
+        expected.extend(
+            (1..=super::MAX_ENRICH_DEPTH as u64).map(|depth| BiliMessage::Custom {
+                cmd: "ECHO".to_string(),
+                data: serde_json::json!(depth),
+            }),
+        );
+        assert_eq!(*seen.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_scheduler_remove_handler() {
+        struct CounterHandler {
+            counter: Arc<AtomicUsize>,
+        }
+        impl super::EventHandler for CounterHandler {
+            fn handle(&self, _msg: &BiliMessage, _context: &super::EventContext) {
+                self.counter.fetch_add(1, Ordering::SeqCst);
+            }
+            fn name(&self) -> &str {
+                "counter"
+            }
+        }
+
+        let kept = Arc::new(AtomicUsize::new(0));
+        let removed = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        let kept_id = scheduler.add_sequential_handler(Arc::new(CounterHandler {
+            counter: Arc::clone(&kept),
+        }));
+        let removed_id = scheduler.add_sequential_handler(Arc::new(CounterHandler {
+            counter: Arc::clone(&removed),
+        }));
+        assert_ne!(kept_id, removed_id);
+
+        let msg = BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"}));
+        scheduler.trigger(msg.clone());
+        assert!(scheduler.remove_handler(removed_id));
+        assert!(!scheduler.remove_handler(removed_id));
+        scheduler.trigger(msg);
+
+        assert_eq!(kept.load(Ordering::SeqCst), 2);
+        assert_eq!(removed.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.handlers(), vec![(kept_id, "counter".to_string())]);
+    }
+
+    #[test]
+    fn test_scheduler_pause_and_resume() {
+        struct Recorder {
+            seen: Arc<Mutex<Vec<String>>>,
+        }
+        impl super::EventHandler for Recorder {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                if let BiliMessage::Danmu { text, .. } = msg {
+                    self.seen.lock().unwrap().push(text.clone());
+                }
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.add_sequential_handler(Arc::new(Recorder {
+            seen: Arc::clone(&seen),
+        }));
+        let danmu = |text: &str| BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: text.to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        };
+
+        scheduler.pause(super::PausePolicy::Buffer { capacity: 2 });
+        assert!(scheduler.is_paused());
+        scheduler.trigger_batch(vec![danmu("1"), danmu("2")]);
+        scheduler.trigger(danmu("3"));
+        assert!(seen.lock().unwrap().is_empty());
+
+        // The oldest message did not fit in the buffer
+        assert_eq!(scheduler.resume(), 1);
+        assert!(!scheduler.is_paused());
+        assert_eq!(*seen.lock().unwrap(), vec!["2", "3"]);
+
+        scheduler.pause(super::PausePolicy::Drop);
+        scheduler.trigger(danmu("4"));
+        assert_eq!(scheduler.resume(), 1);
+        scheduler.trigger(danmu("5"));
+        assert_eq!(*seen.lock().unwrap(), vec!["2", "3", "5"]);
+    }
+
+    #[test]
+    fn test_scheduler_handler_timeout_disables_handler() {
+        struct Sleeper {
+            calls: Arc<AtomicUsize>,
+            timeouts: Arc<AtomicUsize>,
+        }
+        impl super::EventHandler for Sleeper {
+            fn handle(&self, _msg: &BiliMessage, _context: &super::EventContext) {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(200));
+            }
+            fn name(&self) -> &str {
+                "sleeper"
+            }
+            fn timeout(&self) -> Option<super::HandlerTimeout> {
+                Some(
+                    super::HandlerTimeout::new(std::time::Duration::from_millis(20))
+                        .disable_after(2),
+                )
+            }
+            fn on_timeout(&self) {
+                self.timeouts.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let timeouts = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.add_sequential_handler(Arc::new(Sleeper {
+            calls: Arc::clone(&calls),
+            timeouts: Arc::clone(&timeouts),
+        }));
+
+        let msg = BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"}));
+        for _ in 0..3 {
+            let start = std::time::Instant::now();
+            scheduler.trigger(msg.clone());
+            // The scheduler stops waiting at the timeout, not when the handler is done
+            assert!(start.elapsed() < std::time::Duration::from_millis(150));
+        }
+
+        // Disabled after the second timeout, so the third batch never reached it
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(timeouts.load(Ordering::SeqCst), 2);
+        assert_eq!(scheduler.disabled_handlers(), vec!["sleeper".to_string()]);
+    }
+
+    #[test]
+    fn test_scheduler_priority_lane() {
+        /// Blocks on its first message until released
+        struct Gated {
+            started: mpsc::Sender<()>,
+            release: Mutex<mpsc::Receiver<()>>,
+            seen: mpsc::Sender<&'static str>,
+        }
+        impl super::EventHandler for Gated {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                if let BiliMessage::Danmu { text, .. } = msg
+                    && text == "first"
+                {
+                    self.started.send(()).unwrap();
+                    self.release.lock().unwrap().recv().unwrap();
+                }
+                self.seen.send(msg.kind()).unwrap();
+            }
+        }
+
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let (seen_tx, seen_rx) = mpsc::channel();
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.set_execution_mode(super::ExecutionMode::Pooled { workers: 1 });
+        scheduler.add_sequential_handler(Arc::new(Gated {
+            started: started_tx,
+            release: Mutex::new(release_rx),
+            seen: seen_tx,
+        }));
+        let danmu = |text: &str| BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: text.to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        };
+
+        scheduler.trigger(danmu("first"));
+        let timeout = std::time::Duration::from_secs(5);
+        started_rx.recv_timeout(timeout).unwrap();
+        // Backed up behind the blocked handler
+        scheduler.trigger(danmu("spam"));
+        scheduler.trigger_batch(vec![
+            danmu("spam"),
+            BiliMessage::SuperChat {
+                id: 1,
+                user: "fan".to_string(),
+                text: "hi".to_string(),
+                price: 30,
+                duration: 60,
+            },
+        ]);
+        release_tx.send(()).unwrap();
+
+        let seen: Vec<&str> = (0..4)
+            .map(|_| seen_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(seen, vec!["danmu", "super_chat", "danmu", "danmu"]);
+    }
+
+    #[test]
+    fn test_scheduler_priority_lane_staged() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        for stage in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            scheduler.on(super::Subscription::All, move |msg, _| {
+                seen.lock()
+                    .unwrap()
+                    .push(format!("{} {}", stage, msg.kind()))
+            });
+        }
+
+        scheduler.trigger_batch(vec![
+            BiliMessage::Raw(serde_json::json!({"cmd": "LIKE"})),
+            BiliMessage::GuardBuy {
+                user: "fan".to_string(),
+                guard_level: 3,
+                num: 1,
+                price: 198000,
+            },
+        ]);
+
+        // The guard purchase goes through every stage before the rest of its batch
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "first guard_buy",
+                "second guard_buy",
+                "first raw",
+                "second raw"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scheduler_worker_pool_keeps_handler_order() {
+        struct Recorder {
+            seen: mpsc::Sender<String>,
+            delay: std::time::Duration,
+        }
+        impl super::EventHandler for Recorder {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                std::thread::sleep(self.delay);
+                if let BiliMessage::Danmu { text, .. } = msg {
+                    self.seen.send(text.clone()).unwrap();
+                }
+            }
+        }
+
+        let (slow_tx, slow_rx) = mpsc::channel();
+        let (fast_tx, fast_rx) = mpsc::channel();
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.set_execution_mode(super::ExecutionMode::Pooled { workers: 2 });
+        scheduler.add_sequential_handler(Arc::new(Recorder {
+            seen: slow_tx,
+            delay: std::time::Duration::from_millis(20),
+        }));
+        scheduler.add_sequential_handler(Arc::new(Recorder {
+            seen: fast_tx,
+            delay: std::time::Duration::ZERO,
+        }));
+
+        let texts: Vec<String> = (0..5).map(|i| i.to_string()).collect();
+        for text in &texts {
+            scheduler.trigger(BiliMessage::Danmu {
+                user: "user".to_string(),
+                text: text.clone(),
+                uid: 0,
+                guard_level: 0,
+                medal: None,
+            });
+        }
+
+        // The fast handler finishes without waiting for the slow one
+        let timeout = std::time::Duration::from_secs(5);
+        let fast: Vec<String> = (0..5)
+            .map(|_| fast_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(fast, texts);
+        let slow: Vec<String> = (0..5)
+            .map(|_| slow_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(slow, texts);
+    }
+
+    #[test]
+    fn test_scheduler_worker_pool_times_out_hung_handler() {
+        /// Blocks on its first message until released, reporting its timeouts
+        struct Hung {
+            release: Mutex<mpsc::Receiver<()>>,
+            seen: mpsc::Sender<String>,
+            timeouts: mpsc::Sender<()>,
+        }
+        impl super::EventHandler for Hung {
+            fn handle(&self, msg: &BiliMessage, _context: &super::EventContext) {
+                if let BiliMessage::Danmu { text, .. } = msg {
+                    if text == "first" {
+                        self.release.lock().unwrap().recv().unwrap();
+                    }
+                    self.seen.send(text.clone()).unwrap();
+                }
+            }
+            fn timeout(&self) -> Option<super::HandlerTimeout> {
+                Some(super::HandlerTimeout::new(
+                    std::time::Duration::from_millis(20),
+                ))
+            }
+            fn on_timeout(&self) {
+                let _ = self.timeouts.send(());
+            }
+        }
+
+        let (release_tx, release_rx) = mpsc::channel();
+        let (hung_tx, hung_rx) = mpsc::channel();
+        let (timeouts_tx, timeouts_rx) = mpsc::channel();
+        let (other_tx, other_rx) = mpsc::channel();
+        let mut scheduler = super::Scheduler::new(super::EventContext::new(None, 1));
+        scheduler.set_execution_mode(super::ExecutionMode::Pooled { workers: 1 });
+        scheduler.add_sequential_handler(Arc::new(Hung {
+            release: Mutex::new(release_rx),
+            seen: hung_tx,
+            timeouts: timeouts_tx,
+        }));
+        scheduler.on(super::Subscription::All, move |msg, _| {
+            if let BiliMessage::Danmu { text, .. } = msg {
+                other_tx.send(text.clone()).unwrap();
+            }
+        });
+        let danmu = |text: &str| BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: text.to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        };
+
+        scheduler.trigger(danmu("first"));
+        scheduler.trigger(danmu("second"));
+
+        // The deadline passes while the handler still hangs, and the only worker
+        // is free again for the other handler
+        let timeout = std::time::Duration::from_secs(5);
+        timeouts_rx.recv_timeout(timeout).unwrap();
+        let other: Vec<String> = (0..2)
+            .map(|_| other_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(other, vec!["first", "second"]);
+        assert!(hung_rx.try_recv().is_err());
+
+        // Once released, the hung handler catches up in order
+        release_tx.send(()).unwrap();
+        let hung: Vec<String> = (0..2)
+            .map(|_| hung_rx.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(hung, vec!["first", "second"]);
+    }
 }
@@ -0,0 +1,358 @@
+// src/client/send_queue.rs
+//! Queue of outgoing danmaku
+//!
+//! Bilibili refuses danmaku longer than the sender's length limit and turns
+//! away those sent faster than its rate limit. `SendQueue` splits long
+//! messages into pieces that fit and sends the queued pieces one at a time, at
+//! least `min_interval` apart, retrying a piece that was rate limited:
+//!
+//! ```ignore
+//! let queue = SendQueue::new(context, SendLimits::default(), &runtime);
+//! queue.send("a reply longer than twenty characters is sent in two pieces");
+//! queue.send("queued behind it").await?;
+//! queue.reply("thanks!", &ReplyTo::new(uid, uname));
+//! ```
+
+use futures::FutureExt;
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::danmaku::{ReplyTo, SendError, mention, send_danmaku, send_reply};
+use crate::runtime::AsyncRuntime;
+use crate::scheduler::EventContext;
+
+/// Characters per danmaku allowed for ordinary users
+pub const DEFAULT_MAX_CHARS: usize = 20;
+
+/// Interval between danmaku that stays clear of the rate limiter
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Attempts at a rate-limited piece before giving up on it
+const MAX_RETRIES: u32 = 3;
+
+/// Limits the queue keeps to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendLimits {
+    /// Longest danmaku in characters; accounts with a higher user level or a
+    /// guard may send 30 or 40
+    pub max_chars: usize,
+    pub min_interval: Duration,
+}
+
+impl Default for SendLimits {
+    fn default() -> Self {
+        Self {
+            max_chars: DEFAULT_MAX_CHARS,
+            min_interval: DEFAULT_MIN_INTERVAL,
+        }
+    }
+}
+
+/// Split `text` into pieces of at most `max_chars` characters, breaking after
+/// whitespace or punctuation in the second half of a piece when there is one
+pub fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut pieces = Vec::new();
+    let mut rest: Vec<char> = text.trim().chars().collect();
+    while rest.len() > max_chars {
+        let split = (max_chars / 2..max_chars)
+            .rev()
+            .find(|&i| rest[i].is_whitespace() || is_break(rest[i]))
+            .map(|i| i + 1)
+            .unwrap_or(max_chars);
+        let piece: String = rest.drain(..split).collect();
+        pieces.push(piece.trim().to_string());
+        while rest.first().is_some_and(|c| c.is_whitespace()) {
+            rest.remove(0);
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.into_iter().collect());
+    }
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+fn is_break(c: char) -> bool {
+    matches!(
+        c,
+        ',' | '.' | '!' | '?' | ';' | '，' | '。' | '！' | '？' | '；' | '、' | '～'
+    )
+}
+
+/// Sends one piece, as a reply when given a target; the API call, or a
+/// stand-in in tests
+type SendFn =
+    Arc<dyn Fn(String, Option<ReplyTo>) -> BoxFuture<'static, Result<(), SendError>> + Send + Sync>;
+
+/// Sends `text`, as a reply to `reply` if any
+async fn send_to(
+    text: String,
+    reply: Option<ReplyTo>,
+    context: EventContext,
+) -> Result<(), SendError> {
+    match reply {
+        Some(reply) => send_reply(&text, &reply, &context).await,
+        None => send_danmaku(&text, &context).await,
+    }
+}
+
+/// Message waiting in the queue, with the sender of its outcome
+struct Outgoing {
+    pieces: Vec<String>,
+    /// Target every piece replies to
+    reply: Option<ReplyTo>,
+    done: oneshot::Sender<Result<(), SendError>>,
+}
+
+/// Handle to a danmaku queue, cheap to clone; the queue stops when the last
+/// handle is dropped
+#[derive(Clone)]
+pub struct SendQueue {
+    tx: mpsc::UnboundedSender<Outgoing>,
+    limits: SendLimits,
+    pending: Arc<AtomicUsize>,
+}
+
+impl SendQueue {
+    /// Queue sending to the room of `context` with its cookies, worked off on `runtime`
+    pub fn new(context: EventContext, limits: SendLimits, runtime: &AsyncRuntime) -> Self {
+        let send: SendFn =
+            Arc::new(move |text, reply| send_to(text, reply, context.clone()).boxed());
+        Self::with_sender(send, limits, runtime)
+    }
+
+    /// Queue sending with the cookies and headers of `context` to whichever
+    /// room `room` holds when a piece goes out, for clients that switch rooms
+    pub fn following_room(
+        context: EventContext,
+        room: Arc<AtomicU64>,
+        limits: SendLimits,
+        runtime: &AsyncRuntime,
+    ) -> Self {
+        let send: SendFn = Arc::new(move |text, reply| {
+            let context = EventContext {
+                room_id: room.load(Ordering::Relaxed),
+                ..context.clone()
+            };
+            send_to(text, reply, context).boxed()
+        });
+        Self::with_sender(send, limits, runtime)
+    }
+
+    fn with_sender(send: SendFn, limits: SendLimits, runtime: &AsyncRuntime) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        runtime.spawn(run(rx, send, limits, Arc::clone(&pending)));
+        Self {
+            tx,
+            limits,
+            pending,
+        }
+    }
+
+    pub fn limits(&self) -> SendLimits {
+        self.limits
+    }
+
+    /// Pieces queued and not yet sent
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Queue `text`, split to fit the length limit. The message is queued at
+    /// once; the returned future resolves when all its pieces are sent, or
+    /// with the error of the first that is not, and can be dropped.
+    pub fn send(&self, text: &str) -> impl Future<Output = Result<(), SendError>> + use<> {
+        self.enqueue(text, None)
+    }
+
+    /// Queue `text` as a reply to `reply`, threaded under it in the official
+    /// client; each piece of a split reply is threaded
+    pub fn reply(
+        &self,
+        text: &str,
+        reply: &ReplyTo,
+    ) -> impl Future<Output = Result<(), SendError>> + use<> {
+        self.enqueue(text, Some(reply.clone()))
+    }
+
+    /// Queue `text` with an @mention of `reply`'s user, also sent as a reply
+    pub fn mention(
+        &self,
+        text: &str,
+        reply: &ReplyTo,
+    ) -> impl Future<Output = Result<(), SendError>> + use<> {
+        self.enqueue(&mention(&reply.uname, text), Some(reply.clone()))
+    }
+
+    fn enqueue(
+        &self,
+        text: &str,
+        reply: Option<ReplyTo>,
+    ) -> impl Future<Output = Result<(), SendError>> + use<> {
+        let pieces = split_message(text, self.limits.max_chars);
+        let (done, outcome) = oneshot::channel();
+        let count = pieces.len();
+        self.pending.fetch_add(count, Ordering::Relaxed);
+        let queued = self.tx.send(Outgoing {
+            pieces,
+            reply,
+            done,
+        });
+        if queued.is_err() {
+            self.pending.fetch_sub(count, Ordering::Relaxed);
+        }
+        async move {
+            let closed = || SendError::Failed("danmaku queue stopped".to_string());
+            queued.map_err(|_| closed())?;
+            outcome.await.unwrap_or_else(|_| Err(closed()))
+        }
+    }
+}
+
+async fn run(
+    mut rx: mpsc::UnboundedReceiver<Outgoing>,
+    send: SendFn,
+    limits: SendLimits,
+    pending: Arc<AtomicUsize>,
+) {
+    let mut last_sent: Option<Instant> = None;
+    while let Some(outgoing) = rx.recv().await {
+        let mut result = Ok(());
+        let count = outgoing.pieces.len();
+        for (i, piece) in outgoing.pieces.into_iter().enumerate() {
+            let reply = outgoing.reply.clone();
+            result = send_paced(&send, piece, reply, limits, &mut last_sent).await;
+            pending.fetch_sub(1, Ordering::Relaxed);
+            if result.is_err() {
+                pending.fetch_sub(count - i - 1, Ordering::Relaxed);
+                break;
+            }
+        }
+        let _ = outgoing.done.send(result);
+    }
+}
+
+/// Send `piece` once `min_interval` has passed since the last send, retrying
+/// with a growing delay while it is rate limited
+async fn send_paced(
+    send: &SendFn,
+    piece: String,
+    reply: Option<ReplyTo>,
+    limits: SendLimits,
+    last_sent: &mut Option<Instant>,
+) -> Result<(), SendError> {
+    let mut retries = 0;
+    loop {
+        if let Some(last) = *last_sent {
+            tokio::time::sleep_until(last + limits.min_interval * (retries + 1)).await;
+        }
+        let result = send(piece.clone(), reply.clone()).await;
+        *last_sent = Some(Instant::now());
+        match result {
+            Err(SendError::RateLimited) if retries < MAX_RETRIES => {
+                retries += 1;
+                log::debug!("danmaku '{}' rate limited, retry {}", piece, retries);
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::RuntimeFlavor;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("  short  ", 20), vec!["short"]);
+        assert_eq!(
+            split_message("一二三四五六七八九十一二三四五", 6),
+            vec!["一二三四五六", "七八九十一二", "三四五"]
+        );
+        assert_eq!(
+            split_message("hello there general kenobi", 12),
+            vec!["hello there", "general", "kenobi"]
+        );
+        assert_eq!(
+            split_message("欢迎来到直播间，记得点个关注哦", 10),
+            vec!["欢迎来到直播间，", "记得点个关注哦"]
+        );
+        assert!(split_message("   ", 20).is_empty());
+    }
+
+    #[test]
+    fn test_queue_paces_splits_and_retries() {
+        let runtime = AsyncRuntime::new(RuntimeFlavor::CurrentThread).unwrap();
+        let sent: Arc<Mutex<Vec<(String, Instant)>>> = Arc::default();
+        let replies: Arc<Mutex<Vec<Option<ReplyTo>>>> = Arc::default();
+        let record = Arc::clone(&sent);
+        let record_replies = Arc::clone(&replies);
+        let send: SendFn = Arc::new(move |text: String, reply| {
+            record_replies.lock().unwrap().push(reply);
+            let mut sent = record.lock().unwrap();
+            let limited = text == "retry" && !sent.iter().any(|(t, _)| *t == "retry");
+            let rejected = text == "bad";
+            sent.push((text, Instant::now()));
+            async move {
+                match (limited, rejected) {
+                    (true, _) => Err(SendError::RateLimited),
+                    (_, true) => Err(SendError::Rejected {
+                        code: 1,
+                        message: String::new(),
+                    }),
+                    _ => Ok(()),
+                }
+            }
+            .boxed()
+        });
+        let limits = SendLimits {
+            max_chars: 5,
+            min_interval: Duration::from_millis(20),
+        };
+        let queue = SendQueue::with_sender(send, limits, &runtime);
+
+        let first = queue.send("abcde fgh");
+        let second = queue.send("retry");
+        let third = queue.send("bad");
+        let viewer = ReplyTo::new(2, "vi");
+        let fourth = queue.mention("hey you", &viewer);
+        assert_eq!(queue.pending(), 7);
+        assert_eq!(runtime.block_on(first), Ok(()));
+        assert_eq!(runtime.block_on(second), Ok(()));
+        assert!(matches!(
+            runtime.block_on(third),
+            Err(SendError::Rejected { .. })
+        ));
+        assert_eq!(runtime.block_on(fourth), Ok(()));
+        assert_eq!(queue.pending(), 0);
+
+        let sent = sent.lock().unwrap();
+        let texts: Vec<_> = sent.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["abcde", "fgh", "retry", "retry", "bad", "@vi", "hey", "you"]
+        );
+        let replies = replies.lock().unwrap();
+        assert!(replies[..5].iter().all(Option::is_none));
+        assert!(
+            replies[5..]
+                .iter()
+                .all(|reply| reply.as_ref() == Some(&viewer))
+        );
+        for pair in sent.windows(2) {
+            assert!(pair[1].1 - pair[0].1 >= limits.min_interval);
+        }
+        // The retry of the rate-limited piece waits twice as long
+        assert!(sent[3].1 - sent[2].1 >= limits.min_interval * 2);
+    }
+}
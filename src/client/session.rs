@@ -0,0 +1,262 @@
+// src/client/session.rs
+//! Runtime-agnostic connection core
+//!
+//! `Session` drives the danmaku protocol (auth, heartbeats, frame decoding) over
+//! any websocket implementing `Transport`, sleeping with any `Timer`, so it runs
+//! on tokio, async-std, smol or a custom executor. The server url and auth body
+//! come from `websocket::init_server` and `websocket::server_url`, or any other
+//! source. The blocking `BiliLiveClient` and `OpenLiveClient` and the wasm
+//! binding keep a `Session` for the same framing and decoding and only bring
+//! their own socket loop:
+//!
+//! ```ignore
+//! struct SmolTimer;
+//!
+//! impl Timer for SmolTimer {
+//!     async fn sleep(&self, duration: Duration) {
+//!         smol::Timer::after(duration).await;
+//!     }
+//! }
+//!
+//! let mut session = Session::new(auth_body);
+//! session
+//!     .run(&mut transport, &SmolTimer, |packet| {
+//!         if let Packet::Message(json) = packet
+//!             && let Some(msg) = protocol::handle(json)
+//!         {
+//!             println!("{:?}", msg);
+//!         }
+//!     })
+//!     .await?;
+//! ```
+
+use std::future::{Future, poll_fn};
+use std::pin::pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::protocol::{Decoder, Operation, Packet, make_raw_packet};
+
+/// Interval between heartbeats
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A connected websocket carrying binary frames
+pub trait Transport {
+    /// Send one binary frame
+    fn send(&mut self, frame: Vec<u8>) -> impl Future<Output = Result<(), String>>;
+
+    /// Next binary frame, or `None` once the connection is closed.
+    ///
+    /// Must be cancel safe: the future is dropped when a heartbeat is due, and
+    /// no frame may be lost by that.
+    fn recv(&mut self) -> impl Future<Output = Result<Option<Vec<u8>>, String>>;
+}
+
+/// Source of delays for the heartbeat
+pub trait Timer {
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()>;
+}
+
+/// Timer backed by `tokio::time`
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioTimer;
+
+#[cfg(feature = "client")]
+impl Timer for TokioTimer {
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// Protocol state of one connection, independent of the socket and executor
+pub struct Session {
+    auth_body: String,
+    heartbeat_interval: Duration,
+    decoder: Decoder,
+}
+
+impl Session {
+    /// Session authenticating with `auth_body` (`{"uid", "roomid", "key", ...}` as JSON)
+    pub fn new(auth_body: impl Into<String>) -> Self {
+        Self {
+            auth_body: auth_body.into(),
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+            decoder: Decoder::new(),
+        }
+    }
+
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    pub fn with_decoder(mut self, decoder: Decoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.heartbeat_interval
+    }
+
+    /// Authenticate with a new body from now on, e.g. after reconnecting with a
+    /// fresh key; the decoder and its buffers are kept
+    pub fn set_auth_body(&mut self, auth_body: impl Into<String>) {
+        self.auth_body = auth_body.into();
+    }
+
+    /// Auth packet sent first on a new connection
+    pub fn auth_frame(&self) -> Vec<u8> {
+        make_raw_packet(self.auth_body.as_bytes(), Operation::AUTH)
+    }
+
+    pub fn heartbeat_frame(&self) -> Vec<u8> {
+        make_raw_packet(b"{}", Operation::HEARTBEAT)
+    }
+
+    /// Decode one received frame
+    pub fn decode(&mut self, frame: &[u8]) -> Vec<Packet> {
+        self.decoder.decode(frame)
+    }
+
+    /// Authenticate, then pass every packet to `on_packet` and send heartbeats
+    /// until the transport closes (`Ok`) or fails (`Err`)
+    pub async fn run<T, S, F>(
+        &mut self,
+        transport: &mut T,
+        timer: &S,
+        mut on_packet: F,
+    ) -> Result<(), String>
+    where
+        T: Transport,
+        S: Timer,
+        F: FnMut(Packet),
+    {
+        transport.send(self.auth_frame()).await?;
+        transport.send(self.heartbeat_frame()).await?;
+
+        // Kept across received frames so a busy room still gets its heartbeats
+        let mut heartbeat = Box::pin(timer.sleep(self.heartbeat_interval));
+        loop {
+            match race(transport.recv(), heartbeat.as_mut()).await {
+                Race::First(frame) => {
+                    let Some(frame) = frame? else {
+                        return Ok(());
+                    };
+                    for packet in self.decode(&frame) {
+                        if let Packet::AuthReply(_) = packet {
+                            transport.send(self.heartbeat_frame()).await?;
+                        }
+                        on_packet(packet);
+                    }
+                }
+                Race::Second(()) => {
+                    transport.send(self.heartbeat_frame()).await?;
+                    heartbeat = Box::pin(timer.sleep(self.heartbeat_interval));
+                }
+            }
+        }
+    }
+}
+
+enum Race<A, B> {
+    First(A),
+    Second(B),
+}
+
+/// Wait for whichever future finishes first, preferring `first`, and drop the other
+async fn race<A: Future, B: Future>(first: A, second: B) -> Race<A::Output, B::Output> {
+    let mut first = pin!(first);
+    let mut second = pin!(second);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = first.as_mut().poll(cx) {
+            return Poll::Ready(Race::First(output));
+        }
+        if let Poll::Ready(output) = second.as_mut().poll(cx) {
+            return Poll::Ready(Race::Second(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::get_msg_header;
+    use std::collections::VecDeque;
+
+    /// Replays `incoming`, then stays silent until `close_after` frames were sent
+    struct MemoryTransport {
+        incoming: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+        close_after: usize,
+    }
+
+    impl Transport for MemoryTransport {
+        async fn send(&mut self, frame: Vec<u8>) -> Result<(), String> {
+            self.sent.push(frame);
+            Ok(())
+        }
+
+        async fn recv(&mut self) -> Result<Option<Vec<u8>>, String> {
+            if let Some(frame) = self.incoming.pop_front() {
+                return Ok(Some(frame));
+            }
+            if self.sent.len() >= self.close_after {
+                return Ok(None);
+            }
+            std::future::pending().await
+        }
+    }
+
+    /// Timer whose sleeps finish immediately
+    struct NoDelay;
+
+    impl Timer for NoDelay {
+        async fn sleep(&self, _duration: Duration) {}
+    }
+
+    fn server_packet(operation: u32, body: &[u8]) -> Vec<u8> {
+        let mut packet = make_raw_packet(body, Operation::HEARTBEAT);
+        packet[6..8].copy_from_slice(&0_u16.to_be_bytes());
+        packet[8..12].copy_from_slice(&operation.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_session_runs_on_any_executor() {
+        let mut transport = MemoryTransport {
+            incoming: VecDeque::from([
+                server_packet(8, br#"{"code":0}"#),
+                server_packet(5, br#"{"cmd":"LIKE"}"#),
+            ]),
+            sent: Vec::new(),
+            close_after: 5,
+        };
+        let mut session = Session::new(r#"{"roomid":1}"#);
+        let mut packets = Vec::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(session.run(&mut transport, &NoDelay, |packet| packets.push(packet)))
+            .unwrap();
+
+        assert_eq!(
+            packets,
+            vec![
+                Packet::AuthReply(serde_json::json!({"code": 0})),
+                Packet::Message(serde_json::json!({"cmd": "LIKE"})),
+            ]
+        );
+        // Auth, the initial heartbeat, one on the auth reply, then timer-driven ones
+        let operations: Vec<u32> = transport
+            .sent
+            .iter()
+            .map(|frame| get_msg_header(frame).operation)
+            .collect();
+        assert_eq!(operations, vec![7, 2, 2, 2, 2]);
+    }
+}
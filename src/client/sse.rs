@@ -0,0 +1,274 @@
+// src/client/sse.rs
+//! Server-sent events of received messages
+//!
+//! `EventLog` numbers the messages read from a `MessageBus` and keeps the
+//! latest ones in memory; `route` streams them over HTTP as `text/event-stream`,
+//! one event per message named after its kind, with `BiliMessage::to_json` as
+//! data. A client reconnecting with `Last-Event-ID` (or `?last_event_id=`)
+//! first gets the buffered messages it missed.
+//!
+//! ```ignore
+//! let events = Arc::new(EventLog::new(DEFAULT_CAPACITY));
+//! rt.spawn(sse::record(bus.subscribe("sse"), Arc::clone(&events)));
+//! HttpServer::new().stream(sse::PATH, sse::route(events)).serve(addr)?;
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::bus::Subscriber;
+use crate::http_server::Request;
+use crate::models::BiliMessage;
+
+/// Path of the event stream
+pub const PATH: &str = "/events";
+
+/// Events kept for resuming clients by default
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Idle time after which a comment is sent to keep the connection open
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// One numbered message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Increasing from 1
+    pub id: u64,
+    /// `BiliMessage::kind`
+    pub kind: &'static str,
+    /// `BiliMessage::to_json`, serialized
+    pub data: String,
+}
+
+impl Event {
+    /// The event in the `text/event-stream` format
+    pub fn encode(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id, self.kind, self.data
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct LogState {
+    last_id: u64,
+    events: VecDeque<Event>,
+}
+
+/// Latest messages, numbered, for streaming and resuming
+#[derive(Debug)]
+pub struct EventLog {
+    state: Mutex<LogState>,
+    added: Condvar,
+    capacity: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::default(),
+            added: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add `msg`, returning its id
+    pub fn push(&self, msg: &BiliMessage) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let event = Event {
+            id: state.last_id,
+            kind: msg.kind(),
+            data: msg.to_json().to_string(),
+        };
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(event);
+        self.added.notify_all();
+        state.last_id
+    }
+
+    /// Id of the latest event, 0 before the first
+    pub fn last_id(&self) -> u64 {
+        self.state.lock().unwrap().last_id
+    }
+
+    /// Buffered events after `last_id`. An id from before a restart (greater
+    /// than any given out) gets the whole buffer.
+    pub fn since(&self, last_id: u64) -> Vec<Event> {
+        Self::after(&self.state.lock().unwrap(), last_id)
+    }
+
+    /// `since`, waiting up to `timeout` for an event if there is none yet
+    pub fn wait_since(&self, last_id: u64, timeout: Duration) -> Vec<Event> {
+        let state = self.state.lock().unwrap();
+        let last_id = Self::clamp(&state, last_id);
+        let (state, _) = self
+            .added
+            .wait_timeout_while(state, timeout, |state| state.last_id == last_id)
+            .unwrap();
+        Self::after(&state, last_id)
+    }
+
+    /// `last_id`, or 0 for an id from before a restart, which would otherwise
+    /// never equal the latest id and keep `wait_since` from waiting
+    fn clamp(state: &LogState, last_id: u64) -> u64 {
+        if last_id > state.last_id { 0 } else { last_id }
+    }
+
+    fn after(state: &LogState, last_id: u64) -> Vec<Event> {
+        let last_id = Self::clamp(state, last_id);
+        state
+            .events
+            .iter()
+            .filter(|event| event.id > last_id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Add every message of `subscriber` to `log` until the bus closes
+pub async fn record(mut subscriber: Subscriber, log: Arc<EventLog>) {
+    while let Some(batch) = subscriber.recv().await {
+        for msg in batch.iter() {
+            log.push(msg);
+        }
+    }
+}
+
+/// Stream route sending the events of `log`. Without `Last-Event-ID` only
+/// new events are sent; `?types=danmu,gift` limits the stream to those kinds.
+pub fn route(log: Arc<EventLog>) -> impl Fn(&Request, TcpStream) + Send + Sync + 'static {
+    move |request, mut stream| {
+        let resume = request
+            .header("Last-Event-ID")
+            .map(str::to_string)
+            .or_else(|| request.query_param("last_event_id"))
+            .and_then(|id| id.trim().parse().ok());
+        let types: Option<HashSet<String>> = request.query_param("types").map(|types| {
+            types
+                .split(',')
+                .map(|kind| kind.trim().to_string())
+                .collect()
+        });
+        let latest = log.last_id();
+        let mut last_id = resume.map_or(latest, |id| if id > latest { 0 } else { id });
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\nretry: 3000\n\n";
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        loop {
+            let events = log.wait_since(last_id, KEEPALIVE);
+            let mut out = String::new();
+            for event in &events {
+                last_id = event.id;
+                if types
+                    .as_ref()
+                    .is_none_or(|types| types.contains(event.kind))
+                {
+                    out.push_str(&event.encode());
+                }
+            }
+            if out.is_empty() {
+                out.push_str(": keepalive\n\n");
+            }
+            if stream.write_all(out.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_server::HttpServer;
+    use std::io::{BufRead, BufReader};
+
+    fn danmu(text: &str) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: "user".to_string(),
+            text: text.to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_event_log_resumes() {
+        let log = EventLog::new(2);
+        for text in ["a", "b", "c"] {
+            log.push(&danmu(text));
+        }
+        let ids = |events: Vec<Event>| events.iter().map(|e| e.id).collect::<Vec<_>>();
+        // The first event fell out of the buffer
+        assert_eq!(ids(log.since(0)), vec![2, 3]);
+        assert_eq!(ids(log.since(2)), vec![3]);
+        assert!(log.wait_since(3, Duration::from_millis(10)).is_empty());
+        // An id from a previous run replays the buffer
+        assert_eq!(ids(log.since(99)), vec![2, 3]);
+        assert_eq!(
+            ids(log.wait_since(99, Duration::from_millis(10))),
+            vec![2, 3]
+        );
+        let event = &log.since(2)[0];
+        assert!(event.encode().starts_with("id: 3\nevent: danmu\ndata: {"));
+    }
+
+    #[test]
+    fn test_sse_route_streams_missed_events() {
+        let log = Arc::new(EventLog::default());
+        log.push(&danmu("missed"));
+        log.push(&danmu("also missed"));
+        let addr = HttpServer::new()
+            .stream(PATH, route(Arc::clone(&log)))
+            .serve("127.0.0.1:0")
+            .unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /events HTTP/1.1\r\nLast-Event-ID: 1\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut lines = Vec::new();
+        while !lines.iter().any(|line: &String| line.starts_with("data:")) {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            lines.push(line);
+        }
+        assert_eq!(lines[0], "HTTP/1.1 200 OK\r\n");
+        assert!(lines.contains(&"id: 2\n".to_string()));
+        assert!(lines.last().unwrap().contains("also missed"));
+    }
+
+    #[test]
+    fn test_event_log_waits_after_restart() {
+        // A client resuming from a previous run before any event arrived
+        let log = EventLog::new(2);
+        let started = std::time::Instant::now();
+        assert!(log.wait_since(99, Duration::from_millis(50)).is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let log = Arc::new(log);
+        let pusher = Arc::clone(&log);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            pusher.push(&danmu("first"));
+        });
+        let events = log.wait_since(99, Duration::from_secs(5));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, 1);
+    }
+}
@@ -0,0 +1,361 @@
+// src/client/status.rs
+//! Connection lifecycle events and the status derived from them
+
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// State of the websocket connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// Waiting until `retry_at` to run reconnect attempt `attempt` (starting from 1)
+    Reconnecting {
+        attempt: u32,
+        retry_at: Instant,
+    },
+    /// Reconnecting failed; the client is no longer receiving messages
+    Disconnected,
+}
+
+/// Lifecycle event reported by the websocket client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Connected to `room_id`, authenticated as `uid` (0 = guest)
+    Connected {
+        room_id: String,
+        uid: u64,
+    },
+    HeartbeatSent,
+    /// Reply to a heartbeat, with the round-trip time if the heartbeat was timestamped
+    HeartbeatReply {
+        rtt: Option<Duration>,
+    },
+    /// Reconnect attempt `attempt` will run at `retry_at`
+    Reconnecting {
+        attempt: u32,
+        retry_at: Instant,
+    },
+    Disconnected {
+        reason: String,
+    },
+    /// The cookies were rejected while joining; the connection continues as a guest
+    AuthFallback {
+        reason: String,
+    },
+}
+
+/// Connection status shared between the client and the UI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    pub room_id: String,
+    /// Authenticated uid, 0 when connected as a guest
+    pub uid: u64,
+    /// When the current session started; kept across reconnects
+    pub session_start: Instant,
+    /// When the latest connection was established
+    pub connected_at: Option<Instant>,
+    /// Round-trip time of the latest answered heartbeat
+    pub heartbeat_rtt: Option<Duration>,
+    /// Sum of all measured heartbeat round-trip times
+    pub heartbeat_rtt_total: Duration,
+    /// Number of measured heartbeat round-trip times
+    pub heartbeat_rtt_count: u64,
+    pub heartbeats_sent: u64,
+    /// Reason of the latest disconnect
+    pub last_error: Option<String>,
+    /// Why the cookies were dropped, once the connection fell back to a guest
+    pub auth_fallback: Option<String>,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> Self {
+        Self {
+            state: ConnectionState::Disconnected,
+            room_id: String::new(),
+            uid: 0,
+            session_start: Instant::now(),
+            connected_at: None,
+            heartbeat_rtt: None,
+            heartbeat_rtt_total: Duration::ZERO,
+            heartbeat_rtt_count: 0,
+            heartbeats_sent: 0,
+            last_error: None,
+            auth_fallback: None,
+        }
+    }
+
+    /// Update the status with an event that happened now
+    pub fn apply(&mut self, event: ConnectionEvent) {
+        self.apply_at(event, Instant::now());
+    }
+
+    /// Update the status with an event that happened at `now`
+    pub fn apply_at(&mut self, event: ConnectionEvent, now: Instant) {
+        match event {
+            ConnectionEvent::Connected { room_id, uid } => {
+                if self.connected_at.is_none() && self.room_id.is_empty() {
+                    self.session_start = now;
+                }
+                self.state = ConnectionState::Connected;
+                self.room_id = room_id;
+                self.uid = uid;
+                self.connected_at = Some(now);
+                self.last_error = None;
+            }
+            ConnectionEvent::HeartbeatSent => self.heartbeats_sent += 1,
+            ConnectionEvent::HeartbeatReply { rtt } => {
+                if let Some(rtt) = rtt {
+                    self.heartbeat_rtt = Some(rtt);
+                    self.heartbeat_rtt_total += rtt;
+                    self.heartbeat_rtt_count += 1;
+                }
+            }
+            ConnectionEvent::Reconnecting { attempt, retry_at } => {
+                self.state = ConnectionState::Reconnecting { attempt, retry_at };
+                self.connected_at = None;
+            }
+            ConnectionEvent::Disconnected { reason } => {
+                self.state = ConnectionState::Disconnected;
+                self.connected_at = None;
+                self.last_error = Some(reason);
+            }
+            ConnectionEvent::AuthFallback { reason } => {
+                self.uid = 0;
+                self.auth_fallback = Some(reason);
+            }
+        }
+    }
+
+    /// Mean round-trip time of the answered heartbeats
+    pub fn mean_heartbeat_rtt(&self) -> Option<Duration> {
+        let count = u32::try_from(self.heartbeat_rtt_count).ok()?;
+        (count > 0).then(|| self.heartbeat_rtt_total / count)
+    }
+
+    /// Time since the session started
+    pub fn uptime(&self) -> Duration {
+        self.session_start.elapsed()
+    }
+
+    /// Time left before the next reconnect attempt, if one is scheduled
+    pub fn retry_in(&self, now: Instant) -> Option<Duration> {
+        match self.state {
+            ConnectionState::Reconnecting { retry_at, .. } => {
+                Some(retry_at.saturating_duration_since(now))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Timestamps heartbeats to measure their round-trip time from the replies
+#[derive(Debug, Clone, Default)]
+pub struct HeartbeatClock {
+    sent_at: Option<Instant>,
+}
+
+impl HeartbeatClock {
+    pub fn sent(&mut self) {
+        self.sent_at = Some(Instant::now());
+    }
+
+    /// Round-trip time of the pending heartbeat, if one was sent since the last reply
+    pub fn reply(&mut self) -> Option<Duration> {
+        self.reply_at(Instant::now())
+    }
+
+    pub fn reply_at(&mut self, now: Instant) -> Option<Duration> {
+        self.sent_at
+            .take()
+            .map(|sent_at| now.saturating_duration_since(sent_at))
+    }
+
+    /// Forget the pending heartbeat, e.g. when the connection is replaced
+    pub fn reset(&mut self) {
+        self.sent_at = None;
+    }
+}
+
+/// Manual connection request from the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionRequest {
+    /// Reconnect now, skipping any remaining backoff
+    Reconnect,
+    /// Close the connection and stop reconnecting
+    Disconnect,
+}
+
+/// Pending manual request, shared between the client and the UI
+#[derive(Debug, Default)]
+pub struct ConnectionControl {
+    request: Mutex<Option<ConnectionRequest>>,
+    wake: Condvar,
+    /// Handle to the socket of the current connection, see `attach`
+    socket: Mutex<Option<TcpStream>>,
+}
+
+impl ConnectionControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a request, waking a client waiting in reconnect backoff or
+    /// blocked reading the attached socket
+    pub fn request(&self, request: ConnectionRequest) {
+        if let Ok(mut pending) = self.request.lock() {
+            *pending = Some(request);
+            self.wake.notify_all();
+        }
+        // The failed read sends the client to the pending request
+        if let Ok(socket) = self.socket.lock()
+            && let Some(socket) = socket.as_ref()
+        {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Shut `socket` down on the next request, replacing the previous one;
+    /// the client attaches a handle to each connection it opens
+    pub fn attach(&self, socket: Option<TcpStream>) {
+        if let Ok(mut attached) = self.socket.lock() {
+            *attached = socket;
+        }
+    }
+
+    /// Take the pending request, if any
+    pub fn take(&self) -> Option<ConnectionRequest> {
+        self.request.lock().ok()?.take()
+    }
+
+    /// Wait up to `timeout` for a request, returning early when one arrives
+    pub fn wait(&self, timeout: Duration) -> Option<ConnectionRequest> {
+        let pending = self.request.lock().ok()?;
+        let (mut pending, _) = self
+            .wake
+            .wait_timeout_while(pending, timeout, |pending| pending.is_none())
+            .ok()?;
+        pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_status_lifecycle() {
+        let start = Instant::now();
+        let mut status = ConnectionStatus::new();
+        status.apply_at(
+            ConnectionEvent::Connected {
+                room_id: "1".to_string(),
+                uid: 42,
+            },
+            start,
+        );
+        assert_eq!(status.state, ConnectionState::Connected);
+        assert_eq!(status.session_start, start);
+
+        let mut clock = HeartbeatClock::default();
+        assert_eq!(clock.reply_at(start), None);
+        clock.sent();
+        let rtt = clock.reply_at(Instant::now() + Duration::from_millis(80));
+        assert!(rtt >= Some(Duration::from_millis(80)));
+        assert_eq!(clock.reply(), None);
+
+        status.apply_at(ConnectionEvent::HeartbeatSent, start);
+        for millis in [80, 40] {
+            let rtt = Some(Duration::from_millis(millis));
+            status.apply_at(ConnectionEvent::HeartbeatReply { rtt }, start);
+        }
+        status.apply_at(ConnectionEvent::HeartbeatReply { rtt: None }, start);
+        assert_eq!(status.heartbeats_sent, 1);
+        assert_eq!(status.heartbeat_rtt, Some(Duration::from_millis(40)));
+        assert_eq!(status.mean_heartbeat_rtt(), Some(Duration::from_millis(60)));
+
+        let retry_at = start + Duration::from_secs(2);
+        status.apply_at(
+            ConnectionEvent::Reconnecting {
+                attempt: 1,
+                retry_at,
+            },
+            start,
+        );
+        assert_eq!(
+            status.state,
+            ConnectionState::Reconnecting {
+                attempt: 1,
+                retry_at
+            }
+        );
+        assert_eq!(status.connected_at, None);
+        assert_eq!(status.retry_in(start), Some(Duration::from_secs(2)));
+
+        let later = start + Duration::from_secs(5);
+        status.apply_at(
+            ConnectionEvent::Connected {
+                room_id: "1".to_string(),
+                uid: 42,
+            },
+            later,
+        );
+        assert_eq!(status.connected_at, Some(later));
+        assert_eq!(status.session_start, start);
+        assert_eq!(status.retry_in(later), None);
+
+        status.apply_at(
+            ConnectionEvent::AuthFallback {
+                reason: "auth rejected".to_string(),
+            },
+            later,
+        );
+        assert_eq!(status.uid, 0);
+        assert_eq!(status.state, ConnectionState::Connected);
+        assert_eq!(status.auth_fallback.as_deref(), Some("auth rejected"));
+    }
+
+    #[test]
+    fn test_connection_control_wakes_waiter() {
+        let control = std::sync::Arc::new(ConnectionControl::new());
+        assert_eq!(control.wait(Duration::from_millis(1)), None);
+
+        let requester = std::sync::Arc::clone(&control);
+        let handle = std::thread::spawn(move || requester.request(ConnectionRequest::Reconnect));
+        assert_eq!(
+            control.wait(Duration::from_secs(10)),
+            Some(ConnectionRequest::Reconnect)
+        );
+        handle.join().unwrap();
+        assert_eq!(control.take(), None);
+    }
+
+    #[test]
+    fn test_connection_control_interrupts_read() {
+        use std::io::Read;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let _server = listener.accept().unwrap();
+        let control = std::sync::Arc::new(ConnectionControl::new());
+        control.attach(socket.try_clone().ok());
+
+        // Nothing is ever sent, so only the request can end the read
+        let requester = std::sync::Arc::clone(&control);
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            requester.request(ConnectionRequest::Disconnect);
+        });
+        let mut buf = [0_u8; 16];
+        assert!(!matches!(socket.read(&mut buf), Ok(n) if n > 0));
+        handle.join().unwrap();
+        assert_eq!(control.take(), Some(ConnectionRequest::Disconnect));
+    }
+}
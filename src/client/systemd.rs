@@ -0,0 +1,231 @@
+// src/client/systemd.rs
+//! systemd service notifications
+//!
+//! Under a `Type=notify` unit, `Notifier::from_env` opens `$NOTIFY_SOCKET`.
+//! `supervise` sends `READY=1` for the connected clients of a headless mode
+//! and keeps systemd informed after that. Every connection event becomes a
+//! `STATUS=` line, and answered heartbeats feed the watchdog (`WatchdogSec=`). A
+//! connection that stops answering heartbeats for longer than that gets the
+//! service restarted. Heartbeats are answered every 30 seconds, so the
+//! watchdog timeout should be at least 60 seconds.
+//!
+//! ```ini
+//! [Service]
+//! Type=notify
+//! ExecStart=/usr/bin/blivedm --watchlist 24779526
+//! WatchdogSec=90
+//! Restart=on-failure
+//! ```
+
+use futures::{Stream, StreamExt, stream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::status::ConnectionEvent;
+use crate::websocket::BiliLiveClient;
+
+/// Connection to the systemd notification socket
+#[derive(Debug)]
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(unix)]
+    addr: std::os::unix::net::SocketAddr,
+}
+
+impl Notifier {
+    /// Notifier of `$NOTIFY_SOCKET`, `None` when not started by systemd
+    #[cfg(unix)]
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        match Self::open(&path) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                log::warn!("systemd notifications disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_env() -> Option<Self> {
+        None
+    }
+
+    /// Notifier of the socket at `path`; a leading `@` names an abstract socket
+    #[cfg(unix)]
+    pub fn open(path: &str) -> Result<Self, String> {
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let addr = match path.strip_prefix('@') {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Some(name) => {
+                #[cfg(target_os = "android")]
+                use std::os::android::net::SocketAddrExt;
+                #[cfg(target_os = "linux")]
+                use std::os::linux::net::SocketAddrExt;
+                SocketAddr::from_abstract_name(name)
+            }
+            _ => SocketAddr::from_pathname(path),
+        }
+        .map_err(|e| format!("invalid notify socket {}: {}", path, e))?;
+        let socket = UnixDatagram::unbound()
+            .map_err(|e| format!("failed to create notify socket: {}", e))?;
+        Ok(Self { socket, addr })
+    }
+
+    /// Send `state`, newline-separated `KEY=value` assignments such as `READY=1`
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) -> Result<(), String> {
+        self.socket
+            .send_to_addr(state.as_bytes(), &self.addr)
+            .map(|_| ())
+            .map_err(|e| format!("systemd notification failed: {}", e))
+    }
+
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Report the service as started, connected to `rooms`
+    pub fn ready(&self, rooms: &[u64]) {
+        let rooms: Vec<String> = rooms.iter().map(u64::to_string).collect();
+        self.send(&format!(
+            "READY=1\nSTATUS=Connected to room {}",
+            rooms.join(", ")
+        ));
+    }
+
+    fn send(&self, state: &str) {
+        if let Err(e) = self.notify(state) {
+            log::debug!("{}", e);
+        }
+    }
+}
+
+/// Watchdog timeout systemd set for this process, from `$WATCHDOG_USEC`
+pub fn watchdog_timeout() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec)).filter(|timeout| !timeout.is_zero())
+}
+
+/// Notification for `event`, `None` for events systemd need not hear about
+pub fn event_state(event: &ConnectionEvent) -> Option<String> {
+    match event {
+        ConnectionEvent::Connected { room_id, .. } => {
+            Some(format!("STATUS=Connected to room {}\nWATCHDOG=1", room_id))
+        }
+        ConnectionEvent::HeartbeatReply { .. } => Some("WATCHDOG=1".to_string()),
+        ConnectionEvent::Reconnecting { attempt, .. } => {
+            Some(format!("STATUS=Reconnecting (attempt {})", attempt))
+        }
+        ConnectionEvent::Disconnected { reason } => {
+            Some(format!("STATUS=Disconnected: {}", reason))
+        }
+        ConnectionEvent::AuthFallback { reason } => {
+            Some(format!("STATUS=Connected as a guest: {}", reason))
+        }
+        ConnectionEvent::HeartbeatSent => None,
+    }
+}
+
+/// Report the clients of `rooms` as ready and forward their connection events
+/// to systemd, from a background thread until the clients are dropped
+pub fn supervise(
+    notifier: Notifier,
+    rooms: Vec<(u64, Arc<Mutex<BiliLiveClient>>)>,
+) -> Result<(), String> {
+    if let Some(timeout) = watchdog_timeout()
+        && timeout < Duration::from_secs(60)
+    {
+        log::warn!(
+            "watchdog timeout of {:?} is shorter than two heartbeats; use WatchdogSec=60 or more",
+            timeout
+        );
+    }
+    thread::Builder::new()
+        .name("blivedm-systemd".to_string())
+        .spawn(move || {
+            // Subscribing waits for the receive loop to release each client
+            let events: Vec<_> = rooms
+                .iter()
+                .filter_map(|(_, client)| Some(client.lock().ok()?.subscribe_events()))
+                .collect();
+            let room_ids: Vec<u64> = rooms.iter().map(|(room_id, _)| *room_id).collect();
+            drop(rooms);
+            notifier.ready(&room_ids);
+            forward(&notifier, stream::select_all(events));
+        })
+        .map(|_| ())
+        .map_err(|e| format!("failed to start systemd thread: {}", e))
+}
+
+/// Send the notifications of `events` until they end, then `STOPPING=1`
+fn forward<S>(notifier: &Notifier, mut events: S)
+where
+    S: Stream<Item = ConnectionEvent> + Unpin,
+{
+    futures::executor::block_on(async {
+        while let Some(event) = events.next().await {
+            if let Some(state) = event_state(&event) {
+                notifier.send(&state);
+            }
+        }
+    });
+    notifier.send("STOPPING=1");
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Instant;
+
+    #[test]
+    fn test_notifier_sends_states() {
+        let path = std::env::temp_dir().join(format!("blivedm-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let notifier = Notifier::open(path.to_str().unwrap()).unwrap();
+
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        notifier.ready(&[1, 2]);
+        tx.unbounded_send(ConnectionEvent::HeartbeatSent).unwrap();
+        tx.unbounded_send(ConnectionEvent::Reconnecting {
+            attempt: 2,
+            retry_at: Instant::now(),
+        })
+        .unwrap();
+        tx.unbounded_send(ConnectionEvent::HeartbeatReply { rtt: None })
+            .unwrap();
+        drop(tx);
+        forward(&notifier, rx);
+
+        let mut received = Vec::new();
+        let mut buf = [0_u8; 256];
+        for _ in 0..4 {
+            let len = systemd.recv(&mut buf).unwrap();
+            received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert_eq!(
+            received,
+            [
+                "READY=1\nSTATUS=Connected to room 1, 2",
+                "STATUS=Reconnecting (attempt 2)",
+                "WATCHDOG=1",
+                "STOPPING=1",
+            ]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,182 @@
+// src/client/watchlist.rs
+//! Connections to a roster of rooms at once
+//!
+//! A watchlist names rooms by id (`24779526`) or by streamer uid (`uid:2`).
+//! `Watchlist::open` connects a `LiveRoom` to each and merges their messages
+//! into one stream, each tagged with its room:
+//!
+//! ```ignore
+//! let targets = ["24779526", "uid:2"].map(|t| t.parse().unwrap());
+//! let watchlist = Watchlist::open(&targets, Credentials::Guest)?;
+//! let mut messages = watchlist.messages();
+//! while let Some(msg) = messages.next().await {
+//!     println!("{}", msg.to_json());
+//! }
+//! ```
+
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::live_room::{Credentials, LiveRoom};
+use crate::models::BiliMessage;
+use crate::room_management::get_live_api;
+
+pub const ROOM_BY_UID_URL: &str = "https://api.live.bilibili.com/room/v1/Room/getRoomInfoOld";
+
+/// Room of a watchlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    Room(u64),
+    /// Live room of the streamer with this uid
+    Uid(u64),
+}
+
+impl FromStr for WatchTarget {
+    type Err = String;
+
+    /// A room id, or `uid:` followed by a streamer uid
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (target, id): (fn(u64) -> Self, &str) = match s.strip_prefix("uid:") {
+            Some(uid) => (WatchTarget::Uid, uid),
+            None => (WatchTarget::Room, s),
+        };
+        id.trim()
+            .parse()
+            .map(target)
+            .map_err(|_| format!("invalid watchlist entry '{}'", s))
+    }
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTarget::Room(room_id) => write!(f, "{}", room_id),
+            WatchTarget::Uid(uid) => write!(f, "uid:{}", uid),
+        }
+    }
+}
+
+impl WatchTarget {
+    /// Room id of the target, looking up the room of a uid
+    pub fn resolve(&self) -> Result<u64, String> {
+        match *self {
+            WatchTarget::Room(room_id) => Ok(room_id),
+            WatchTarget::Uid(uid) => {
+                let data =
+                    get_live_api(&format!("{}?mid={}", ROOM_BY_UID_URL, uid), "room of uid")?;
+                room_id_from_data(&data).ok_or_else(|| format!("uid {} has no live room", uid))
+            }
+        }
+    }
+}
+
+fn room_id_from_data(data: &Value) -> Option<u64> {
+    data["roomid"].as_u64().filter(|room_id| *room_id != 0)
+}
+
+/// Message received in one of the rooms of a watchlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomMessage {
+    pub room_id: u64,
+    pub message: BiliMessage,
+}
+
+impl RoomMessage {
+    /// `BiliMessage::to_json` with an added `room_id` field
+    pub fn to_json(&self) -> Value {
+        let mut json = self.message.to_json();
+        json["room_id"] = self.room_id.into();
+        json
+    }
+}
+
+/// Open rooms of a watchlist; dropping it closes them
+pub struct Watchlist {
+    rooms: Vec<(u64, LiveRoom)>,
+}
+
+impl Watchlist {
+    /// Connect to every target, blocking until all are tried. Targets that
+    /// cannot be resolved or connected are logged and skipped; it is an error
+    /// only if none connects.
+    pub fn open(targets: &[WatchTarget], credentials: Credentials) -> Result<Self, String> {
+        let mut rooms: Vec<(u64, LiveRoom)> = Vec::new();
+        for target in targets {
+            let room = target.resolve().and_then(|room_id| {
+                if rooms.iter().any(|(id, _)| *id == room_id) {
+                    return Err(format!("room {} is listed twice", room_id));
+                }
+                LiveRoom::open(room_id, credentials.clone()).map(|room| (room_id, room))
+            });
+            match room {
+                Ok(room) => rooms.push(room),
+                Err(e) => log::warn!("skipping watchlist entry {}: {}", target, e),
+            }
+        }
+        if rooms.is_empty() {
+            return Err("no room of the watchlist could be opened".to_string());
+        }
+        log::info!("watching {} rooms", rooms.len());
+        Ok(Self { rooms })
+    }
+
+    /// Ids of the open rooms
+    pub fn room_ids(&self) -> Vec<u64> {
+        self.rooms.iter().map(|(room_id, _)| *room_id).collect()
+    }
+
+    pub fn room(&self, room_id: u64) -> Option<&LiveRoom> {
+        self.rooms
+            .iter()
+            .find(|(id, _)| *id == room_id)
+            .map(|(_, room)| room)
+    }
+
+    /// Messages of all rooms from now on, in arrival order
+    pub fn messages(&self) -> BoxStream<'static, RoomMessage> {
+        stream::select_all(self.rooms.iter().map(|(room_id, room)| {
+            let room_id = *room_id;
+            room.messages()
+                .map(move |message| RoomMessage { room_id, message })
+        }))
+        .boxed()
+    }
+
+    pub fn close(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_targets_and_tagging() {
+        assert_eq!("24779526".parse(), Ok(WatchTarget::Room(24779526)));
+        assert_eq!(" uid:2 ".parse(), Ok(WatchTarget::Uid(2)));
+        assert!("uid:".parse::<WatchTarget>().is_err());
+        assert!("room".parse::<WatchTarget>().is_err());
+        assert_eq!(WatchTarget::Uid(2).to_string(), "uid:2");
+
+        assert_eq!(
+            room_id_from_data(&serde_json::json!({"roomStatus": 1, "roomid": 24779526})),
+            Some(24779526)
+        );
+        assert_eq!(
+            room_id_from_data(&serde_json::json!({"roomStatus": 0, "roomid": 0})),
+            None
+        );
+
+        let msg = RoomMessage {
+            room_id: 24779526,
+            message: BiliMessage::VoiceJoinSwitch { open: true },
+        };
+        assert_eq!(
+            msg.to_json(),
+            serde_json::json!({"type": "voice_join_switch", "open": true, "room_id": 24779526})
+        );
+    }
+}
@@ -1,42 +1,314 @@
 // src/client/websocket.rs
 //! WebSocket client for Bilibili live danmaku messages (refactored from bili_live_dm)
 
-use native_tls::TlsStream;
 use serde_json::Value;
 use std::net::TcpStream;
 use std::panic;
+use tungstenite::client::IntoClientRequest;
 use tungstenite::{client, Message, WebSocket};
 
 use url::Url;
 
-use futures_channel::mpsc::Sender;
+use futures_channel::mpsc::{self, Sender, UnboundedReceiver, UnboundedSender};
 use http::Response;
 use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::auth::*;
-use crate::models::{AuthMessage, BiliMessage, DanmuServer, MsgHead};
+use crate::extension::ParserRegistry;
+use crate::gift_catalog::GiftCatalog;
+use crate::history::OnlineHistory;
+use crate::models::{AuthMessage, BiliMessage, DanmuServer};
+use crate::payloads::PayloadLog;
+use crate::protocol::{CommandFilter, Packet};
+pub use crate::protocol::{
+    Operation, decompress, get_msg_header, handle, make_packet, make_raw_packet,
+};
+use crate::runtime::AsyncRuntime;
+use crate::session::{HEARTBEAT_INTERVAL, Session};
+use crate::status::{
+    ConnectionControl, ConnectionEvent, ConnectionRequest, ConnectionStatus, HeartbeatClock,
+};
+use crate::tls::{self, TlsStream};
 
+/// Delays before each attempt to reconnect after the connection is lost
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub delays: Vec<Duration>,
+}
+
+impl Default for ReconnectPolicy {
+    /// Retry at once, then after 2 and 5 seconds
+    fn default() -> Self {
+        Self::with_delays([0, 2, 5].map(Duration::from_secs))
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn with_delays<I: IntoIterator<Item = Duration>>(delays: I) -> Self {
+        Self {
+            delays: delays.into_iter().collect(),
+        }
+    }
+
+    /// Report a lost connection as `Disconnected` without reconnecting
+    pub fn never() -> Self {
+        Self { delays: Vec::new() }
+    }
+}
+
+/// Compression the server is asked to use for message bundles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// zlib, protocol version 2
+    Zlib,
+    /// brotli, protocol version 3
+    #[default]
+    Brotli,
+}
+
+impl Compression {
+    /// `protover` of the auth packet
+    pub fn protover(self) -> i32 {
+        match self {
+            Compression::Zlib => 2,
+            Compression::Brotli => 3,
+        }
+    }
+}
+
+/// Websocket, auth packet and authenticated uid of a joined room
+type Connection = (WebSocket<TlsStream>, String, u64);
+
+/// Options applied to every connection of a client
+#[derive(Clone, Default)]
+struct ConnectOptions {
+    proxy: Option<String>,
+    compression: Compression,
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    tls_config: Option<Arc<tls::rustls::ClientConfig>>,
+    /// Validated `RequestHeaders` for the HTTP calls and the websocket upgrade
+    request_headers: Option<reqwest::header::HeaderMap>,
+}
+
+/// Builder for `BiliLiveClient`, from `BiliLiveClient::builder`:
+///
+/// ```ignore
+/// let client = BiliLiveClient::builder("24779526")
+///     .cookies("SESSDATA=...")
+///     .proxy("http://127.0.0.1:8080")
+///     .reconnect_policy(ReconnectPolicy::never())
+///     .build(tx)?;
+/// ```
+#[derive(Clone)]
+pub struct BiliLiveClientBuilder {
+    room_id: String,
+    cookies: Option<String>,
+    browser_cookies: bool,
+    options: ConnectOptions,
+    heartbeat_interval: Duration,
+    reconnect: ReconnectPolicy,
+    runtime: Option<AsyncRuntime>,
+    guest_fallback: bool,
+    payload_log: Option<Arc<PayloadLog>>,
+    request_headers: Option<RequestHeaders>,
+    parsers: ParserRegistry,
+}
+
+impl BiliLiveClientBuilder {
+    pub fn new(room_id: impl Into<String>) -> Self {
+        Self {
+            room_id: room_id.into(),
+            cookies: None,
+            browser_cookies: true,
+            options: ConnectOptions::default(),
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+            reconnect: ReconnectPolicy::default(),
+            runtime: None,
+            guest_fallback: true,
+            payload_log: None,
+            request_headers: None,
+            parsers: ParserRegistry::default(),
+        }
+    }
+
+    pub fn cookies(mut self, cookies: impl Into<String>) -> Self {
+        self.cookies = Some(cookies.into());
+        self
+    }
+
+    /// Whether to look for cookies in the browser when none with `SESSDATA`
+    /// were given (default); without it the client connects with the given
+    /// cookies as they are, or as a guest
+    pub fn browser_cookies(mut self, enabled: bool) -> Self {
+        self.browser_cookies = enabled;
+        self
+    }
+
+    /// Open the websocket through an HTTP proxy (`http://host:port`) with
+    /// `CONNECT`. HTTP API calls go through reqwest, which reads the proxy from
+    /// `HTTPS_PROXY` / `ALL_PROXY` instead.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.options.proxy = Some(proxy.into());
+        self
+    }
+
+    /// TLS config of the websocket instead of one trusting the bundled roots,
+    /// e.g. from `tls::pinned_config`
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    pub fn tls_config(mut self, config: Arc<tls::rustls::ClientConfig>) -> Self {
+        self.options.tls_config = Some(config);
+        self
+    }
+
+    /// User agent, referer and extra headers of the HTTP calls and the
+    /// websocket upgrade; `build` fails if a header is invalid
+    pub fn request_headers(mut self, headers: RequestHeaders) -> Self {
+        self.request_headers = Some(headers);
+        self
+    }
+
+    /// Interval the heartbeat driver should use, see `BiliLiveClient::heartbeat_interval`
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Parsers for commands the crate does not know yet, run before the built-in parsing
+    pub fn parsers(mut self, parsers: ParserRegistry) -> Self {
+        self.parsers = parsers;
+        self
+    }
+
+    /// Record the server JSON of every message sent in `log`
+    pub fn payload_log(mut self, log: Arc<PayloadLog>) -> Self {
+        self.payload_log = Some(log);
+        self
+    }
+
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    /// Runtime for async work around the client, shared through `BiliLiveClient::runtime`
+    pub fn runtime(mut self, runtime: AsyncRuntime) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Whether to join as a guest when the cookies are rejected (default),
+    /// reported by an `AuthFallback` event, instead of failing to connect
+    pub fn guest_fallback(mut self, enabled: bool) -> Self {
+        self.guest_fallback = enabled;
+        self
+    }
+
+    /// Connect, sending parsed messages to `sender`
+    pub fn build(self, sender: Sender<BiliMessage>) -> Result<BiliLiveClient, String> {
+        let mut cookies = if self.browser_cookies {
+            get_cookies_or_browser(self.cookies.as_deref())
+                .ok_or_else(|| "No cookies found in provided value or browser cookies. Please log into bilibili.com in your browser or provide cookies manually.".to_string())?
+        } else {
+            self.cookies.unwrap_or_default()
+        };
+        let mut options = self.options;
+        if let Some(headers) = &self.request_headers {
+            options.request_headers = Some(headers.header_map()?);
+        }
+        let ((ws, auth_msg, uid), fallback) = BiliLiveClient::connect_or_guest(
+            &mut cookies,
+            &self.room_id,
+            &options,
+            self.guest_fallback,
+        )?;
+        let mut client = BiliLiveClient {
+            ws,
+            cookies,
+            gift_catalog: BiliLiveClient::load_gift_catalog(&self.room_id, &options),
+            room_id: self.room_id,
+            session: Session::new(auth_msg).with_heartbeat_interval(self.heartbeat_interval),
+            ss: sender,
+            uid,
+            status: Arc::new(Mutex::new(ConnectionStatus::new())),
+            control: Arc::new(ConnectionControl::new()),
+            stopped: false,
+            heartbeat_clock: HeartbeatClock::default(),
+            listeners: Vec::new(),
+            online_history: Arc::new(Mutex::new(OnlineHistory::default())),
+            command_filter: Arc::new(CommandFilter::default()),
+            options,
+            reconnect: self.reconnect,
+            runtime: self.runtime,
+            guest_fallback: self.guest_fallback,
+            payload_log: self.payload_log,
+            parsers: self.parsers,
+        };
+        client.attach_socket();
+        client.emit_connected();
+        if let Some(reason) = fallback {
+            client.fall_back_to_guest(reason);
+        }
+        Ok(client)
+    }
+}
+
+/// Connection to the danmaku server of one room.
+///
+/// The server binds a connection to the room id of its auth packet and has no
+/// command for joining further rooms, so rooms cannot share a connection even
+/// on the same host; watching N rooms takes N clients (or `LiveRoom`s).
 pub struct BiliLiveClient {
-    ws: WebSocket<TlsStream<TcpStream>>,
+    ws: WebSocket<TlsStream>,
     cookies: String,
     room_id: String,
-    auth_msg: String,
+    /// Auth body, frame encoding and decoding of the current connection
+    session: Session,
     ss: Sender<BiliMessage>,
+    /// Empty until the background fetch of `load_gift_catalog` finishes
+    gift_catalog: Arc<RwLock<GiftCatalog>>,
+    /// Authenticated uid, 0 when connected as a guest
+    uid: u64,
+    status: Arc<Mutex<ConnectionStatus>>,
+    control: Arc<ConnectionControl>,
+    /// Set by a manual disconnect; nothing is read or sent until a reconnect
+    stopped: bool,
+    heartbeat_clock: HeartbeatClock,
+    /// Receivers of connection events, see `subscribe_events`
+    listeners: Vec<UnboundedSender<ConnectionEvent>>,
+    online_history: Arc<Mutex<OnlineHistory>>,
+    command_filter: Arc<CommandFilter>,
+    options: ConnectOptions,
+    reconnect: ReconnectPolicy,
+    runtime: Option<AsyncRuntime>,
+    /// Rejoin as a guest when the cookies are rejected
+    guest_fallback: bool,
+    /// Receives the server JSON of every message sent, see `PayloadLog`
+    payload_log: Option<Arc<PayloadLog>>,
+    /// Custom command parsers, consulted before `handle`
+    parsers: ParserRegistry,
 }
 
 impl BiliLiveClient {
+    /// Builder with all connection options, for `room_id`
+    pub fn builder(room_id: impl Into<String>) -> BiliLiveClientBuilder {
+        BiliLiveClientBuilder::new(room_id)
+    }
+
     pub fn new(cookies: &str, room_id: &str, r: Sender<BiliMessage>) -> Self {
-        let (ws, auth_msg) = Self::connect_with_auth(cookies, room_id)
-            .unwrap_or_else(|e| panic!("Failed to create websocket client: {}", e));
-        BiliLiveClient {
-            ws,
-            cookies: cookies.to_string(),
-            room_id: room_id.to_string(),
-            auth_msg,
-            ss: r,
-        }
+        Self::builder(room_id)
+            .cookies(cookies)
+            .browser_cookies(false)
+            .build(r)
+            .unwrap_or_else(|e| panic!("Failed to create websocket client: {}", e))
     }
 
     /// Create a new client with automatic browser cookie detection
@@ -46,16 +318,155 @@ impl BiliLiveClient {
         room_id: &str,
         r: Sender<BiliMessage>,
     ) -> Result<Self, String> {
-        let resolved_cookies = get_cookies_or_browser(cookies)
-            .ok_or_else(|| "No cookies found in provided value or browser cookies. Please log into bilibili.com in your browser or provide cookies manually.".to_string())?;
-        let (ws, auth_msg) = Self::connect_with_auth(&resolved_cookies, room_id)?;
-        Ok(BiliLiveClient {
-            ws,
-            cookies: resolved_cookies,
-            room_id: room_id.to_string(),
-            auth_msg,
-            ss: r,
-        })
+        let builder = Self::builder(room_id);
+        match cookies {
+            Some(cookies) => builder.cookies(cookies),
+            None => builder,
+        }
+        .build(r)
+    }
+
+    /// Fetch the room gift catalog on a background thread, so a slow API does
+    /// not hold up connecting; gifts keep their message values until it is in
+    fn load_gift_catalog(room_id: &str, options: &ConnectOptions) -> Arc<RwLock<GiftCatalog>> {
+        let catalog = Arc::new(RwLock::new(GiftCatalog::default()));
+        let loaded = Arc::clone(&catalog);
+        let room_id = room_id.parse::<u64>().unwrap_or(0);
+        let headers = options.request_headers.clone();
+        let spawned = thread::Builder::new()
+            .name("blivedm-gift-catalog".to_string())
+            .spawn(
+                move || match GiftCatalog::fetch_with_headers(room_id, headers.as_ref()) {
+                    Ok(fetched) => {
+                        if let Ok(mut catalog) = loaded.write() {
+                            *catalog = fetched;
+                        }
+                    }
+                    Err(e) => log::warn!("gift prices unavailable, using message values: {}", e),
+                },
+            );
+        if let Err(e) = spawned {
+            log::warn!("failed to start gift catalog thread: {}", e);
+        }
+        catalog
+    }
+
+    /// Gift price catalog of the connected room, empty while still loading
+    pub fn gift_catalog(&self) -> GiftCatalog {
+        self.gift_catalog
+            .read()
+            .map(|catalog| catalog.clone())
+            .unwrap_or_default()
+    }
+
+    /// Room id the client is connected to
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    /// Cookies the client authenticated with, empty for a guest
+    pub fn cookies(&self) -> &str {
+        &self.cookies
+    }
+
+    /// Headers from `BiliLiveClientBuilder::request_headers`, for the
+    /// `EventContext` of API calls on behalf of this client
+    pub fn request_headers(&self) -> Option<reqwest::header::HeaderMap> {
+        self.options.request_headers.clone()
+    }
+
+    /// Shared connection status, updated on connection lifecycle events
+    pub fn connection_status(&self) -> Arc<Mutex<ConnectionStatus>> {
+        Arc::clone(&self.status)
+    }
+
+    /// Shared control for requesting a manual reconnect or disconnect
+    pub fn connection_control(&self) -> Arc<ConnectionControl> {
+        Arc::clone(&self.control)
+    }
+
+    /// Round-trip time of the latest answered heartbeat
+    pub fn heartbeat_rtt(&self) -> Option<Duration> {
+        self.status.lock().ok()?.heartbeat_rtt
+    }
+
+    /// Shared popularity and watched-count samples of the current room
+    pub fn online_history(&self) -> Arc<Mutex<OnlineHistory>> {
+        Arc::clone(&self.online_history)
+    }
+
+    /// Shared ignore list of noisy commands, dropped before the message channel
+    pub fn command_filter(&self) -> Arc<CommandFilter> {
+        Arc::clone(&self.command_filter)
+    }
+
+    /// How often `send_heart_beat` should be called
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.session.heartbeat_interval()
+    }
+
+    /// Runtime given to the builder, if any
+    pub fn runtime(&self) -> Option<&AsyncRuntime> {
+        self.runtime.as_ref()
+    }
+
+    /// Receive every connection event from now on, including heartbeat round-trip times
+    pub fn subscribe_events(&mut self) -> UnboundedReceiver<ConnectionEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.listeners.retain(|listener| !listener.is_closed());
+        self.listeners.push(tx);
+        rx
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        for listener in &self.listeners {
+            let _ = listener.unbounded_send(event.clone());
+        }
+        if let Ok(mut status) = self.status.lock() {
+            status.apply(event);
+        }
+    }
+
+    fn emit_connected(&self) {
+        self.emit(ConnectionEvent::Connected {
+            room_id: self.room_id.clone(),
+            uid: self.uid,
+        });
+    }
+
+    /// Drop the rejected cookies; later connections join as a guest
+    fn fall_back_to_guest(&mut self, reason: String) {
+        log::warn!("{}; continuing as a guest", reason);
+        self.cookies.clear();
+        self.emit(ConnectionEvent::AuthFallback { reason });
+    }
+
+    /// Connect to another room, replacing the current connection
+    pub fn switch_room(&mut self, room_id: &str) -> Result<(), String> {
+        let ((ws, auth_msg, uid), fallback) = Self::connect_or_guest(
+            &mut self.cookies,
+            room_id,
+            &self.options,
+            self.guest_fallback,
+        )?;
+        self.ws = ws;
+        self.attach_socket();
+        self.session.set_auth_body(auth_msg);
+        self.uid = uid;
+        if let Some(reason) = fallback {
+            self.fall_back_to_guest(reason);
+        }
+        self.stopped = false;
+        self.room_id = room_id.to_string();
+        self.gift_catalog = Self::load_gift_catalog(room_id, &self.options);
+        if let Ok(mut history) = self.online_history.lock() {
+            history.clear();
+        }
+        self.emit_connected();
+        self.send_auth_internal()?;
+        self.send_heart_beat_internal()?;
+        log::info!("switched to room {}", room_id);
+        Ok(())
     }
 
     pub fn send_auth(&mut self) {
@@ -71,59 +482,68 @@ impl BiliLiveClient {
     }
 
     pub fn parse_ws_message(&mut self, resv: Vec<u8>) {
-        let mut offset = 0;
-        let header = &resv[0..16];
-        let mut head_1 = get_msg_header(header);
-        if head_1.operation == 5 || head_1.operation == 8 {
-            loop {
-                let body: &[u8] = &resv[offset + 16..offset + (head_1.pack_len as usize)];
-                self.parse_business_message(head_1, body);
-                offset += head_1.pack_len as usize;
-                if offset >= resv.len() {
-                    break;
+        for packet in self.session.decode(&resv) {
+            match packet {
+                Packet::Message(json) => {
+                    if json["cmd"] == "WATCHED_CHANGE"
+                        && let Some(watched) = json["data"]["num"].as_u64()
+                        && let Ok(mut history) = self.online_history.lock()
+                    {
+                        history.record_watched(watched);
+                    }
+                    if self.command_filter.suppress(&json) {
+                        continue;
+                    }
+                    let raw = self.payload_log.as_ref().map(|log| (log, json.clone()));
+                    if let Some(mut msg) = self.parsers.handle(json) {
+                        if let Ok(catalog) = self.gift_catalog.read() {
+                            catalog.annotate(&mut msg);
+                        }
+                        if let Some((log, json)) = raw {
+                            log.record(&msg, &json);
+                        }
+                        let _ = self.ss.try_send(msg);
+                    }
+                }
+                Packet::HeartbeatReply { popularity } => {
+                    let rtt = self.heartbeat_clock.reply();
+                    log::info!("popularity:{}, heartbeat rtt: {:?}", popularity, rtt);
+                    if let Ok(mut history) = self.online_history.lock() {
+                        history.record_popularity(u64::from(popularity));
+                    }
+                    self.emit(ConnectionEvent::HeartbeatReply { rtt });
                 }
-                let temp_head = &resv[offset..(offset + 16)];
-                head_1 = get_msg_header(temp_head);
+                Packet::AuthReply(reply) => match reply["code"].as_i64() {
+                    Some(code) if code != 0 => self.auth_rejected(code),
+                    _ => self.send_heart_beat(),
+                },
             }
-        } else if head_1.operation == 3 {
-            let mut body: [u8; 4] = [0, 0, 0, 0];
-            body[0] = resv[16];
-            body[1] = resv[17];
-            body[2] = resv[18];
-            body[3] = resv[19];
-            let popularity = i32::from_be_bytes(body);
-            log::info!("popularity:{}", popularity);
-        } else {
-            log::error!(
-                "unknown message operation={:?}, header={:?}}}",
-                head_1.operation,
-                head_1
-            )
         }
     }
 
-    pub fn parse_business_message(&mut self, h: MsgHead, b: &[u8]) {
-        if h.operation == 5 {
-            if h.ver == 3 {
-                let res: Vec<u8> = decompress(b).unwrap();
-                self.parse_ws_message(res);
-            } else if h.ver == 0 {
-                let s = String::from_utf8(b.to_vec()).unwrap();
-                let res_json: Value = serde_json::from_str(s.as_str()).unwrap();
-                if let Some(msg) = handle(res_json) {
-                    let _ = self.ss.try_send(msg);
-                }
-            } else {
-                log::error!("Unknown compression format");
-            }
-        } else if h.operation == 8 {
-            self.send_heart_beat();
-        } else {
-            log::error!("Unknown message format {}", h.operation);
+    /// The server refused the auth packet and is about to close the connection
+    fn auth_rejected(&mut self, code: i64) {
+        let reason = format!("auth rejected by the server (code {})", code);
+        if !self.guest_fallback || self.cookies.is_empty() {
+            log::error!("{}", reason);
+            return;
+        }
+        self.fall_back_to_guest(reason);
+        if let Err(e) = self.reconnect() {
+            log::error!("rejoining as a guest failed: {}", e);
         }
     }
 
+    /// Read and handle the next message. A blocked read is cut short by a
+    /// request on `connection_control`, which is then acted on.
     pub fn receive(&mut self) -> Result<(), String> {
+        if let Some(request) = self.control.take() {
+            return self.handle_request(request);
+        }
+        if self.stopped {
+            return Ok(());
+        }
+
         if self.ws.can_read() {
             let msg = self.ws.read();
             match msg {
@@ -135,6 +555,10 @@ impl BiliLiveClient {
                     Ok(())
                 }
                 Err(e) => {
+                    // The control shut the socket down to deliver a request
+                    if let Some(request) = self.control.take() {
+                        return self.handle_request(request);
+                    }
                     let msg = format!("read msg error: {}", e);
                     log::warn!("{}", msg);
                     self.reconnect().map_err(|reconnect_err| {
@@ -147,80 +571,146 @@ impl BiliLiveClient {
         }
     }
 
+    fn handle_request(&mut self, request: ConnectionRequest) -> Result<(), String> {
+        match request {
+            ConnectionRequest::Disconnect => {
+                self.disconnect();
+                Ok(())
+            }
+            ConnectionRequest::Reconnect => {
+                self.stopped = false;
+                self.reconnect()
+            }
+        }
+    }
+
+    /// Let the control shut down the current socket to interrupt a read
+    fn attach_socket(&self) {
+        self.control
+            .attach(tls::socket(self.ws.get_ref()).try_clone().ok());
+    }
+
     fn connect_with_auth(
         cookies: &str,
         room_id: &str,
-    ) -> Result<(WebSocket<TlsStream<TcpStream>>, String), String> {
-        panic::catch_unwind(|| {
-            let (v, auth) = init_server(cookies, room_id);
-            let (ws, _res) = connect_result(v["host_list"].clone())?;
+        options: &ConnectOptions,
+    ) -> Result<Connection, String> {
+        // The options are only read, so a panic cannot leave them half updated
+        panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let (v, mut auth) =
+                init_server_with_headers(cookies, room_id, options.request_headers.as_ref());
+            auth.protover = options.compression.protover();
+            let (ws, _res) = connect_with_options(&server_url(&v["host_list"]), options)?;
             let auth_msg = serde_json::to_string(&auth)
                 .map_err(|e| format!("serialize auth payload failed: {}", e))?;
-            Ok((ws, auth_msg))
-        })
+            Ok((ws, auth_msg, auth.uid))
+        }))
         .map_err(|_| format!("websocket setup panicked for room {}", room_id))?
     }
 
+    /// `connect_with_auth`, retried as a guest if joining with `cookies` fails
+    /// and `guest_fallback` is set. On a fallback `cookies` are cleared and the
+    /// failure is returned with the connection.
+    fn connect_or_guest(
+        cookies: &mut String,
+        room_id: &str,
+        options: &ConnectOptions,
+        guest_fallback: bool,
+    ) -> Result<(Connection, Option<String>), String> {
+        match Self::connect_with_auth(cookies, room_id, options) {
+            Err(e) if guest_fallback && !cookies.is_empty() => {
+                let connection =
+                    Self::connect_with_auth("", room_id, options).map_err(|guest_err| {
+                        format!("{}; joining as a guest failed: {}", e, guest_err)
+                    })?;
+                cookies.clear();
+                let reason = format!("joining with cookies failed: {}", e);
+                Ok((connection, Some(reason)))
+            }
+            result => result.map(|connection| (connection, None)),
+        }
+    }
+
     fn send_auth_internal(&mut self) -> Result<(), String> {
-        match self.ws.send(Message::Binary(make_packet(
-            self.auth_msg.as_str(),
-            Operation::AUTH,
-        ))) {
+        if self.stopped {
+            return Ok(());
+        }
+        match self.ws.send(Message::Binary(self.session.auth_frame())) {
             Ok(()) => Ok(()),
             Err(e) => {
                 let msg = format!("send auth error: {}", e);
                 log::warn!("{}", msg);
                 self.reconnect()?;
                 self.ws
-                    .send(Message::Binary(make_packet(
-                        self.auth_msg.as_str(),
-                        Operation::AUTH,
-                    )))
+                    .send(Message::Binary(self.session.auth_frame()))
                     .map_err(|retry_err| format!("{}; resend auth failed: {}", msg, retry_err))
             }
         }
     }
 
     fn send_heart_beat_internal(&mut self) -> Result<(), String> {
+        if self.stopped {
+            return Ok(());
+        }
         match self
             .ws
-            .send(Message::Binary(make_packet("{}", Operation::HEARTBEAT)))
+            .send(Message::Binary(self.session.heartbeat_frame()))
         {
-            Ok(()) => Ok(()),
+            Ok(()) => {
+                self.heartbeat_clock.sent();
+                self.emit(ConnectionEvent::HeartbeatSent);
+                Ok(())
+            }
             Err(e) => {
                 let msg = format!("send heartbeat error: {}", e);
                 log::warn!("{}", msg);
                 self.reconnect()?;
                 self.ws
-                    .send(Message::Binary(make_packet("{}", Operation::HEARTBEAT)))
+                    .send(Message::Binary(self.session.heartbeat_frame()))
                     .map_err(|retry_err| format!("{}; resend heartbeat failed: {}", msg, retry_err))
             }
         }
     }
 
     fn reconnect(&mut self) -> Result<(), String> {
-        let backoff = [1_u64, 2, 5];
         let mut last_err = None;
 
-        for (idx, delay_secs) in backoff.iter().enumerate() {
-            if idx > 0 {
-                thread::sleep(Duration::from_secs(*delay_secs));
+        for (idx, delay) in self.reconnect.delays.clone().into_iter().enumerate() {
+            self.emit(ConnectionEvent::Reconnecting {
+                attempt: idx as u32 + 1,
+                retry_at: Instant::now() + delay,
+            });
+            // A manual reconnect skips the rest of the backoff
+            if !delay.is_zero() && self.control.wait(delay) == Some(ConnectionRequest::Disconnect) {
+                self.disconnect();
+                return Err("disconnected by user".to_string());
             }
 
-            match Self::connect_with_auth(&self.cookies, &self.room_id) {
-                Ok((ws, auth_msg)) => {
+            let connection = Self::connect_or_guest(
+                &mut self.cookies,
+                &self.room_id,
+                &self.options,
+                self.guest_fallback,
+            );
+            match connection {
+                Ok(((ws, auth_msg, uid), fallback)) => {
                     self.ws = ws;
-                    self.auth_msg = auth_msg;
-                    let auth_resend = self.ws.send(Message::Binary(make_packet(
-                        self.auth_msg.as_str(),
-                        Operation::AUTH,
-                    )));
+                    self.attach_socket();
+                    self.session.set_auth_body(auth_msg);
+                    self.uid = uid;
+                    if let Some(reason) = fallback {
+                        self.fall_back_to_guest(reason);
+                    }
+                    let auth_resend = self.ws.send(Message::Binary(self.session.auth_frame()));
                     let heartbeat_resend = self
                         .ws
-                        .send(Message::Binary(make_packet("{}", Operation::HEARTBEAT)));
+                        .send(Message::Binary(self.session.heartbeat_frame()));
 
                     match (auth_resend, heartbeat_resend) {
                         (Ok(()), Ok(())) => {
+                            self.heartbeat_clock.sent();
+                            self.emit_connected();
+                            self.emit(ConnectionEvent::HeartbeatSent);
                             log::info!(
                                 "websocket reconnected on attempt {} for room {}",
                                 idx + 1,
@@ -267,7 +757,23 @@ impl BiliLiveClient {
             }
         }
 
-        Err(last_err.unwrap_or_else(|| "unknown reconnect failure".to_string()))
+        let reason = last_err.unwrap_or_else(|| "reconnecting is disabled".to_string());
+        self.emit(ConnectionEvent::Disconnected {
+            reason: reason.clone(),
+        });
+        Err(reason)
+    }
+
+    /// Close the connection and stop reading and sending until a reconnect is requested
+    fn disconnect(&mut self) {
+        if let Err(e) = self.ws.close(None) {
+            log::debug!("websocket close failed: {}", e);
+        }
+        self.stopped = true;
+        log::info!("websocket disconnected from room {}", self.room_id);
+        self.emit(ConnectionEvent::Disconnected {
+            reason: "disconnected by user".to_string(),
+        });
     }
 }
 
@@ -299,16 +805,22 @@ fn find_server(vd: Vec<DanmuServer>) -> (String, String, String) {
 }
 
 pub fn init_server(cookies: &str, room_id: &str) -> (Value, AuthMessage) {
+    init_server_with_headers(cookies, room_id, None)
+}
+
+/// `init_server` with custom request headers, see `auth::RequestHeaders`
+pub fn init_server_with_headers(
+    cookies: &str,
+    room_id: &str,
+    custom: Option<&reqwest::header::HeaderMap>,
+) -> (Value, AuthMessage) {
     let mut auth_map = HashMap::new();
     let mut headers = reqwest::header::HeaderMap::new();
     headers.insert(
         reqwest::header::COOKIE,
         reqwest::header::HeaderValue::from_str(cookies).unwrap(),
     );
-    headers.insert(
-        reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static(crate::auth::USER_AGENT),
-    );
+    apply_custom_headers(&mut headers, custom);
     log::debug!("headers: {:?}", headers);
 
     // Extract SESSDATA from cookies for authentication
@@ -356,128 +868,110 @@ pub fn init_server(cookies: &str, room_id: &str) -> (Value, AuthMessage) {
     (server_info.clone(), auth_msg)
 }
 
-pub fn connect(v: Value) -> (WebSocket<TlsStream<TcpStream>>, Response<Option<Vec<u8>>>) {
+pub fn connect(v: Value) -> (WebSocket<TlsStream>, Response<Option<Vec<u8>>>) {
     connect_result(v).expect("Can't connect")
 }
 
-pub fn connect_result(
-    v: Value,
-) -> Result<(WebSocket<TlsStream<TcpStream>>, Response<Option<Vec<u8>>>), String> {
-    let danmu_server = gen_damu_list(&v);
-    let (host, url, ws_url) = find_server(danmu_server);
-    let connector: native_tls::TlsConnector =
-        native_tls::TlsConnector::new().map_err(|e| format!("tls init failed: {}", e))?;
-    let stream: TcpStream = TcpStream::connect(url.as_str())
-        .map_err(|e| format!("tcp connect to {} failed: {}", url, e))?;
-    let stream: native_tls::TlsStream<TcpStream> = connector
-        .connect(host.as_str(), stream)
-        .map_err(|e| format!("tls connect to {} failed: {}", host, e))?;
-    let parsed_url =
-        Url::parse(ws_url.as_str()).map_err(|e| format!("invalid websocket url: {}", e))?;
-    client(parsed_url, stream).map_err(|e| format!("websocket handshake failed: {}", e))
+/// Connected websocket and the handshake response, or an error message
+pub type ConnectResult = Result<(WebSocket<TlsStream>, Response<Option<Vec<u8>>>), String>;
+
+pub fn connect_result(v: Value) -> ConnectResult {
+    connect_url(&server_url(&v))
 }
 
-pub enum Operation {
-    AUTH,
-    HEARTBEAT,
+/// `wss://` url of the first danmaku server in a `host_list`
+pub fn server_url(host_list: &Value) -> String {
+    let (_host, _url, ws_url) = find_server(gen_damu_list(host_list));
+    ws_url
 }
 
-pub fn make_packet(body: &str, ops: Operation) -> Vec<u8> {
-    let json: Value = serde_json::from_str(body).unwrap();
-    let temp = json.to_string();
-    let body_content: &[u8] = temp.as_bytes();
-    let pack_len: [u8; 4] = ((16 + body.len()) as u32).to_be_bytes();
-    let raw_header_size: [u8; 2] = (16 as u16).to_be_bytes();
-    let ver: [u8; 2] = (1 as u16).to_be_bytes();
-    let operation: [u8; 4] = match ops {
-        Operation::AUTH => (7 as u32).to_be_bytes(),
-        Operation::HEARTBEAT => (2 as u32).to_be_bytes(),
-    };
-    let seq_id: [u8; 4] = (1 as u32).to_be_bytes();
-    let mut res = pack_len.to_vec();
-    res.append(&mut raw_header_size.to_vec());
-    res.append(&mut ver.to_vec());
-    res.append(&mut operation.to_vec());
-    res.append(&mut seq_id.to_vec());
-    res.append(&mut body_content.to_vec());
-    res
+/// Open a websocket to a `wss://host:port/path` url
+pub fn connect_url(ws_url: &str) -> ConnectResult {
+    connect_url_via(ws_url, None)
 }
 
-pub fn get_msg_header(v_s: &[u8]) -> MsgHead {
-    let mut pack_len: [u8; 4] = [0; 4];
-    let mut raw_header_size: [u8; 2] = [0; 2];
-    let mut ver: [u8; 2] = [0; 2];
-    let mut operation: [u8; 4] = [0; 4];
-    let mut seq_id: [u8; 4] = [0; 4];
-    for (i, v) in v_s.iter().enumerate() {
-        if i < 4 {
-            pack_len[i] = *v;
-            continue;
-        }
-        if i < 6 {
-            raw_header_size[i - 4] = *v;
-            continue;
-        }
-        if i < 8 {
-            ver[i - 6] = *v;
-            continue;
-        }
-        if i < 12 {
-            operation[i - 8] = *v;
-            continue;
-        }
-        if i < 16 {
-            seq_id[i - 12] = *v;
-            continue;
-        }
-    }
-    MsgHead {
-        pack_len: u32::from_be_bytes(pack_len),
-        raw_header_size: u16::from_be_bytes(raw_header_size),
-        ver: u16::from_be_bytes(ver),
-        operation: u32::from_be_bytes(operation),
-        seq_id: u32::from_be_bytes(seq_id),
-    }
+/// Open a websocket to a `wss://host:port/path` url, tunnelled through an HTTP
+/// proxy (`http://host:port`) if one is given
+pub fn connect_url_via(ws_url: &str, proxy: Option<&str>) -> ConnectResult {
+    let options = ConnectOptions {
+        proxy: proxy.map(str::to_string),
+        ..Default::default()
+    };
+    connect_with_options(ws_url, &options)
 }
 
-pub fn decompress(body: &[u8]) -> std::io::Result<Vec<u8>> {
-    use brotlic::DecompressorReader;
-    use std::io::Read;
-    let mut decompressed_reader: DecompressorReader<&[u8]> = DecompressorReader::new(body);
-    let mut decoded_input = Vec::new();
-    let _ = decompressed_reader.read_to_end(&mut decoded_input)?;
-    Ok(decoded_input)
+fn connect_with_options(ws_url: &str, options: &ConnectOptions) -> ConnectResult {
+    let parsed_url = Url::parse(ws_url).map_err(|e| format!("invalid websocket url: {}", e))?;
+    let host = parsed_url
+        .host_str()
+        .ok_or_else(|| format!("websocket url has no host: {}", ws_url))?
+        .to_string();
+    let port = parsed_url.port_or_known_default().unwrap_or(443);
+    let url = format!("{}:{}", host, port);
+    let stream: TcpStream = match options.proxy.as_deref() {
+        Some(proxy) => proxy_tunnel(proxy, &url)?,
+        None => TcpStream::connect(url.as_str())
+            .map_err(|e| format!("tcp connect to {} failed: {}", url, e))?,
+    };
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    let stream = match &options.tls_config {
+        Some(config) => tls::connect_with(host.as_str(), stream, Arc::clone(config))?,
+        None => tls::connect(host.as_str(), stream)?,
+    };
+    #[cfg(feature = "native-tls")]
+    let stream = tls::connect(host.as_str(), stream)?;
+    let mut request = parsed_url
+        .into_client_request()
+        .map_err(|e| format!("invalid websocket request: {}", e))?;
+    apply_custom_headers(request.headers_mut(), options.request_headers.as_ref());
+    client(request, stream).map_err(|e| format!("websocket handshake failed: {}", e))
 }
 
-/// here we detail [info format is online](https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/live/message_stream.md)
-/// .
-pub fn handle(json: Value) -> Option<BiliMessage> {
-    let category = json["cmd"].as_str().unwrap_or("");
-    let data = json.get("data").unwrap_or(&json);
-    match category {
-        "DANMU_MSG" => Some(BiliMessage::Danmu {
-            user: json["info"][2][1]
-                .as_str()
-                .unwrap_or("<unknown>")
-                .to_string(),
-            text: json["info"][1].as_str().unwrap_or("").to_string(),
-        }),
-        "SEND_GIFT" => Some(BiliMessage::Gift {
-            user: data["uname"]
-                .as_str()
-                .or_else(|| data["sender_uinfo"]["base"]["name"].as_str())
-                .unwrap_or("<unknown>")
-                .to_string(),
-            gift: data["giftName"].as_str().unwrap_or("").to_string(),
-            num: data["num"].as_u64().unwrap_or(1).to_string(),
-        }),
-        "ONLINE_RANK_COUNT" => Some(BiliMessage::OnlineRankCount {
-            count: json["data"]["count"].as_u64().unwrap_or(0),
-            online_count: json["data"]["online_count"].as_u64().unwrap_or(0),
-        }),
-        // Add more cases for other types as needed
-        _ => Some(BiliMessage::Raw(json)),
+/// TCP stream to `target` (`host:port`) through an HTTP proxy, opened with `CONNECT`
+fn proxy_tunnel(proxy: &str, target: &str) -> Result<TcpStream, String> {
+    let proxy_url = Url::parse(proxy).map_err(|e| format!("invalid proxy url {}: {}", proxy, e))?;
+    if proxy_url.scheme() != "http" {
+        return Err(format!(
+            "unsupported proxy scheme '{}': only http:// proxies are supported",
+            proxy_url.scheme()
+        ));
+    }
+    if !proxy_url.username().is_empty() {
+        return Err("proxy authentication is not supported".to_string());
+    }
+    let proxy_host = proxy_url
+        .host_str()
+        .ok_or_else(|| format!("proxy url has no host: {}", proxy))?;
+    let proxy_addr = format!(
+        "{}:{}",
+        proxy_host,
+        proxy_url.port_or_known_default().unwrap_or(80)
+    );
+    let mut stream = TcpStream::connect(proxy_addr.as_str())
+        .map_err(|e| format!("tcp connect to proxy {} failed: {}", proxy_addr, e))?;
+    stream
+        .write_all(format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", target).as_bytes())
+        .map_err(|e| format!("proxy request failed: {}", e))?;
+
+    // Read the response head byte by byte, leaving the tunnelled bytes unread
+    let mut head = Vec::new();
+    let mut byte = [0_u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8192 {
+            return Err("proxy response head too long".to_string());
+        }
+        match stream.read(&mut byte) {
+            Ok(0) => return Err("proxy closed the connection".to_string()),
+            Ok(_) => head.push(byte[0]),
+            Err(e) => return Err(format!("proxy response failed: {}", e)),
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("proxy refused tunnel to {}: {}", target, status));
     }
+    Ok(stream)
 }
 
 /// Enhanced init_server that can automatically detect cookies from browser
@@ -517,4 +1011,31 @@ mod tests {
         let (tx, _rx) = channel(10);
         let _client = BiliLiveClient::new(&cookies, room_id, tx);
     }
+
+    #[test]
+    fn test_proxy_tunnel() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            for status in ["200 Connection established", "403 Forbidden"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0_u8; 1024];
+                let len = stream.read(&mut request).unwrap();
+                assert!(request[..len].starts_with(b"CONNECT example.com:443 HTTP/1.1\r\n"));
+                write!(stream, "HTTP/1.1 {}\r\n\r\ntunnelled", status).unwrap();
+            }
+        });
+
+        let mut stream = proxy_tunnel(&proxy, "example.com:443").unwrap();
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "tunnelled");
+
+        let err = proxy_tunnel(&proxy, "example.com:443").unwrap_err();
+        assert!(err.contains("403"));
+        assert!(proxy_tunnel("socks5://127.0.0.1:1080", "example.com:443").is_err());
+        server.join().unwrap();
+    }
 }
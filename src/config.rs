@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -11,13 +12,157 @@ pub struct Config {
     #[serde(default)]
     pub auto_reply: Option<AutoReplyConfig>,
     #[serde(default)]
+    pub tui: Option<TuiConfig>,
+    #[serde(default)]
     pub debug: Option<bool>,
+    /// Async runtime: "current_thread", "multi_thread" or a worker count
+    #[serde(default)]
+    pub runtime: Option<String>,
+    /// Address serving Prometheus metrics and the HTTP API, e.g. "127.0.0.1:9184"
+    #[serde(default)]
+    pub metrics_addr: Option<String>,
+    /// Address serving the gRPC message stream (`grpc` feature), e.g. "127.0.0.1:50051"
+    #[serde(default)]
+    pub grpc_addr: Option<String>,
+    /// Unix socket (named pipe on Windows) serving messages as JSON lines
+    #[serde(default)]
+    pub ipc: Option<String>,
+    /// Run plugins independently on this many worker threads instead of in stages
+    #[serde(default)]
+    pub handler_workers: Option<usize>,
+    /// Seconds a plugin may take per batch before the scheduler stops waiting for it
+    #[serde(default)]
+    pub handler_timeout: Option<f64>,
+    /// Rooms (`24779526`) or streamer uids (`uid:2`) to watch at once, printing
+    /// their messages as JSON lines instead of starting the TUI
+    #[serde(default)]
+    pub watchlist: Option<Vec<String>>,
+    /// Follow the live rooms of all followed streamers, printing their messages
+    /// as JSON lines instead of starting the TUI
+    #[serde(default)]
+    pub follow_feed: Option<bool>,
+    /// Messages sent to the room on a schedule while it is live
+    #[serde(default)]
+    pub announcements: Option<Vec<AnnouncementConfig>>,
+    /// Viewer commands such as `!song`, answered in the room
+    #[serde(default)]
+    pub chat_commands: Option<ChatCommandsConfig>,
+    /// Loyalty points for watch time and chat activity
+    #[serde(default)]
+    pub loyalty: Option<LoyaltyConfig>,
+    /// SQLite history of the messages of each user
+    #[serde(default)]
+    pub chat_store: Option<ChatStoreConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TuiConfig {
+    /// Keywords whose danmaku and super chats are highlighted
+    #[serde(default)]
+    pub highlight_keywords: Vec<String>,
+    /// Ring the terminal bell when a highlighted message arrives
+    #[serde(default)]
+    pub highlight_bell: bool,
+    /// Notification per message kind: "bell", "flash" (terminal title) or "none";
+    /// the kind "keyword" applies to highlighted messages, e.g.
+    /// `notify = { super_chat = "flash", keyword = "bell" }`
+    #[serde(default)]
+    pub notify: BTreeMap<String, String>,
+    /// User names whose messages are hidden locally
+    #[serde(default)]
+    pub mute_users: Vec<String>,
+    /// User ids whose messages are hidden locally
+    #[serde(default)]
+    pub mute_uids: Vec<u64>,
+    /// Maximum number of messages kept in the message buffer
+    #[serde(default)]
+    pub buffer_size: Option<usize>,
+    /// File that also receives log records, rotated at 10 MiB
+    #[serde(default)]
+    pub log_file: Option<String>,
+    /// Key binding style: "default" or "vim"
+    #[serde(default)]
+    pub keymap: Option<String>,
+    /// Language of TUI strings: "en" or "zh-CN"; defaults to the LANG environment
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Pane arrangement and sizes
+    #[serde(default)]
+    pub layout: Option<LayoutConfig>,
+    /// Inline emote images: "auto" (detect the terminal, default), "off", "kitty", "iterm2"
+    /// or "sixel" (needs the sixel feature)
+    #[serde(default)]
+    pub images: Option<String>,
+    /// Show senders' avatars in place of the [Danmu] tag when images are on
+    #[serde(default)]
+    pub avatars: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LayoutConfig {
+    /// "vertical" (message list above the input box) or "horizontal" (stats pane on the right)
+    pub orientation: Option<String>,
+    /// Height of the input box in percent (default 10)
+    pub input_percent: Option<u16>,
+    /// Show the input box; false makes the TUI read-only (default true)
+    pub show_input: Option<bool>,
+    /// Width of the stats pane in percent, horizontal orientation only (default 30)
+    pub stats_percent: Option<u16>,
+    /// Show danmaku, gifts and events in separate panes (default false, toggled with Ctrl+S)
+    pub split: Option<bool>,
+}
+
+impl LayoutConfig {
+    /// Convert to blivedm::tui::TuiLayout, using defaults for unset fields
+    pub fn to_tui_layout(&self) -> Result<blivedm::tui::TuiLayout, String> {
+        let default = blivedm::tui::TuiLayout::default();
+        Ok(blivedm::tui::TuiLayout {
+            orientation: match &self.orientation {
+                Some(orientation) => orientation.parse()?,
+                None => default.orientation,
+            },
+            input_percent: self.input_percent.unwrap_or(default.input_percent),
+            show_input: self.show_input.unwrap_or(default.show_input),
+            stats_percent: self.stats_percent.unwrap_or(default.stats_percent),
+            split: self.split.unwrap_or(default.split),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ConnectionConfig {
     pub cookies: Option<String>,
     pub room_id: Option<String>,
+    /// User-Agent for the HTTP API calls and the websocket upgrade
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Referer replacing the live room page
+    #[serde(default)]
+    pub referer: Option<String>,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Message commands dropped before any handler, replacing the default list
+    #[serde(default)]
+    pub ignored_commands: Option<Vec<String>>,
+    /// HTTP proxy for the danmaku websocket, e.g. "http://127.0.0.1:8080"
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Seconds between polls of the follower and fan club counts; off when unset
+    #[serde(default)]
+    pub follower_poll: Option<u64>,
+    /// Saved account profile whose cookies are used when none are given
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Chromium profile directory browser cookies are read from
+    #[serde(default)]
+    pub browser_profile: Option<String>,
+    /// Longest danmaku sent in one piece, in characters; longer ones are split
+    #[serde(default)]
+    pub danmaku_max_chars: Option<usize>,
+    /// Minimum milliseconds between two sent danmaku
+    #[serde(default)]
+    pub danmaku_interval_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -38,10 +183,20 @@ pub struct TtsConfig {
     pub ali_language_type: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TriggerConfig {
+    #[serde(default)]
     pub keywords: Vec<String>,
+    /// Response template, see `blivedm::plugins::auto_reply::TriggerConfig`
     pub response: String,
+    /// Message kinds answered, danmaku when empty
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    #[serde(default)]
+    pub guard_only: bool,
+    /// Thread the response under the user's danmaku
+    #[serde(default)]
+    pub reply: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +205,8 @@ pub struct AutoReplyConfig {
     pub enabled: bool,
     #[serde(default = "default_cooldown")]
     pub cooldown_seconds: u64,
+    #[serde(default = "default_user_cooldown")]
+    pub user_cooldown_seconds: u64,
     #[serde(default)]
     pub triggers: Vec<TriggerConfig>,
 }
@@ -59,6 +216,7 @@ impl Default for AutoReplyConfig {
         Self {
             enabled: false,
             cooldown_seconds: default_cooldown(),
+            user_cooldown_seconds: default_user_cooldown(),
             triggers: vec![],
         }
     }
@@ -68,12 +226,17 @@ fn default_cooldown() -> u64 {
     5
 }
 
+fn default_user_cooldown() -> u64 {
+    30
+}
+
 impl AutoReplyConfig {
     /// Convert to blivedm::plugins::auto_reply::AutoReplyConfig
     pub fn to_plugin_config(&self) -> blivedm::plugins::auto_reply::AutoReplyConfig {
         blivedm::plugins::auto_reply::AutoReplyConfig {
             enabled: self.enabled,
             cooldown_seconds: self.cooldown_seconds,
+            user_cooldown_seconds: self.user_cooldown_seconds,
             triggers: self
                 .triggers
                 .iter()
@@ -89,10 +252,96 @@ impl TriggerConfig {
         blivedm::plugins::auto_reply::TriggerConfig {
             keywords: self.keywords.clone(),
             response: self.response.clone(),
+            kinds: self.kinds.clone(),
+            guard_only: self.guard_only,
+            reply: self.reply,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementConfig {
+    pub message: String,
+    /// Send every this many minutes of live time
+    #[serde(default)]
+    pub every_minutes: Option<u64>,
+    /// Cron schedule in local time, e.g. "0 20 * * *"
+    #[serde(default)]
+    pub cron: Option<String>,
+}
+
+impl AnnouncementConfig {
+    /// Convert to blivedm::plugins::announcer::Announcement
+    pub fn to_announcement(&self) -> Result<blivedm::plugins::announcer::Announcement, String> {
+        use blivedm::plugins::announcer::{Announcement, Schedule};
+        let schedule = match (&self.cron, self.every_minutes) {
+            (Some(cron), None) => Schedule::Cron(cron.parse()?),
+            (None, Some(minutes)) if minutes > 0 => {
+                Schedule::Every(std::time::Duration::from_secs(minutes * 60))
+            }
+            _ => {
+                return Err(format!(
+                    "announcement '{}' needs either every_minutes or cron",
+                    self.message
+                ));
+            }
+        };
+        Ok(Announcement {
+            message: self.message.clone(),
+            schedule,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ChatCommandsConfig {
+    /// Prefix of the commands, "!" by default
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Default cooldown of each command in seconds
+    #[serde(default)]
+    pub cooldown_seconds: Option<u64>,
+    #[serde(default)]
+    pub commands: Vec<ChatCommandConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCommandConfig {
+    /// Command name without the prefix
+    pub name: String,
+    /// Fixed response with the placeholders {user}, {uid} and {args}
+    #[serde(default)]
+    pub response: Option<String>,
+    /// Program whose output is the response
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Arguments of the script, before the viewer's
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cooldown_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LoyaltyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub points_per_minute: Option<i64>,
+    /// Points per danmaku, earned at most once a minute
+    #[serde(default)]
+    pub points_per_danmu: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ChatStoreConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Database file, by default `chat.sqlite` in the data directory
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
 impl Config {
     /// Load configuration from file with fallback locations
     pub fn load_from_file(config_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
@@ -178,18 +427,34 @@ impl Config {
             auto_reply: Some(AutoReplyConfig {
                 enabled: false,
                 cooldown_seconds: 5,
+                user_cooldown_seconds: 30,
                 triggers: vec![
                     TriggerConfig {
                         keywords: vec!["你好".to_string(), "hello".to_string()],
                         response: "欢迎来到直播间！".to_string(),
+                        ..Default::default()
                     },
                     TriggerConfig {
                         keywords: vec!["谢谢".to_string(), "thanks".to_string()],
                         response: "不客气～".to_string(),
+                        ..Default::default()
                     },
                 ],
             }),
+            tui: None,
             debug: None,
+            runtime: None,
+            metrics_addr: None,
+            grpc_addr: None,
+            ipc: None,
+            handler_workers: None,
+            handler_timeout: None,
+            watchlist: None,
+            follow_feed: None,
+            announcements: None,
+            chat_commands: None,
+            loyalty: None,
+            chat_store: None,
         };
 
         let toml_string = toml::to_string_pretty(&example_config)
@@ -262,6 +527,10 @@ impl Config {
         if let Some(auto_reply_config) = auto_reply {
             println!("  enabled: {}", auto_reply_config.enabled);
             println!("  cooldown_seconds: {}", auto_reply_config.cooldown_seconds);
+            println!(
+                "  user_cooldown_seconds: {}",
+                auto_reply_config.user_cooldown_seconds
+            );
             println!(
                 "  triggers: {} configured",
                 auto_reply_config.triggers.len()
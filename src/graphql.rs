@@ -0,0 +1,340 @@
+// src/graphql.rs
+//! GraphQL API over the state of the running client
+//!
+//! Queries cover the room, the session statistics, the latest messages and,
+//! with a chat store, the history of a user; the `messages` subscription is
+//! the live message feed. `routes` answers queries at `/graphql` (POST a JSON
+//! `{"query": ...}` body, or GET with `?query=`), and `stream` answers
+//! subscriptions at `/graphql/stream` as server-sent events in the
+//! graphql-sse format: one `next` event per result, then `complete`.
+//!
+//! ```ignore
+//! let schema = graphql::schema(sources);
+//! HttpServer::new()
+//!     .stream(graphql::STREAM_PATH, graphql::stream(schema.clone()))
+//!     .route(graphql::PATH, graphql::routes(schema))
+//!     .serve(addr)?;
+//! ```
+
+use async_graphql::{Context, EmptyMutation, Json, Object, SimpleObject, Subscription, Variables};
+use futures::{Stream, StreamExt, future};
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::client::bus::MessageBus;
+use crate::client::chat_store::{ChatStore, UserHistory};
+use crate::client::http_server::{Request, Response};
+use crate::client::sse::EventLog;
+use crate::client::status::{ConnectionState, ConnectionStatus};
+use crate::plugins::session_summary::SessionSummaryHandler;
+
+/// Path of queries
+pub const PATH: &str = "/graphql";
+
+/// Path of subscriptions
+pub const STREAM_PATH: &str = "/graphql/stream";
+
+/// Latest messages a `recent` query returns by default
+const RECENT_LIMIT: usize = 50;
+
+/// Where the API reads from
+#[derive(Clone)]
+pub struct GraphqlSources {
+    pub bus: MessageBus,
+    pub room_id: Arc<AtomicU64>,
+    pub room_title: Arc<Mutex<String>>,
+    pub room_streamer: Arc<Mutex<String>>,
+    pub status: Arc<Mutex<ConnectionStatus>>,
+    pub summary: Arc<SessionSummaryHandler>,
+    pub events: Arc<EventLog>,
+    pub chat_store: Option<Arc<ChatStore>>,
+}
+
+pub type BlivedmSchema = async_graphql::Schema<Query, EmptyMutation, SubscriptionRoot>;
+
+/// Schema reading from `sources`
+pub fn schema(sources: GraphqlSources) -> BlivedmSchema {
+    async_graphql::Schema::build(Query, EmptyMutation, SubscriptionRoot)
+        .data(sources)
+        .finish()
+}
+
+#[derive(SimpleObject)]
+pub struct Room {
+    pub room_id: u64,
+    /// Empty until known
+    pub title: String,
+    /// Empty until known
+    pub streamer: String,
+    pub connected: bool,
+    pub uptime_seconds: f64,
+    /// Round-trip time of the latest answered heartbeat
+    pub heartbeat_rtt_ms: Option<f64>,
+}
+
+/// Revenue and activity of the session, see `SessionSummary`
+#[derive(SimpleObject)]
+pub struct Stats {
+    pub duration_seconds: u64,
+    /// Gold coins
+    pub gift_value: u64,
+    /// Gold coins
+    pub guard_value: u64,
+    /// CNY
+    pub super_chat_total: u64,
+    pub super_chat_count: u64,
+    pub new_guards: u64,
+    pub unique_chatters: usize,
+    pub danmu_count: u64,
+    pub peak_messages_per_minute: u64,
+    /// Gifts, guards and super chats in CNY
+    pub total_rmb: f64,
+}
+
+#[derive(SimpleObject)]
+pub struct Message {
+    /// Event id, as in the `/events` stream; null for live messages
+    pub id: Option<u64>,
+    /// Message kind, as `BiliMessage::kind`
+    #[graphql(name = "type")]
+    pub kind: String,
+    /// Fields of the message, as in the JSON lines output
+    pub data: Json<serde_json::Value>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn room(&self, ctx: &Context<'_>) -> Room {
+        let sources = ctx.data_unchecked::<GraphqlSources>();
+        let status = sources.status.lock().unwrap().clone();
+        Room {
+            room_id: sources.room_id.load(Ordering::Relaxed),
+            title: sources.room_title.lock().unwrap().clone(),
+            streamer: sources.room_streamer.lock().unwrap().clone(),
+            connected: status.state == ConnectionState::Connected,
+            uptime_seconds: status.uptime().as_secs_f64(),
+            heartbeat_rtt_ms: status.heartbeat_rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+        }
+    }
+
+    async fn stats(&self, ctx: &Context<'_>) -> Stats {
+        let summary = ctx.data_unchecked::<GraphqlSources>().summary.summary();
+        Stats {
+            duration_seconds: summary.duration.as_secs(),
+            gift_value: summary.gift_value,
+            guard_value: summary.guard_value,
+            super_chat_total: summary.super_chat_total,
+            super_chat_count: summary.super_chat_count,
+            new_guards: summary.new_guards,
+            unique_chatters: summary.unique_chatters,
+            danmu_count: summary.danmu_count,
+            peak_messages_per_minute: summary.peak_messages_per_minute,
+            total_rmb: summary.total_rmb(),
+        }
+    }
+
+    /// Latest buffered messages, oldest first, optionally only some kinds
+    async fn recent(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<usize>,
+        types: Option<Vec<String>>,
+    ) -> Vec<Message> {
+        let events = ctx.data_unchecked::<GraphqlSources>().events.since(0);
+        let mut messages: Vec<Message> = events
+            .into_iter()
+            .rev()
+            .filter(|event| wanted(&types, event.kind))
+            .take(limit.unwrap_or(RECENT_LIMIT))
+            .map(|event| Message {
+                id: Some(event.id),
+                kind: event.kind.to_string(),
+                data: Json(serde_json::from_str(&event.data).unwrap_or_default()),
+            })
+            .collect();
+        messages.reverse();
+        messages
+    }
+
+    /// Stored history of a uid or user name, null without a chat store
+    async fn user(
+        &self,
+        ctx: &Context<'_>,
+        user: String,
+        limit: Option<usize>,
+    ) -> async_graphql::Result<Option<Json<UserHistory>>> {
+        let Some(store) = &ctx.data_unchecked::<GraphqlSources>().chat_store else {
+            return Ok(None);
+        };
+        let history = store.history(&user.parse()?, limit.unwrap_or(RECENT_LIMIT))?;
+        Ok(Some(Json(history)))
+    }
+}
+
+fn wanted(types: &Option<Vec<String>>, kind: &str) -> bool {
+    types
+        .as_ref()
+        .is_none_or(|types| types.iter().any(|t| t == kind))
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Messages received from now on, optionally only some kinds
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        types: Option<Vec<String>>,
+    ) -> impl Stream<Item = Message> + use<> {
+        ctx.data_unchecked::<GraphqlSources>()
+            .bus
+            .stream("graphql")
+            .filter(move |msg| future::ready(wanted(&types, msg.kind())))
+            .map(|msg| Message {
+                id: None,
+                kind: msg.kind().to_string(),
+                data: Json(msg.to_json()),
+            })
+    }
+}
+
+/// The GraphQL request in a POST body or the query string
+fn graphql_request(request: &Request) -> Result<async_graphql::Request, String> {
+    if request.method == "POST" {
+        return serde_json::from_str(&request.body)
+            .map_err(|e| format!("invalid GraphQL request: {}", e));
+    }
+    let query = request.query_param("query").ok_or("missing query")?;
+    let mut graphql = async_graphql::Request::new(query);
+    if let Some(variables) = request.query_param("variables") {
+        let variables =
+            serde_json::from_str(&variables).map_err(|e| format!("invalid variables: {}", e))?;
+        graphql = graphql.variables(Variables::from_json(variables));
+    }
+    if let Some(name) = request.query_param("operationName") {
+        graphql = graphql.operation_name(name);
+    }
+    Ok(graphql)
+}
+
+/// Route answering queries at `PATH`
+pub fn routes(
+    schema: BlivedmSchema,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static {
+    move |request| {
+        if request.path != PATH {
+            return None;
+        }
+        Some(match graphql_request(request) {
+            Ok(graphql) => Response::json(&futures::executor::block_on(schema.execute(graphql))),
+            Err(e) => Response::error(400, &e),
+        })
+    }
+}
+
+/// Stream route answering subscriptions (and queries) at `STREAM_PATH`
+pub fn stream(schema: BlivedmSchema) -> impl Fn(&Request, TcpStream) + Send + Sync + 'static {
+    move |request, mut stream| {
+        let graphql = match graphql_request(request) {
+            Ok(graphql) => graphql,
+            Err(e) => {
+                let body = serde_json::json!({ "error": e }).to_string();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                return;
+            }
+        };
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        for response in futures::executor::block_on_stream(schema.execute_stream(graphql)) {
+            let data = serde_json::to_string(&response).unwrap_or_default();
+            if write!(stream, "event: next\ndata: {}\n\n", data).is_err() {
+                return;
+            }
+        }
+        let _ = stream.write_all(b"event: complete\ndata:\n\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::models::BiliMessage;
+    use crate::client::scheduler::{EventContext, EventHandler};
+
+    fn danmu(text: &str) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: "viewer".to_string(),
+            text: text.to_string(),
+            uid: 2,
+            guard_level: 0,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_graphql_queries_and_subscription() {
+        let summary = Arc::new(SessionSummaryHandler::new());
+        summary.handle(&danmu("hi"), &EventContext::new(None, 1));
+        let events = Arc::new(EventLog::default());
+        events.push(&danmu("first"));
+        events.push(&BiliMessage::OnlineRankCount {
+            count: 1,
+            online_count: 2,
+        });
+        let bus = MessageBus::default();
+        let schema = schema(GraphqlSources {
+            bus: bus.clone(),
+            room_id: Arc::new(AtomicU64::new(1)),
+            room_title: Arc::new(Mutex::new("title".to_string())),
+            room_streamer: Arc::default(),
+            status: Arc::new(Mutex::new(ConnectionStatus::new())),
+            summary,
+            events,
+            chat_store: None,
+        });
+
+        let routes = routes(schema.clone());
+        let post = |query: &str| {
+            let body = serde_json::json!({ "query": query }).to_string();
+            let request = Request::parse(&format!("POST /graphql HTTP/1.1\r\n\r\n{}", body));
+            let response = routes(&request.unwrap()).unwrap();
+            serde_json::from_str::<serde_json::Value>(&response.body).unwrap()
+        };
+        let result = post(
+            "{ room { roomId title connected } stats { danmuCount } recent(types: [\"danmu\"]) { id type data } user(user: \"2\") }",
+        );
+        let data = &result["data"];
+        assert_eq!(data["room"]["title"], "title");
+        assert_eq!(data["room"]["connected"], false);
+        assert_eq!(data["stats"]["danmuCount"], 1);
+        assert_eq!(data["recent"][0]["id"], 1);
+        assert_eq!(data["recent"][0]["data"]["text"], "first");
+        assert_eq!(data["recent"].as_array().unwrap().len(), 1);
+        assert!(data["user"].is_null());
+
+        let mut feed =
+            schema.execute_stream("subscription { messages(types: [\"danmu\"]) { type data } }");
+        let next = futures::executor::block_on(async {
+            // Wait for the subscription to subscribe to the bus
+            let next = feed.next();
+            futures::pin_mut!(next);
+            assert!(futures::poll!(&mut next).is_pending());
+            bus.publish(vec![danmu("live")]);
+            next.await
+        });
+        let data = next.unwrap().data.into_json().unwrap();
+        assert_eq!(data["messages"]["data"]["text"], "live");
+    }
+}
@@ -2,16 +2,34 @@
 //! Bilibili live room danmaku WebSocket client library with TTS and plugin support
 
 pub mod client;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "cli")]
 pub mod plugins;
+#[cfg(feature = "cli")]
 pub mod tui;
 
 // Re-export commonly used items from client
-pub use client::{auth, get_cookies_or_browser, models, scheduler, websocket};
+#[cfg(feature = "accounts")]
+pub use client::accounts;
 #[cfg(feature = "browser_cookies")]
 pub use client::browser_cookies;
+#[cfg(feature = "chat_store")]
+pub use client::chat_store;
+#[cfg(feature = "grpc")]
+pub use client::grpc;
+#[cfg(feature = "client")]
+pub use client::{
+    auth, bus, danmaku, follow_feed, followers, get_cookies_or_browser, gift_catalog, health,
+    http_server, images, ipc, json_rpc, live_room, login, metrics, moderation, open_live,
+    room_management, runtime, scheduler, send_queue, sse, systemd, tls, watchlist, websocket,
+};
+pub use client::{extension, history, models, payloads, protocol, session, status};
 
 // Re-export plugin modules and helpers
+#[cfg(feature = "cli")]
 pub use plugins::{
-    auto_reply, auto_reply_handler, terminal_display, terminal_display_handler, tts, tts_handler,
-    tts_handler_command, tts_handler_default,
+    announcer, auto_reply, auto_reply_handler, chat_commands, keyword_alert, loyalty,
+    session_summary, stats, stats_export, terminal_display, terminal_display_handler, tts,
+    tts_handler, tts_handler_command, tts_handler_default,
 };
@@ -3,25 +3,61 @@
 
 mod config;
 
+use blivedm::client::accounts::{self, Account, AccountStore};
+use blivedm::client::auth::{self, RequestHeaders};
+use blivedm::client::bus::{self, MessageBus};
+use blivedm::client::chat_store::{self, ChatStore, ChatStoreHandler};
 use blivedm::client::get_cookies_or_browser;
-use blivedm::client::scheduler::{EventContext, Scheduler};
+use blivedm::client::health::{self, HealthProbe};
+use blivedm::client::http_server::HttpServer;
+use blivedm::client::images::EmoteCatalog;
+use blivedm::client::live_room::{Credentials, LiveRoom};
+use blivedm::client::login::{self, GeetestResult};
+use blivedm::client::metrics;
+use blivedm::client::payloads::PayloadLog;
+use blivedm::client::runtime::{AsyncRuntime, RuntimeFlavor};
+use blivedm::client::scheduler::{
+    EventContext, ExecutionMode, HandlerId, HandlerTimeout, MAX_BATCH_SIZE, PausePolicy, Scheduler,
+};
+use blivedm::client::send_queue::{SendLimits, SendQueue};
+use blivedm::client::sse::{self, EventLog};
+use blivedm::client::status::{ConnectionControl, ConnectionRequest};
+use blivedm::client::watchlist::{RoomMessage, WatchTarget, Watchlist};
 use blivedm::client::websocket::BiliLiveClient;
+use blivedm::client::{
+    follow_feed, followers, ipc, json_rpc, moderation, room_management, systemd,
+};
+use blivedm::plugins::announcer::AnnouncerHandler;
+use blivedm::plugins::auto_reply::AutoReplyHandler;
+use blivedm::plugins::chat_commands::{self, ChatCommandHandler};
+use blivedm::plugins::keyword_alert::{AlertRules, KeywordAlertHandler, KeywordMatcher};
+use blivedm::plugins::loyalty::{self, LoyaltyHandler, PointRates, PointsLedger};
+use blivedm::plugins::session_summary::{self, SessionSummaryHandler};
+use blivedm::plugins::stats::{self, StatsHandler, ViewerStats};
+use blivedm::plugins::stats_export::{self, BucketSize, StatsExportHandler};
 use blivedm::plugins::terminal_display::TerminalDisplayHandler;
 use blivedm::plugins::tts::TtsHandler;
-use blivedm::tui::{TuiApp, TuiLogger, run_tui};
+use blivedm::tui::buffer::DEFAULT_CAPACITY;
+use blivedm::tui::i18n;
+use blivedm::tui::{
+    CommandRegistry, Graphics, GraphicsProtocol, Keymap, Locale, MessageBuffer, MessageRate,
+    MuteList, SuperChatBoard, TuiApp, TuiLogger, run_tui,
+};
 use clap::{CommandFactory, Parser};
 use clap_complete::{Shell, generate};
-use config::Config;
+use config::{ChatStoreConfig, Config};
 use futures::channel::mpsc;
 use futures::stream::StreamExt;
-use std::collections::VecDeque;
 use std::env;
+use std::io::Write;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use tokio::runtime::Runtime;
+
+/// Messages held back by `/pause` until `/resume`
+const PAUSE_BUFFER_SIZE: usize = 4096;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -37,6 +73,26 @@ struct Args {
     #[arg(long, value_name = "COOKIES")]
     cookies: Option<String>,
 
+    /// Use the cookies of this saved account profile
+    #[arg(long, value_name = "NAME")]
+    account: Option<String>,
+
+    /// Save the cookies in use (or those of --sms-login) as this account profile
+    #[arg(long, value_name = "NAME")]
+    save_account: Option<String>,
+
+    /// List the saved account profiles and exit
+    #[arg(long)]
+    list_accounts: bool,
+
+    /// Chromium profile directory to read browser cookies from (default: Default)
+    #[arg(long, value_name = "PROFILE")]
+    browser_profile: Option<String>,
+
+    /// List the Chromium profiles of the installed browsers and exit
+    #[arg(long)]
+    list_browser_profiles: bool,
+
     /// Room ID to connect to
     #[arg(long, value_name = "ROOM_ID")]
     room_id: Option<String>,
@@ -101,6 +157,89 @@ struct Args {
     #[arg(long)]
     auto_reply: bool,
 
+    /// Async runtime: "current_thread", "multi_thread" (default) or a worker count
+    #[arg(long, value_name = "RUNTIME")]
+    runtime: Option<RuntimeFlavor>,
+
+    /// Serve Prometheus metrics (connection state, heartbeat RTT) and the HTTP API on this address
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<String>,
+
+    /// Serve the gRPC message stream (proto/blivedm.proto) on this address
+    #[arg(long, value_name = "ADDR")]
+    grpc_addr: Option<String>,
+
+    /// Write messages as JSON lines to local processes connecting to this Unix
+    /// socket (or named pipe, e.g. \\.\pipe\blivedm, on Windows)
+    #[arg(long, value_name = "PATH")]
+    ipc: Option<String>,
+
+    /// Run plugins independently on this many worker threads instead of in stages
+    #[arg(long, value_name = "N")]
+    handler_workers: Option<usize>,
+
+    /// Stop waiting for a plugin after this many seconds per batch, disabling
+    /// it after 3 timeouts
+    #[arg(long, value_name = "SECS")]
+    handler_timeout: Option<f64>,
+
+    /// Poll the streamer's follower and fan club counts every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    follower_poll: Option<u64>,
+
+    /// Watch these comma-separated rooms (or `uid:<UID>` streamers) at once,
+    /// printing their messages as JSON lines instead of starting the TUI
+    #[arg(long, value_name = "ROOMS", value_delimiter = ',')]
+    watchlist: Vec<String>,
+
+    /// Follow every live room of the streamers the logged-in account follows,
+    /// printing their messages as JSON lines instead of starting the TUI
+    #[arg(long)]
+    follow_feed: bool,
+
+    /// Speak JSON-RPC over stdin/stdout (subscribe, send_danmaku, stats)
+    /// instead of starting the TUI, for hosts embedding the client
+    #[arg(long)]
+    json_rpc: bool,
+
+    /// Comma-separated keywords to highlight in the message list
+    #[arg(long, value_name = "KEYWORDS")]
+    highlight: Option<String>,
+
+    /// Ring the terminal bell when a highlighted message arrives
+    #[arg(long)]
+    highlight_bell: bool,
+
+    /// Comma-separated notifications per message kind, e.g.
+    /// "super_chat=flash,guard_buy=bell,keyword=bell" (bell, flash or none)
+    #[arg(long, value_name = "RULES")]
+    notify: Option<String>,
+
+    /// Maximum number of messages kept in the TUI message buffer (default 1000)
+    #[arg(long, value_name = "COUNT")]
+    buffer_size: Option<usize>,
+
+    /// Also write logs to this file (rotated to <PATH>.1 at 10 MiB)
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Write the session summary to this file on exit instead of printing it
+    #[arg(long, value_name = "PATH")]
+    summary_file: Option<PathBuf>,
+
+    /// Write per-minute (or --stats-bucket hour) message, chatter and gift
+    /// aggregates to this .csv or .json file on exit
+    #[arg(long, value_name = "PATH")]
+    stats_export: Option<PathBuf>,
+
+    /// Bucket size of --stats-export: minute (default) or hour
+    #[arg(long, value_name = "SIZE")]
+    stats_bucket: Option<BucketSize>,
+
+    /// Log in with an SMS code sent to this phone number and print the cookies
+    #[arg(long, value_name = "PHONE")]
+    sms_login: Option<String>,
+
     /// Generate shell completion script (bash, zsh, fish, powershell, elvish)
     #[arg(long, value_name = "SHELL")]
     generate_completion: Option<Shell>,
@@ -125,13 +264,55 @@ fn main() {
         }
     };
 
+    if let Some(phone) = &args.sms_login {
+        if let Err(e) = run_sms_login(phone, args.save_account.as_deref()) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.list_accounts {
+        if let Err(e) = list_accounts() {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.list_browser_profiles {
+        list_browser_profiles();
+        return;
+    }
+    let browser_profile = args.browser_profile.clone().or_else(|| {
+        config
+            .connection
+            .as_ref()
+            .and_then(|connection| connection.browser_profile.clone())
+    });
+    if let Some(profile) = &browser_profile {
+        use_browser_profile(profile);
+    }
+
     // Initialize logging with precedence: CLI args > env vars > config file
     let debug_enabled =
         args.debug || env::var("DEBUG").unwrap_or_default() == "1" || config.debug.unwrap_or(false);
 
     // Load cookies and room_id with precedence: CLI args > env vars > config file > defaults
+    let account = args.account.clone().or_else(|| {
+        config
+            .connection
+            .as_ref()
+            .and_then(|connection| connection.account.clone())
+    });
+    let account_cookies = account.map(|name| match account_cookies(&name) {
+        Ok(cookies) => cookies,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    });
     let cookies = args
         .cookies
+        .or(account_cookies)
         .or_else(|| {
             env::var("Cookie")
                 .ok()
@@ -152,6 +333,16 @@ fn main() {
         cookies
     };
 
+    if let Some(name) = &args.save_account {
+        match &cookies {
+            Some(cookies) => match save_account(name, cookies) {
+                Ok(path) => eprintln!("Saved account '{}' to {}", name, path.display()),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+            None => eprintln!("No cookies to save as account '{}'", name),
+        }
+    }
+
     let room_id = args
         .room_id
         .or_else(|| env::var("ROOM_ID").ok())
@@ -227,12 +418,16 @@ fn main() {
             Some(config::AutoReplyConfig {
                 enabled: auto_reply_config.enabled,
                 cooldown_seconds: auto_reply_config.cooldown_seconds,
+                user_cooldown_seconds: auto_reply_config.user_cooldown_seconds,
                 triggers: auto_reply_config
                     .triggers
                     .iter()
                     .map(|t| config::TriggerConfig {
                         keywords: t.keywords.clone(),
                         response: t.response.clone(),
+                        kinds: t.kinds.clone(),
+                        guard_only: t.guard_only,
+                        reply: t.reply,
                     })
                     .collect(),
             })
@@ -262,6 +457,49 @@ fn main() {
         std::process::exit(0);
     }
 
+    // One runtime for the message loop, commands and the auto reply plugin
+    let runtime_flavor = match args.runtime {
+        Some(flavor) => flavor,
+        None => match config.runtime.as_deref().map(str::parse) {
+            Some(Ok(flavor)) => flavor,
+            Some(Err(e)) => {
+                eprintln!("Error in configuration: {}", e);
+                std::process::exit(1);
+            }
+            None => RuntimeFlavor::default(),
+        },
+    };
+
+    let watchlist = if args.watchlist.is_empty() {
+        config.watchlist.clone().unwrap_or_default()
+    } else {
+        args.watchlist.clone()
+    };
+    if !watchlist.is_empty() {
+        run_watchlist(&watchlist, cookies, debug_enabled);
+        return;
+    }
+    if args.follow_feed || config.follow_feed.unwrap_or(false) {
+        run_follow_feed(cookies, debug_enabled);
+        return;
+    }
+    if args.json_rpc {
+        run_json_rpc(&room_id, cookies, runtime_flavor, debug_enabled);
+        return;
+    }
+
+    let tui_config = config.tui.clone().unwrap_or_default();
+    let highlight_keywords = match &args.highlight {
+        Some(keywords) => keywords.split(',').map(|s| s.to_string()).collect(),
+        None => tui_config.highlight_keywords,
+    };
+    let highlight = KeywordMatcher::new(highlight_keywords);
+    let highlight_bell = args.highlight_bell || tui_config.highlight_bell;
+    let mute_list = Arc::new(Mutex::new(MuteList::new(
+        tui_config.mute_users,
+        tui_config.mute_uids,
+    )));
+
     // Initialize TuiLogger to capture logs into a shared buffer for the TUI logs panel.
     // When debug is enabled, capture Debug level; otherwise capture Info level.
     let log_level = if debug_enabled {
@@ -269,11 +507,51 @@ fn main() {
     } else {
         log::LevelFilter::Info
     };
-    let log_buffer = TuiLogger::init(log_level);
+    let log_file = args
+        .log_file
+        .clone()
+        .or_else(|| tui_config.log_file.clone().map(PathBuf::from));
+    let log_buffer = match &log_file {
+        Some(path) => TuiLogger::init_with_log_file(log_level, path).unwrap_or_else(|e| {
+            let buffer = TuiLogger::init(log_level);
+            log::warn!("Failed to open log file {}: {}", path.display(), e);
+            buffer
+        }),
+        None => TuiLogger::init(log_level),
+    };
 
     // Create client with automatic browser cookie detection
     let (tx, mut rx) = mpsc::channel(64);
-    let mut client = match BiliLiveClient::new_auto(cookies.as_deref(), &room_id, tx) {
+    let room_info_tx = tx.clone();
+    let follower_tx = tx.clone();
+    let payload_log = Arc::new(PayloadLog::default());
+    let mut builder = BiliLiveClient::builder(&room_id).payload_log(Arc::clone(&payload_log));
+    if let Some(cookies) = &cookies {
+        builder = builder.cookies(cookies);
+    }
+    if let Some(proxy) = config
+        .connection
+        .as_ref()
+        .and_then(|connection| connection.proxy.as_ref())
+    {
+        builder = builder.proxy(proxy);
+    }
+    // Custom user agent and headers for API calls and the websocket upgrade
+    if let Some(connection) = &config.connection {
+        let mut request_headers = RequestHeaders {
+            user_agent: connection.user_agent.clone(),
+            referer: connection.referer.clone(),
+            ..Default::default()
+        };
+        for (name, value) in &connection.headers {
+            request_headers = request_headers.with_header(name, value);
+        }
+        match request_headers.header_map() {
+            Ok(_) => builder = builder.request_headers(request_headers),
+            Err(e) => log::warn!("Ignoring configured request headers: {}", e),
+        }
+    }
+    let mut client = match builder.build(tx) {
         Ok(client) => {
             log::info!("Successfully created client with automatic cookie detection");
             client
@@ -286,8 +564,35 @@ fn main() {
             std::process::exit(1);
         }
     };
+    let command_filter = client.command_filter();
+    let request_headers = client.request_headers();
+    if let Some(commands) = config
+        .connection
+        .as_ref()
+        .and_then(|connection| connection.ignored_commands.as_ref())
+    {
+        command_filter.set_ignored(commands);
+    }
     client.send_auth();
     client.send_heart_beat();
+    let connection_status = client.connection_status();
+
+    // Per-user chat history, shared across rooms
+    let chat_store = config
+        .chat_store
+        .as_ref()
+        .filter(|chat_store| chat_store.enabled)
+        .and_then(
+            |chat_store_config| match open_chat_store(chat_store_config) {
+                Ok(store) => Some(Arc::new(store)),
+                Err(e) => {
+                    eprintln!("Chat history disabled: {}", e);
+                    None
+                }
+            },
+        );
+    let connection_control = client.connection_control();
+    let heartbeat_interval = client.heartbeat_interval();
     let shared_client: Arc<Mutex<BiliLiveClient>> = Arc::new(Mutex::new(client));
     let heart_beats: Arc<Mutex<BiliLiveClient>> = Arc::clone(&shared_client);
 
@@ -302,7 +607,7 @@ fn main() {
                     break;
                 }
             }
-            thread::sleep(Duration::new(20, 0));
+            thread::sleep(heartbeat_interval);
         }
     });
 
@@ -342,21 +647,99 @@ fn main() {
     }
 
     // Create shared message buffer for TUI
-    let message_buffer: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
+    let buffer_size = args
+        .buffer_size
+        .or(tui_config.buffer_size)
+        .unwrap_or(DEFAULT_CAPACITY);
+    let message_buffer = Arc::new(Mutex::new(MessageBuffer::with_capacity(buffer_size)));
 
     // Create shared online count for TUI title display
     let online_count: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+    let message_rate = Arc::new(Mutex::new(MessageRate::new()));
+    let super_chats = Arc::new(Mutex::new(SuperChatBoard::new()));
+    let online_rank = Arc::new(Mutex::new(Vec::new()));
+    let room_title = Arc::new(Mutex::new(String::new()));
+    let room_streamer = Arc::new(Mutex::new(String::new()));
 
-    let context = EventContext::new(cookies.clone(), room_id.parse::<u64>().unwrap_or(0));
-    let mut scheduler = Scheduler::new(context);
-    let terminal_handler = Arc::new(TerminalDisplayHandler::with_online_count(
-        Arc::clone(&message_buffer),
-        Arc::clone(&online_count),
-    ));
+    let rt = match AsyncRuntime::new(runtime_flavor) {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let context = EventContext::new(cookies.clone(), room_id.parse::<u64>().unwrap_or(0))
+        .with_request_headers(request_headers);
+    let mut scheduler = Scheduler::new(context.clone());
+    if let Some(workers) = args.handler_workers.or(config.handler_workers) {
+        scheduler.set_execution_mode(ExecutionMode::Pooled { workers });
+    }
+    if let Some(secs) = args.handler_timeout.or(config.handler_timeout) {
+        match Duration::try_from_secs_f64(secs) {
+            Ok(limit) => {
+                scheduler.set_default_timeout(Some(HandlerTimeout::new(limit).disable_after(3)))
+            }
+            Err(e) => log::warn!("Ignoring handler timeout {}: {}", secs, e),
+        }
+    }
+    let terminal_handler = Arc::new(
+        TerminalDisplayHandler::with_online_count(
+            Arc::clone(&message_buffer),
+            Arc::clone(&online_count),
+        )
+        .with_message_rate(Arc::clone(&message_rate))
+        .with_mute_list(Arc::clone(&mute_list))
+        .with_super_chats(Arc::clone(&super_chats))
+        .with_online_rank(Arc::clone(&online_rank))
+        .with_room_title(Arc::clone(&room_title))
+        .with_room_streamer(Arc::clone(&room_streamer))
+        .with_payload_log(payload_log),
+    );
     scheduler.add_sequential_handler(terminal_handler);
+    let summary_handler = Arc::new(SessionSummaryHandler::new());
+    scheduler.add_sequential_handler(summary_handler.clone());
+    let stats_export = Arc::new(StatsExportHandler::new());
+    scheduler.add_sequential_handler(stats_export.clone());
+    // Viewers in the chat store are returning viewers, not first-time chatters
+    let known_users = match chat_store.as_ref().map(|store| store.known_users()) {
+        Some(Ok(users)) => users,
+        Some(Err(e)) => {
+            log::warn!("Failed to load known viewers: {}", e);
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    let viewer_stats = Arc::new(Mutex::new(ViewerStats::with_known_users(known_users)));
+    scheduler.add_sequential_handler(Arc::new(StatsHandler::new(Arc::clone(&viewer_stats))));
+    if let Some(store) = &chat_store {
+        scheduler.add_sequential_handler(Arc::new(ChatStoreHandler::new(Arc::clone(store))));
+    }
+    let mut alert_rules = AlertRules::default();
+    if highlight_bell && !highlight.is_empty() {
+        alert_rules = AlertRules::keyword_bell();
+    }
+    match AlertRules::from_entries(&tui_config.notify) {
+        Ok(rules) => alert_rules.merge(rules),
+        Err(e) => log::warn!("Ignoring tui.notify: {}", e),
+    }
+    if let Some(list) = &args.notify {
+        match AlertRules::parse_list(list) {
+            Ok(rules) => alert_rules.merge(rules),
+            Err(e) => log::warn!("Ignoring --notify: {}", e),
+        }
+    }
+    let mut alerts = None;
+    if !alert_rules.is_empty() {
+        let handler = KeywordAlertHandler::with_rules(highlight.clone(), alert_rules);
+        alerts = Some(handler.alerts());
+        scheduler.add_sequential_handler(Arc::new(handler));
+    }
+    // Kept even when added so `/tts off` and `/tts on` can remove and re-add it
+    let mut tts_handler = None;
     if let Some(server_url) = tts_server {
         // REST API TTS configuration
-        let tts_handler = Arc::new(TtsHandler::new_rest_api_with_volume(
+        tts_handler = Some(Arc::new(TtsHandler::new_rest_api_with_volume(
             server_url,
             tts_voice,
             tts_backend,
@@ -364,21 +747,19 @@ fn main() {
             tts_format,
             tts_sample_rate,
             tts_volume,
-        ));
-        scheduler.add_sequential_handler(tts_handler);
+        )));
         println!("TTS configured with REST API server");
     } else if let Some(api_key) = ali_api_key {
         // Alibaba DashScope TTS configuration
         let model = ali_model.unwrap_or_else(|| "qwen3-tts-flash".to_string());
         let voice = ali_voice.unwrap_or_else(|| "Cherry".to_string());
-        let tts_handler = Arc::new(TtsHandler::new_ali_tts(
+        tts_handler = Some(Arc::new(TtsHandler::new_ali_tts(
             api_key,
             model.clone(),
             voice.clone(),
             ali_language_type,
             tts_volume,
-        ));
-        scheduler.add_sequential_handler(tts_handler);
+        )));
         println!(
             "TTS configured with Alibaba DashScope (model: {}, voice: {})",
             model, voice
@@ -388,18 +769,40 @@ fn main() {
         let cmd_args = tts_args
             .map(|s| s.split(',').map(|s| s.to_string()).collect())
             .unwrap_or_default();
-        let tts_handler = Arc::new(TtsHandler::new_command(tts_cmd, cmd_args));
-        scheduler.add_sequential_handler(tts_handler);
+        tts_handler = Some(Arc::new(TtsHandler::new_command(tts_cmd, cmd_args)));
         println!("TTS configured with local command");
     } else {
         println!(
             "No TTS configuration provided. Use --ali-api-key, --tts-server, or --tts-command to enable TTS."
         );
     }
+    let tts = tts_handler.map(|handler| {
+        let id = scheduler.add_sequential_handler(handler.clone());
+        (handler, Some(id))
+    });
+
+    // Sent messages are split to the length limit and paced for the rate limiter;
+    // the TUI and the auto reply plugin share the queue
+    let current_room = Arc::new(AtomicU64::new(room_id.parse::<u64>().unwrap_or(0)));
+    let mut send_limits = SendLimits::default();
+    if let Some(connection) = &config.connection {
+        if let Some(max_chars) = connection.danmaku_max_chars {
+            send_limits.max_chars = max_chars;
+        }
+        if let Some(interval) = connection.danmaku_interval_ms {
+            send_limits.min_interval = Duration::from_millis(interval);
+        }
+    }
+    let send_queue =
+        SendQueue::following_room(context.clone(), Arc::clone(&current_room), send_limits, &rt);
 
     // Add auto reply plugin if enabled
     if auto_reply_config.enabled {
-        let auto_reply_handler = blivedm::plugins::auto_reply_handler(auto_reply_config);
+        let auto_reply_handler = Arc::new(AutoReplyHandler::with_queue(
+            auto_reply_config,
+            send_queue.clone(),
+            rt.clone(),
+        ));
         scheduler.add_sequential_handler(auto_reply_handler);
         println!("Auto reply plugin enabled");
     } else {
@@ -408,6 +811,91 @@ fn main() {
         );
     }
 
+    // Scheduled announcements, paused while the room is offline
+    let announcements: Vec<_> = config
+        .announcements
+        .iter()
+        .flatten()
+        .filter_map(|announcement| {
+            announcement
+                .to_announcement()
+                .map_err(|e| log::warn!("Ignoring announcement: {}", e))
+                .ok()
+        })
+        .collect();
+    if !announcements.is_empty() {
+        let count = announcements.len();
+        scheduler.add_sequential_handler(Arc::new(AnnouncerHandler::new(
+            announcements,
+            send_queue.clone(),
+            &rt,
+        )));
+        println!("{} scheduled announcement(s) enabled", count);
+    }
+
+    // Viewer !commands, answered through the send queue
+    let mut commands = ChatCommandHandler::new(send_queue.clone(), rt.clone());
+    if let Some(commands_config) = &config.chat_commands {
+        if let Some(prefix) = &commands_config.prefix {
+            commands = commands.with_prefix(prefix);
+        }
+        let default_cooldown = commands_config
+            .cooldown_seconds
+            .map_or(chat_commands::DEFAULT_COOLDOWN, Duration::from_secs);
+        for command in &commands_config.commands {
+            let cooldown = command
+                .cooldown_seconds
+                .map_or(default_cooldown, Duration::from_secs);
+            match (&command.response, &command.script) {
+                (Some(response), None) => {
+                    commands.register_response(&command.name, cooldown, response)
+                }
+                (None, Some(script)) => {
+                    commands.register_script(&command.name, cooldown, script, command.args.clone())
+                }
+                _ => log::warn!(
+                    "Ignoring chat command {}: set either response or script",
+                    command.name
+                ),
+            }
+        }
+    }
+
+    // Loyalty points, kept per room in the data directory
+    let loyalty = config
+        .loyalty
+        .as_ref()
+        .filter(|loyalty| loyalty.enabled)
+        .and_then(|loyalty_config| {
+            let mut rates = PointRates::default();
+            if let Some(points) = loyalty_config.points_per_minute {
+                rates.per_minute = points;
+            }
+            if let Some(points) = loyalty_config.points_per_danmu {
+                rates.per_danmu = points;
+            }
+            match points_ledger(&room_id) {
+                Ok(ledger) => Some(Arc::new(LoyaltyHandler::new(ledger, rates))),
+                Err(e) => {
+                    eprintln!("Loyalty points disabled: {}", e);
+                    None
+                }
+            }
+        });
+    if let Some(loyalty) = &loyalty {
+        scheduler.add_sequential_handler(loyalty.clone());
+        loyalty::register_chat_command(Arc::clone(loyalty), &mut commands);
+        println!("Loyalty points enabled");
+    }
+
+    if commands.commands().next().is_some() {
+        println!(
+            "Chat commands enabled: {}",
+            commands.commands().collect::<Vec<_>>().join(", ")
+        );
+        scheduler.add_sequential_handler(Arc::new(commands));
+    }
+
     // Add initial system message to buffer
     TuiApp::add_message(&message_buffer, format!("[System] Bilibili Danmu Client"));
     TuiApp::add_message(
@@ -429,12 +917,99 @@ fn main() {
         );
     }
 
-    // create a thread to process the rx channel messages using tokio runtime and pass to scheduler
-    let rt = Arc::new(Runtime::new().unwrap());
-    let rt_clone = Arc::clone(&rt);
+    // Check the login in the background, so rotten cookies are not mistaken for a guest session
+    if let Some(cookies) = cookies.clone() {
+        let buffer = Arc::clone(&message_buffer);
+        thread::spawn(move || report_login(&cookies, &buffer));
+    }
+
+    // Publish the rx channel messages on the bus; the scheduler is one of its subscribers
+    let scheduler = Arc::new(Mutex::new(scheduler));
+    let message_bus = MessageBus::default();
+    // Staged handlers block until every stage is done, so keep them off the runtime
+    let scheduler_feed = message_bus.subscribe("scheduler");
+    let dispatched = Arc::clone(&scheduler);
+    if let Err(e) = thread::Builder::new()
+        .name("blivedm-dispatch".to_string())
+        .spawn(move || bus::dispatch(scheduler_feed, dispatched))
+    {
+        eprintln!("Error: failed to start the dispatch thread: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(addr) = args.metrics_addr.as_ref().or(config.metrics_addr.as_ref()) {
+        let events = Arc::new(EventLog::default());
+        rt.spawn(sse::record(
+            message_bus.subscribe("sse"),
+            Arc::clone(&events),
+        ));
+        let probe = Arc::new(HealthProbe::new(
+            Arc::clone(&connection_status),
+            message_bus.clone(),
+        ));
+        rt.spawn(health::record(
+            message_bus.subscribe("health"),
+            Arc::clone(&probe),
+        ));
+        let mut server = HttpServer::new()
+            .stream(sse::PATH, sse::route(Arc::clone(&events)))
+            .route("/", health::routes(probe));
+        #[cfg(feature = "graphql")]
+        {
+            let schema = blivedm::graphql::schema(blivedm::graphql::GraphqlSources {
+                bus: message_bus.clone(),
+                room_id: Arc::clone(&current_room),
+                room_title: Arc::clone(&room_title),
+                room_streamer: Arc::clone(&room_streamer),
+                status: Arc::clone(&connection_status),
+                summary: Arc::clone(&summary_handler),
+                events,
+                chat_store: chat_store.clone(),
+            });
+            server = server
+                .stream(
+                    blivedm::graphql::STREAM_PATH,
+                    blivedm::graphql::stream(schema.clone()),
+                )
+                .route(blivedm::graphql::PATH, blivedm::graphql::routes(schema));
+        }
+        if cookies.is_some() {
+            let room = Arc::clone(&current_room);
+            let context = context.clone();
+            server = server.route(
+                room_management::API_PREFIX,
+                room_management::routes(rt.clone(), move || EventContext {
+                    room_id: room.load(Ordering::Relaxed),
+                    ..context.clone()
+                }),
+            );
+        }
+        server = server.route(stats::API_PATH, stats::routes(Arc::clone(&viewer_stats)));
+        if let Some(store) = &chat_store {
+            server = server.route(
+                chat_store::API_PREFIX,
+                chat_store::routes(Arc::clone(store)),
+            );
+        }
+        if let Err(e) = metrics::serve_with(addr, Arc::clone(&connection_status), server) {
+            log::warn!("{}", e);
+        }
+    }
+    if let Some(addr) = args.grpc_addr.as_ref().or(config.grpc_addr.as_ref()) {
+        serve_grpc(&rt, addr, &message_bus, Arc::clone(&current_room));
+    }
+    if let Some(path) = args.ipc.as_ref().or(config.ipc.as_ref()) {
+        rt.spawn(ipc::serve(path.clone(), message_bus.clone()));
+    }
     rt.spawn(async move {
         while let Some(msg) = rx.next().await {
-            scheduler.trigger(msg);
+            // Messages of one aggregated packet arrive together; publish them as a batch
+            let mut batch = vec![msg];
+            while batch.len() < MAX_BATCH_SIZE
+                && let Ok(msg) = rx.try_recv()
+            {
+                batch.push(msg);
+            }
+            message_bus.publish(batch);
         }
     });
 
@@ -445,22 +1020,103 @@ fn main() {
         Arc::clone(&online_count),
     );
     tui_app.set_log_buffer(log_buffer);
+    tui_app.set_message_rate(message_rate);
+    tui_app.set_super_chats(super_chats);
+    tui_app.set_online_rank(online_rank);
+    tui_app.set_room_title(room_title);
+    tui_app.set_room_streamer(room_streamer);
+    tui_app.set_viewer_stats(viewer_stats);
+    if let Some(store) = chat_store {
+        tui_app.set_chat_store(store);
+    }
+    tui_app.set_connection_status(connection_status);
+    tui_app.set_highlight(highlight);
+    tui_app.set_mute_list(mute_list);
+    if let Some(alerts) = alerts {
+        tui_app.set_alerts(alerts);
+    }
+    if let Some(keymap) = &tui_config.keymap {
+        match keymap.parse::<Keymap>() {
+            Ok(keymap) => tui_app.set_keymap(keymap),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+    if let Some(layout) = &tui_config.layout {
+        match layout.to_tui_layout() {
+            Ok(layout) => tui_app.set_layout(layout),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+    let mut emotes = None;
+    match GraphicsProtocol::from_setting(tui_config.images.as_deref().unwrap_or("auto")) {
+        Ok(Some(protocol)) => {
+            let catalog = Arc::new(Mutex::new(EmoteCatalog::default()));
+            fetch_emotes(Arc::clone(&catalog), &current_room, cookies.clone());
+            tui_app.set_graphics(Graphics::new(
+                protocol,
+                Arc::clone(&catalog),
+                tui_config.avatars,
+            ));
+            emotes = Some(catalog);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+    match Locale::detect(tui_config.locale.as_deref()) {
+        Ok(locale) => i18n::set_locale(locale),
+        Err(e) => log::warn!("{}", e),
+    }
 
-    let context_for_chat = EventContext::new(cookies.clone(), room_id.parse::<u64>().unwrap_or(0));
-    let message_buffer_for_feedback = Arc::clone(&message_buffer);
+    tui_app.set_current_room(Arc::clone(&current_room));
+    // ROOM_CHANGE only covers changes made while connected; refresh to know the current title
+    if let Err(e) = room_management::watch_room_info(
+        Arc::clone(&current_room),
+        room_management::ROOM_INFO_INTERVAL,
+        room_info_tx,
+    ) {
+        log::warn!("{}", e);
+    }
+    let follower_poll = args.follower_poll.or(config
+        .connection
+        .as_ref()
+        .and_then(|connection| connection.follower_poll));
+    if let Some(secs) = follower_poll
+        && let Err(e) = followers::watch_followers(
+            Arc::clone(&current_room),
+            Duration::from_secs(secs.max(1)),
+            follower_tx,
+        )
+    {
+        log::warn!("{}", e);
+    }
+    let command_env = CommandEnv {
+        rt: rt.clone(),
+        buffer: Arc::clone(&message_buffer),
+        context: context.clone(),
+        current_room,
+        emotes,
+    };
+    register_commands(
+        &mut tui_app.commands,
+        command_env.clone(),
+        Arc::clone(&shared_client),
+        connection_control,
+        Arc::clone(&scheduler),
+        tts,
+    );
+    session_summary::register_commands(Arc::clone(&summary_handler), &mut tui_app.commands);
+    stats_export::register_commands(Arc::clone(&stats_export), &mut tui_app.commands);
+    if let Some(loyalty) = &loyalty {
+        loyalty::register_commands(Arc::clone(loyalty), &mut tui_app.commands);
+    }
 
-    // Run TUI with message sending callback
+    // Run TUI with message sending callback; slash commands are handled by the TUI
     let tui_result = run_tui(tui_app, move |message| {
-        let context_clone = context_for_chat.clone();
-        let rt_for_send = Arc::clone(&rt_clone);
-        let buffer_clone = Arc::clone(&message_buffer_for_feedback);
-
-        rt_for_send.spawn(async move {
-            if let Err(e) = blivedm::plugins::send_danmaku_message(&message, &context_clone).await {
-                TuiApp::add_message(
-                    &buffer_clone,
-                    format!("[System] Error sending message: {}", e),
-                );
+        let sent = send_queue.send(&message);
+        let buffer = Arc::clone(&command_env.buffer);
+        command_env.rt.spawn(async move {
+            if let Err(e) = sent.await {
+                TuiApp::add_message(&buffer, format!("[System] Error sending message: {}", e));
             }
         });
     });
@@ -469,6 +1125,35 @@ fn main() {
         eprintln!("TUI error: {}", e);
     }
 
+    if let Some(loyalty) = &loyalty
+        && let Err(e) = loyalty.save()
+    {
+        eprintln!("Failed to save loyalty points: {}", e);
+    }
+
+    let summary = summary_handler.summary();
+    match &args.summary_file {
+        Some(path) => match std::fs::write(path, format!("{}\n", summary)) {
+            Ok(()) => println!("Session summary written to {:?}", path),
+            Err(e) => {
+                eprintln!("Failed to write session summary to {:?}: {}", path, e);
+                println!("{}", summary);
+            }
+        },
+        None => println!("{}", summary),
+    }
+    if let Some(path) = &args.stats_export {
+        match stats_export.export(path, args.stats_bucket.unwrap_or_default()) {
+            Ok(count) => println!("Exported {} stats buckets to {:?}", count, path),
+            Err(e) => eprintln!("Failed to export stats: {}", e),
+        }
+    }
+
+    log::info!(
+        "Suppressed {} messages of ignored commands",
+        command_filter.suppressed()
+    );
+
     // close the client
     match shared_client.lock() {
         Ok(mut _client) => {}
@@ -479,3 +1164,653 @@ fn main() {
     // wait for the threads to finish
     thread::sleep(Duration::new(1, 0));
 }
+
+/// Print the messages of all rooms of `watchlist` to stdout as JSON lines,
+/// tagged with their room id, until the connections end
+fn run_watchlist(watchlist: &[String], cookies: Option<String>, debug: bool) {
+    init_headless_logging(debug);
+    let targets = match watchlist
+        .iter()
+        .map(|target| target.parse::<WatchTarget>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("Error in watchlist: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let credentials = match cookies {
+        Some(cookies) => Credentials::Cookies(cookies),
+        None => Credentials::Guest,
+    };
+    let watchlist = match Watchlist::open(&targets, credentials) {
+        Ok(watchlist) => watchlist,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    supervise_with_systemd(
+        watchlist
+            .room_ids()
+            .into_iter()
+            .filter_map(|room_id| Some((room_id, watchlist.room(room_id)?.client())))
+            .collect(),
+    );
+    print_json_lines(watchlist.messages());
+}
+
+/// Follow the live rooms of the streamers the logged-in account follows,
+/// printing their messages to stdout as JSON lines
+fn run_follow_feed(cookies: Option<String>, debug: bool) {
+    init_headless_logging(debug);
+    let Some(cookies) = cookies else {
+        eprintln!("Error: the follow feed needs a login; pass --cookies or log in with a browser");
+        std::process::exit(1);
+    };
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    if let Err(e) = follow_feed::watch_follow_feed(cookies, follow_feed::FOLLOW_FEED_INTERVAL, tx) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    print_json_lines(rx);
+}
+
+/// Connect to `room_id` and answer JSON-RPC requests from stdin until it is
+/// closed
+fn run_json_rpc(room_id: &str, cookies: Option<String>, flavor: RuntimeFlavor, debug: bool) {
+    init_headless_logging(debug);
+    let credentials = match cookies {
+        Some(cookies) => Credentials::Cookies(cookies),
+        None => Credentials::Guest,
+    };
+    let result = AsyncRuntime::new(flavor).and_then(|rt| {
+        let room_id = room_id
+            .parse()
+            .map_err(|_| format!("invalid room id '{}'", room_id))?;
+        let room = LiveRoom::open(room_id, credentials)?;
+        supervise_with_systemd(vec![(room_id, room.client())]);
+        json_rpc::serve_stdio(room, rt)
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Under a systemd `Type=notify` unit, report the connected `rooms` as ready
+/// and keep systemd informed about their connections
+fn supervise_with_systemd(rooms: Vec<(u64, Arc<Mutex<BiliLiveClient>>)>) {
+    if let Some(notifier) = systemd::Notifier::from_env()
+        && let Err(e) = systemd::supervise(notifier, rooms)
+    {
+        log::warn!("{}", e);
+    }
+}
+
+/// Log the account of `cookies` and post it as a `[System]` message, warning
+/// when the cookies are invalid or about to expire
+fn report_login(cookies: &str, buffer: &Arc<Mutex<MessageBuffer>>) {
+    let warn = |text: String| {
+        log::warn!("{}", text);
+        TuiApp::add_message(buffer, format!("[System] {}", text));
+    };
+    match auth::check_login(cookies) {
+        Ok(Some(status)) => {
+            let text = format!("Logged in as {} (uid {})", status.username, status.uid);
+            log::info!("{}", text);
+            TuiApp::add_message(buffer, format!("[System] {}", text));
+            match status.expires_in() {
+                Some(left) if left.is_zero() => {
+                    warn("Cookies have expired; log in again".to_string())
+                }
+                Some(left) if left < login::EXPIRY_WARNING => warn(format!(
+                    "Cookies expire in {} hours; log in again soon",
+                    left.as_secs() / 3600
+                )),
+                _ => {}
+            }
+        }
+        Ok(None) => warn(
+            "Cookies are invalid or expired; the room is joined anonymously and sending fails"
+                .to_string(),
+        ),
+        Err(e) => warn(format!("Could not verify cookies: {}", e)),
+    }
+}
+
+/// Walk through an SMS login on the terminal and print the session cookies
+fn run_sms_login(phone: &str, save_as: Option<&str>) -> Result<(), String> {
+    let prompt = |question: &str| -> Result<String, String> {
+        eprint!("{}: ", question);
+        let mut answer = String::new();
+        std::io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| format!("failed to read answer: {}", e))?;
+        Ok(answer.trim().to_string())
+    };
+
+    let mut login = auth::sms_login(phone);
+    let captcha = login.captcha()?;
+    eprintln!("Solve this geetest captcha in a browser (e.g. a geetest validator page):");
+    eprintln!("  gt:        {}", captcha.gt);
+    eprintln!("  challenge: {}", captcha.challenge);
+    let validate = prompt("validate")?;
+    login.send_code(&captcha, &GeetestResult::new(&validate))?;
+    let code = prompt(&format!("Code sent to {}", phone))?;
+    let cookies = login.verify(&code)?;
+    if let Some(name) = save_as {
+        let path = save_account(name, &cookies)?;
+        eprintln!(
+            "Logged in; saved as account '{}' in {}, use it with --account {}",
+            name,
+            path.display(),
+            name
+        );
+        return Ok(());
+    }
+    eprintln!("Logged in; pass these cookies with --cookies or put them in the config:");
+    println!("{}", cookies);
+    Ok(())
+}
+
+/// Store of the account profiles, next to the default configuration file
+/// Chat store at the configured path, or in the data directory
+fn open_chat_store(config: &ChatStoreConfig) -> Result<ChatStore, String> {
+    let path = match &config.path {
+        Some(path) => PathBuf::from(path),
+        None => dirs::data_dir()
+            .ok_or("Unable to determine data directory")?
+            .join("blivedm_rs")
+            .join(chat_store::CHAT_STORE_FILE),
+    };
+    ChatStore::open(path)
+}
+
+/// Loyalty points of `room_id`, in the data directory
+fn points_ledger(room_id: &str) -> Result<PointsLedger, String> {
+    let dir = dirs::data_dir()
+        .ok_or("Unable to determine data directory")?
+        .join("blivedm_rs");
+    PointsLedger::load(dir.join(format!("points-{}.json", room_id)))
+}
+
+fn account_store() -> Result<AccountStore, String> {
+    let dir = dirs::config_dir()
+        .ok_or("Unable to determine config directory")?
+        .join("blivedm_rs");
+    AccountStore::load(dir.join(accounts::ACCOUNTS_FILE))
+}
+
+/// Cookies of the saved account `name`
+fn account_cookies(name: &str) -> Result<String, String> {
+    let store = account_store()?;
+    match store.get(name) {
+        Some(account) => Ok(account.cookies.clone()),
+        None => Err(format!(
+            "no account named '{}' in {}; save one with --save-account",
+            name,
+            store.path().display()
+        )),
+    }
+}
+
+/// Save `cookies` as the account `name`, with the username if the login
+/// check succeeds, returning the store path
+fn save_account(name: &str, cookies: &str) -> Result<PathBuf, String> {
+    let mut store = account_store()?;
+    let mut account = Account::new(cookies);
+    if let Ok(Some(status)) = auth::check_login(cookies) {
+        account.username = Some(status.username);
+        account.uid = Some(status.uid);
+    }
+    store.insert(name, account);
+    store.save()?;
+    Ok(store.path().to_path_buf())
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(rt: &AsyncRuntime, addr: &str, bus: &MessageBus, room_id: Arc<AtomicU64>) {
+    match addr.parse() {
+        Ok(addr) => {
+            rt.spawn(blivedm::grpc::serve(addr, bus.clone(), room_id));
+        }
+        Err(e) => eprintln!("Invalid gRPC address {}: {}", addr, e),
+    }
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_rt: &AsyncRuntime, _addr: &str, _bus: &MessageBus, _room_id: Arc<AtomicU64>) {
+    eprintln!("Ignoring the gRPC address: built without the grpc feature");
+}
+
+#[cfg(feature = "browser_cookies")]
+fn use_browser_profile(profile: &str) {
+    blivedm::browser_cookies::set_chromium_profile(Some(profile));
+}
+
+#[cfg(not(feature = "browser_cookies"))]
+fn use_browser_profile(_profile: &str) {
+    eprintln!("Ignoring the browser profile: built without the browser_cookies feature");
+}
+
+#[cfg(feature = "browser_cookies")]
+fn list_browser_profiles() {
+    use blivedm::browser_cookies::Browser;
+
+    for browser in Browser::get_all_supported() {
+        let profiles = browser.profiles();
+        if !profiles.is_empty() {
+            println!("{:?}: {}", browser, profiles.join(", "));
+        }
+    }
+}
+
+#[cfg(not(feature = "browser_cookies"))]
+fn list_browser_profiles() {
+    eprintln!("Built without the browser_cookies feature");
+}
+
+fn list_accounts() -> Result<(), String> {
+    let store = account_store()?;
+    if store.accounts().next().is_none() {
+        println!("No saved accounts in {}", store.path().display());
+    }
+    for (name, account) in store.accounts() {
+        match (&account.username, account.uid) {
+            (Some(username), Some(uid)) => println!("{}\t{} (uid {})", name, username, uid),
+            _ => println!("{}", name),
+        }
+    }
+    Ok(())
+}
+
+/// Log to stderr, leaving stdout to the JSON lines of the headless modes
+fn init_headless_logging(debug: bool) {
+    let level = if debug {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    let _ = env_logger::builder().filter_level(level).try_init();
+}
+
+/// Write every message of `messages` to stdout as a JSON line until the
+/// stream ends or stdout is closed
+fn print_json_lines(mut messages: impl futures::Stream<Item = RoomMessage> + Unpin) {
+    futures::executor::block_on(async {
+        let mut stdout = std::io::stdout().lock();
+        while let Some(msg) = messages.next().await {
+            if writeln!(stdout, "{}", msg.to_json()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Shared state used by the commands registered in main
+#[derive(Clone)]
+struct CommandEnv {
+    rt: AsyncRuntime,
+    buffer: Arc<Mutex<MessageBuffer>>,
+    /// Cookies and request headers; the room is the one in `current_room`
+    context: EventContext,
+    /// Room currently connected, updated by `/room`
+    current_room: Arc<AtomicU64>,
+    /// Emotes shown as images, refetched by `/room`
+    emotes: Option<Arc<Mutex<EmoteCatalog>>>,
+}
+
+impl CommandEnv {
+    fn context(&self) -> EventContext {
+        EventContext {
+            room_id: self.current_room.load(Ordering::Relaxed),
+            ..self.context.clone()
+        }
+    }
+
+    fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = String> + Send + 'static,
+    {
+        spawn_command(&self.rt, Arc::clone(&self.buffer), task);
+    }
+}
+
+/// Register the moderation, room management, `/room` and connection commands
+fn register_commands(
+    registry: &mut CommandRegistry,
+    env: CommandEnv,
+    client: Arc<Mutex<BiliLiveClient>>,
+    control: Arc<ConnectionControl>,
+    scheduler: Arc<Mutex<Scheduler>>,
+    mut tts: Option<(Arc<TtsHandler>, Option<HandlerId>)>,
+) {
+    let reconnect_control = Arc::clone(&control);
+    registry.register(
+        "/reconnect",
+        "/reconnect",
+        "Reconnect now, skipping any backoff",
+        move |_| {
+            reconnect_control.request(ConnectionRequest::Reconnect);
+            Ok(vec!["Reconnecting...".to_string()])
+        },
+    );
+    registry.register(
+        "/disconnect",
+        "/disconnect",
+        "Close the connection and stop reconnecting",
+        move |_| {
+            control.request(ConnectionRequest::Disconnect);
+            Ok(vec!["Disconnecting...".to_string()])
+        },
+    );
+
+    let ban_env = env.clone();
+    registry.register(
+        "/ban",
+        "/ban <uid> [hours]",
+        "Block user, or silence for hours",
+        move |args| {
+            let (uid, hours) = parse_ban_args(args.raw())?;
+            let context = ban_env.context();
+            ban_env.spawn(async move {
+                let result = match hours {
+                    Some(hours) => moderation::silence_user(uid, hours, &context).await,
+                    None => moderation::block_user(uid, &context).await,
+                };
+                match (result, hours) {
+                    (Ok(()), Some(hours)) => format!("Silenced uid {} for {} hour(s)", uid, hours),
+                    (Ok(()), None) => format!("Blocked uid {}", uid),
+                    (Err(e), _) => format!("Error banning uid {}: {}", uid, e),
+                }
+            });
+            Ok(Vec::new())
+        },
+    );
+
+    let title_env = env.clone();
+    registry.register(
+        "/title",
+        "/title <text>",
+        "Update room title",
+        move |args| {
+            if args.is_empty() {
+                return Err("Usage: /title <new title>".to_string());
+            }
+            let title = args.raw().to_string();
+            let context = title_env.context();
+            title_env.spawn(async move {
+                match room_management::update_title(&title, &context).await {
+                    Ok(()) => format!("Room title updated to '{}'", title),
+                    Err(e) => format!("Error updating title: {}", e),
+                }
+            });
+            Ok(Vec::new())
+        },
+    );
+
+    let area_env = env.clone();
+    registry.register("/area", "/area <id>", "Change live area", move |args| {
+        let area_id: u32 = args.parse(0, "Usage: /area <area_id>")?;
+        let context = area_env.context();
+        area_env.spawn(async move {
+            match room_management::update_area(area_id, &context).await {
+                Ok(()) => format!("Room area changed to {}", area_id),
+                Err(e) => format!("Error changing area: {}", e),
+            }
+        });
+        Ok(Vec::new())
+    });
+
+    let live_env = env.clone();
+    // Stream key of the last `/live start`, kept out of the message buffer
+    let stream_key: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+    registry.register(
+        "/live",
+        "/live start <area> | stop | key",
+        "Start or stop the live, or copy the stream key",
+        move |args| {
+            let context = live_env.context();
+            match parse_live_args(args.raw())? {
+                LiveCommand::Start(area_id) => {
+                    let stream_key = Arc::clone(&stream_key);
+                    live_env.spawn(async move {
+                        match room_management::start_live(area_id, &context).await {
+                            Ok(rtmp) => {
+                                let masked = rtmp.masked_code();
+                                *stream_key.lock().unwrap() = Some(rtmp.code);
+                                format!(
+                                    "Live started (RTMP server: {}, stream key: {}; /live key copies it)",
+                                    rtmp.addr, masked
+                                )
+                            }
+                            Err(e) => format!("Error starting live: {}", e),
+                        }
+                    })
+                }
+                LiveCommand::Stop => live_env.spawn(async move {
+                    match room_management::stop_live(&context).await {
+                        Ok(()) => "Live stopped".to_string(),
+                        Err(e) => format!("Error stopping live: {}", e),
+                    }
+                }),
+                LiveCommand::CopyKey => {
+                    let key = stream_key.lock().unwrap().clone();
+                    let key = key.ok_or("No stream key yet; start the live with /live start")?;
+                    arboard::Clipboard::new()
+                        .and_then(|mut clipboard| clipboard.set_text(key))
+                        .map_err(|e| format!("Error copying the stream key: {}", e))?;
+                    return Ok(vec!["Stream key copied to the clipboard".to_string()]);
+                }
+            }
+            Ok(Vec::new())
+        },
+    );
+
+    let pause_scheduler = Arc::clone(&scheduler);
+    registry.register(
+        "/pause",
+        "/pause [drop]",
+        "Hold incoming messages, or drop them",
+        move |args| {
+            let (policy, feedback) = match args.raw() {
+                "" => (
+                    PausePolicy::Buffer {
+                        capacity: PAUSE_BUFFER_SIZE,
+                    },
+                    "Paused; messages are held until /resume",
+                ),
+                "drop" => (
+                    PausePolicy::Drop,
+                    "Paused; messages are dropped until /resume",
+                ),
+                _ => return Err("Usage: /pause [drop]".to_string()),
+            };
+            let scheduler = pause_scheduler.lock().map_err(|e| e.to_string())?;
+            scheduler.pause(policy);
+            Ok(vec![feedback.to_string()])
+        },
+    );
+    let resume_scheduler = Arc::clone(&scheduler);
+    let resume_buffer = Arc::clone(&env.buffer);
+    registry.register(
+        "/resume",
+        "/resume",
+        "Dispatch held messages and carry on",
+        move |_| {
+            let scheduler = Arc::clone(&resume_scheduler);
+            let buffer = Arc::clone(&resume_buffer);
+            // Dispatching the held messages waits for the handlers, so run it off the TUI thread
+            thread::spawn(move || {
+                let feedback = match scheduler.lock() {
+                    Ok(scheduler) if scheduler.is_paused() => {
+                        format!("Resumed ({} messages dropped)", scheduler.resume())
+                    }
+                    Ok(_) => "Not paused".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                TuiApp::add_message(&buffer, format!("[System] {}", feedback));
+            });
+            Ok(Vec::new())
+        },
+    );
+
+    let handlers_scheduler = Arc::clone(&scheduler);
+    registry.register(
+        "/handlers",
+        "/handlers",
+        "List the active plugins",
+        move |_| {
+            let scheduler = handlers_scheduler.lock().map_err(|e| e.to_string())?;
+            Ok(scheduler
+                .handlers()
+                .into_iter()
+                .map(|(id, name)| format!("{}: {}", id, name))
+                .collect())
+        },
+    );
+
+    let tts_scheduler = Arc::clone(&scheduler);
+    registry.register(
+        "/tts",
+        "/tts on|off",
+        "Enable or disable text-to-speech",
+        move |args| {
+            let Some((handler, id)) = tts.as_mut() else {
+                return Err(
+                    "No TTS configured; use --ali-api-key, --tts-server or --tts-command"
+                        .to_string(),
+                );
+            };
+            let mut scheduler = tts_scheduler.lock().map_err(|e| e.to_string())?;
+            match (args.raw(), *id) {
+                ("on", None) => {
+                    *id = Some(scheduler.add_sequential_handler(handler.clone()));
+                    Ok(vec!["TTS enabled".to_string()])
+                }
+                ("off", Some(current)) => {
+                    scheduler.remove_handler(current);
+                    *id = None;
+                    Ok(vec!["TTS disabled".to_string()])
+                }
+                ("on", Some(_)) => Err("TTS is already enabled".to_string()),
+                ("off", None) => Err("TTS is already disabled".to_string()),
+                _ => Err("Usage: /tts on|off".to_string()),
+            }
+        },
+    );
+
+    registry.register(
+        "/room",
+        "/room <id>",
+        "Switch to another live room",
+        move |args| {
+            let room: u64 = args.parse(0, "Usage: /room <room_id>")?;
+            let env = env.clone();
+            let client = Arc::clone(&client);
+            let scheduler = Arc::clone(&scheduler);
+            // Reconnecting blocks, so run it off the TUI thread
+            thread::spawn(move || {
+                let result = client
+                    .lock()
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut client| client.switch_room(&room.to_string()));
+                let feedback = match result {
+                    Ok(()) => {
+                        env.current_room.store(room, Ordering::Relaxed);
+                        if let Ok(mut scheduler) = scheduler.lock() {
+                            scheduler.set_context(env.context());
+                        }
+                        if let Some(emotes) = &env.emotes {
+                            fetch_emotes(
+                                Arc::clone(emotes),
+                                &env.current_room,
+                                env.context.cookies.clone(),
+                            );
+                        }
+                        format!("Switched to room {}", room)
+                    }
+                    Err(e) => format!("Error switching to room {}: {}", room, e),
+                };
+                TuiApp::add_message(&env.buffer, format!("[System] {}", feedback));
+            });
+            Ok(vec![format!("Switching to room {}...", room)])
+        },
+    );
+}
+
+/// Replace `catalog` with the emotes of the current room on a background
+/// thread, unless the room changes again before they arrive
+fn fetch_emotes(
+    catalog: Arc<Mutex<EmoteCatalog>>,
+    current_room: &Arc<AtomicU64>,
+    cookies: Option<String>,
+) {
+    let current_room = Arc::clone(current_room);
+    let room = current_room.load(Ordering::Relaxed);
+    thread::spawn(
+        move || match EmoteCatalog::fetch(room, cookies.as_deref()) {
+            Ok(fetched) if current_room.load(Ordering::Relaxed) == room => {
+                *catalog.lock().unwrap() = fetched
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Emotes unavailable: {}", e),
+        },
+    );
+}
+
+/// Run a TUI command on the runtime and report its outcome as a `[System]` message
+fn spawn_command<F>(rt: &AsyncRuntime, buffer: Arc<Mutex<MessageBuffer>>, task: F)
+where
+    F: Future<Output = String> + Send + 'static,
+{
+    rt.spawn(async move {
+        let feedback = task.await;
+        TuiApp::add_message(&buffer, format!("[System] {}", feedback));
+    });
+}
+
+/// Parse `/ban <uid> [hours]` arguments; no hours means a permanent block
+fn parse_ban_args(args: &str) -> Result<(u64, Option<u32>), String> {
+    const USAGE: &str = "Usage: /ban <uid> [hours]";
+    let mut parts = args.split_whitespace();
+    let uid = parts
+        .next()
+        .and_then(|uid| uid.parse::<u64>().ok())
+        .ok_or(USAGE)?;
+    let hours = match parts.next() {
+        Some(hours) => Some(hours.parse::<u32>().map_err(|_| USAGE)?),
+        None => None,
+    };
+    if parts.next().is_some() {
+        return Err(USAGE.to_string());
+    }
+    Ok((uid, hours))
+}
+
+/// Subcommand of `/live`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LiveCommand {
+    Start(u32),
+    Stop,
+    /// Copy the stream key of the last start to the clipboard
+    CopyKey,
+}
+
+/// Parse `/live start <area_id>`, `/live stop` or `/live key`
+fn parse_live_args(args: &str) -> Result<LiveCommand, String> {
+    const USAGE: &str = "Usage: /live start <area_id> | /live stop | /live key";
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        ["start", area_id] => area_id
+            .parse::<u32>()
+            .map(LiveCommand::Start)
+            .map_err(|_| USAGE.to_string()),
+        ["stop"] => Ok(LiveCommand::Stop),
+        ["key"] => Ok(LiveCommand::CopyKey),
+        _ => Err(USAGE.to_string()),
+    }
+}
@@ -0,0 +1,347 @@
+use crate::client::models::BiliMessage;
+use crate::client::runtime::AsyncRuntime;
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::client::send_queue::SendQueue;
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// How often the announcer checks whether an announcement is due
+const TICK: Duration = Duration::from_secs(5);
+
+/// Cron-like schedule of five fields, `minute hour day-of-month month
+/// day-of-week`, each `*`, a number, a range `a-b` or a list of them, with an
+/// optional step (`*/15`, `9-17/2`). Sunday is 0 or 7; like cron, a day
+/// matches either day field when both are restricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day-of-month or day-of-week field is `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Bitmask of the values of one cron field within `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("invalid cron field '{}'", field);
+    let mut mask = 0_u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("cron schedule '{}' needs five fields", s));
+        };
+        let mut weekday_mask = parse_field(weekdays, 0, 7)?;
+        // Sunday may be written as 7
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_mask,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl Cron {
+    /// Whether the schedule fires in the minute of `time`
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+        bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.months, time.month())
+            && day_matches
+    }
+}
+
+/// When an announcement is sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every interval of live time, first one interval after going live
+    Every(Duration),
+    /// At the minutes of a cron schedule in local time, when live then
+    Cron(Cron),
+}
+
+/// Message sent to the room on a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub message: String,
+    pub schedule: Schedule,
+}
+
+/// Announcements and when each was last due
+#[derive(Debug)]
+struct Announcer {
+    announcements: Vec<Announcement>,
+    /// Next send of each `Every` announcement, `None` while offline
+    next_due: Vec<Option<Instant>>,
+    /// Last minute each `Cron` announcement was sent in
+    last_fired: Vec<Option<NaiveDateTime>>,
+}
+
+impl Announcer {
+    fn new(announcements: Vec<Announcement>) -> Self {
+        let count = announcements.len();
+        Self {
+            announcements,
+            next_due: vec![None; count],
+            last_fired: vec![None; count],
+        }
+    }
+
+    /// Messages due at `now` (local time `local`); nothing is sent while
+    /// offline, and intervals start over when the stream goes live again
+    fn due(&mut self, now: Instant, local: NaiveDateTime, live: bool) -> Vec<String> {
+        let mut due = Vec::new();
+        let minute = local.with_second(0).and_then(|t| t.with_nanosecond(0));
+        for (i, announcement) in self.announcements.iter().enumerate() {
+            match &announcement.schedule {
+                Schedule::Every(interval) => {
+                    if !live {
+                        self.next_due[i] = None;
+                        continue;
+                    }
+                    let next = self.next_due[i].get_or_insert(now + *interval);
+                    if now >= *next {
+                        *next = now + *interval;
+                        due.push(announcement.message.clone());
+                    }
+                }
+                Schedule::Cron(cron) => {
+                    if live && cron.matches(&local) && self.last_fired[i] != minute {
+                        self.last_fired[i] = minute;
+                        due.push(announcement.message.clone());
+                    }
+                }
+            }
+        }
+        due
+    }
+}
+
+/// A plugin sending announcements to the room on a schedule while it is live.
+/// The room counts as offline until a room info or live message says
+/// otherwise.
+pub struct AnnouncerHandler {
+    live: Arc<AtomicBool>,
+    announcer: Arc<Mutex<Announcer>>,
+}
+
+impl AnnouncerHandler {
+    /// Start sending `announcements` through `queue`; the timer runs on
+    /// `runtime` until the handler is dropped
+    pub fn new(announcements: Vec<Announcement>, queue: SendQueue, runtime: &AsyncRuntime) -> Self {
+        let handler = Self {
+            live: Arc::new(AtomicBool::new(false)),
+            announcer: Arc::new(Mutex::new(Announcer::new(announcements))),
+        };
+        runtime.spawn(run(
+            Arc::downgrade(&handler.announcer),
+            Arc::clone(&handler.live),
+            queue,
+        ));
+        handler
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+}
+
+async fn run(announcer: Weak<Mutex<Announcer>>, live: Arc<AtomicBool>, queue: SendQueue) {
+    let mut ticks = tokio::time::interval(TICK);
+    loop {
+        ticks.tick().await;
+        let Some(announcer) = announcer.upgrade() else {
+            return;
+        };
+        let due = announcer.lock().unwrap().due(
+            Instant::now(),
+            Local::now().naive_local(),
+            live.load(Ordering::Relaxed),
+        );
+        for message in due {
+            let sent = queue.send(&message);
+            tokio::spawn(async move {
+                if let Err(e) = sent.await {
+                    log::warn!("Failed to send announcement: {}", e);
+                }
+            });
+        }
+    }
+}
+
+impl EventHandler for AnnouncerHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["room_info", "stream_started", "stream_ended", "raw"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, context: &EventContext) {
+        let live = match msg {
+            BiliMessage::RoomInfo { live, .. } => *live,
+            BiliMessage::StreamStarted { room_id, .. } if *room_id == context.room_id => true,
+            BiliMessage::StreamEnded { room_id, .. } if *room_id == context.room_id => false,
+            BiliMessage::Raw(json) => match json["cmd"].as_str() {
+                Some("LIVE") => true,
+                Some("PREPARING") | Some("ROUND") => false,
+                _ => return,
+            },
+            _ => return,
+        };
+        if self.live.swap(live, Ordering::Relaxed) != live {
+            log::info!(
+                "Announcements {}",
+                if live {
+                    "resumed"
+                } else {
+                    "paused, room offline"
+                }
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        // 2024-06-02 is a Sunday
+        NaiveDate::from_ymd_opt(2024, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron() {
+        let cron: Cron = "*/15 20-23 * * *".parse().unwrap();
+        assert!(cron.matches(&at(3, 20, 45, 0)));
+        assert!(!cron.matches(&at(3, 20, 46, 0)));
+        assert!(!cron.matches(&at(3, 19, 0, 0)));
+
+        let weekend: Cron = "0 12 * * 6,7".parse().unwrap();
+        assert!(weekend.matches(&at(1, 12, 0, 0)));
+        assert!(weekend.matches(&at(2, 12, 0, 0)));
+        assert!(!weekend.matches(&at(3, 12, 0, 0)));
+        // Either day field matches when both are restricted
+        let either: Cron = "0 0 3 * 0".parse().unwrap();
+        assert!(either.matches(&at(2, 0, 0, 0)));
+        assert!(either.matches(&at(3, 0, 0, 0)));
+        assert!(!either.matches(&at(4, 0, 0, 0)));
+
+        assert!("* * * *".parse::<Cron>().is_err());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn test_announcer_pauses_while_offline() {
+        let cron = "30 20 * * *".parse().unwrap();
+        let mut announcer = Announcer::new(vec![
+            Announcement {
+                message: "every".to_string(),
+                schedule: Schedule::Every(Duration::from_secs(600)),
+            },
+            Announcement {
+                message: "cron".to_string(),
+                schedule: Schedule::Cron(cron),
+            },
+        ]);
+        let start = Instant::now();
+        let minutes = |n: u64| start + Duration::from_secs(60 * n);
+
+        assert!(announcer.due(minutes(0), at(3, 20, 0, 0), true).is_empty());
+        assert_eq!(
+            announcer.due(minutes(10), at(3, 20, 10, 0), true),
+            vec!["every"]
+        );
+        assert!(
+            announcer
+                .due(minutes(15), at(3, 20, 15, 0), true)
+                .is_empty()
+        );
+        // Offline, the cron announcement is skipped and the interval stops
+        assert!(
+            announcer
+                .due(minutes(30), at(3, 20, 30, 0), false)
+                .is_empty()
+        );
+        assert!(
+            announcer
+                .due(minutes(31), at(3, 20, 31, 0), true)
+                .is_empty()
+        );
+        assert!(
+            announcer
+                .due(minutes(40), at(3, 20, 40, 0), true)
+                .is_empty()
+        );
+        assert_eq!(
+            announcer.due(minutes(41), at(3, 20, 41, 0), true),
+            vec!["every"]
+        );
+
+        // Once per matching minute
+        assert_eq!(
+            announcer.due(minutes(42), at(4, 20, 30, 1), true),
+            vec!["cron"]
+        );
+        assert!(
+            announcer
+                .due(minutes(42), at(4, 20, 30, 40), true)
+                .is_empty()
+        );
+    }
+}
@@ -1,19 +1,47 @@
-use crate::client::models::BiliMessage;
-use crate::client::scheduler::{EventContext, EventHandler};
-use log::{debug, error, info, warn};
-use reqwest::header::{HeaderMap, HeaderValue};
-use serde::Serialize;
-use std::sync::{Arc, Mutex};
+pub use crate::client::auth::extract_csrf_token;
+use crate::client::danmaku::ReplyTo;
+pub use crate::client::danmaku::send_danmaku_message;
+use crate::client::models::{BiliMessage, guard_level_name};
+use crate::client::runtime::{AsyncRuntime, RuntimeFlavor};
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::client::send_queue::{SendLimits, SendQueue};
+use futures::FutureExt;
+use log::{debug, error};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::runtime::Runtime;
+
+/// Message kinds a trigger can answer
+pub const TRIGGER_KINDS: [&str; 4] = ["danmu", "super_chat", "guard_buy", "entry_effect"];
 
 /// Configuration for keyword-response triggers
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TriggerConfig {
-    /// Keywords that trigger this response
+    /// Keywords that trigger this response; any message of `kinds` matches
+    /// when empty
     pub keywords: Vec<String>,
-    /// Response message to send
+    /// Response message to send, a template with the placeholders `{user}`,
+    /// `{uid}`, `{text}`, `{guard}` (guard level name) and `{count}` (times
+    /// this trigger answered the user, this reply included)
     pub response: String,
+    /// Kinds of message answered, from `TRIGGER_KINDS`; danmaku only when empty
+    pub kinds: Vec<String>,
+    /// Only answer guards (captains and above)
+    pub guard_only: bool,
+    /// Send the response as a reply threaded under the user, when their uid
+    /// is known
+    pub reply: bool,
+}
+
+impl TriggerConfig {
+    fn answers(&self, kind: &str) -> bool {
+        if self.kinds.is_empty() {
+            kind == "danmu"
+        } else {
+            self.kinds.iter().any(|k| k == kind)
+        }
+    }
 }
 
 /// Configuration for the auto reply plugin
@@ -23,6 +51,8 @@ pub struct AutoReplyConfig {
     pub enabled: bool,
     /// Minimum cooldown between replies in seconds
     pub cooldown_seconds: u64,
+    /// Minimum cooldown between replies to the same user in seconds
+    pub user_cooldown_seconds: u64,
     /// List of trigger configurations
     pub triggers: Vec<TriggerConfig>,
 }
@@ -32,351 +62,249 @@ impl Default for AutoReplyConfig {
         Self {
             enabled: false,
             cooldown_seconds: 5,
+            user_cooldown_seconds: 30,
             triggers: vec![
                 TriggerConfig {
                     keywords: vec!["你好".to_string(), "hello".to_string()],
                     response: "欢迎来到直播间！".to_string(),
+                    ..Default::default()
                 },
                 TriggerConfig {
                     keywords: vec!["谢谢".to_string(), "thanks".to_string()],
                     response: "不客气～".to_string(),
+                    ..Default::default()
                 },
             ],
         }
     }
 }
 
-/// Parameters for sending a danmaku message to Bilibili API
-#[derive(Serialize, Debug)]
-struct SendDanmakuRequest {
-    csrf: String,
-    roomid: u64,
-    msg: String,
-    rnd: u64,
-    fontsize: u32,
-    color: u32,
-    mode: u32,
-    bubble: u32,
-    room_type: u32,
-    jumpfrom: u32,
-    reply_mid: u32,
-    reply_attr: u32,
-    reply_uname: String,
-    replay_dmid: String,
-    statistics: String,
-    csrf_token: String,
+/// Message a trigger can answer
+#[derive(Debug, Clone, Copy)]
+struct Incoming<'a> {
+    kind: &'static str,
+    user: &'a str,
+    /// Sender uid, 0 when unknown
+    uid: u64,
+    text: &'a str,
+    guard_level: u8,
 }
 
-/// Extract CSRF token from cookies string
-pub fn extract_csrf_token(cookies: &str) -> Option<String> {
-    for cookie in cookies.split(';') {
-        let cookie = cookie.trim();
-        if cookie.starts_with("bili_jct=") {
-            return Some(cookie[9..].to_string());
+impl<'a> Incoming<'a> {
+    fn from_message(msg: &'a BiliMessage) -> Option<Self> {
+        let incoming = |user, uid, text, guard_level| Incoming {
+            kind: msg.kind(),
+            user,
+            uid,
+            text,
+            guard_level,
+        };
+        match msg {
+            BiliMessage::Danmu {
+                user,
+                text,
+                uid,
+                guard_level,
+                ..
+            } => Some(incoming(user, *uid, text, *guard_level)),
+            BiliMessage::SuperChat { user, text, .. } => Some(incoming(user, 0, text, 0)),
+            BiliMessage::GuardBuy {
+                user, guard_level, ..
+            } => Some(incoming(user, 0, "", *guard_level)),
+            BiliMessage::EntryEffect {
+                uid,
+                user,
+                guard_level,
+                text,
+            } => Some(incoming(user, *uid, text, *guard_level)),
+            _ => None,
         }
     }
-    None
-}
-
-/// Send a danmaku message to the Bilibili live room
-///
-/// # Arguments
-/// * `message` - The text message to send
-/// * `context` - Event context containing cookies and room_id
-///
-/// # Returns
-/// Returns Ok(()) on success, or an error if the request fails
-pub async fn send_danmaku_message(
-    message: &str,
-    context: &EventContext,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let cookies = match &context.cookies {
-        Some(cookies) => cookies,
-        None => {
-            return Err("No cookies available for sending danmaku".into());
-        }
-    };
 
-    let csrf_token = match extract_csrf_token(cookies) {
-        Some(token) => token,
-        None => {
-            return Err("Could not extract CSRF token from cookies".into());
+    /// Key of the sender for per-user cooldowns and counts
+    fn user_key(&self) -> String {
+        match self.uid {
+            0 => self.user.to_string(),
+            uid => uid.to_string(),
         }
-    };
-
-    // Current timestamp
-    let rnd = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-
-    let request = SendDanmakuRequest {
-        csrf: csrf_token.clone(),
-        roomid: context.room_id,
-        msg: message.to_string(),
-        rnd,
-        fontsize: 25,
-        color: 16777215, // White color
-        mode: 1,         // Scroll mode
-        bubble: 0,
-        room_type: 0,
-        jumpfrom: 0,
-        reply_mid: 0,
-        reply_attr: 0,
-        reply_uname: String::new(),
-        replay_dmid: String::new(),
-        statistics: r#"{"appId":100,"platform":5}"#.to_string(),
-        csrf_token,
-    };
-
-    // Set up headers
-    let mut headers = HeaderMap::new();
-    headers.insert("Cookie", HeaderValue::from_str(cookies)?);
-    headers.insert(
-        "User-Agent",
-        HeaderValue::from_static(
-            "Mozilla/5.0 (X11; Linux x86_64; rv:138.0) Gecko/20100101 Firefox/138.0",
-        ),
-    );
-    headers.insert(
-        "Referer",
-        HeaderValue::from_str(&format!("https://live.bilibili.com/{}", context.room_id))?,
-    );
-
-    debug!("Sending danmaku: {}", message);
-
-    let http_client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()?;
-
-    let response = http_client
-        .post("https://api.live.bilibili.com/msg/send")
-        .headers(headers)
-        .form(&request)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        info!("Successfully sent danmaku: {}", message);
-        Ok(())
-    } else {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        warn!("Failed to send danmaku, status: {}", status);
-        debug!("Response body: {}", body);
-        Err(format!("Failed to send danmaku: {} - {}", status, body).into())
     }
 }
 
-/// Auto reply handler that monitors danmaku for keywords and sends responses
+/// Replies sent so far, for the cooldowns and `{count}`
+#[derive(Debug, Default)]
+struct ReplyState {
+    last_reply: Option<Instant>,
+    last_reply_to: HashMap<String, Instant>,
+    /// Replies per trigger index and user
+    counts: HashMap<(usize, String), u64>,
+}
+
+/// Auto reply handler that answers danmaku and other messages matching its
+/// triggers, sending the responses through a `SendQueue`
+#[derive(Clone)]
 pub struct AutoReplyHandler {
     config: AutoReplyConfig,
-    last_reply: Arc<Mutex<Option<Instant>>>,
-    http_client: reqwest::Client,
-    runtime: Arc<Runtime>,
+    state: Arc<Mutex<ReplyState>>,
+    /// Queue of the responses, created from the first message's context
+    /// unless given
+    queue: Arc<OnceLock<SendQueue>>,
+    /// Room the queue sends to, following the message context
+    room: Arc<AtomicU64>,
+    runtime: AsyncRuntime,
 }
 
 impl AutoReplyHandler {
     /// Create a new auto reply handler with the given configuration
     pub fn new(config: AutoReplyConfig) -> Self {
-        let http_client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(10))
-            .build()
-            .expect("Failed to create HTTP client");
-
-        let runtime = Arc::new(Runtime::new().expect("Failed to create tokio runtime"));
+        let runtime =
+            AsyncRuntime::new(RuntimeFlavor::MultiThread).expect("Failed to create tokio runtime");
+        Self::with_runtime(config, runtime)
+    }
 
+    /// Create an auto reply handler sending replies on the given runtime
+    pub fn with_runtime(config: AutoReplyConfig, runtime: AsyncRuntime) -> Self {
         Self {
             config,
-            last_reply: Arc::new(Mutex::new(None)),
-            http_client,
+            state: Arc::default(),
+            queue: Arc::default(),
+            room: Arc::default(),
             runtime,
         }
     }
 
-    /// Check if any keyword matches the message text
-    fn find_matching_trigger(&self, text: &str) -> Option<&TriggerConfig> {
-        let text_lower = text.to_lowercase();
-
-        for trigger in &self.config.triggers {
-            for keyword in &trigger.keywords {
-                if text_lower.contains(&keyword.to_lowercase()) {
-                    return Some(trigger);
-                }
-            }
-        }
+    /// Create an auto reply handler sending replies through `queue`, shared
+    /// with other senders so they are paced together
+    pub fn with_queue(config: AutoReplyConfig, queue: SendQueue, runtime: AsyncRuntime) -> Self {
+        let handler = Self::with_runtime(config, runtime);
+        let _ = handler.queue.set(queue);
+        handler
+    }
 
-        None
+    /// First trigger answering the message
+    fn find_matching_trigger(&self, incoming: &Incoming) -> Option<(usize, &TriggerConfig)> {
+        let text_lower = incoming.text.to_lowercase();
+        self.config
+            .triggers
+            .iter()
+            .enumerate()
+            .find(|(_, trigger)| {
+                trigger.answers(incoming.kind)
+                    && (!trigger.guard_only || incoming.guard_level != 0)
+                    && (trigger.keywords.is_empty()
+                        || trigger
+                            .keywords
+                            .iter()
+                            .any(|keyword| text_lower.contains(&keyword.to_lowercase())))
+            })
     }
 
-    /// Get the response from the trigger
-    fn select_response(&self, trigger: &TriggerConfig) -> Option<String> {
+    /// Fill the placeholders of the trigger's response template
+    fn select_response(
+        &self,
+        trigger: &TriggerConfig,
+        incoming: &Incoming,
+        count: u64,
+    ) -> Option<String> {
         if trigger.response.is_empty() {
             return None;
         }
-        Some(trigger.response.clone())
+        Some(
+            trigger
+                .response
+                .replace("{user}", incoming.user)
+                .replace("{uid}", &incoming.uid.to_string())
+                .replace("{text}", incoming.text)
+                .replace("{guard}", guard_level_name(incoming.guard_level))
+                .replace("{count}", &count.to_string()),
+        )
     }
 
-    /// Check if enough time has passed since the last reply
-    fn check_cooldown(&self) -> bool {
-        let last_reply = self.last_reply.lock().unwrap();
-
-        match *last_reply {
-            Some(last_time) => {
-                let elapsed = last_time.elapsed();
-                elapsed >= Duration::from_secs(self.config.cooldown_seconds)
-            }
-            None => true,
-        }
-    }
-
-    /// Update the last reply timestamp
-    fn update_last_reply(&self) {
-        let mut last_reply = self.last_reply.lock().unwrap();
-        *last_reply = Some(Instant::now());
+    /// Check if enough time has passed since the last reply, and since the
+    /// last reply to the user of `user_key`
+    fn check_cooldown(&self, user_key: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        let cooled = |last: Option<&Instant>, seconds| {
+            last.is_none_or(|last| last.elapsed() >= Duration::from_secs(seconds))
+        };
+        cooled(state.last_reply.as_ref(), self.config.cooldown_seconds)
+            && cooled(
+                state.last_reply_to.get(user_key),
+                self.config.user_cooldown_seconds,
+            )
     }
 
-    /// Extract CSRF token from cookies
-    fn extract_csrf_token(&self, cookies: &str) -> Option<String> {
-        extract_csrf_token(cookies)
+    /// Update the last reply timestamps, returning the reply count of the
+    /// trigger for the user
+    fn update_last_reply(&self, trigger: usize, user_key: &str) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.last_reply = Some(now);
+        state.last_reply_to.insert(user_key.to_string(), now);
+        let count = state
+            .counts
+            .entry((trigger, user_key.to_string()))
+            .or_default();
+        *count += 1;
+        *count
     }
 
-    /// Send a danmaku message to the Bilibili API
-    async fn send_danmaku(
-        &self,
-        message: &str,
-        context: &EventContext,
-    ) -> Result<(), reqwest::Error> {
-        let cookies = match &context.cookies {
-            Some(cookies) => cookies,
-            None => {
-                warn!("No cookies available for sending danmaku");
-                return Ok(());
-            }
-        };
-
-        let csrf_token = match self.extract_csrf_token(cookies) {
-            Some(token) => token,
-            None => {
-                error!("Could not extract CSRF token from cookies");
-                return Ok(());
-            }
-        };
-
-        // Current timestamp
-        let rnd = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        let request = SendDanmakuRequest {
-            csrf: csrf_token.clone(),
-            roomid: context.room_id,
-            msg: message.to_string(),
-            rnd,
-            fontsize: 25,
-            color: 16777215, // White color
-            mode: 1,         // Scroll mode
-            bubble: 0,
-            room_type: 0,
-            jumpfrom: 0,
-            reply_mid: 0,
-            reply_attr: 0,
-            reply_uname: String::new(),
-            replay_dmid: String::new(),
-            statistics: r#"{"appId":100,"platform":5}"#.to_string(),
-            csrf_token,
-        };
-
-        // Set up headers
-        let mut headers = HeaderMap::new();
-        headers.insert("Cookie", HeaderValue::from_str(cookies).unwrap());
-        headers.insert(
-            "User-Agent",
-            HeaderValue::from_static(
-                "Mozilla/5.0 (X11; Linux x86_64; rv:138.0) Gecko/20100101 Firefox/138.0",
-            ),
-        );
-        headers.insert(
-            "Referer",
-            HeaderValue::from_str(&format!("https://live.bilibili.com/{}", context.room_id))
-                .unwrap(),
-        );
-
-        debug!("Sending danmaku: {}", message);
-
-        let response = self
-            .http_client
-            .post("https://api.live.bilibili.com/msg/send")
-            .headers(headers)
-            .form(&request)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            info!("Successfully sent danmaku: {}", message);
-        } else {
-            warn!("Failed to send danmaku, status: {}", response.status());
-            let body = response.text().await.unwrap_or_default();
-            debug!("Response body: {}", body);
+    /// Response to `msg` and the reply target, when a trigger answers it and
+    /// the cooldowns have passed
+    fn respond(&self, msg: &BiliMessage) -> Option<(String, Option<ReplyTo>)> {
+        let incoming = Incoming::from_message(msg)?;
+        let (index, trigger) = self.find_matching_trigger(&incoming)?;
+        let user_key = incoming.user_key();
+        if !self.check_cooldown(&user_key) {
+            debug!("Auto reply on cooldown, skipping");
+            return None;
         }
+        let count = self.update_last_reply(index, &user_key);
+        let response = self.select_response(trigger, &incoming, count)?;
+        let reply_to =
+            (trigger.reply && incoming.uid != 0).then(|| ReplyTo::new(incoming.uid, incoming.user));
+        Some((response, reply_to))
+    }
 
-        Ok(())
+    fn queue(&self, context: &EventContext) -> &SendQueue {
+        self.room.store(context.room_id, Ordering::Relaxed);
+        self.queue.get_or_init(|| {
+            SendQueue::following_room(
+                context.clone(),
+                Arc::clone(&self.room),
+                SendLimits::default(),
+                &self.runtime,
+            )
+        })
     }
 }
 
 impl EventHandler for AutoReplyHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(TRIGGER_KINDS.into_iter().filter(|kind| {
+            self.config
+                .triggers
+                .iter()
+                .any(|trigger| trigger.answers(kind))
+        }))
+    }
+
     fn handle(&self, msg: &BiliMessage, context: &EventContext) {
         if !self.config.enabled {
             return;
         }
 
-        // Only process danmaku messages
-        if let BiliMessage::Danmu { user: _, text } = msg {
-            // Check for keyword match
-            if let Some(trigger) = self.find_matching_trigger(text) {
-                // Check cooldown
-                if !self.check_cooldown() {
-                    debug!("Auto reply on cooldown, skipping");
-                    return;
-                }
-
-                // Select response
-                if let Some(response) = self.select_response(trigger) {
-                    debug!(
-                        "Auto reply triggered by '{}', responding with '{}'",
-                        text, response
-                    );
-
-                    // Update cooldown
-                    self.update_last_reply();
-
-                    // Send the reply asynchronously
-                    let runtime = Arc::clone(&self.runtime);
-                    let _http_client = self.http_client.clone();
-                    let response_msg = response.clone();
-                    let context_clone = context.clone();
-                    let handler = self.clone();
-
-                    runtime.spawn(async move {
-                        if let Err(e) = handler.send_danmaku(&response_msg, &context_clone).await {
-                            error!("Failed to send auto reply: {}", e);
-                        }
-                    });
+        if let Some((response, reply_to)) = self.respond(msg) {
+            debug!("Auto reply to {}: '{}'", msg.kind(), response);
+            let queue = self.queue(context);
+            let sent = match &reply_to {
+                Some(reply_to) => queue.reply(&response, reply_to).boxed(),
+                None => queue.send(&response).boxed(),
+            };
+            self.runtime.spawn(async move {
+                if let Err(e) = sent.await {
+                    error!("Failed to send auto reply: {}", e);
                 }
-            }
-        }
-    }
-}
-
-// Implement Clone for AutoReplyHandler
-impl Clone for AutoReplyHandler {
-    fn clone(&self) -> Self {
-        Self {
-            config: self.config.clone(),
-            last_reply: Arc::clone(&self.last_reply),
-            http_client: self.http_client.clone(),
-            runtime: Arc::clone(&self.runtime),
+            });
         }
     }
 }
@@ -387,17 +315,32 @@ mod tests {
     use crate::client::models::BiliMessage;
     use crate::client::scheduler::{EventContext, EventHandler};
 
+    fn danmu(text: &str, uid: u64, guard_level: u8) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: "test_user".to_string(),
+            text: text.to_string(),
+            uid,
+            guard_level,
+            medal: None,
+        }
+    }
+
+    fn matches(handler: &AutoReplyHandler, msg: &BiliMessage) -> bool {
+        let incoming = Incoming::from_message(msg).unwrap();
+        handler.find_matching_trigger(&incoming).is_some()
+    }
+
     #[test]
     fn test_keyword_matching() {
         let config = AutoReplyConfig::default();
         let handler = AutoReplyHandler::new(config);
 
         // Test keyword matching
-        assert!(handler.find_matching_trigger("你好世界").is_some());
-        assert!(handler.find_matching_trigger("Hello world").is_some());
-        assert!(handler.find_matching_trigger("谢谢大家").is_some());
-        assert!(handler.find_matching_trigger("Thanks everyone").is_some());
-        assert!(handler.find_matching_trigger("random text").is_none());
+        assert!(matches(&handler, &danmu("你好世界", 1, 0)));
+        assert!(matches(&handler, &danmu("Hello world", 1, 0)));
+        assert!(matches(&handler, &danmu("谢谢大家", 1, 0)));
+        assert!(matches(&handler, &danmu("Thanks everyone", 1, 0)));
+        assert!(!matches(&handler, &danmu("random text", 1, 0)));
     }
 
     #[test]
@@ -406,9 +349,18 @@ mod tests {
         let handler = AutoReplyHandler::new(config);
 
         let trigger = &handler.config.triggers[0];
-        let response = handler.select_response(trigger);
+        let msg = danmu("你好", 1, 0);
+        let response = handler.select_response(trigger, &Incoming::from_message(&msg).unwrap(), 1);
         assert!(response.is_some());
         assert_eq!(response.unwrap(), trigger.response);
+
+        let trigger = TriggerConfig {
+            response: "欢迎{guard}{user}（{uid}）第{count}次：{text}".to_string(),
+            ..Default::default()
+        };
+        let msg = danmu("hi", 7, 3);
+        let response = handler.select_response(&trigger, &Incoming::from_message(&msg).unwrap(), 2);
+        assert_eq!(response.unwrap(), "欢迎舰长test_user（7）第2次：hi");
     }
 
     #[test]
@@ -416,37 +368,100 @@ mod tests {
         let config = AutoReplyConfig {
             enabled: true,
             cooldown_seconds: 1,
+            user_cooldown_seconds: 0,
             triggers: vec![],
         };
         let handler = AutoReplyHandler::new(config);
 
         // Initial check should pass
-        assert!(handler.check_cooldown());
+        assert!(handler.check_cooldown("1"));
 
         // Update timestamp
-        handler.update_last_reply();
+        handler.update_last_reply(0, "1");
 
         // Should be on cooldown now
-        assert!(!handler.check_cooldown());
+        assert!(!handler.check_cooldown("1"));
 
         // Wait for cooldown
         std::thread::sleep(Duration::from_secs(2));
 
         // Should be off cooldown now
-        assert!(handler.check_cooldown());
+        assert!(handler.check_cooldown("1"));
     }
 
     #[test]
-    fn test_csrf_extraction() {
-        let config = AutoReplyConfig::default();
+    fn test_user_cooldown_and_counts() {
+        let config = AutoReplyConfig {
+            enabled: true,
+            cooldown_seconds: 0,
+            user_cooldown_seconds: 60,
+            triggers: vec![
+                TriggerConfig {
+                    response: "欢迎{guard}{user}".to_string(),
+                    kinds: vec!["entry_effect".to_string(), "guard_buy".to_string()],
+                    guard_only: true,
+                    reply: true,
+                    ..Default::default()
+                },
+                TriggerConfig {
+                    keywords: vec!["问".to_string()],
+                    response: "第{count}次".to_string(),
+                    ..Default::default()
+                },
+            ],
+        };
         let handler = AutoReplyHandler::new(config);
+        let entry = BiliMessage::EntryEffect {
+            uid: 2,
+            user: "captain".to_string(),
+            guard_level: 3,
+            text: "欢迎舰长 captain 进入直播间".to_string(),
+        };
+        assert_eq!(
+            handler.respond(&entry),
+            Some((
+                "欢迎舰长captain".to_string(),
+                Some(ReplyTo::new(2, "captain"))
+            ))
+        );
+        // Same user within the user cooldown, another user is answered
+        assert_eq!(handler.respond(&entry), None);
+        assert_eq!(
+            handler.respond(&danmu("问一下", 3, 0)),
+            Some(("第1次".to_string(), None))
+        );
+        assert_eq!(handler.respond(&danmu("问一下", 3, 0)), None);
+        // Not a guard, or not a kind any trigger answers
+        assert_eq!(handler.respond(&danmu("hi", 4, 0)), None);
+        assert!(handler.subscription().accepts(&BiliMessage::GuardBuy {
+            user: String::new(),
+            guard_level: 3,
+            num: 1,
+            price: 0
+        }));
+        assert!(!handler.subscription().accepts(&BiliMessage::SuperChat {
+            id: 1,
+            user: String::new(),
+            text: String::new(),
+            price: 30,
+            duration: 60
+        }));
+
+        handler.state.lock().unwrap().last_reply_to.clear();
+        assert_eq!(
+            handler.respond(&danmu("再问", 3, 0)),
+            Some(("第2次".to_string(), None))
+        );
+    }
 
+    #[test]
+    fn test_csrf_extraction() {
         let cookies = "SESSDATA=abc123; bili_jct=csrf_token_here; other=value";
-        let csrf = handler.extract_csrf_token(cookies);
+        let csrf = extract_csrf_token(cookies);
         assert_eq!(csrf, Some("csrf_token_here".to_string()));
 
         let cookies_no_csrf = "SESSDATA=abc123; other=value";
-        let csrf = handler.extract_csrf_token(cookies_no_csrf);
+        let csrf = extract_csrf_token(cookies_no_csrf);
         assert_eq!(csrf, None);
     }
 
@@ -455,9 +470,11 @@ mod tests {
         let config = AutoReplyConfig {
             enabled: true,
             cooldown_seconds: 0, // No cooldown for testing
+            user_cooldown_seconds: 0,
             triggers: vec![TriggerConfig {
                 keywords: vec!["test".to_string()],
                 response: "test response".to_string(),
+                ..Default::default()
             }],
         };
         let handler = AutoReplyHandler::new(config);
@@ -465,12 +482,10 @@ mod tests {
         let context = EventContext {
             cookies: Some("bili_jct=test_csrf; SESSDATA=test".to_string()),
             room_id: 12345,
+            request_headers: None,
         };
 
-        let msg = BiliMessage::Danmu {
-            user: "test_user".to_string(),
-            text: "this is a test message".to_string(),
-        };
+        let msg = danmu("this is a test message", 0, 0);
 
         // This should trigger the auto reply (but won't actually send due to test environment)
         handler.handle(&msg, &context);
@@ -0,0 +1,340 @@
+use crate::client::danmaku::ReplyTo;
+use crate::client::models::BiliMessage;
+use crate::client::runtime::AsyncRuntime;
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::client::send_queue::SendQueue;
+use crate::tui::command::{CommandArgs, split_command};
+use futures::FutureExt;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Prefix of viewer commands, as in `!song`
+pub const DEFAULT_PREFIX: &str = "!";
+
+/// Time a command stays unavailable after it ran
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// Time a script may take before it is killed
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Viewer command found in a danmaku
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatCommandCall {
+    /// Command name without the prefix, lowercased
+    pub name: String,
+    /// Text after the command name, trimmed
+    pub args: String,
+    pub user: String,
+    /// Sender uid, 0 when hidden
+    pub uid: u64,
+    pub guard_level: u8,
+    pub room_id: u64,
+}
+
+impl ChatCommandCall {
+    /// Arguments split on whitespace
+    pub fn args(&self) -> CommandArgs<'_> {
+        CommandArgs::new(&self.args)
+    }
+}
+
+/// Reply to a command call; `None` sends nothing
+type ChatCommandFn = Arc<dyn Fn(&ChatCommandCall) -> Option<String> + Send + Sync>;
+
+struct ChatCommand {
+    cooldown: Duration,
+    handler: ChatCommandFn,
+}
+
+/// A plugin answering viewer commands such as `!song` or `!uptime` in danmaku.
+/// Commands run off the scheduler thread and are answered through a
+/// `SendQueue`, threaded under the viewer's danmaku.
+///
+/// ```ignore
+/// let mut commands = ChatCommandHandler::new(queue, runtime);
+/// commands.register("uptime", DEFAULT_COOLDOWN, move |_| Some(format!("已开播 {}", uptime())));
+/// commands.register_response("song", DEFAULT_COOLDOWN, "{user}，歌单在简介里");
+/// commands.register_script("rank", DEFAULT_COOLDOWN, "./rank.sh", Vec::new());
+/// scheduler.add_sequential_handler(Arc::new(commands));
+/// ```
+pub struct ChatCommandHandler {
+    prefix: String,
+    commands: BTreeMap<String, ChatCommand>,
+    /// When each command last ran
+    last_run: Mutex<HashMap<String, Instant>>,
+    queue: SendQueue,
+    runtime: AsyncRuntime,
+}
+
+impl ChatCommandHandler {
+    pub fn new(queue: SendQueue, runtime: AsyncRuntime) -> Self {
+        Self {
+            prefix: DEFAULT_PREFIX.to_string(),
+            commands: BTreeMap::new(),
+            last_run: Mutex::default(),
+            queue,
+            runtime,
+        }
+    }
+
+    /// Recognize commands starting with `prefix` instead of `!`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Register a command answered by `handler`, replacing any existing
+    /// command with the same name; names are matched case-insensitively
+    pub fn register<F>(&mut self, name: &str, cooldown: Duration, handler: F)
+    where
+        F: Fn(&ChatCommandCall) -> Option<String> + Send + Sync + 'static,
+    {
+        self.commands.insert(
+            name.to_lowercase(),
+            ChatCommand {
+                cooldown,
+                handler: Arc::new(handler),
+            },
+        );
+    }
+
+    /// Register a command answered with `response`, filling the placeholders
+    /// `{user}`, `{uid}` and `{args}`
+    pub fn register_response(&mut self, name: &str, cooldown: Duration, response: &str) {
+        let response = response.to_string();
+        self.register(name, cooldown, move |call| {
+            Some(
+                response
+                    .replace("{user}", &call.user)
+                    .replace("{uid}", &call.uid.to_string())
+                    .replace("{args}", &call.args),
+            )
+        });
+    }
+
+    /// Register a command answered by the output of `program`, see `run_script`
+    pub fn register_script(
+        &mut self,
+        name: &str,
+        cooldown: Duration,
+        program: &str,
+        args: Vec<String>,
+    ) {
+        let program = program.to_string();
+        self.register(name, cooldown, move |call| {
+            run_script(&program, &args, call)
+                .map_err(|e| log::warn!("!{} script failed: {}", call.name, e))
+                .ok()
+                .flatten()
+        });
+    }
+
+    /// Names of the registered commands, sorted
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    /// The registered command called by a danmaku, if any
+    fn parse(&self, msg: &BiliMessage, context: &EventContext) -> Option<ChatCommandCall> {
+        let BiliMessage::Danmu {
+            user,
+            text,
+            uid,
+            guard_level,
+            ..
+        } = msg
+        else {
+            return None;
+        };
+        let (name, args) = split_command(text.trim().strip_prefix(&self.prefix)?);
+        let name = name.to_lowercase();
+        if !self.commands.contains_key(&name) {
+            return None;
+        }
+        Some(ChatCommandCall {
+            name,
+            args: args.trim().to_string(),
+            user: user.clone(),
+            uid: *uid,
+            guard_level: *guard_level,
+            room_id: context.room_id,
+        })
+    }
+
+    /// Whether the command `name` is off cooldown, starting its cooldown if so
+    fn take_cooldown(&self, name: &str, cooldown: Duration) -> bool {
+        let mut last_run = self.last_run.lock().unwrap();
+        let now = Instant::now();
+        if last_run
+            .get(name)
+            .is_some_and(|last| now.duration_since(*last) < cooldown)
+        {
+            return false;
+        }
+        last_run.insert(name.to_string(), now);
+        true
+    }
+}
+
+impl EventHandler for ChatCommandHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, context: &EventContext) {
+        let Some(call) = self.parse(msg, context) else {
+            return;
+        };
+        let command = &self.commands[&call.name];
+        if !self.take_cooldown(&call.name, command.cooldown) {
+            log::debug!("!{} on cooldown, ignoring {}", call.name, call.user);
+            return;
+        }
+        let handler = Arc::clone(&command.handler);
+        let queue = self.queue.clone();
+        let runtime = self.runtime.clone();
+        // Handlers may block, e.g. on a script or an API call
+        self.runtime.handle().spawn_blocking(move || {
+            let Some(reply) = handler(&call) else {
+                return;
+            };
+            let sent = if call.uid != 0 {
+                queue
+                    .reply(&reply, &ReplyTo::new(call.uid, &call.user))
+                    .boxed()
+            } else {
+                queue.send(&reply).boxed()
+            };
+            runtime.spawn(async move {
+                if let Err(e) = sent.await {
+                    log::warn!("Failed to answer !{}: {}", call.name, e);
+                }
+            });
+        });
+    }
+}
+
+/// Run `program` with `args` followed by the viewer's arguments, returning its
+/// trimmed standard output, `None` when empty. The call is also passed in the
+/// environment as `BLIVEDM_COMMAND`, `BLIVEDM_USER`, `BLIVEDM_UID`,
+/// `BLIVEDM_GUARD_LEVEL` and `BLIVEDM_ROOM_ID`.
+pub fn run_script(
+    program: &str,
+    args: &[String],
+    call: &ChatCommandCall,
+) -> Result<Option<String>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .args(call.args().parts())
+        .env("BLIVEDM_COMMAND", &call.name)
+        .env("BLIVEDM_USER", &call.user)
+        .env("BLIVEDM_UID", call.uid.to_string())
+        .env("BLIVEDM_GUARD_LEVEL", call.guard_level.to_string())
+        .env("BLIVEDM_ROOM_ID", call.room_id.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < SCRIPT_TIMEOUT => {
+                thread::sleep(Duration::from_millis(20))
+            }
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("{} timed out", program));
+            }
+            Err(e) => return Err(format!("failed to wait for {}: {}", program, e)),
+        }
+    };
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status));
+    }
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout
+            .read_to_string(&mut output)
+            .map_err(|e| format!("failed to read output of {}: {}", program, e))?;
+    }
+    let output = output.trim();
+    Ok((!output.is_empty()).then(|| output.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::runtime::RuntimeFlavor;
+    use crate::client::send_queue::SendLimits;
+
+    fn danmu(text: &str) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: "viewer".to_string(),
+            text: text.to_string(),
+            uid: 2,
+            guard_level: 3,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_chat_commands() {
+        let runtime = AsyncRuntime::new(RuntimeFlavor::CurrentThread).unwrap();
+        let context = EventContext::new(None, 1);
+        let queue = SendQueue::new(context.clone(), SendLimits::default(), &runtime);
+        let mut commands = ChatCommandHandler::new(queue, runtime);
+        commands.register_response("Song", Duration::from_secs(60), "{user}点了{args}");
+        commands.register("uptime", Duration::ZERO, |_| Some("1h".to_string()));
+
+        let call = commands.parse(&danmu(" !SONG  晴天 "), &context).unwrap();
+        assert_eq!(call.name, "song");
+        assert_eq!(call.args, "晴天");
+        assert_eq!(call.room_id, 1);
+        assert_eq!(
+            (commands.commands["song"].handler)(&call),
+            Some("viewer点了晴天".to_string())
+        );
+        assert!(commands.parse(&danmu("!unknown"), &context).is_none());
+        assert!(commands.parse(&danmu("song"), &context).is_none());
+        assert_eq!(
+            commands.commands().collect::<Vec<_>>(),
+            vec!["song", "uptime"]
+        );
+
+        assert!(commands.take_cooldown("song", Duration::from_secs(60)));
+        assert!(!commands.take_cooldown("song", Duration::from_secs(60)));
+        assert!(commands.take_cooldown("uptime", Duration::ZERO));
+        assert!(commands.take_cooldown("uptime", Duration::ZERO));
+
+        let commands = commands.with_prefix("#");
+        assert!(commands.parse(&danmu("#uptime"), &context).is_some());
+        assert!(commands.parse(&danmu("!uptime"), &context).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_script() {
+        let call = ChatCommandCall {
+            name: "rank".to_string(),
+            args: "3 x".to_string(),
+            user: "viewer".to_string(),
+            uid: 2,
+            guard_level: 0,
+            room_id: 1,
+        };
+        let script = |body: &str| vec!["-c".to_string(), body.to_string(), "sh".to_string()];
+        assert_eq!(
+            run_script("sh", &script("echo \"$BLIVEDM_USER top $1 of $#\""), &call),
+            Ok(Some("viewer top 3 of 2".to_string()))
+        );
+        assert_eq!(run_script("sh", &script("true"), &call), Ok(None));
+        assert!(run_script("sh", &script("exit 1"), &call).is_err());
+        assert!(run_script("/nonexistent/script", &[], &call).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::client::models::{BiliMessage, MESSAGE_KINDS};
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a flashed terminal title stays before the previous one is restored
+pub const FLASH_DURATION: Duration = Duration::from_secs(5);
+
+/// Case-insensitive keyword matcher for highlighting chat messages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeywordMatcher {
+    /// Lowercased keywords, empty ones removed
+    keywords: Vec<String>,
+}
+
+impl KeywordMatcher {
+    pub fn new<I, S>(keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            keywords: keywords
+                .into_iter()
+                .map(|keyword| keyword.as_ref().trim().to_lowercase())
+                .filter(|keyword| !keyword.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty()
+    }
+
+    /// Whether `text` contains any of the keywords
+    pub fn matches(&self, text: &str) -> bool {
+        if self.keywords.is_empty() {
+            return false;
+        }
+        let text = text.to_lowercase();
+        self.keywords.iter().any(|keyword| text.contains(keyword))
+    }
+}
+
+/// What to do when a message should grab attention
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AlertAction {
+    /// Ring the terminal bell, which most terminals turn into an urgency hint
+    Bell,
+    /// Show the message in the terminal title for `FLASH_DURATION`
+    Flash,
+    #[default]
+    None,
+}
+
+impl FromStr for AlertAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bell" => Ok(AlertAction::Bell),
+            "flash" => Ok(AlertAction::Flash),
+            "none" | "off" => Ok(AlertAction::None),
+            other => Err(format!(
+                "unknown notification '{}', expected bell, flash or none",
+                other
+            )),
+        }
+    }
+}
+
+/// Notification for each message kind, plus one for keyword matches
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlertRules {
+    /// By `BiliMessage::kind`, e.g. `super_chat`
+    kinds: HashMap<String, AlertAction>,
+    /// For danmaku and super chats matching a highlight keyword
+    keyword: AlertAction,
+}
+
+impl AlertRules {
+    /// Rules from `kind = action` entries, where the kind `keyword` stands for
+    /// keyword matches, e.g. `super_chat = "flash"` or `keyword = "bell"`
+    pub fn from_entries<I, K, V>(entries: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut rules = Self::default();
+        for (kind, action) in entries {
+            let action = action.as_ref().parse()?;
+            match kind.as_ref().trim() {
+                "keyword" => rules.keyword = action,
+                kind if MESSAGE_KINDS.contains(&kind) => {
+                    rules.kinds.insert(kind.to_string(), action);
+                }
+                other => {
+                    return Err(format!(
+                        "unknown message kind '{}', expected keyword or one of {}",
+                        other,
+                        MESSAGE_KINDS.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Rules from `kind=action` pairs separated by commas, as given on the command line
+    pub fn parse_list(list: &str) -> Result<Self, String> {
+        let entries = list
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected kind=action, got '{}'", entry.trim()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_entries(entries)
+    }
+
+    /// Ring the bell on keyword matches, as `highlight_bell` did before these rules
+    pub fn keyword_bell() -> Self {
+        Self {
+            keyword: AlertAction::Bell,
+            ..Self::default()
+        }
+    }
+
+    /// Add the rules of `other`, which win where both set one
+    pub fn merge(&mut self, other: AlertRules) {
+        self.kinds.extend(other.kinds);
+        if other.keyword != AlertAction::None {
+            self.keyword = other.keyword;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyword == AlertAction::None
+            && self
+                .kinds
+                .values()
+                .all(|action| *action == AlertAction::None)
+    }
+
+    /// Action for `msg`; a keyword match takes precedence over its kind
+    pub fn action(&self, msg: &BiliMessage, matcher: &KeywordMatcher) -> AlertAction {
+        if self.keyword != AlertAction::None
+            && let BiliMessage::Danmu { text, .. } | BiliMessage::SuperChat { text, .. } = msg
+            && matcher.matches(text)
+        {
+            return self.keyword;
+        }
+        self.kinds.get(msg.kind()).copied().unwrap_or_default()
+    }
+}
+
+/// Short description of `msg` for the flashed terminal title
+pub fn alert_title(msg: &BiliMessage) -> String {
+    let title = match msg {
+        BiliMessage::Danmu { user, text, .. } => format!("{}: {}", user, text),
+        BiliMessage::SuperChat {
+            user, text, price, ..
+        } => format!("SC ¥{} {}: {}", price, user, text),
+        BiliMessage::Gift {
+            user, gift, num, ..
+        } => format!("{} sent {} x{}", user, gift, num),
+        BiliMessage::GuardBuy { user, .. } => format!("{} bought guard", user),
+        other => other.kind().to_string(),
+    };
+    // Control characters would end the escape sequence early
+    title.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Bells and title flashes waiting to be written by the TUI between frames,
+/// so they never interleave with the escape sequences of a frame
+#[derive(Debug, Default)]
+pub struct TerminalAlerts {
+    state: Mutex<AlertState>,
+}
+
+#[derive(Debug, Default)]
+struct AlertState {
+    bell: bool,
+    /// Title of the latest flash, not written yet
+    title: Option<String>,
+    /// When the previous title comes back, while a flashed one shows
+    restore_at: Option<Instant>,
+}
+
+impl TerminalAlerts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bell(&self) {
+        self.state.lock().unwrap().bell = true;
+    }
+
+    /// Show `title` for `FLASH_DURATION`, replacing a flash still showing
+    pub fn flash(&self, title: String) {
+        self.state.lock().unwrap().title = Some(title);
+    }
+
+    /// Escape sequences to write at `now`: a pending bell and flash, or the
+    /// previous title once the last flash has shown for `FLASH_DURATION`
+    pub fn take_output(&self, now: Instant) -> String {
+        let mut state = self.state.lock().unwrap();
+        let mut out = String::new();
+        if std::mem::take(&mut state.bell) {
+            out.push('\x07');
+        }
+        if let Some(title) = state.title.take() {
+            // Save the current title on the xterm title stack, once per run of flashes
+            if state.restore_at.is_none() {
+                out.push_str("\x1b[22;0t");
+            }
+            out.push_str(&format!("\x1b]2;{}\x1b\\", title));
+            state.restore_at = Some(now + FLASH_DURATION);
+        } else if state.restore_at.is_some_and(|at| now >= at) {
+            out.push_str("\x1b[23;0t");
+            state.restore_at = None;
+        }
+        out
+    }
+
+    /// Escape sequence restoring the previous title if a flash shows, for exit
+    pub fn restore_title(&self) -> &'static str {
+        let mut state = self.state.lock().unwrap();
+        state.title = None;
+        match state.restore_at.take() {
+            Some(_) => "\x1b[23;0t",
+            None => "",
+        }
+    }
+}
+
+/// A plugin that rings the terminal bell or flashes the terminal title when a
+/// message of a configured kind arrives or a danmaku or super chat matches a
+/// keyword. The alerts are queued in `alerts` for the TUI to write.
+pub struct KeywordAlertHandler {
+    matcher: KeywordMatcher,
+    rules: AlertRules,
+    alerts: Arc<TerminalAlerts>,
+}
+
+impl KeywordAlertHandler {
+    /// Ring the bell on keyword matches
+    pub fn new(matcher: KeywordMatcher) -> Self {
+        Self::with_rules(matcher, AlertRules::keyword_bell())
+    }
+
+    pub fn with_rules(matcher: KeywordMatcher, rules: AlertRules) -> Self {
+        Self {
+            matcher,
+            rules,
+            alerts: Arc::new(TerminalAlerts::new()),
+        }
+    }
+
+    /// Queue of the alerts, to hand to `TuiApp::set_alerts`
+    pub fn alerts(&self) -> Arc<TerminalAlerts> {
+        Arc::clone(&self.alerts)
+    }
+}
+
+impl EventHandler for KeywordAlertHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::All
+    }
+
+    fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        match self.rules.action(msg, &self.matcher) {
+            AlertAction::Bell => self.alerts.bell(),
+            AlertAction::Flash => self.alerts.flash(alert_title(msg)),
+            AlertAction::None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_matcher() {
+        let matcher = KeywordMatcher::new(["点歌", "MyName", " "]);
+        assert!(matcher.matches("我要点歌"));
+        assert!(matcher.matches("hi myname!"));
+        assert!(!matcher.matches("hello"));
+        assert!(!KeywordMatcher::default().matches("anything"));
+    }
+
+    #[test]
+    fn test_alert_rules() {
+        let matcher = KeywordMatcher::new(["点歌"]);
+        let danmu = |text: &str| BiliMessage::Danmu {
+            user: "alice".to_string(),
+            text: text.to_string(),
+            uid: 1,
+            guard_level: 0,
+            medal: None,
+        };
+        let super_chat = BiliMessage::SuperChat {
+            id: 1,
+            user: "bob".to_string(),
+            text: "hi\nthere".to_string(),
+            price: 30,
+            duration: 60,
+        };
+
+        let rules = AlertRules::parse_list("super_chat=flash, keyword=bell").unwrap();
+        assert_eq!(rules.action(&super_chat, &matcher), AlertAction::Flash);
+        assert_eq!(
+            rules.action(&danmu("我要点歌"), &matcher),
+            AlertAction::Bell
+        );
+        assert_eq!(rules.action(&danmu("hello"), &matcher), AlertAction::None);
+        assert!(AlertRules::parse_list("danmu").is_err());
+        assert!(AlertRules::parse_list("danmu=loud").is_err());
+        // A typo would otherwise never fire
+        assert!(AlertRules::parse_list("superchat=flash").is_err());
+
+        let mut rules = AlertRules::from_entries([("super_chat", "none")]).unwrap();
+        assert!(rules.is_empty());
+        rules.merge(AlertRules::keyword_bell());
+        assert_eq!(rules.action(&danmu("点歌"), &matcher), AlertAction::Bell);
+
+        assert_eq!(alert_title(&super_chat), "SC ¥30 bob: hithere");
+    }
+
+    #[test]
+    fn test_terminal_alerts() {
+        let alerts = TerminalAlerts::new();
+        let start = Instant::now();
+        assert_eq!(alerts.take_output(start), "");
+
+        alerts.bell();
+        alerts.flash("first".to_string());
+        alerts.flash("second".to_string());
+        // Only the latest flash is shown, after saving the title
+        assert_eq!(
+            alerts.take_output(start),
+            "\x07\x1b[22;0t\x1b]2;second\x1b\\"
+        );
+        assert_eq!(alerts.take_output(start), "");
+
+        // A flash while one shows keeps the saved title and restarts the time
+        let later = start + FLASH_DURATION / 2;
+        alerts.flash("third".to_string());
+        assert_eq!(alerts.take_output(later), "\x1b]2;third\x1b\\");
+        assert_eq!(alerts.take_output(start + FLASH_DURATION), "");
+        assert_eq!(alerts.take_output(later + FLASH_DURATION), "\x1b[23;0t");
+        assert_eq!(alerts.take_output(later + FLASH_DURATION * 2), "");
+
+        alerts.flash("fourth".to_string());
+        alerts.take_output(start);
+        assert_eq!(alerts.restore_title(), "\x1b[23;0t");
+        assert_eq!(alerts.restore_title(), "");
+    }
+}
@@ -0,0 +1,408 @@
+use crate::client::models::BiliMessage;
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::plugins::chat_commands::{ChatCommandHandler, DEFAULT_COOLDOWN};
+use crate::tui::command::CommandRegistry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A viewer counts as watching for this long after entering or chatting; the
+/// room does not report who is still watching
+pub const PRESENCE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Interval between watch time updates
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval between writes of a changed ledger
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Points earned for watching and chatting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointRates {
+    pub per_minute: i64,
+    /// Points per danmaku, earned at most once per `chat_interval`
+    pub per_danmu: i64,
+    pub chat_interval: Duration,
+}
+
+impl Default for PointRates {
+    fn default() -> Self {
+        Self {
+            per_minute: 1,
+            per_danmu: 2,
+            chat_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Points and activity of one viewer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewerPoints {
+    /// Name last seen
+    pub name: String,
+    pub points: i64,
+    pub watch_seconds: u64,
+    pub danmu_count: u64,
+}
+
+/// Points of the viewers of a room by uid, kept in a JSON file
+#[derive(Debug, Clone, Default)]
+pub struct PointsLedger {
+    path: Option<PathBuf>,
+    viewers: BTreeMap<u64, ViewerPoints>,
+}
+
+impl PointsLedger {
+    /// Ledger kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the ledger in `path`; a missing file is an empty ledger
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let viewers = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("invalid points file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            path: Some(path),
+            viewers,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn get(&self, uid: u64) -> Option<&ViewerPoints> {
+        self.viewers.get(&uid)
+    }
+
+    /// Viewers with the most points, highest first
+    pub fn top(&self, count: usize) -> Vec<(u64, &ViewerPoints)> {
+        let mut viewers: Vec<_> = self.viewers.iter().map(|(uid, v)| (*uid, v)).collect();
+        viewers.sort_by(|a, b| b.1.points.cmp(&a.1.points).then(a.0.cmp(&b.0)));
+        viewers.truncate(count);
+        viewers
+    }
+
+    fn viewer(&mut self, uid: u64, name: &str) -> &mut ViewerPoints {
+        let viewer = self.viewers.entry(uid).or_default();
+        if !name.is_empty() {
+            viewer.name = name.to_string();
+        }
+        viewer
+    }
+
+    /// Add `points` (negative to deduct) to a viewer, returning their total
+    pub fn award(&mut self, uid: u64, name: &str, points: i64) -> i64 {
+        let viewer = self.viewer(uid, name);
+        viewer.points += points;
+        viewer.points
+    }
+
+    /// Write the ledger back, if it has a file
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(&self.viewers)
+            .map_err(|e| format!("failed to serialize points: {}", e))?;
+        fs::File::create(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Viewer seen recently
+#[derive(Debug, Clone, Copy)]
+struct Presence {
+    last_seen: Instant,
+    /// Watch time is credited up to here
+    credited_until: Instant,
+    last_chat_award: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct LoyaltyState {
+    ledger: PointsLedger,
+    presence: HashMap<u64, Presence>,
+    last_tick: Instant,
+    last_save: Instant,
+    dirty: bool,
+}
+
+impl LoyaltyState {
+    /// Record activity of a viewer, awarding chat points for a danmaku
+    fn seen(&mut self, uid: u64, name: &str, danmu: bool, rates: &PointRates, now: Instant) {
+        let presence = self.presence.entry(uid).or_insert(Presence {
+            last_seen: now,
+            credited_until: now,
+            last_chat_award: None,
+        });
+        presence.last_seen = now;
+        let chat_points = danmu
+            && presence
+                .last_chat_award
+                .is_none_or(|last| now.duration_since(last) >= rates.chat_interval);
+        if chat_points {
+            presence.last_chat_award = Some(now);
+        }
+        let viewer = self.ledger.viewer(uid, name);
+        if danmu {
+            viewer.danmu_count += 1;
+        }
+        if chat_points {
+            viewer.points += rates.per_danmu;
+        }
+        self.dirty = true;
+    }
+
+    /// Credit watch time of the viewers present until `now`, forgetting those
+    /// not seen within the presence window
+    fn tick(&mut self, rates: &PointRates, now: Instant) {
+        self.last_tick = now;
+        for (uid, presence) in self.presence.iter_mut() {
+            let end = now.min(presence.last_seen + PRESENCE_WINDOW);
+            if end <= presence.credited_until {
+                continue;
+            }
+            let viewer = self.ledger.viewers.entry(*uid).or_default();
+            let minutes_before = viewer.watch_seconds / 60;
+            viewer.watch_seconds += (end - presence.credited_until).as_secs();
+            viewer.points += (viewer.watch_seconds / 60 - minutes_before) as i64 * rates.per_minute;
+            presence.credited_until = end;
+            self.dirty = true;
+        }
+        self.presence
+            .retain(|_, presence| now < presence.last_seen + PRESENCE_WINDOW);
+    }
+}
+
+/// A plugin awarding loyalty points for watch time and chat activity.
+///
+/// Viewers are present from entering the room (INTERACT_WORD or an entry
+/// effect) or chatting until `PRESENCE_WINDOW` after their last activity.
+/// Watch time is credited as messages arrive, and the ledger is saved every
+/// minute while it changes.
+pub struct LoyaltyHandler {
+    rates: PointRates,
+    state: Mutex<LoyaltyState>,
+}
+
+impl LoyaltyHandler {
+    pub fn new(ledger: PointsLedger, rates: PointRates) -> Self {
+        let now = Instant::now();
+        Self {
+            rates,
+            state: Mutex::new(LoyaltyState {
+                ledger,
+                presence: HashMap::new(),
+                last_tick: now,
+                last_save: now,
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Points of the viewer `uid`
+    pub fn points(&self, uid: u64) -> Option<ViewerPoints> {
+        self.state.lock().unwrap().ledger.get(uid).cloned()
+    }
+
+    /// Viewers with the most points, highest first
+    pub fn top(&self, count: usize) -> Vec<(u64, ViewerPoints)> {
+        let state = self.state.lock().unwrap();
+        state
+            .ledger
+            .top(count)
+            .into_iter()
+            .map(|(uid, viewer)| (uid, viewer.clone()))
+            .collect()
+    }
+
+    /// Add `points` (negative to deduct) to the viewer `uid`, returning their total
+    pub fn award(&self, uid: u64, name: &str, points: i64) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        state.dirty = true;
+        state.ledger.award(uid, name, points)
+    }
+
+    /// Credit watch time up to now and write the ledger
+    pub fn save(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.tick(&self.rates, Instant::now());
+        state.dirty = false;
+        state.last_save = Instant::now();
+        state.ledger.save()
+    }
+}
+
+/// Viewer activity in a message: uid, name and whether it is a danmaku
+fn activity(msg: &BiliMessage) -> Option<(u64, &str, bool)> {
+    match msg {
+        BiliMessage::Danmu { user, uid, .. } => Some((*uid, user.as_str(), true)),
+        BiliMessage::EntryEffect { uid, user, .. } | BiliMessage::ComboSend { uid, user, .. } => {
+            Some((*uid, user.as_str(), false))
+        }
+        BiliMessage::Raw(json) if json["cmd"].as_str() == Some("INTERACT_WORD") => {
+            let data = &json["data"];
+            Some((
+                data["uid"].as_u64()?,
+                data["uname"].as_str().unwrap_or(""),
+                false,
+            ))
+        }
+        _ => None,
+    }
+    .filter(|(uid, _, _)| *uid != 0)
+}
+
+impl EventHandler for LoyaltyHandler {
+    fn subscription(&self) -> Subscription {
+        // Every message drives the watch time clock
+        Subscription::All
+    }
+
+    fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if let Some((uid, name, danmu)) = activity(msg) {
+            state.seen(uid, name, danmu, &self.rates, now);
+        }
+        if now.duration_since(state.last_tick) >= TICK_INTERVAL {
+            state.tick(&self.rates, now);
+        }
+        if state.dirty && now.duration_since(state.last_save) >= SAVE_INTERVAL {
+            state.dirty = false;
+            state.last_save = now;
+            if let Err(e) = state.ledger.save() {
+                log::warn!("Failed to save loyalty points: {}", e);
+            }
+        }
+    }
+}
+
+/// Register the `!points` viewer command, answering with the viewer's points
+pub fn register_chat_command(handler: Arc<LoyaltyHandler>, commands: &mut ChatCommandHandler) {
+    commands.register("points", DEFAULT_COOLDOWN, move |call| {
+        let points = handler.points(call.uid).map_or(0, |viewer| viewer.points);
+        (call.uid != 0).then(|| format!("{} 有 {} 积分", call.user, points))
+    });
+}
+
+/// Register the `/points` and `/award` TUI commands
+pub fn register_commands(handler: Arc<LoyaltyHandler>, registry: &mut CommandRegistry) {
+    let points = Arc::clone(&handler);
+    registry.register(
+        "/points",
+        "/points [uid]",
+        "Show loyalty points of a viewer, or the top viewers",
+        move |args| {
+            let line = |uid: u64, viewer: &ViewerPoints| {
+                format!(
+                    "{} ({}): {} points, watched {} min, {} danmaku",
+                    viewer.name,
+                    uid,
+                    viewer.points,
+                    viewer.watch_seconds / 60,
+                    viewer.danmu_count
+                )
+            };
+            if args.is_empty() {
+                return Ok(points
+                    .top(10)
+                    .iter()
+                    .map(|(uid, viewer)| line(*uid, viewer))
+                    .collect());
+            }
+            let uid = args.parse(0, "Usage: /points [uid]")?;
+            match points.points(uid) {
+                Some(viewer) => Ok(vec![line(uid, &viewer)]),
+                None => Err(format!("No points recorded for {}", uid)),
+            }
+        },
+    );
+    registry.register(
+        "/award",
+        "/award <uid> <points>",
+        "Give (or with a negative number take) loyalty points",
+        move |args| {
+            let usage = "Usage: /award <uid> <points>";
+            let uid = args.parse(0, usage)?;
+            let amount = args.parse(1, usage)?;
+            let total = handler.award(uid, "", amount);
+            Ok(vec![format!("{} now has {} points", uid, total)])
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points_accrual() {
+        let rates = PointRates::default();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut state = LoyaltyState {
+            ledger: PointsLedger::new(),
+            presence: HashMap::new(),
+            last_tick: start,
+            last_save: start,
+            dirty: false,
+        };
+
+        state.seen(2, "chatty", true, &rates, at(0));
+        state.seen(2, "chatty", true, &rates, at(30));
+        state.seen(3, "lurker", false, &rates, at(0));
+        state.tick(&rates, at(90));
+        let chatty = state.ledger.get(2).unwrap();
+        // One chat award within the interval, one minute watched
+        assert_eq!((chatty.points, chatty.danmu_count), (2 + 1, 2));
+        assert_eq!(chatty.watch_seconds, 90);
+
+        state.seen(2, "chatty", true, &rates, at(100));
+        // The lurker stops counting ten minutes after entering
+        state.tick(&rates, at(20 * 60));
+        let lurker = state.ledger.get(3).unwrap();
+        assert_eq!(lurker.watch_seconds, 600);
+        assert_eq!(lurker.points, 10);
+        assert!(!state.presence.contains_key(&3));
+        assert_eq!(state.ledger.get(2).unwrap().watch_seconds, 700);
+        assert_eq!(state.ledger.get(2).unwrap().points, 4 + 11);
+
+        assert_eq!(state.ledger.award(3, "", -4), 6);
+        assert_eq!(state.ledger.get(3).unwrap().name, "lurker");
+        let top: Vec<_> = state.ledger.top(5).iter().map(|(uid, _)| *uid).collect();
+        assert_eq!(top, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_ledger_round_trip() {
+        let path = std::env::temp_dir().join(format!("blivedm-points-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut ledger = PointsLedger::load(&path).unwrap();
+        ledger.award(2, "viewer", 5);
+        ledger.save().unwrap();
+        let ledger = PointsLedger::load(&path).unwrap();
+        assert_eq!(ledger.get(2).unwrap().points, 5);
+        assert_eq!(ledger.path(), Some(path.as_path()));
+        let _ = fs::remove_file(&path);
+
+        let json = serde_json::json!({"cmd": "INTERACT_WORD", "data": {"uid": 7, "uname": "new", "msg_type": 1}});
+        assert_eq!(activity(&BiliMessage::Raw(json)), Some((7, "new", false)));
+    }
+}
@@ -1,9 +1,16 @@
+pub mod announcer;
 pub mod auto_reply;
+pub mod chat_commands;
+pub mod keyword_alert;
+pub mod loyalty;
+pub mod session_summary;
+pub mod stats;
+pub mod stats_export;
 pub mod terminal_display;
 pub mod tts;
 
 use crate::client::scheduler::EventHandler;
-use std::collections::VecDeque;
+use crate::tui::buffer::MessageBuffer;
 use std::sync::{Arc, Mutex};
 
 // Re-export danmaku sending utility for easy access
@@ -11,7 +18,7 @@ pub use auto_reply::send_danmaku_message;
 
 /// Helper to create the handler as Arc<dyn EventHandler>
 pub fn terminal_display_handler(
-    message_buffer: Arc<Mutex<VecDeque<String>>>,
+    message_buffer: Arc<Mutex<MessageBuffer>>,
 ) -> Arc<dyn EventHandler> {
     Arc::new(terminal_display::TerminalDisplayHandler::new(
         message_buffer,
@@ -0,0 +1,256 @@
+use crate::client::gift_catalog::coins_to_rmb;
+use crate::client::models::BiliMessage;
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::tui::command::CommandRegistry;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Revenue and activity totals for the current session
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSummary {
+    /// Time since the handler was created
+    pub duration: Duration,
+    /// Total gift value in gold coins
+    pub gift_value: u64,
+    /// Total guard purchase value in gold coins
+    pub guard_value: u64,
+    /// Total super chat value in CNY
+    pub super_chat_total: u64,
+    /// Number of super chats received
+    pub super_chat_count: u64,
+    /// Guard months bought during the session
+    pub new_guards: u64,
+    /// Number of distinct users who sent danmaku
+    pub unique_chatters: usize,
+    /// Number of danmaku received
+    pub danmu_count: u64,
+    /// Highest number of danmaku received within one minute
+    pub peak_messages_per_minute: u64,
+}
+
+impl SessionSummary {
+    /// Total revenue (gifts, guards and super chats) in CNY
+    pub fn total_rmb(&self) -> f64 {
+        coins_to_rmb(self.gift_value + self.guard_value) + self.super_chat_total as f64
+    }
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.duration.as_secs();
+        writeln!(
+            f,
+            "Session summary ({:02}:{:02}:{:02})",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        writeln!(
+            f,
+            "  Gift value:       ¥{:.1}",
+            coins_to_rmb(self.gift_value)
+        )?;
+        writeln!(
+            f,
+            "  Super chats:      ¥{} ({} received)",
+            self.super_chat_total, self.super_chat_count
+        )?;
+        writeln!(
+            f,
+            "  New guards:       {} (¥{:.1})",
+            self.new_guards,
+            coins_to_rmb(self.guard_value)
+        )?;
+        writeln!(f, "  Total revenue:    ¥{:.1}", self.total_rmb())?;
+        writeln!(f, "  Danmaku:          {}", self.danmu_count)?;
+        writeln!(f, "  Unique chatters:  {}", self.unique_chatters)?;
+        write!(f, "  Peak msgs/minute: {}", self.peak_messages_per_minute)
+    }
+}
+
+struct SummaryState {
+    started: Instant,
+    summary: SessionSummary,
+    chatters: HashSet<String>,
+    current_minute: u64,
+    current_minute_count: u64,
+}
+
+/// A plugin that accumulates a revenue and activity summary for the session
+pub struct SessionSummaryHandler {
+    state: Mutex<SummaryState>,
+}
+
+impl SessionSummaryHandler {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SummaryState {
+                started: Instant::now(),
+                summary: SessionSummary::default(),
+                chatters: HashSet::new(),
+                current_minute: 0,
+                current_minute_count: 0,
+            }),
+        }
+    }
+
+    /// Get a snapshot of the session summary so far
+    pub fn summary(&self) -> SessionSummary {
+        let state = self.state.lock().unwrap();
+        let mut summary = state.summary.clone();
+        summary.duration = state.started.elapsed();
+        summary
+    }
+
+    fn record_danmu(state: &mut SummaryState, user: &str, minute: u64) {
+        if minute != state.current_minute {
+            state.current_minute = minute;
+            state.current_minute_count = 0;
+        }
+        state.current_minute_count += 1;
+        state.summary.peak_messages_per_minute = state
+            .summary
+            .peak_messages_per_minute
+            .max(state.current_minute_count);
+        state.summary.danmu_count += 1;
+        if state.chatters.insert(user.to_string()) {
+            state.summary.unique_chatters = state.chatters.len();
+        }
+    }
+}
+
+/// Register `/stats`, which shows the running summary of `handler`
+pub fn register_commands(handler: Arc<SessionSummaryHandler>, registry: &mut CommandRegistry) {
+    registry.register(
+        "/stats",
+        "/stats",
+        "Show session revenue summary",
+        move |_| {
+            Ok(handler
+                .summary()
+                .to_string()
+                .lines()
+                .map(String::from)
+                .collect())
+        },
+    );
+}
+
+impl Default for SessionSummaryHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventHandler for SessionSummaryHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu", "gift", "super_chat", "guard_buy"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        let mut state = self.state.lock().unwrap();
+        match msg {
+            BiliMessage::Danmu { user, .. } => {
+                let minute = state.started.elapsed().as_secs() / 60;
+                Self::record_danmu(&mut state, user, minute);
+            }
+            BiliMessage::Gift { value, .. } => {
+                state.summary.gift_value += value;
+            }
+            BiliMessage::SuperChat { price, .. } => {
+                state.summary.super_chat_total += price;
+                state.summary.super_chat_count += 1;
+            }
+            BiliMessage::GuardBuy { num, price, .. } => {
+                state.summary.new_guards += num;
+                state.summary.guard_value += price;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> EventContext {
+        EventContext {
+            cookies: None,
+            room_id: 12345,
+            request_headers: None,
+        }
+    }
+
+    #[test]
+    fn test_session_summary_totals() {
+        let handler = SessionSummaryHandler::new();
+        let context = context();
+        for user in ["a", "b", "a"] {
+            handler.handle(
+                &BiliMessage::Danmu {
+                    user: user.to_string(),
+                    text: "hi".to_string(),
+                    uid: 0,
+                    guard_level: 0,
+                    medal: None,
+                },
+                &context,
+            );
+        }
+        handler.handle(
+            &BiliMessage::Gift {
+                user: "a".to_string(),
+                gift: "小花花".to_string(),
+                num: "5".to_string(),
+                gift_id: 31036,
+                value: 500,
+                combo_id: None,
+            },
+            &context,
+        );
+        handler.handle(
+            &BiliMessage::SuperChat {
+                id: 1,
+                user: "b".to_string(),
+                text: "hello".to_string(),
+                price: 30,
+                duration: 60,
+            },
+            &context,
+        );
+        handler.handle(
+            &BiliMessage::GuardBuy {
+                user: "c".to_string(),
+                guard_level: 3,
+                num: 1,
+                price: 198000,
+            },
+            &context,
+        );
+
+        let summary = handler.summary();
+        assert_eq!(summary.danmu_count, 3);
+        assert_eq!(summary.unique_chatters, 2);
+        assert_eq!(summary.peak_messages_per_minute, 3);
+        assert_eq!(summary.gift_value, 500);
+        assert_eq!(summary.super_chat_total, 30);
+        assert_eq!(summary.super_chat_count, 1);
+        assert_eq!(summary.new_guards, 1);
+        assert_eq!(summary.total_rmb(), 0.5 + 198.0 + 30.0);
+    }
+
+    #[test]
+    fn test_peak_messages_per_minute() {
+        let handler = SessionSummaryHandler::new();
+        let mut state = handler.state.lock().unwrap();
+        SessionSummaryHandler::record_danmu(&mut state, "a", 0);
+        SessionSummaryHandler::record_danmu(&mut state, "a", 1);
+        SessionSummaryHandler::record_danmu(&mut state, "b", 1);
+        SessionSummaryHandler::record_danmu(&mut state, "c", 2);
+        assert_eq!(state.summary.peak_messages_per_minute, 2);
+        assert_eq!(state.summary.unique_chatters, 3);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::client::chat_store::UserRef;
+use crate::client::gift_catalog::rmb_to_coins;
+use crate::client::http_server::{Request, Response};
+use crate::client::models::{BiliMessage, FanMedal};
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Path of the stats route, see `routes`
+pub const API_PATH: &str = "/api/stats";
+
+/// Chatters listed by the stats route
+const API_TOP_CHATTERS: usize = 10;
+
+/// Per-user activity collected during the session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserStats {
+    /// Latest display name
+    pub name: String,
+    /// Latest known uid, 0 if unknown
+    pub uid: u64,
+    /// Latest known guard level (0 = none)
+    pub guard_level: u8,
+    /// Latest fan medal worn in chat
+    pub medal: Option<FanMedal>,
+    /// Time of the first event from this user, relative to session start
+    pub first_seen: Duration,
+    /// Time of the latest event from this user, relative to session start
+    pub last_seen: Duration,
+    /// Number of danmaku sent
+    pub messages: u64,
+    /// Total value of gifts, guards and super chats sent, in gold coins
+    pub gift_value: u64,
+    /// Whether the user was not in the known user set when first seen
+    pub first_time: bool,
+}
+
+/// Incrementally aggregated viewer statistics for a session
+///
+/// Viewers are keyed by uid. Gifts, guards and super chats carry only a
+/// name, so they count for the uid that name last chatted with, or for the
+/// name until it does.
+#[derive(Debug, Clone)]
+pub struct ViewerStats {
+    started: Instant,
+    known_users: HashSet<UserRef>,
+    users: HashMap<UserRef, UserStats>,
+    /// Uid each name last chatted with
+    uids: HashMap<String, u64>,
+    /// Messages recorded by `BiliMessage::kind`
+    kind_counts: HashMap<&'static str, u64>,
+}
+
+/// Stats served by the stats route
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsSnapshot {
+    pub unique_chatters: usize,
+    pub first_time_chatters: usize,
+    pub total_messages: u64,
+    /// Messages by `BiliMessage::kind`
+    pub kinds: BTreeMap<&'static str, u64>,
+    /// Names and danmaku counts, most active first
+    pub top_chatters: Vec<(String, u64)>,
+}
+
+impl UserStats {
+    /// Add the activity of `other`, recorded for the same viewer
+    fn merge(&mut self, other: &UserStats) {
+        if other.last_seen >= self.last_seen {
+            self.name = other.name.clone();
+            self.medal = other.medal.clone();
+            if other.uid != 0 {
+                self.uid = other.uid;
+            }
+        }
+        if self.guard_level == 0 {
+            self.guard_level = other.guard_level;
+        }
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.messages += other.messages;
+        self.gift_value += other.gift_value;
+    }
+}
+
+impl ViewerStats {
+    pub fn new() -> Self {
+        Self::with_known_users(std::iter::empty())
+    }
+
+    /// Create stats that treat `known_users`, such as those of a `ChatStore`,
+    /// as returning viewers, so they are not counted as first-time chatters
+    pub fn with_known_users<I>(known_users: I) -> Self
+    where
+        I: IntoIterator<Item = UserRef>,
+    {
+        Self {
+            started: Instant::now(),
+            known_users: known_users.into_iter().collect(),
+            users: HashMap::new(),
+            uids: HashMap::new(),
+            kind_counts: HashMap::new(),
+        }
+    }
+
+    /// Record a message received now
+    pub fn record(&mut self, msg: &BiliMessage) {
+        let at = self.started.elapsed();
+        self.record_at(msg, at);
+    }
+
+    /// Record a message received `at` after session start
+    pub fn record_at(&mut self, msg: &BiliMessage, at: Duration) {
+        *self.kind_counts.entry(msg.kind()).or_default() += 1;
+        match msg {
+            BiliMessage::Danmu {
+                user,
+                uid,
+                guard_level,
+                medal,
+                ..
+            } => {
+                if *uid != 0 {
+                    self.link(user, *uid);
+                }
+                let entry = self.user_entry(user, *uid, at);
+                entry.messages += 1;
+                entry.guard_level = *guard_level;
+                entry.medal = medal.clone();
+            }
+            BiliMessage::Gift { user, value, .. } => {
+                self.user_entry(user, 0, at).gift_value += value
+            }
+            BiliMessage::GuardBuy {
+                user,
+                guard_level,
+                price,
+                ..
+            } => {
+                let entry = self.user_entry(user, 0, at);
+                entry.gift_value += price;
+                // Lower levels rank higher; keep the best guard the user holds
+                if entry.guard_level == 0 || *guard_level < entry.guard_level {
+                    entry.guard_level = *guard_level;
+                }
+            }
+            BiliMessage::SuperChat { user, price, .. } => {
+                self.user_entry(user, 0, at).gift_value += rmb_to_coins(*price)
+            }
+            _ => {}
+        }
+    }
+
+    /// Remember that `name` belongs to `uid`, and move what was counted for the
+    /// name alone to the uid
+    fn link(&mut self, name: &str, uid: u64) {
+        self.uids.insert(name.to_string(), uid);
+        let Some(named) = self.users.remove(&UserRef::Name(name.to_string())) else {
+            return;
+        };
+        let first_time = !self.known_users.contains(&UserRef::Uid(uid));
+        match self.users.entry(UserRef::Uid(uid)) {
+            Entry::Occupied(mut entry) => entry.get_mut().merge(&named),
+            Entry::Vacant(entry) => {
+                entry.insert(UserStats {
+                    first_time,
+                    ..named
+                });
+            }
+        }
+    }
+
+    /// Add the activity recorded in `other`, such as the stats of a later
+    /// period, to these stats
+    pub fn merge(&mut self, other: &ViewerStats) {
+        for (kind, count) in &other.kind_counts {
+            *self.kind_counts.entry(kind).or_default() += count;
+        }
+        for (key, user) in &other.users {
+            match self.users.entry(key.clone()) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge(user),
+                Entry::Vacant(entry) => {
+                    entry.insert(user.clone());
+                }
+            }
+        }
+        self.uids
+            .extend(other.uids.iter().map(|(name, uid)| (name.clone(), *uid)));
+    }
+
+    /// Key of the viewer `name` with `uid`, 0 if the message has none
+    fn key(&self, name: &str, uid: u64) -> UserRef {
+        match uid {
+            0 => self
+                .uids
+                .get(name)
+                .map_or_else(|| UserRef::Name(name.to_string()), |uid| UserRef::Uid(*uid)),
+            uid => UserRef::Uid(uid),
+        }
+    }
+
+    fn user_entry(&mut self, name: &str, uid: u64, at: Duration) -> &mut UserStats {
+        let key = self.key(name, uid);
+        let first_time = !self.known_users.contains(&key);
+        let entry = self.users.entry(key).or_insert_with(|| UserStats {
+            first_seen: at,
+            first_time,
+            ..Default::default()
+        });
+        entry.name = name.to_string();
+        if uid != 0 {
+            entry.uid = uid;
+        }
+        entry.last_seen = at;
+        entry
+    }
+
+    /// Look up the stats of a single user by name
+    pub fn user(&self, name: &str) -> Option<&UserStats> {
+        self.users.get(&self.key(name, 0))
+    }
+
+    /// Iterate over all users seen this session, with their latest names
+    pub fn users(&self) -> impl Iterator<Item = (&str, &UserStats)> {
+        self.users
+            .values()
+            .map(|stats| (stats.name.as_str(), stats))
+    }
+
+    /// Number of distinct users who sent at least one danmaku
+    pub fn unique_chatters(&self) -> usize {
+        self.chatters().count()
+    }
+
+    /// Chatters who were not known before this session
+    pub fn first_time_chatters(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .chatters()
+            .filter(|(_, stats)| stats.first_time)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Number of danmaku sent by `name`
+    pub fn messages_by(&self, name: &str) -> u64 {
+        self.user(name).map_or(0, |stats| stats.messages)
+    }
+
+    /// Total number of danmaku recorded
+    pub fn total_messages(&self) -> u64 {
+        self.users.values().map(|stats| stats.messages).sum()
+    }
+
+    /// Value of all gifts, guards and super chats recorded, in gold coins
+    pub fn gift_value(&self) -> u64 {
+        self.users.values().map(|stats| stats.gift_value).sum()
+    }
+
+    /// Number of recorded messages of `kind`, as named by `BiliMessage::kind`
+    pub fn kind_count(&self, kind: &str) -> u64 {
+        self.kind_counts.get(kind).copied().unwrap_or(0)
+    }
+
+    /// The `n` users with the most danmaku, most active first
+    pub fn top_chatters(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut chatters: Vec<(&str, u64)> = self
+            .chatters()
+            .map(|(name, stats)| (name, stats.messages))
+            .collect();
+        chatters.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        chatters.truncate(n);
+        chatters
+    }
+
+    /// Share of chatters who were still active at least `window` after their
+    /// first message, from 0.0 to 1.0
+    pub fn retention(&self, window: Duration) -> f64 {
+        let (total, retained) = self
+            .chatters()
+            .fold((0, 0), |(total, retained), (_, stats)| {
+                let stayed = stats.last_seen.saturating_sub(stats.first_seen) >= window;
+                (total + 1, retained + usize::from(stayed))
+            });
+        if total == 0 {
+            0.0
+        } else {
+            retained as f64 / total as f64
+        }
+    }
+
+    /// Totals of the session, as served by the stats route
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            unique_chatters: self.unique_chatters(),
+            first_time_chatters: self.first_time_chatters().len(),
+            total_messages: self.total_messages(),
+            kinds: self
+                .kind_counts
+                .iter()
+                .map(|(kind, count)| (*kind, *count))
+                .collect(),
+            top_chatters: self
+                .top_chatters(API_TOP_CHATTERS)
+                .into_iter()
+                .map(|(name, count)| (name.to_string(), count))
+                .collect(),
+        }
+    }
+
+    fn chatters(&self) -> impl Iterator<Item = (&str, &UserStats)> {
+        self.users().filter(|(_, stats)| stats.messages > 0)
+    }
+}
+
+/// HTTP route serving the `StatsSnapshot` of `stats` as JSON at `API_PATH`
+pub fn routes(
+    stats: Arc<Mutex<ViewerStats>>,
+) -> impl Fn(&Request) -> Option<Response> + Send + Sync + 'static {
+    move |request| {
+        if request.path != API_PATH {
+            return None;
+        }
+        Some(match stats.lock() {
+            Ok(stats) => Response::json(&stats.snapshot()),
+            Err(e) => Response::error(500, &e.to_string()),
+        })
+    }
+}
+
+impl Default for ViewerStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A plugin that feeds every message into a shared [`ViewerStats`]
+pub struct StatsHandler {
+    stats: Arc<Mutex<ViewerStats>>,
+}
+
+impl StatsHandler {
+    pub fn new(stats: Arc<Mutex<ViewerStats>>) -> Self {
+        Self { stats }
+    }
+
+    /// Shared stats that can be queried while the handler is running
+    pub fn stats(&self) -> Arc<Mutex<ViewerStats>> {
+        Arc::clone(&self.stats)
+    }
+}
+
+impl EventHandler for StatsHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu", "gift", "super_chat", "guard_buy"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.record(msg);
+        }
+    }
+
+    fn handle_batch(&self, msgs: &[BiliMessage], _context: &EventContext) {
+        if let Ok(mut stats) = self.stats.lock() {
+            for msg in msgs {
+                stats.record(msg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn danmu(user: &str, uid: u64) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: user.to_string(),
+            text: "hi".to_string(),
+            uid,
+            guard_level: 0,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_viewer_stats_counts() {
+        let mut stats = ViewerStats::with_known_users([UserRef::Uid(1)]);
+        stats.record_at(&danmu("alice", 1), Duration::from_secs(0));
+        stats.record_at(&danmu("bob", 2), Duration::from_secs(10));
+        stats.record_at(&danmu("alice", 1), Duration::from_secs(20));
+        stats.record_at(
+            &BiliMessage::Gift {
+                user: "carol".to_string(),
+                gift: "小花花".to_string(),
+                num: "1".to_string(),
+                gift_id: 31036,
+                value: 100,
+                combo_id: None,
+            },
+            Duration::from_secs(30),
+        );
+
+        assert_eq!(stats.unique_chatters(), 2);
+        assert_eq!(stats.total_messages(), 3);
+        assert_eq!(stats.messages_by("alice"), 2);
+        assert_eq!(stats.messages_by("carol"), 0);
+        assert_eq!(stats.first_time_chatters(), vec!["bob"]);
+        assert_eq!(stats.top_chatters(1), vec![("alice", 2)]);
+        assert_eq!(stats.user("carol").map(|u| u.gift_value), Some(100));
+        assert_eq!(
+            (stats.kind_count("danmu"), stats.kind_count("gift")),
+            (3, 1)
+        );
+        assert_eq!(stats.kind_count("super_chat"), 0);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.first_time_chatters, 1);
+        assert_eq!(snapshot.kinds.get("gift"), Some(&1));
+        assert_eq!(snapshot.top_chatters[0], ("alice".to_string(), 2));
+
+        let routes = routes(Arc::new(Mutex::new(stats)));
+        let get = |path: &str| {
+            routes(&Request::parse(&format!("GET {} HTTP/1.1\r\n\r\n", path)).unwrap())
+        };
+        let body: serde_json::Value = serde_json::from_str(&get(API_PATH).unwrap().body).unwrap();
+        assert_eq!(body["unique_chatters"], 2);
+        assert_eq!(body["kinds"]["danmu"], 3);
+        assert!(get("/api/other").is_none());
+    }
+
+    #[test]
+    fn test_viewer_stats_keyed_by_uid() {
+        let mut stats = ViewerStats::with_known_users([UserRef::Name("carol".to_string())]);
+        // A gift before the first danmaku is counted for the name
+        stats.record_at(
+            &BiliMessage::SuperChat {
+                id: 1,
+                user: "alice".to_string(),
+                text: "hi".to_string(),
+                price: 30,
+                duration: 60,
+            },
+            Duration::from_secs(0),
+        );
+        stats.record_at(&danmu("alice", 1), Duration::from_secs(10));
+        // Renamed, still one viewer
+        stats.record_at(&danmu("alicia", 1), Duration::from_secs(20));
+        // Same name, another viewer
+        stats.record_at(&danmu("alicia", 3), Duration::from_secs(30));
+        stats.record_at(&danmu("carol", 0), Duration::from_secs(40));
+
+        assert_eq!(stats.unique_chatters(), 3);
+        let alice = stats.users().find(|(_, user)| user.uid == 1).unwrap().1;
+        assert_eq!(alice.name, "alicia");
+        assert_eq!(alice.messages, 2);
+        assert_eq!(alice.gift_value, rmb_to_coins(30));
+        assert_eq!(alice.first_seen, Duration::from_secs(0));
+        assert_eq!(stats.first_time_chatters(), vec!["alicia", "alicia"]);
+    }
+
+    #[test]
+    fn test_viewer_stats_retention() {
+        let mut stats = ViewerStats::new();
+        assert_eq!(stats.retention(Duration::from_secs(60)), 0.0);
+
+        stats.record_at(&danmu("a", 1), Duration::from_secs(0));
+        stats.record_at(&danmu("a", 1), Duration::from_secs(120));
+        stats.record_at(&danmu("b", 2), Duration::from_secs(30));
+        assert_eq!(stats.retention(Duration::from_secs(60)), 0.5);
+    }
+}
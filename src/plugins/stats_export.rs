@@ -0,0 +1,304 @@
+use crate::client::models::BiliMessage;
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
+use crate::plugins::stats::ViewerStats;
+use crate::tui::command::CommandRegistry;
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Length of the aggregated time buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BucketSize {
+    #[default]
+    Minute,
+    Hour,
+}
+
+impl BucketSize {
+    pub fn seconds(self) -> u64 {
+        match self {
+            BucketSize::Minute => 60,
+            BucketSize::Hour => 3600,
+        }
+    }
+}
+
+impl FromStr for BucketSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minute" | "m" => Ok(BucketSize::Minute),
+            "hour" | "h" => Ok(BucketSize::Hour),
+            _ => Err(format!(
+                "unknown bucket size '{}', expected minute or hour",
+                s
+            )),
+        }
+    }
+}
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// Format given by the extension of `path`
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Ok(ExportFormat::Csv),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(ExportFormat::Json),
+            _ => Err(format!(
+                "{} is neither a .csv nor a .json file",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Activity within one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatsBucket {
+    /// Unix timestamp of the start of the bucket
+    pub start: u64,
+    /// Local time of the start of the bucket
+    pub time: String,
+    /// Danmaku received
+    pub messages: u64,
+    /// Distinct users who sent danmaku
+    pub unique_chatters: usize,
+    /// Value of gifts, guards and super chats in gold coins (1000 = 1 CNY)
+    pub gift_value: u64,
+}
+
+/// Per-minute `ViewerStats` of a session, from which coarser buckets are built
+#[derive(Debug, Default)]
+pub struct Timeline {
+    /// Stats by unix minute
+    minutes: BTreeMap<u64, ViewerStats>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `msg`, received at unix time `timestamp`
+    pub fn record(&mut self, msg: &BiliMessage, timestamp: u64) {
+        self.minutes
+            .entry(timestamp / 60)
+            .or_default()
+            .record_at(msg, Duration::from_secs(timestamp));
+    }
+
+    /// Buckets of `size` from the first to the last active one, including
+    /// empty ones in between
+    pub fn buckets(&self, size: BucketSize) -> Vec<StatsBucket> {
+        let per_bucket = size.seconds() / 60;
+        let (Some(first), Some(last)) = (self.minutes.keys().next(), self.minutes.keys().last())
+        else {
+            return Vec::new();
+        };
+        (first / per_bucket..=last / per_bucket)
+            .map(|bucket| {
+                let start_minute = bucket * per_bucket;
+                let mut stats = ViewerStats::new();
+                for (_, minute) in self.minutes.range(start_minute..start_minute + per_bucket) {
+                    stats.merge(minute);
+                }
+                let start = start_minute * 60;
+                StatsBucket {
+                    start,
+                    time: local_time(start),
+                    messages: stats.total_messages(),
+                    unique_chatters: stats.unique_chatters(),
+                    gift_value: stats.gift_value(),
+                }
+            })
+            .collect()
+    }
+}
+
+fn local_time(timestamp: u64) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// `buckets` as CSV with a header row
+pub fn to_csv(buckets: &[StatsBucket]) -> String {
+    let mut out = String::from("start,time,messages,unique_chatters,gift_value\n");
+    for bucket in buckets {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            bucket.start, bucket.time, bucket.messages, bucket.unique_chatters, bucket.gift_value
+        );
+    }
+    out
+}
+
+/// `buckets` as a pretty-printed JSON array
+pub fn to_json(buckets: &[StatsBucket]) -> String {
+    serde_json::to_string_pretty(buckets).unwrap_or_default()
+}
+
+/// A plugin recording per-minute message counts, unique chatters and gift
+/// value, exported as per-minute or per-hour CSV or JSON
+pub struct StatsExportHandler {
+    timeline: Mutex<Timeline>,
+}
+
+impl StatsExportHandler {
+    pub fn new() -> Self {
+        Self {
+            timeline: Mutex::new(Timeline::new()),
+        }
+    }
+
+    pub fn buckets(&self, size: BucketSize) -> Vec<StatsBucket> {
+        self.timeline.lock().unwrap().buckets(size)
+    }
+
+    /// Write the buckets of `size` to `path`, as CSV or JSON by its extension,
+    /// returning the number of buckets written
+    pub fn export(&self, path: &Path, size: BucketSize) -> Result<usize, String> {
+        let format = ExportFormat::from_path(path)?;
+        let buckets = self.buckets(size);
+        let contents = match format {
+            ExportFormat::Csv => to_csv(&buckets),
+            ExportFormat::Json => to_json(&buckets),
+        };
+        std::fs::write(path, contents)
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        Ok(buckets.len())
+    }
+}
+
+impl Default for StatsExportHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Register `/export [minute|hour] <path>`, which writes the buckets of
+/// `handler` to a .csv or .json file
+pub fn register_commands(handler: Arc<StatsExportHandler>, registry: &mut CommandRegistry) {
+    registry.register(
+        "/export",
+        "/export [minute|hour] <path>",
+        "Export per-minute or per-hour stats as CSV/JSON",
+        move |args| {
+            let (size, path) = match args.parts() {
+                [path] => (BucketSize::default(), *path),
+                [size, path] => (size.parse()?, *path),
+                _ => return Err("Usage: /export [minute|hour] <path>".to_string()),
+            };
+            let count = handler.export(Path::new(path), size)?;
+            Ok(vec![format!("Exported {} buckets to {}", count, path)])
+        },
+    );
+}
+
+impl EventHandler for StatsExportHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu", "gift", "super_chat", "guard_buy"])
+    }
+
+    fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.timeline.lock().unwrap().record(msg, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::gift_catalog::rmb_to_coins;
+
+    fn danmu(user: &str, uid: u64) -> BiliMessage {
+        BiliMessage::Danmu {
+            user: user.to_string(),
+            text: "hi".to_string(),
+            uid,
+            guard_level: 0,
+            medal: None,
+        }
+    }
+
+    #[test]
+    fn test_timeline_buckets() {
+        let mut timeline = Timeline::new();
+        let hour = 3600 * 100;
+        timeline.record(&danmu("a", 1), hour + 5);
+        timeline.record(&danmu("renamed", 1), hour + 50);
+        timeline.record(&danmu("b", 0), hour + 70);
+        timeline.record(
+            &BiliMessage::Gift {
+                user: "c".to_string(),
+                gift: "小花花".to_string(),
+                num: "1".to_string(),
+                gift_id: 1,
+                value: 100,
+                combo_id: None,
+            },
+            hour + 190,
+        );
+        timeline.record(
+            &BiliMessage::SuperChat {
+                id: 1,
+                user: "b".to_string(),
+                text: "hi".to_string(),
+                price: 30,
+                duration: 60,
+            },
+            hour + 200,
+        );
+        timeline.record(&danmu("a", 1), hour + 3600);
+
+        let minutes = timeline.buckets(BucketSize::Minute);
+        assert_eq!(minutes.len(), 61);
+        assert_eq!(
+            (
+                minutes[0].start,
+                minutes[0].messages,
+                minutes[0].unique_chatters
+            ),
+            (hour, 2, 1)
+        );
+        assert_eq!(minutes[2].messages, 0);
+        assert_eq!(minutes[3].gift_value, 100 + rmb_to_coins(30));
+
+        let hours = timeline.buckets(BucketSize::Hour);
+        assert_eq!(hours.len(), 2);
+        assert_eq!(
+            (
+                hours[0].messages,
+                hours[0].unique_chatters,
+                hours[0].gift_value
+            ),
+            (3, 2, 100 + rmb_to_coins(30))
+        );
+        let csv = to_csv(&hours);
+        assert!(csv.starts_with("start,time,messages,unique_chatters,gift_value\n360000,"));
+        assert_eq!(csv.lines().count(), 3);
+        let json: serde_json::Value = serde_json::from_str(&to_json(&hours)).unwrap();
+        assert_eq!(json[1]["messages"], 1);
+
+        assert!(Timeline::new().buckets(BucketSize::Hour).is_empty());
+        assert_eq!("hour".parse(), Ok(BucketSize::Hour));
+        assert!(ExportFormat::from_path(Path::new("stats.txt")).is_err());
+    }
+}
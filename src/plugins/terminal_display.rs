@@ -1,46 +1,206 @@
-use crate::client::models::BiliMessage;
+use crate::client::gift_catalog::coins_to_rmb;
+use crate::client::models::{BiliMessage, RankEntry, guard_level_name};
+use crate::client::payloads::PayloadLog;
 use crate::client::scheduler::{EventContext, EventHandler};
-use std::collections::VecDeque;
+use crate::tui::buffer::MessageBuffer;
+use crate::tui::message::TuiMessage;
+use crate::tui::mute::MuteList;
+use crate::tui::rate::MessageRate;
+use crate::tui::superchat::SuperChatBoard;
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex};
 
 /// A plugin that adds BiliMessages to a shared message buffer for TUI display.
 pub struct TerminalDisplayHandler {
     /// Shared message buffer for TUI
-    message_buffer: Arc<Mutex<VecDeque<String>>>,
+    message_buffer: Arc<Mutex<MessageBuffer>>,
     /// Shared online count for TUI title display
     online_count: Arc<AtomicU64>,
+    /// Shared chat rate tracker for the TUI title sparkline
+    message_rate: Option<Arc<Mutex<MessageRate>>>,
+    /// Shared list of locally muted users
+    mute_list: Option<Arc<Mutex<MuteList>>>,
+    /// Shared board of super chats pinned above the message list
+    super_chats: Option<Arc<Mutex<SuperChatBoard>>>,
+    /// Shared online rank for the TUI leaderboard sidebar
+    online_rank: Option<Arc<Mutex<Vec<RankEntry>>>>,
+    /// Shared stream title for the TUI title bar
+    room_title: Option<Arc<Mutex<String>>>,
+    /// Shared streamer name for the TUI title bar
+    room_streamer: Option<Arc<Mutex<String>>>,
+    /// Server JSON of the messages, for the payload inspector
+    payload_log: Option<Arc<PayloadLog>>,
 }
 
 impl TerminalDisplayHandler {
     /// Create a new TerminalDisplayHandler with a shared message buffer
-    pub fn new(message_buffer: Arc<Mutex<VecDeque<String>>>) -> Self {
+    pub fn new(message_buffer: Arc<Mutex<MessageBuffer>>) -> Self {
         Self {
             message_buffer,
             online_count: Arc::new(AtomicU64::new(0)),
+            message_rate: None,
+            mute_list: None,
+            super_chats: None,
+            online_rank: None,
+            room_title: None,
+            room_streamer: None,
+            payload_log: None,
         }
     }
 
     /// Create a new TerminalDisplayHandler with shared message buffer and online count
     pub fn with_online_count(
-        message_buffer: Arc<Mutex<VecDeque<String>>>,
+        message_buffer: Arc<Mutex<MessageBuffer>>,
         online_count: Arc<AtomicU64>,
     ) -> Self {
         Self {
             message_buffer,
             online_count,
+            message_rate: None,
+            mute_list: None,
+            super_chats: None,
+            online_rank: None,
+            room_title: None,
+            room_streamer: None,
+            payload_log: None,
         }
     }
+
+    /// Count danmaku and super chats in the shared message rate tracker
+    pub fn with_message_rate(mut self, message_rate: Arc<Mutex<MessageRate>>) -> Self {
+        self.message_rate = Some(message_rate);
+        self
+    }
+
+    /// Drop messages from users in the shared mute list
+    pub fn with_mute_list(mut self, mute_list: Arc<Mutex<MuteList>>) -> Self {
+        self.mute_list = Some(mute_list);
+        self
+    }
+
+    /// Pin super chats to the shared board for their paid duration
+    pub fn with_super_chats(mut self, super_chats: Arc<Mutex<SuperChatBoard>>) -> Self {
+        self.super_chats = Some(super_chats);
+        self
+    }
+
+    /// Keep the current stream title in a shared string
+    pub fn with_room_title(mut self, room_title: Arc<Mutex<String>>) -> Self {
+        self.room_title = Some(room_title);
+        self
+    }
+
+    /// Keep the streamer name of the room in a shared string
+    pub fn with_room_streamer(mut self, room_streamer: Arc<Mutex<String>>) -> Self {
+        self.room_streamer = Some(room_streamer);
+        self
+    }
+
+    /// Keep the latest online rank in a shared list
+    pub fn with_online_rank(mut self, online_rank: Arc<Mutex<Vec<RankEntry>>>) -> Self {
+        self.online_rank = Some(online_rank);
+        self
+    }
+
+    /// Attach the server JSON recorded by the client to each message
+    pub fn with_payload_log(mut self, payload_log: Arc<PayloadLog>) -> Self {
+        self.payload_log = Some(payload_log);
+        self
+    }
+
+    fn is_muted(&self, msg: &BiliMessage) -> bool {
+        let (user, uid) = match msg {
+            BiliMessage::Danmu { user, uid, .. } => (user, *uid),
+            BiliMessage::Gift { user, .. }
+            | BiliMessage::SuperChat { user, .. }
+            | BiliMessage::GuardBuy { user, .. } => (user, 0),
+            _ => return false,
+        };
+        self.mute_list
+            .as_ref()
+            .and_then(|list| list.lock().ok())
+            .is_some_and(|list| list.is_muted(user, uid))
+    }
 }
 
 impl EventHandler for TerminalDisplayHandler {
     fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
+        if self.is_muted(msg) {
+            return;
+        }
+
+        if let Some(rate) = &self.message_rate
+            && matches!(
+                msg,
+                BiliMessage::Danmu { .. } | BiliMessage::SuperChat { .. }
+            )
+            && let Ok(mut rate) = rate.lock()
+        {
+            rate.record();
+        }
+
+        if let Some(board) = &self.super_chats
+            && let BiliMessage::SuperChat {
+                id,
+                user,
+                text,
+                price,
+                duration,
+            } = msg
+            && let Ok(mut board) = board.lock()
+        {
+            board.pin(*id, user, text, *price, *duration);
+        }
+
+        if let Some(board) = &self.super_chats
+            && let BiliMessage::SuperChatDelete { ids } = msg
+            && let Ok(mut board) = board.lock()
+        {
+            for id in ids {
+                board.remove(*id);
+            }
+        }
+
         let formatted_msg = match msg {
-            BiliMessage::Danmu { user, text } => {
+            BiliMessage::Danmu { user, text, .. } => {
                 format!("[Danmu] {}: {}", user, text)
             }
-            BiliMessage::Gift { user, gift , num} => {
-                format!("[Gift] {} sent a gift: {} X {}", user, gift, num)
+            BiliMessage::Gift {
+                user,
+                gift,
+                num,
+                value,
+                ..
+            } => {
+                if *value > 0 {
+                    format!(
+                        "[Gift] {} sent a gift: {} X {} (¥{:.1})",
+                        user,
+                        gift,
+                        num,
+                        coins_to_rmb(*value)
+                    )
+                } else {
+                    format!("[Gift] {} sent a gift: {} X {}", user, gift, num)
+                }
+            }
+            BiliMessage::SuperChat {
+                user, text, price, ..
+            } => {
+                format!("[SuperChat] {} (¥{}): {}", user, price, text)
+            }
+            BiliMessage::GuardBuy {
+                user,
+                guard_level,
+                num,
+                ..
+            } => {
+                format!(
+                    "[Guard] {} bought {} X {}",
+                    user,
+                    guard_level_name(*guard_level),
+                    num
+                )
             }
             BiliMessage::OnlineRankCount { online_count, .. } => {
                 // Update the shared online count for TUI title display
@@ -48,6 +208,107 @@ impl EventHandler for TerminalDisplayHandler {
                 // Don't add to message buffer - just update the title counter
                 return;
             }
+            BiliMessage::OnlineRank { entries } => {
+                // Update the shared rank for the leaderboard sidebar
+                if let Some(online_rank) = &self.online_rank
+                    && let Ok(mut online_rank) = online_rank.lock()
+                {
+                    *online_rank = entries.clone();
+                }
+                return;
+            }
+            BiliMessage::SuperChatDelete { ids } => {
+                format!("[SuperChat] removed {} super chat(s)", ids.len())
+            }
+            // The gifts of a combo are shown individually
+            BiliMessage::ComboSend { .. } => return,
+            BiliMessage::DanmuAggregation { text, count } => {
+                format!("[Aggregation] {} people said: {}", count, text)
+            }
+            BiliMessage::LotteryStart {
+                prize,
+                prize_num,
+                requirement,
+                ..
+            } => {
+                format!("[Lottery] {} X {} ({})", prize, prize_num, requirement)
+            }
+            BiliMessage::LotteryAward { prize, winners, .. } => {
+                let names: Vec<&str> = winners.iter().map(|w| w.name.as_str()).collect();
+                format!("[Lottery] {} won by {}", prize, names.join(", "))
+            }
+            BiliMessage::VoiceJoinList { apply_count } => {
+                format!("[Voice] {} viewer(s) applying to join", apply_count)
+            }
+            BiliMessage::VoiceJoinSwitch { open } => {
+                let state = if *open { "opened" } else { "closed" };
+                format!("[Voice] applications {}", state)
+            }
+            BiliMessage::VoiceJoinStatus {
+                user, connected, ..
+            } => {
+                let action = if *connected { "joined" } else { "left" };
+                format!("[Voice] {} {} voice chat", user, action)
+            }
+            BiliMessage::EntryEffect { text, .. } => format!("[Entry] {}", text),
+            BiliMessage::RoomChange {
+                title,
+                area_name,
+                parent_area_name,
+                ..
+            } => {
+                if let Some(room_title) = &self.room_title
+                    && let Ok(mut room_title) = room_title.lock()
+                {
+                    *room_title = title.clone();
+                }
+                format!("[Room] {} ({} / {})", title, parent_area_name, area_name)
+            }
+            BiliMessage::RoomInfo {
+                title,
+                streamer,
+                area_name,
+                parent_area_name,
+                ..
+            } => {
+                if let Some(room_title) = &self.room_title
+                    && let Ok(mut room_title) = room_title.lock()
+                {
+                    *room_title = title.clone();
+                }
+                if let Some(room_streamer) = &self.room_streamer
+                    && let Ok(mut room_streamer) = room_streamer.lock()
+                {
+                    *room_streamer = streamer.clone();
+                }
+                format!(
+                    "[Room] {}: {} ({} / {})",
+                    streamer, title, parent_area_name, area_name
+                )
+            }
+            BiliMessage::FollowerChange {
+                followers,
+                followers_delta,
+                fans_club,
+                fans_club_delta,
+            } => format!(
+                "[Followers] {} ({:+}) | Fans club {} ({:+})",
+                followers, followers_delta, fans_club, fans_club_delta
+            ),
+            BiliMessage::StreamStarted {
+                room_id,
+                streamer,
+                title,
+                ..
+            } => format!(
+                "[Live] {} started streaming in {}: {}",
+                streamer, room_id, title
+            ),
+            BiliMessage::StreamEnded {
+                room_id, streamer, ..
+            } => format!("[Live] {} stopped streaming in {}", streamer, room_id),
+            BiliMessage::Custom { cmd, .. } => format!("[Custom] {}", cmd),
+            BiliMessage::Extension(ext) => format!("[Custom] {}", ext.cmd()),
             BiliMessage::Raw(json) => {
                 format!("[Raw] {}", json["cmd"].as_str().unwrap_or("Unknown"))
             }
@@ -55,8 +316,18 @@ impl EventHandler for TerminalDisplayHandler {
             BiliMessage::Unsupported => "[Unsupported message type]".to_string(),
         };
 
+        // Keep the server JSON for the inspector; unparsed commands carry it
+        let payload = match msg {
+            BiliMessage::Raw(json) => Some(json.clone()),
+            _ => self.payload_log.as_ref().and_then(|log| log.take(msg)),
+        };
+        let mut message = TuiMessage::new(formatted_msg).with_source(msg.clone());
+        if let Some(payload) = payload {
+            message = message.with_payload(payload);
+        }
+
         // Add message to buffer using the TuiApp helper method
-        crate::tui::app::TuiApp::add_message(&self.message_buffer, formatted_msg);
+        crate::tui::app::TuiApp::add_message(&self.message_buffer, message);
     }
 }
 
@@ -65,61 +336,153 @@ mod tests {
     use super::*;
     use crate::client::models::BiliMessage;
     use crate::client::scheduler::EventHandler;
-    use std::collections::VecDeque;
     use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_terminal_display_handler_adds_danmu() {
-        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
         let handler = TerminalDisplayHandler::new(Arc::clone(&buffer));
         let msg = BiliMessage::Danmu {
             user: "test_user".to_string(),
             text: "hello world".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
 
         let messages = buffer.lock().unwrap();
         assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0], "[Danmu] test_user: hello world");
+        assert_eq!(messages[0].text, "[Danmu] test_user: hello world");
+    }
+
+    #[test]
+    fn test_terminal_display_handler_keeps_server_json() {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
+        let log = Arc::new(PayloadLog::default());
+        let handler =
+            TerminalDisplayHandler::new(Arc::clone(&buffer)).with_payload_log(Arc::clone(&log));
+        let msg = BiliMessage::Danmu {
+            user: "test_user".to_string(),
+            text: "hello world".to_string(),
+            uid: 2,
+            guard_level: 0,
+            medal: None,
+        };
+        let json = serde_json::json!({"cmd": "DANMU_MSG", "info": [[], "hello world"]});
+        log.record(&msg, &json);
+        let context = EventContext {
+            cookies: None,
+            room_id: 12345,
+            request_headers: None,
+        };
+        handler.handle(&msg, &context);
+
+        let messages = buffer.lock().unwrap();
+        assert_eq!(messages[0].payload, Some(json));
+        assert_eq!(messages[0].source, Some(msg));
+        assert!(log.is_empty());
     }
 
     #[test]
     fn test_terminal_display_handler_adds_gift() {
-        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
         let handler = TerminalDisplayHandler::new(Arc::clone(&buffer));
         let msg = BiliMessage::Gift {
             user: "gift_user".to_string(),
             gift: "rocket".to_string(),
             num: "count".to_string(),
+            gift_id: 0,
+            value: 0,
+            combo_id: None,
+        };
+        let context = EventContext {
+            cookies: None,
+            room_id: 12345,
+            request_headers: None,
+        };
+        handler.handle(&msg, &context);
+
+        let messages = buffer.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "[Gift] gift_user sent a gift: rocket");
+    }
+
+    #[test]
+    fn test_terminal_display_handler_adds_gift_value() {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
+        let handler = TerminalDisplayHandler::new(Arc::clone(&buffer));
+        let msg = BiliMessage::Gift {
+            user: "gift_user".to_string(),
+            gift: "小花花".to_string(),
+            num: "5".to_string(),
+            gift_id: 31036,
+            value: 500,
+            combo_id: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
 
+        let messages = buffer.lock().unwrap();
+        assert_eq!(
+            messages[0].text,
+            "[Gift] gift_user sent a gift: 小花花 X 5 (¥0.5)"
+        );
+    }
+
+    #[test]
+    fn test_terminal_display_handler_skips_muted() {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
+        let mute_list = Arc::new(Mutex::new(MuteList::new(
+            vec!["spam_bot".to_string()],
+            vec![42],
+        )));
+        let handler =
+            TerminalDisplayHandler::new(Arc::clone(&buffer)).with_mute_list(Arc::clone(&mute_list));
+        let context = EventContext {
+            cookies: None,
+            room_id: 12345,
+            request_headers: None,
+        };
+        for (user, uid) in [("spam_bot", 1), ("renamed_bot", 42), ("viewer", 7)] {
+            let msg = BiliMessage::Danmu {
+                user: user.to_string(),
+                text: "hello".to_string(),
+                uid,
+                guard_level: 0,
+                medal: None,
+            };
+            handler.handle(&msg, &context);
+        }
+
         let messages = buffer.lock().unwrap();
         assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0], "[Gift] gift_user sent a gift: rocket");
+        assert_eq!(messages[0].text, "[Danmu] viewer: hello");
     }
 
     #[test]
     fn test_terminal_display_handler_adds_unsupported() {
-        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
         let handler = TerminalDisplayHandler::new(Arc::clone(&buffer));
         let msg = BiliMessage::Unsupported;
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
 
         let messages = buffer.lock().unwrap();
         assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0], "[Unsupported message type]");
+        assert_eq!(messages[0].text, "[Unsupported message type]");
     }
 }
@@ -1,5 +1,6 @@
 use crate::client::models::BiliMessage;
-use crate::client::scheduler::{EventContext, EventHandler};
+use crate::client::runtime::{AsyncRuntime, RuntimeFlavor};
+use crate::client::scheduler::{EventContext, EventHandler, Subscription};
 use base64::{Engine as _, engine::general_purpose};
 use log::{debug, error, info, warn};
 use rodio::{Decoder, OutputStream, Sink};
@@ -164,6 +165,15 @@ pub struct TtsHandler {
 impl TtsHandler {
     /// Create a new TTS handler with the specified mode
     pub fn new(mode: TtsMode) -> Self {
+        Self::spawn(mode, None)
+    }
+
+    /// Create a TTS handler whose HTTP requests run on a shared runtime
+    pub fn with_runtime(mode: TtsMode, runtime: AsyncRuntime) -> Self {
+        Self::spawn(mode, Some(runtime))
+    }
+
+    fn spawn(mode: TtsMode, runtime: Option<AsyncRuntime>) -> Self {
         let (sender, receiver) = mpsc::channel::<String>();
 
         // Clone the mode for the worker thread
@@ -172,10 +182,10 @@ impl TtsHandler {
         // Spawn worker thread to process TTS queue sequentially
         let worker_handle = thread::spawn(move || match &mode_clone {
             TtsMode::RestApi { .. } => {
-                Self::run_rest_api_worker(receiver, mode_clone);
+                Self::run_rest_api_worker(receiver, mode_clone, runtime);
             }
             TtsMode::AliTts { .. } => {
-                Self::run_ali_tts_worker(receiver, mode_clone);
+                Self::run_ali_tts_worker(receiver, mode_clone, runtime);
             }
             TtsMode::Command { .. } => {
                 Self::run_command_worker(receiver, mode_clone);
@@ -289,7 +299,11 @@ impl TtsHandler {
     }
 
     /// Worker thread for REST API TTS processing
-    fn run_rest_api_worker(receiver: std::sync::mpsc::Receiver<String>, mode: TtsMode) {
+    fn run_rest_api_worker(
+        receiver: std::sync::mpsc::Receiver<String>,
+        mode: TtsMode,
+        runtime: Option<AsyncRuntime>,
+    ) {
         if let TtsMode::RestApi {
             server_url,
             voice,
@@ -300,8 +314,11 @@ impl TtsHandler {
             volume,
         } = mode
         {
-            // Create a tokio runtime for HTTP requests
-            let rt = tokio::runtime::Runtime::new().unwrap();
+            // Without a shared runtime, HTTP requests get a single-threaded one
+            let rt = match runtime {
+                Some(runtime) => runtime,
+                None => AsyncRuntime::new(RuntimeFlavor::CurrentThread).unwrap(),
+            };
             let client = reqwest::Client::new();
 
             // Initialize audio output stream (this will be reused for all audio playback)
@@ -384,7 +401,11 @@ impl TtsHandler {
     }
 
     /// Worker thread for Alibaba DashScope TTS processing with SSE streaming
-    fn run_ali_tts_worker(receiver: std::sync::mpsc::Receiver<String>, mode: TtsMode) {
+    fn run_ali_tts_worker(
+        receiver: std::sync::mpsc::Receiver<String>,
+        mode: TtsMode,
+        runtime: Option<AsyncRuntime>,
+    ) {
         use futures::StreamExt;
 
         if let TtsMode::AliTts {
@@ -395,8 +416,11 @@ impl TtsHandler {
             volume,
         } = mode
         {
-            // Create a tokio runtime for HTTP requests
-            let rt = tokio::runtime::Runtime::new().unwrap();
+            // Without a shared runtime, HTTP requests get a single-threaded one
+            let rt = match runtime {
+                Some(runtime) => runtime,
+                None => AsyncRuntime::new(RuntimeFlavor::CurrentThread).unwrap(),
+            };
             let client = reqwest::Client::new();
 
             // Initialize audio output stream (this will be reused for all audio playback)
@@ -605,8 +629,12 @@ impl TtsHandler {
 }
 
 impl EventHandler for TtsHandler {
+    fn subscription(&self) -> Subscription {
+        Subscription::kinds(["danmu"])
+    }
+
     fn handle(&self, msg: &BiliMessage, _context: &EventContext) {
-        if let BiliMessage::Danmu { user, text } = msg {
+        if let BiliMessage::Danmu { user, text, .. } = msg {
             let message = format!("{}说：{}", user, text);
             // Send message to the queue for sequential processing
             let _ = self.sender.send(message);
@@ -629,10 +657,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "测试用户".to_string(),
             text: text.clone(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -651,10 +683,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "test_user".to_string(),
             text: "hello world".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -677,10 +713,14 @@ mod tests {
             let msg = BiliMessage::Danmu {
                 user: user.to_string(),
                 text: text.to_string(),
+                uid: 0,
+                guard_level: 0,
+                medal: None,
             };
             let context = EventContext {
                 cookies: None,
                 room_id: 12345,
+                request_headers: None,
             };
             handler.handle(&msg, &context);
         }
@@ -700,10 +740,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "test_user".to_string(),
             text: "test message".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
 
@@ -722,10 +766,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "用户".to_string(),
             text: "你好".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -741,10 +789,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "用户".to_string(),
             text: "你好".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -775,10 +827,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "test_user".to_string(),
             text: "volume test".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
 
@@ -795,6 +851,7 @@ mod tests {
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler_custom.handle(&msg, &context);
     }
@@ -807,10 +864,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "测试用户".to_string(),
             text: "你好".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -828,10 +889,14 @@ mod tests {
         let msg = BiliMessage::Danmu {
             user: "test_user".to_string(),
             text: "hello world".to_string(),
+            uid: 0,
+            guard_level: 0,
+            medal: None,
         };
         let context = EventContext {
             cookies: None,
             room_id: 12345,
+            request_headers: None,
         };
         handler.handle(&msg, &context);
     }
@@ -1,17 +1,103 @@
 // src/tui/app.rs
 //! TUI application state management
 
-use std::collections::VecDeque;
+use crate::client::chat_store::{ChatStore, UserHistory, UserRef};
+use crate::client::models::RankEntry;
+use crate::client::status::ConnectionStatus;
+use crate::plugins::keyword_alert::{KeywordMatcher, TerminalAlerts};
+use crate::plugins::stats::{UserStats, ViewerStats};
+use crate::tui::buffer::MessageBuffer;
+use crate::tui::command::{CommandRegistry, split_command};
+use crate::tui::follow::Follow;
+use crate::tui::graphics::{Graphics, ImagePlacement};
+use crate::tui::i18n::{tr, trf};
+use crate::tui::layout::{Pane, TuiLayout};
+use crate::tui::logger::LogEntry;
+use crate::tui::message::TuiMessage;
+use crate::tui::mute::MuteList;
+use crate::tui::rate::MessageRate;
+use crate::tui::superchat::{PinnedSuperChat, SuperChatBoard};
+use crate::tui::wrap::WrapCache;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
-/// Maximum number of messages to keep in buffer
-const MAX_MESSAGES: usize = 1000;
+/// Maximum number of sent inputs to keep for recall
+const MAX_INPUT_HISTORY: usize = 100;
+
+/// Number of stored messages and gifts the user info popup lists
+const USER_HISTORY_MESSAGES: usize = 5;
+
+/// Message categories for `/filter` and the prefix of their messages
+const MESSAGE_CATEGORIES: [(&str, &str); 6] = [
+    ("danmu", "[Danmu]"),
+    ("gifts", "[Gift]"),
+    ("superchat", "[SuperChat]"),
+    ("guard", "[Guard]"),
+    ("system", "[System]"),
+    ("raw", "[Raw]"),
+];
+
+/// Message kinds counted in the message list title, as (label, `BiliMessage::kind`)
+const TITLE_COUNTERS: [(&str, &str); 4] = [
+    ("D", "danmu"),
+    ("G", "gift"),
+    ("SC", "super_chat"),
+    ("GD", "guard_buy"),
+];
+
+/// Commands handled by the TUI itself, as (usage, description)
+const BUILTIN_COMMANDS: [(&str, &str); 9] = [
+    ("/help", "List available commands"),
+    ("/user <name>", "Show user info"),
+    (
+        "/mute [name|uid:<uid>]",
+        "Hide a user locally, or list muted users",
+    ),
+    ("/unmute <name|uid:<uid>>", "Show a muted user again"),
+    (
+        "/follow <name|uid>",
+        "Only show a user's messages, with context",
+    ),
+    ("/unfollow", "Show all messages again"),
+    (
+        "/filter <category> on|off",
+        "Show or hide a message category",
+    ),
+    (
+        "/save <path>",
+        "Write visible messages to a text or .jsonl file",
+    ),
+    ("/quit", "Exit app"),
+];
+
+/// Key binding style of the message pane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Keymap {
+    /// Keys type into the input box; Up/Down and PgUp/PgDn scroll
+    #[default]
+    Default,
+    /// Modal: normal mode scrolls with j/k, gg/G and searches with /; `i` starts typing
+    Vim,
+}
+
+impl std::str::FromStr for Keymap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Keymap::Default),
+            "vim" => Ok(Keymap::Vim),
+            _ => Err(format!("Unknown keymap '{}' (expected default or vim)", s)),
+        }
+    }
+}
 
 /// TUI Application state
 pub struct TuiApp {
     /// Shared message buffer (thread-safe)
-    pub message_buffer: Arc<Mutex<VecDeque<String>>>,
+    pub message_buffer: Arc<Mutex<MessageBuffer>>,
     /// Current scroll offset (0 = bottom, 1 = one line up, etc.)
     pub scroll_offset: usize,
     /// Whether auto-scroll is enabled
@@ -20,16 +106,92 @@ pub struct TuiApp {
     pub input: String,
     /// Cursor position in input
     pub cursor_position: usize,
+    /// Previously sent inputs, oldest first
+    input_history: Vec<String>,
+    /// Position in `input_history` while recalling, None when editing a new input
+    history_index: Option<usize>,
+    /// Input being edited before history recall started
+    history_draft: String,
     /// Room ID being monitored
     pub room_id: String,
+    /// Shared id of the room currently connected, overrides `room_id` once set
+    current_room: Option<Arc<AtomicU64>>,
+    /// Slash commands registered by the application and plugins
+    pub commands: CommandRegistry,
+    /// Message prefixes hidden with `/filter`
+    hidden_prefixes: HashSet<&'static str>,
+    /// Keywords highlighted in danmaku and super chats
+    highlight: KeywordMatcher,
+    /// Shared list of locally muted users
+    pub mute_list: Arc<Mutex<MuteList>>,
     /// Whether to quit the application
     pub should_quit: bool,
     /// Shared online user count (thread-safe, updated from event handler)
     pub online_count: Arc<AtomicU64>,
+    /// Shared chat message rate (thread-safe, updated from event handler)
+    pub message_rate: Arc<Mutex<MessageRate>>,
+    /// Shared super chats pinned above the message list
+    pub super_chats: Arc<Mutex<SuperChatBoard>>,
+    /// Shared online rank shown in the leaderboard sidebar
+    pub online_rank: Arc<Mutex<Vec<RankEntry>>>,
+    /// Shared stream title shown in the title bar, empty until known
+    pub room_title: Arc<Mutex<String>>,
+    /// Shared streamer name shown in the title bar, empty until known
+    pub room_streamer: Arc<Mutex<String>>,
+    /// Whether to show the leaderboard sidebar
+    pub show_rank: bool,
+    /// Shared viewer statistics used by the user info popup
+    pub viewer_stats: Option<Arc<Mutex<ViewerStats>>>,
+    /// Shared connection status shown in the status bar
+    pub connection_status: Option<Arc<Mutex<ConnectionStatus>>>,
+    /// Stored chat history, searched for the user info popup
+    pub chat_store: Option<Arc<ChatStore>>,
+    /// User shown in the user info popup
+    pub user_popup: Option<String>,
+    /// Stored history of the user in the popup, loaded when it opens
+    pub user_history: Option<UserHistory>,
+    /// Message shown in the payload inspector
+    pub inspector: Option<TuiMessage>,
+    /// Scroll offset of the payload inspector
+    pub inspector_scroll: u16,
+    /// Snapshot shown while ingestion is paused; new messages keep arriving in the buffer
+    pub paused_messages: Option<Vec<TuiMessage>>,
+    /// `MessageBuffer::pushed` when the display was paused
+    paused_pushed: u64,
+    /// Wrapped lines of rendered messages
+    pub wrap_cache: WrapCache,
     /// Whether to show raw event messages
     pub show_raw: bool,
+    /// Whether to prefix messages with their receive time
+    pub show_timestamps: bool,
     /// Shared log buffer for capturing log messages (thread-safe)
-    pub log_buffer: Arc<Mutex<VecDeque<String>>>,
+    pub log_buffer: Arc<Mutex<VecDeque<LogEntry>>>,
+    /// Most verbose log level shown in the logs panel
+    pub log_level_filter: log::LevelFilter,
+    /// Key binding style of the message pane
+    pub keymap: Keymap,
+    /// Pane arrangement and sizes
+    pub layout: TuiLayout,
+    /// User whose messages the message list is limited to
+    pub follow: Option<Follow>,
+    /// Inline images, on terminals that can show them
+    pub graphics: Option<Graphics>,
+    /// Images to draw over the message list after the current frame
+    pub image_placements: Vec<ImagePlacement>,
+    /// Bells and title flashes written between frames
+    pub alerts: Option<Arc<TerminalAlerts>>,
+    /// Pane that scroll keys move in the split view
+    pub focused_pane: Pane,
+    /// Scroll offset of the gifts and events panes in the split view (0 = bottom)
+    pub side_scroll: HashMap<Pane, usize>,
+    /// Whether the vim keymap is in insert mode
+    pub vim_insert: bool,
+    /// Whether `g` was pressed in vim normal mode, waiting for a second `g`
+    pub vim_pending_g: bool,
+    /// Active search in the current pane
+    pub log_search: Option<String>,
+    /// Search query being typed
+    pub log_search_input: Option<String>,
     /// Whether to show the logs panel
     pub show_logs: bool,
     /// Scroll offset for logs panel (0 = bottom)
@@ -41,11 +203,13 @@ pub struct TuiApp {
     /// Whether Vim-style visual selection is active
     pub visual_mode: bool,
     /// Frozen message snapshot used while visual mode is active
-    frozen_messages: Vec<String>,
+    frozen_messages: Vec<TuiMessage>,
     /// Frozen log snapshot used while visual mode is active
-    frozen_logs: Vec<String>,
+    frozen_logs: Vec<LogEntry>,
     /// Rendered wrapped lines for the active pane
     rendered_lines: Vec<String>,
+    /// Source message index of each rendered line
+    rendered_message_indices: Vec<usize>,
     /// First visible rendered line for the active pane
     rendered_start_line: usize,
     /// Visible height for the active pane
@@ -62,13 +226,13 @@ pub struct TuiApp {
 
 impl TuiApp {
     /// Create a new TUI application with shared message buffer
-    pub fn new(message_buffer: Arc<Mutex<VecDeque<String>>>, room_id: String) -> Self {
+    pub fn new(message_buffer: Arc<Mutex<MessageBuffer>>, room_id: String) -> Self {
         Self::with_online_count(message_buffer, room_id, Arc::new(AtomicU64::new(0)))
     }
 
     /// Create a new TUI application with shared message buffer and online count
     pub fn with_online_count(
-        message_buffer: Arc<Mutex<VecDeque<String>>>,
+        message_buffer: Arc<Mutex<MessageBuffer>>,
         room_id: String,
         online_count: Arc<AtomicU64>,
     ) -> Self {
@@ -78,11 +242,49 @@ impl TuiApp {
             auto_scroll: true,
             input: String::new(),
             cursor_position: 0,
+            input_history: Vec::new(),
+            history_index: None,
+            history_draft: String::new(),
             room_id,
+            current_room: None,
+            commands: CommandRegistry::new(),
+            hidden_prefixes: HashSet::new(),
+            highlight: KeywordMatcher::default(),
+            mute_list: Arc::new(Mutex::new(MuteList::default())),
             should_quit: false,
             online_count,
+            message_rate: Arc::new(Mutex::new(MessageRate::new())),
+            super_chats: Arc::new(Mutex::new(SuperChatBoard::new())),
+            online_rank: Arc::new(Mutex::new(Vec::new())),
+            room_title: Arc::new(Mutex::new(String::new())),
+            room_streamer: Arc::new(Mutex::new(String::new())),
+            show_rank: false,
+            viewer_stats: None,
+            connection_status: None,
+            chat_store: None,
+            user_popup: None,
+            user_history: None,
+            inspector: None,
+            inspector_scroll: 0,
+            paused_messages: None,
+            paused_pushed: 0,
+            wrap_cache: WrapCache::new(),
             show_raw: false,
+            show_timestamps: false,
             log_buffer: Arc::new(Mutex::new(VecDeque::new())),
+            log_level_filter: log::LevelFilter::Trace,
+            keymap: Keymap::Default,
+            layout: TuiLayout::default(),
+            follow: None,
+            graphics: None,
+            image_placements: Vec::new(),
+            alerts: None,
+            focused_pane: Pane::Chat,
+            side_scroll: HashMap::new(),
+            vim_insert: false,
+            vim_pending_g: false,
+            log_search: None,
+            log_search_input: None,
             show_logs: false,
             log_scroll_offset: 0,
             log_auto_scroll: true,
@@ -91,6 +293,7 @@ impl TuiApp {
             frozen_messages: Vec::new(),
             frozen_logs: Vec::new(),
             rendered_lines: Vec::new(),
+            rendered_message_indices: Vec::new(),
             rendered_start_line: 0,
             rendered_visible_height: 0,
             visual_anchor: 0,
@@ -110,21 +313,437 @@ impl TuiApp {
         online_count.store(count, Ordering::Relaxed);
     }
 
+    /// Set the message rate tracker (used to share with the TerminalDisplayHandler)
+    pub fn set_message_rate(&mut self, message_rate: Arc<Mutex<MessageRate>>) {
+        self.message_rate = message_rate;
+    }
+
+    /// Get the chat rate sparkline and the message count of the current bucket
+    pub fn get_message_rate(&self) -> Option<(String, u64)> {
+        let mut rate = self.message_rate.lock().ok()?;
+        Some((rate.sparkline(), rate.current()))
+    }
+
+    /// Session message counts by kind, such as `D:1532 G:87 SC:3 GD:0`
+    pub fn get_message_counters(&self) -> Option<String> {
+        let stats = self.viewer_stats.as_ref()?.lock().ok()?;
+        Some(
+            TITLE_COUNTERS
+                .iter()
+                .map(|(label, kind)| format!("{}:{}", label, stats.kind_count(kind)))
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    /// Set the pinned super chat board (used to share with the TerminalDisplayHandler)
+    pub fn set_super_chats(&mut self, super_chats: Arc<Mutex<SuperChatBoard>>) {
+        self.super_chats = super_chats;
+    }
+
+    /// Get the super chats that are still pinned
+    pub fn get_super_chats(&self) -> Vec<PinnedSuperChat> {
+        self.super_chats
+            .lock()
+            .map(|mut board| board.active())
+            .unwrap_or_default()
+    }
+
+    /// Set the online rank (used to share with the TerminalDisplayHandler)
+    pub fn set_online_rank(&mut self, online_rank: Arc<Mutex<Vec<RankEntry>>>) {
+        self.online_rank = online_rank;
+    }
+
+    /// Get the latest online rank
+    pub fn get_online_rank(&self) -> Vec<RankEntry> {
+        self.online_rank
+            .lock()
+            .map(|rank| rank.clone())
+            .unwrap_or_default()
+    }
+
+    /// Set the stream title (used to share with the TerminalDisplayHandler)
+    pub fn set_room_title(&mut self, room_title: Arc<Mutex<String>>) {
+        self.room_title = room_title;
+    }
+
+    /// Get the current stream title, empty until known
+    pub fn get_room_title(&self) -> String {
+        self.room_title
+            .lock()
+            .map(|title| title.clone())
+            .unwrap_or_default()
+    }
+
+    /// Set the streamer name (used to share with the TerminalDisplayHandler)
+    pub fn set_room_streamer(&mut self, room_streamer: Arc<Mutex<String>>) {
+        self.room_streamer = room_streamer;
+    }
+
+    /// Get the streamer name of the room, empty until known
+    pub fn get_room_streamer(&self) -> String {
+        self.room_streamer
+            .lock()
+            .map(|streamer| streamer.clone())
+            .unwrap_or_default()
+    }
+
+    /// Set the viewer statistics used by the user info popup
+    pub fn set_viewer_stats(&mut self, viewer_stats: Arc<Mutex<ViewerStats>>) {
+        self.viewer_stats = Some(viewer_stats);
+    }
+
+    /// Set the chat store whose history the user info popup shows
+    pub fn set_chat_store(&mut self, chat_store: Arc<ChatStore>) {
+        self.chat_store = Some(chat_store);
+    }
+
+    /// Set the connection status shown in the status bar
+    pub fn set_connection_status(&mut self, status: Arc<Mutex<ConnectionStatus>>) {
+        self.connection_status = Some(status);
+    }
+
+    /// Snapshot of the connection status, if the status bar is enabled
+    pub fn get_connection_status(&self) -> Option<ConnectionStatus> {
+        self.connection_status
+            .as_ref()
+            .and_then(|status| status.lock().ok().map(|status| status.clone()))
+    }
+
+    /// Open the user info popup for `name`
+    pub fn open_user_popup(&mut self, name: &str) {
+        self.show_help = false;
+        self.user_popup = Some(name.to_string());
+        self.user_history = self.chat_store.as_ref().and_then(|store| {
+            // Prefer the uid seen this session, which also covers renames
+            let user = match self.user_popup_info() {
+                Some((_, Some(stats))) if stats.uid > 0 => UserRef::Uid(stats.uid),
+                _ => UserRef::Name(name.to_string()),
+            };
+            store
+                .history(&user, USER_HISTORY_MESSAGES)
+                .map_err(|e| log::warn!("{}", e))
+                .ok()
+        });
+    }
+
+    /// Close the user info popup
+    pub fn close_user_popup(&mut self) {
+        self.user_popup = None;
+        self.user_history = None;
+    }
+
+    /// Get the user shown in the popup and their session stats, if any
+    pub fn user_popup_info(&self) -> Option<(String, Option<UserStats>)> {
+        let name = self.user_popup.clone()?;
+        let stats = self
+            .viewer_stats
+            .as_ref()
+            .and_then(|stats| stats.lock().ok()?.user(&name).cloned());
+        Some((name, stats))
+    }
+
+    /// Get the sender of the message under the pane cursor
+    pub fn user_at_cursor(&self) -> Option<String> {
+        if self.show_logs {
+            return None;
+        }
+
+        let message = self.message_at_cursor()?;
+        message_sender(&message.text).map(str::to_string)
+    }
+
+    /// Open the payload inspector for the message under the pane cursor
+    pub fn open_inspector(&mut self) {
+        if let Some(message) = self.message_at_cursor() {
+            self.show_help = false;
+            self.inspector = Some(message);
+            self.inspector_scroll = 0;
+        }
+    }
+
+    /// Close the payload inspector
+    pub fn close_inspector(&mut self) {
+        self.inspector = None;
+    }
+
+    /// Scroll the payload inspector up
+    pub fn inspector_up(&mut self, amount: u16) {
+        self.inspector_scroll = self.inspector_scroll.saturating_sub(amount);
+    }
+
+    /// Scroll the payload inspector down
+    pub fn inspector_down(&mut self, amount: u16) {
+        self.inspector_scroll = self.inspector_scroll.saturating_add(amount);
+    }
+
+    /// Get the message under the pane cursor
+    pub fn message_at_cursor(&self) -> Option<TuiMessage> {
+        let cursor = self.pane_cursor()?;
+        let index = self.rendered_message_indices.get(cursor).copied()?;
+        self.get_messages().into_iter().nth(index)
+    }
+
+    /// Share the id of the connected room so room switches show up in the title
+    pub fn set_current_room(&mut self, current_room: Arc<AtomicU64>) {
+        self.current_room = Some(current_room);
+    }
+
+    /// Get the id of the room being monitored
+    pub fn get_room_id(&self) -> String {
+        match self
+            .current_room
+            .as_ref()
+            .map(|room| room.load(Ordering::Relaxed))
+        {
+            Some(room) if room > 0 => room.to_string(),
+            _ => self.room_id.clone(),
+        }
+    }
+
+    /// Set the keywords highlighted in the message list
+    pub fn set_highlight(&mut self, highlight: KeywordMatcher) {
+        self.highlight = highlight;
+    }
+
+    /// Whether a danmaku or super chat contains a highlight keyword
+    pub fn is_highlighted(&self, msg: &str) -> bool {
+        ["[Danmu] ", "[SuperChat] "]
+            .iter()
+            .find_map(|prefix| msg.strip_prefix(prefix))
+            .is_some_and(|text| self.highlight.matches(text))
+    }
+
+    /// Show emotes, and optionally avatars, as images in the message list
+    pub fn set_graphics(&mut self, graphics: Graphics) {
+        self.graphics = Some(graphics);
+    }
+
+    /// Write the alerts of a `KeywordAlertHandler` to the terminal
+    pub fn set_alerts(&mut self, alerts: Arc<TerminalAlerts>) {
+        self.alerts = Some(alerts);
+    }
+
+    /// Set the mute list (used to share with the TerminalDisplayHandler)
+    pub fn set_mute_list(&mut self, mute_list: Arc<Mutex<MuteList>>) {
+        self.mute_list = mute_list;
+    }
+
+    /// Whether a message passes the raw toggle, mute list and `/filter` settings
+    pub fn is_message_visible(&self, msg: &str) -> bool {
+        if !self.show_raw && msg.starts_with("[Raw]") {
+            return false;
+        }
+        // Messages received before a user was muted are still in the buffer
+        if let Some(sender) = message_sender(msg)
+            && self
+                .mute_list
+                .lock()
+                .is_ok_and(|list| list.is_name_muted(sender))
+        {
+            return false;
+        }
+        !self
+            .hidden_prefixes
+            .iter()
+            .any(|prefix| msg.starts_with(prefix))
+    }
+
+    /// Run a slash command, posting its feedback as `[System]` messages.
+    /// Returns false if `input` is not a command.
+    pub fn run_command(&mut self, input: &str) -> bool {
+        if !input.starts_with('/') {
+            return false;
+        }
+
+        let (name, args) = split_command(input);
+        let output = match name {
+            "/quit" | "/exit" => {
+                self.quit();
+                Ok(Vec::new())
+            }
+            "/help" => Ok(self.command_help()),
+            "/user" if !args.trim().is_empty() => {
+                self.open_user_popup(args.trim());
+                Ok(Vec::new())
+            }
+            "/user" => Err(trf("Usage: {}", &[&"/user <name>"])),
+            "/filter" => self.filter_command(args),
+            "/follow" if !args.trim().is_empty() => {
+                let follow = Follow::parse(args.trim());
+                let message = trf("Following {} (/unfollow or Esc to stop)", &[&follow.entry]);
+                self.follow = Some(follow);
+                self.scroll_to_bottom();
+                Ok(vec![message])
+            }
+            "/follow" => Err(trf("Usage: {}", &[&"/follow <name|uid>"])),
+            "/unfollow" => match self.follow.take() {
+                Some(follow) => Ok(vec![trf("Stopped following {}", &[&follow.entry])]),
+                None => Err(tr("Not following anyone").to_string()),
+            },
+            "/mute" => self.mute_command(args.trim(), true),
+            "/unmute" => self.mute_command(args.trim(), false),
+            "/save" if !args.trim().is_empty() => self.save_command(args.trim()),
+            "/save" => Err(trf("Usage: {}", &[&"/save <path>"])),
+            _ => self
+                .commands
+                .execute(input)
+                .unwrap_or_else(|| Err(trf("Unknown command: {} (try /help)", &[&name]))),
+        };
+
+        let lines = output.unwrap_or_else(|e| vec![e]);
+        for line in lines {
+            Self::add_message(&self.message_buffer, format!("[System] {}", line));
+        }
+        true
+    }
+
+    fn command_help(&self) -> Vec<String> {
+        let builtins = BUILTIN_COMMANDS
+            .iter()
+            .map(|(usage, description)| (usage.to_string(), tr(description).to_string()));
+        let registered = self
+            .commands
+            .specs()
+            .map(|spec| (spec.usage.clone(), spec.description.clone()));
+        let mut lines = vec![tr("Commands:").to_string()];
+        lines.extend(
+            builtins
+                .chain(registered)
+                .map(|(usage, description)| format!("  {:<28} {}", usage, description)),
+        );
+        lines
+    }
+
+    fn mute_command(&mut self, entry: &str, mute: bool) -> Result<Vec<String>, String> {
+        let mut list = self
+            .mute_list
+            .lock()
+            .map_err(|_| tr("Mute list is unavailable").to_string())?;
+        if entry.is_empty() {
+            if !mute {
+                return Err(trf("Usage: {}", &[&"/unmute <name|uid:<uid>>"]));
+            }
+            return Ok(vec![if list.is_empty() {
+                tr("No muted users").to_string()
+            } else {
+                trf("Muted: {}", &[&list.entries().join(", ")])
+            }]);
+        }
+
+        let changed = if mute {
+            list.mute(entry)
+        } else {
+            list.unmute(entry)
+        };
+        let message = match (mute, changed) {
+            (true, true) => trf("Muted {}", &[&entry]),
+            (true, false) => trf("{} is already muted", &[&entry]),
+            (false, true) => trf("Unmuted {}", &[&entry]),
+            (false, false) => trf("{} is not muted", &[&entry]),
+        };
+        Ok(vec![message])
+    }
+
+    fn filter_command(&mut self, args: &str) -> Result<Vec<String>, String> {
+        let usage = || {
+            trf(
+                "Usage: {}",
+                &[&"/filter <danmu|gifts|superchat|guard|system|raw> on|off"],
+            )
+        };
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let (category, visible) = match parts.as_slice() {
+            [] => {
+                let states = MESSAGE_CATEGORIES
+                    .iter()
+                    .map(|(category, prefix)| {
+                        let visible = if *category == "raw" {
+                            self.show_raw
+                        } else {
+                            !self.hidden_prefixes.contains(prefix)
+                        };
+                        format!("{}:{}", category, if visible { "on" } else { "off" })
+                    })
+                    .collect::<Vec<_>>();
+                return Ok(vec![trf("Filters: {}", &[&states.join(" ")])]);
+            }
+            [category, "on"] => (*category, true),
+            [category, "off"] => (*category, false),
+            _ => return Err(usage()),
+        };
+
+        let (category, prefix) = MESSAGE_CATEGORIES
+            .iter()
+            .find(|(name, _)| *name == category)
+            .ok_or_else(usage)?;
+        if *category == "raw" {
+            self.show_raw = visible;
+        } else if visible {
+            self.hidden_prefixes.remove(prefix);
+        } else {
+            self.hidden_prefixes.insert(prefix);
+        }
+        let message = if visible {
+            "{} messages shown"
+        } else {
+            "{} messages hidden"
+        };
+        Ok(vec![trf(message, &[category])])
+    }
+
+    /// Write the currently visible messages to `path`, as JSON lines if it ends in `.jsonl`
+    fn save_command(&self, path: &str) -> Result<Vec<String>, String> {
+        let messages: Vec<TuiMessage> = self
+            .get_messages()
+            .into_iter()
+            .filter(|msg| self.is_message_visible(&msg.text))
+            .collect();
+        let jsonl = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jsonl"));
+
+        let mut contents = String::new();
+        for msg in &messages {
+            if jsonl {
+                contents.push_str(&msg.to_json().to_string());
+            } else {
+                contents.push_str(&msg.display_text(true));
+            }
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| trf("Failed to save messages to {}: {}", &[&path, &e]))?;
+
+        Ok(vec![trf(
+            "Saved {} message(s) to {}",
+            &[&messages.len(), &path],
+        )])
+    }
+
     /// Add a message to the buffer (called from event handler)
-    pub fn add_message(buffer: &Arc<Mutex<VecDeque<String>>>, message: String) {
+    pub fn add_message(buffer: &Arc<Mutex<MessageBuffer>>, message: impl Into<TuiMessage>) {
         if let Ok(mut messages) = buffer.lock() {
-            messages.push_back(message);
-            while messages.len() > MAX_MESSAGES {
-                messages.pop_front();
-            }
+            messages.push(message.into());
         }
     }
 
+    /// Buffered message count, buffer capacity and number of evicted messages
+    pub fn buffer_stats(&self) -> (usize, usize, u64) {
+        self.message_buffer
+            .lock()
+            .map(|messages| (messages.len(), messages.capacity(), messages.evicted()))
+            .unwrap_or_default()
+    }
+
     /// Get messages for display (returns a copy of the buffer)
-    pub fn get_messages(&self) -> Vec<String> {
+    pub fn get_messages(&self) -> Vec<TuiMessage> {
         if self.visual_mode {
             return self.frozen_messages.clone();
         }
+        if let Some(paused) = &self.paused_messages {
+            return paused.clone();
+        }
 
         if let Ok(messages) = self.message_buffer.lock() {
             messages.iter().cloned().collect()
@@ -133,6 +752,33 @@ impl TuiApp {
         }
     }
 
+    /// Freeze the displayed messages, or show everything received meanwhile
+    pub fn toggle_pause(&mut self) {
+        if self.paused_messages.take().is_none()
+            && let Ok(messages) = self.message_buffer.lock()
+        {
+            self.paused_messages = Some(messages.iter().cloned().collect());
+            self.paused_pushed = messages.pushed();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_messages.is_some()
+    }
+
+    /// Number of messages received since the display was paused
+    pub fn paused_new_messages(&self) -> usize {
+        if !self.is_paused() {
+            return 0;
+        }
+        // Counted with the push total, as the buffer may have evicted the
+        // messages shown when pausing
+        self.message_buffer
+            .lock()
+            .map(|messages| messages.pushed().saturating_sub(self.paused_pushed) as usize)
+            .unwrap_or(0)
+    }
+
     /// Get the number of messages in buffer
     pub fn message_count(&self) -> usize {
         if let Ok(messages) = self.message_buffer.lock() {
@@ -142,6 +788,14 @@ impl TuiApp {
         }
     }
 
+    /// Id of the newest buffered message; unlike the count it changes even when the buffer is full
+    pub fn latest_message_id(&self) -> Option<u64> {
+        self.message_buffer
+            .lock()
+            .ok()
+            .and_then(|messages| messages.back().map(|msg| msg.id))
+    }
+
     /// Scroll up (increase offset)
     pub fn scroll_up(&mut self, amount: usize) {
         let max_offset = self.message_count().saturating_sub(1);
@@ -212,14 +866,62 @@ impl TuiApp {
             .unwrap_or(self.input.len())
     }
 
-    /// Get current input and clear it
+    /// Get current input and clear it, recording it in the input history
     pub fn take_input(&mut self) -> String {
         let input = self.input.clone();
         self.input.clear();
         self.cursor_position = 0;
+        self.history_index = None;
+        self.history_draft.clear();
+
+        if !input.is_empty() && self.input_history.last() != Some(&input) {
+            self.input_history.push(input.clone());
+            if self.input_history.len() > MAX_INPUT_HISTORY {
+                self.input_history.remove(0);
+            }
+        }
+
         input
     }
 
+    /// Replace the input with the previous history entry
+    pub fn history_prev(&mut self) {
+        if self.input_history.is_empty() {
+            return;
+        }
+
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => {
+                self.history_draft = self.input.clone();
+                self.input_history.len() - 1
+            }
+        };
+        self.history_index = Some(index);
+        self.set_input(self.input_history[index].clone());
+    }
+
+    /// Replace the input with the next history entry, or the draft past the newest one
+    pub fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+
+        if index + 1 < self.input_history.len() {
+            self.history_index = Some(index + 1);
+            self.set_input(self.input_history[index + 1].clone());
+        } else {
+            self.history_index = None;
+            let draft = std::mem::take(&mut self.history_draft);
+            self.set_input(draft);
+        }
+    }
+
+    fn set_input(&mut self, input: String) {
+        self.cursor_position = input.chars().count();
+        self.input = input;
+    }
+
     /// Quit the application
     pub fn quit(&mut self) {
         self.should_quit = true;
@@ -230,6 +932,15 @@ impl TuiApp {
         self.show_raw = !self.show_raw;
     }
 
+    /// Toggle receive time display
+    pub fn toggle_show_rank(&mut self) {
+        self.show_rank = !self.show_rank;
+    }
+
+    pub fn toggle_show_timestamps(&mut self) {
+        self.show_timestamps = !self.show_timestamps;
+    }
+
     /// Toggle logs panel visibility
     pub fn toggle_show_logs(&mut self) {
         self.show_logs = !self.show_logs;
@@ -254,6 +965,14 @@ impl TuiApp {
         }
     }
 
+    /// Newest log line, to notice new logs when the log buffer is full
+    pub fn latest_log(&self) -> Option<String> {
+        self.log_buffer
+            .lock()
+            .ok()
+            .and_then(|logs| logs.back().map(|entry| entry.text.clone()))
+    }
+
     /// Scroll logs up (increase offset)
     pub fn log_scroll_up(&mut self, amount: usize) {
         let max_offset = self.log_message_count().saturating_sub(1);
@@ -277,19 +996,217 @@ impl TuiApp {
         self.log_auto_scroll = true;
     }
 
+    /// Select the key binding style; the vim keymap starts in normal mode
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+        self.vim_insert = false;
+        self.vim_pending_g = false;
+    }
+
+    /// Set the pane arrangement and sizes
+    pub fn set_layout(&mut self, layout: TuiLayout) {
+        self.layout = layout;
+        if self.is_read_only() {
+            self.input.clear();
+            self.cursor_position = 0;
+            self.vim_insert = false;
+        }
+    }
+
+    /// Switch between the single message list and separate chat, gifts and events panes
+    pub fn toggle_split_panes(&mut self) {
+        self.layout.split = !self.layout.split;
+        self.focused_pane = Pane::Chat;
+        self.side_scroll.clear();
+    }
+
+    /// Move scrolling to the next pane of the split view
+    pub fn focus_next_pane(&mut self) {
+        if self.layout.split {
+            self.focused_pane = self.focused_pane.next();
+        }
+    }
+
+    /// Whether a message belongs in the main message list, which only holds
+    /// danmaku in the split view
+    pub fn is_main_pane_message(&self, msg: &str) -> bool {
+        !self.layout.split || Pane::of(msg) == Pane::Chat
+    }
+
+    /// Side pane of the split view that scroll keys move, if one is focused
+    fn focused_side_pane(&self) -> Option<Pane> {
+        (self.layout.split && !self.show_logs && self.focused_pane != Pane::Chat)
+            .then_some(self.focused_pane)
+    }
+
+    /// Whether the input box is hidden, so nothing can be typed or sent
+    pub fn is_read_only(&self) -> bool {
+        !self.layout.show_input
+    }
+
+    /// Whether keys are handled by vim normal mode in the message pane
+    pub fn is_vim_normal(&self) -> bool {
+        self.keymap == Keymap::Vim && !self.vim_insert && !self.show_logs
+    }
+
+    /// Whether the vim keymap is typing into the input box
+    pub fn is_vim_insert(&self) -> bool {
+        self.keymap == Keymap::Vim && self.vim_insert && !self.show_logs
+    }
+
+    /// Switch the vim keymap between insert and normal mode
+    pub fn set_vim_insert(&mut self, insert: bool) {
+        self.vim_insert = insert && !self.is_read_only();
+        self.vim_pending_g = false;
+    }
+
+    /// Start typing a search in the current pane
+    pub fn start_log_search(&mut self) {
+        self.log_search_input = Some(String::new());
+    }
+
+    /// Type a character into the search
+    pub fn log_search_push(&mut self, c: char) {
+        if let Some(input) = &mut self.log_search_input {
+            input.push(c);
+        }
+    }
+
+    /// Delete the last character of the search
+    pub fn log_search_pop(&mut self) {
+        if let Some(input) = &mut self.log_search_input {
+            input.pop();
+        }
+    }
+
+    /// Stop typing without changing the active search
+    pub fn cancel_log_search(&mut self) {
+        self.log_search_input = None;
+    }
+
+    /// Make the typed query the active search and jump to its newest match above the cursor
+    pub fn submit_log_search(&mut self) {
+        let Some(query) = self.log_search_input.take() else {
+            return;
+        };
+        if query.is_empty() {
+            self.log_search = None;
+            return;
+        }
+        self.log_search = Some(query);
+        if let Some(cursor) = self.pane_cursor() {
+            // Search up from just below the cursor so a match on the cursor line counts
+            self.pane_cursor = cursor + 1;
+        }
+        self.log_search_jump(false);
+    }
+
+    /// Clear the active search
+    pub fn clear_log_search(&mut self) {
+        self.log_search = None;
+    }
+
+    /// Texts of the entries in the current pane, indexed like the rendered line indices
+    fn pane_entry_texts(&self) -> Vec<String> {
+        if self.show_logs {
+            self.get_log_messages()
+                .into_iter()
+                .map(|entry| entry.text)
+                .collect()
+        } else {
+            self.get_messages()
+                .into_iter()
+                .map(|msg| msg.text)
+                .collect()
+        }
+    }
+
+    /// Number of rendered entries in the current pane matching the active search
+    pub fn log_search_matches(&self) -> usize {
+        let Some(query) = &self.log_search else {
+            return 0;
+        };
+        let query = query.to_ascii_lowercase();
+        let texts = self.pane_entry_texts();
+        let mut entries = self.rendered_message_indices.clone();
+        entries.dedup();
+        entries
+            .into_iter()
+            .filter(|&entry| {
+                texts
+                    .get(entry)
+                    .is_some_and(|text| text.to_ascii_lowercase().contains(&query))
+            })
+            .count()
+    }
+
+    /// Move the pane cursor to the next (`forward`) or previous entry matching the search
+    pub fn log_search_jump(&mut self, forward: bool) {
+        let Some(query) = &self.log_search else {
+            return;
+        };
+        if self.visual_mode || self.rendered_lines.is_empty() {
+            return;
+        }
+
+        let query = query.to_ascii_lowercase();
+        let texts = self.pane_entry_texts();
+        // First rendered line of every matching entry
+        let matches: Vec<usize> = self
+            .rendered_message_indices
+            .iter()
+            .enumerate()
+            .filter(|&(line, &entry)| {
+                (line == 0 || self.rendered_message_indices[line - 1] != entry)
+                    && texts
+                        .get(entry)
+                        .is_some_and(|text| text.to_ascii_lowercase().contains(&query))
+            })
+            .map(|(line, _)| line)
+            .collect();
+
+        let cursor = self.pane_cursor;
+        let target = if forward {
+            matches
+                .iter()
+                .find(|&&line| line > cursor)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|&&line| line < cursor)
+                .or(matches.last())
+        };
+        if let Some(&line) = target {
+            self.pane_cursor = line;
+            self.pane_cursor_initialized = true;
+            self.sync_pane_view();
+        }
+    }
+
     /// Set the log buffer (used to share with the TuiLogger)
-    pub fn set_log_buffer(&mut self, log_buffer: Arc<Mutex<VecDeque<String>>>) {
+    pub fn set_log_buffer(&mut self, log_buffer: Arc<Mutex<VecDeque<LogEntry>>>) {
         self.log_buffer = log_buffer;
     }
 
-    /// Get log messages for display
-    pub fn get_log_messages(&self) -> Vec<String> {
+    /// Show only log entries at `level` or more severe
+    pub fn set_log_level_filter(&mut self, level: log::LevelFilter) {
+        self.log_level_filter = level;
+        self.log_scroll_to_bottom();
+    }
+
+    /// Get log messages for display, filtered by the level filter
+    pub fn get_log_messages(&self) -> Vec<LogEntry> {
         if self.visual_mode {
             return self.frozen_logs.clone();
         }
 
         if let Ok(logs) = self.log_buffer.lock() {
-            logs.iter().cloned().collect()
+            logs.iter()
+                .filter(|entry| entry.level <= self.log_level_filter)
+                .cloned()
+                .collect()
         } else {
             Vec::new()
         }
@@ -299,6 +1216,7 @@ impl TuiApp {
     pub fn set_rendered_lines(
         &mut self,
         lines: Vec<String>,
+        message_indices: Vec<usize>,
         start_line: usize,
         visible_height: usize,
     ) -> usize {
@@ -314,6 +1232,7 @@ impl TuiApp {
             && self.active_auto_scroll();
 
         self.rendered_lines = lines;
+        self.rendered_message_indices = message_indices;
         self.rendered_start_line = start_line;
         self.rendered_visible_height = visible_height.max(1);
 
@@ -345,16 +1264,9 @@ impl TuiApp {
             return;
         }
 
-        self.frozen_messages = self
-            .message_buffer
-            .lock()
-            .map(|messages| messages.iter().cloned().collect())
-            .unwrap_or_default();
-        self.frozen_logs = self
-            .log_buffer
-            .lock()
-            .map(|logs| logs.iter().cloned().collect())
-            .unwrap_or_default();
+        // Keep the paused snapshot if the display is paused
+        self.frozen_messages = self.get_messages();
+        self.frozen_logs = self.get_log_messages();
         self.show_help = false;
         self.visual_mode = true;
         self.visual_cursor = self.pane_cursor;
@@ -380,6 +1292,11 @@ impl TuiApp {
 
     /// Move the normal pane cursor up
     pub fn pane_up(&mut self, amount: usize) {
+        if let Some(pane) = self.focused_side_pane() {
+            let offset = self.side_scroll.entry(pane).or_default();
+            *offset = offset.saturating_add(amount);
+            return;
+        }
         if self.visual_mode || self.rendered_lines.is_empty() {
             return;
         }
@@ -390,6 +1307,11 @@ impl TuiApp {
 
     /// Move the normal pane cursor down
     pub fn pane_down(&mut self, amount: usize) {
+        if let Some(pane) = self.focused_side_pane() {
+            let offset = self.side_scroll.entry(pane).or_default();
+            *offset = offset.saturating_sub(amount);
+            return;
+        }
         if self.visual_mode || self.rendered_lines.is_empty() {
             return;
         }
@@ -401,6 +1323,11 @@ impl TuiApp {
 
     /// Jump the normal pane cursor to the first line
     pub fn pane_top(&mut self) {
+        if let Some(pane) = self.focused_side_pane() {
+            // Clamped to the first line when rendered
+            self.side_scroll.insert(pane, usize::MAX);
+            return;
+        }
         if self.visual_mode || self.rendered_lines.is_empty() {
             return;
         }
@@ -411,6 +1338,10 @@ impl TuiApp {
 
     /// Jump the normal pane cursor to the last line
     pub fn pane_bottom(&mut self) {
+        if let Some(pane) = self.focused_side_pane() {
+            self.side_scroll.insert(pane, 0);
+            return;
+        }
         if self.visual_mode || self.rendered_lines.is_empty() {
             return;
         }
@@ -490,10 +1421,37 @@ impl TuiApp {
         }
     }
 
-    /// Return the selected text from the current visual range
+    /// Return the full text of every message touched by the current visual range,
+    /// one message per line
     pub fn selected_text(&self) -> Option<String> {
         let (start, end) = self.visual_range()?;
-        Some(self.rendered_lines[start..=end].join("\n"))
+        let first = self.rendered_message_indices.get(start).copied()?;
+        let last = self.rendered_message_indices.get(end).copied()?;
+        let messages: Vec<String> = (first..=last)
+            .filter_map(|message| self.message_text(message))
+            .collect();
+        Some(messages.join("\n"))
+    }
+
+    /// Number of messages touched by the current visual range
+    pub fn selected_message_count(&self) -> usize {
+        let Some((start, end)) = self.visual_range() else {
+            return 0;
+        };
+        let mut indices = self.rendered_message_indices[start..=end].to_vec();
+        indices.dedup();
+        indices.len()
+    }
+
+    fn message_text(&self, message: usize) -> Option<String> {
+        let text: String = self
+            .rendered_message_indices
+            .iter()
+            .zip(&self.rendered_lines)
+            .filter(|(index, _)| **index == message)
+            .map(|(_, line)| line.as_str())
+            .collect();
+        (!text.is_empty()).then_some(text)
     }
 
     fn initial_visible_cursor(&self) -> usize {
@@ -571,3 +1529,211 @@ impl TuiApp {
         }
     }
 }
+
+/// Extract the sender name from a formatted chat message line
+fn message_sender(line: &str) -> Option<&str> {
+    if let Some(rest) = line.strip_prefix("[Danmu] ") {
+        rest.split_once(": ").map(|(user, _)| user)
+    } else if let Some(rest) = line.strip_prefix("[Gift] ") {
+        rest.split_once(" sent a gift: ").map(|(user, _)| user)
+    } else if let Some(rest) = line.strip_prefix("[SuperChat] ") {
+        rest.split_once(" (¥").map(|(user, _)| user)
+    } else if let Some(rest) = line.strip_prefix("[Guard] ") {
+        rest.split_once(" bought ").map(|(user, _)| user)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with(messages: &[&str]) -> TuiApp {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
+        for msg in messages {
+            TuiApp::add_message(&buffer, *msg);
+        }
+        TuiApp::new(buffer, "1".to_string())
+    }
+
+    fn system_lines(app: &TuiApp) -> Vec<String> {
+        app.get_messages()
+            .into_iter()
+            .filter_map(|msg| msg.text.strip_prefix("[System] ").map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_run_command() {
+        let mut app = app_with(&["[Danmu] alice: hi", "[Gift] bob sent a gift: 小花花 X 1"]);
+        assert!(!app.run_command("hello"));
+
+        assert!(app.run_command("/filter gifts off"));
+        assert!(!app.is_message_visible("[Gift] bob sent a gift: 小花花 X 1"));
+        assert!(app.run_command("/mute alice"));
+        assert!(!app.is_message_visible("[Danmu] alice: hi"));
+        assert!(app.run_command("/unmute alice"));
+        assert!(app.is_message_visible("[Danmu] alice: hi"));
+        assert!(app.run_command("/filter"));
+        assert!(app.run_command("/user"));
+        assert_eq!(
+            system_lines(&app),
+            vec![
+                "gifts messages hidden",
+                "Muted alice",
+                "Unmuted alice",
+                "Filters: danmu:on gifts:off superchat:on guard:on system:on raw:off",
+                "Usage: /user <name>",
+            ]
+        );
+
+        app.commands
+            .register("/echo", "/echo <text>", "Echo text", |args| {
+                Ok(vec![args.raw().to_string()])
+            });
+        assert!(app.run_command("/echo hi there"));
+        assert_eq!(system_lines(&app).last().unwrap(), "hi there");
+        assert!(app.run_command("/quit"));
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let mut app = app_with(&[]);
+        // Not sent as a danmaku; the error says how to list the commands
+        assert!(app.run_command("/banana 1"));
+        assert_eq!(
+            system_lines(&app),
+            vec!["Unknown command: /banana (try /help)"]
+        );
+    }
+
+    #[test]
+    fn test_save_command() {
+        let mut app = app_with(&["[Danmu] alice: hi", "[Gift] bob sent a gift: 小花花 X 1"]);
+        app.run_command("/filter gifts off");
+        let dir = std::env::temp_dir().join(format!("blivedm-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text_path = dir.join("chat.txt");
+        let jsonl_path = dir.join("chat.jsonl");
+
+        app.run_command(&format!("/save {}", text_path.display()));
+        app.run_command(&format!("/save {}", jsonl_path.display()));
+        let text = std::fs::read_to_string(&text_path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        // The gift is filtered out; the feedback of the first /save is saved by the second
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" [Danmu] alice: hi"));
+        assert!(lines[1].ends_with(" [System] gifts messages hidden"));
+
+        let jsonl = std::fs::read_to_string(&jsonl_path).unwrap();
+        let records: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["text"], "[Danmu] alice: hi");
+        assert!(
+            system_lines(&app)
+                .last()
+                .unwrap()
+                .starts_with("Saved 3 message(s) to ")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pause_counts_evicted_messages() {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::with_capacity(2)));
+        let mut app = TuiApp::new(Arc::clone(&buffer), "1".to_string());
+        TuiApp::add_message(&buffer, "[Danmu] a: 1");
+        app.toggle_pause();
+        for text in ["[Danmu] b: 2", "[Danmu] c: 3", "[Danmu] d: 4"] {
+            TuiApp::add_message(&buffer, text);
+        }
+        assert_eq!(app.paused_new_messages(), 3);
+        assert_eq!(app.get_messages().len(), 1);
+        app.toggle_pause();
+        assert_eq!(app.paused_new_messages(), 0);
+        assert_eq!(app.get_messages()[1].text, "[Danmu] d: 4");
+    }
+
+    #[test]
+    fn test_input_history() {
+        let mut app = app_with(&[]);
+        for input in ["first", "second", "second"] {
+            app.input = input.to_string();
+            app.take_input();
+        }
+        app.enter_char('d');
+        app.history_prev();
+        assert_eq!(app.input, "second");
+        app.history_prev();
+        app.history_prev();
+        assert_eq!(app.input, "first");
+        app.history_next();
+        assert_eq!(app.input, "second");
+        app.history_next();
+        assert_eq!((app.input.as_str(), app.cursor_position), ("d", 1));
+    }
+
+    #[test]
+    fn test_search_jump() {
+        let messages = [
+            "[Danmu] a: hello",
+            "[Danmu] b: world",
+            "[Danmu] c: Hello again",
+        ];
+        let mut app = app_with(&messages);
+        let lines = messages.iter().map(|msg| msg.to_string()).collect();
+        app.set_rendered_lines(lines, vec![0, 1, 2], 0, 10);
+        assert_eq!(app.pane_cursor(), Some(2));
+
+        app.start_log_search();
+        "HELLO".chars().for_each(|c| app.log_search_push(c));
+        app.submit_log_search();
+        assert_eq!(app.log_search.as_deref(), Some("HELLO"));
+        assert_eq!(app.log_search_matches(), 2);
+        // The match on the cursor line counts
+        assert_eq!(app.pane_cursor(), Some(2));
+        app.log_search_jump(false);
+        assert_eq!(app.pane_cursor(), Some(0));
+        app.log_search_jump(false);
+        assert_eq!(app.pane_cursor(), Some(2));
+        app.log_search_jump(true);
+        assert_eq!(app.pane_cursor(), Some(0));
+
+        app.start_log_search();
+        app.submit_log_search();
+        assert_eq!(app.log_search, None);
+    }
+
+    #[test]
+    fn test_log_level_filter_and_keymap() {
+        let mut app = app_with(&[]);
+        let entry = |level, text: &str| LogEntry {
+            level,
+            text: text.to_string(),
+        };
+        app.log_buffer.lock().unwrap().extend([
+            entry(log::Level::Error, "[ERROR] broken"),
+            entry(log::Level::Info, "[INFO] connected"),
+            entry(log::Level::Debug, "[DEBUG] packet"),
+        ]);
+        app.set_log_level_filter(log::LevelFilter::Info);
+        assert_eq!(app.get_log_messages().len(), 2);
+        app.set_log_level_filter(log::LevelFilter::Error);
+        let logs = app.get_log_messages();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].text, "[ERROR] broken");
+
+        assert_eq!("vim".parse::<Keymap>(), Ok(Keymap::Vim));
+        assert_eq!("Default".parse::<Keymap>(), Ok(Keymap::Default));
+        assert!("emacs".parse::<Keymap>().is_err());
+        app.set_keymap(Keymap::Vim);
+        assert!(app.is_vim_normal());
+        app.set_vim_insert(true);
+        assert!(app.is_vim_insert());
+    }
+}
@@ -0,0 +1,105 @@
+// src/tui/buffer.rs
+//! Bounded message buffer shared between the display plugin and the TUI
+
+use crate::tui::message::TuiMessage;
+use std::collections::VecDeque;
+use std::ops::Deref;
+
+/// Default number of messages kept in the buffer
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Ring buffer of TUI messages that drops the oldest message when full
+#[derive(Debug, Clone)]
+pub struct MessageBuffer {
+    messages: VecDeque<TuiMessage>,
+    capacity: usize,
+    /// Number of messages dropped to stay within capacity
+    evicted: u64,
+}
+
+impl MessageBuffer {
+    /// Create a buffer holding up to `DEFAULT_CAPACITY` messages
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Create a buffer holding up to `capacity` messages (at least one)
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            evicted: 0,
+        }
+    }
+
+    /// Append a message, dropping the oldest ones beyond capacity
+    pub fn push(&mut self, message: TuiMessage) {
+        self.messages.push_back(message);
+        self.truncate();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, dropping the oldest messages if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.truncate();
+    }
+
+    /// Number of messages dropped so far to stay within capacity
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Number of messages pushed so far, whether still buffered or evicted
+    pub fn pushed(&self) -> u64 {
+        self.evicted + self.messages.len() as u64
+    }
+
+    fn truncate(&mut self) {
+        while self.messages.len() > self.capacity {
+            self.messages.pop_front();
+            self.evicted += 1;
+        }
+    }
+}
+
+impl Default for MessageBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MessageBuffer {
+    type Target = VecDeque<TuiMessage>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_buffer_evicts_oldest() {
+        let mut buffer = MessageBuffer::with_capacity(2);
+        for text in ["a", "b", "c"] {
+            buffer.push(TuiMessage::from(text));
+        }
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer[0].text, "b");
+        assert_eq!(buffer.evicted(), 1);
+
+        buffer.set_capacity(1);
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer[0].text, "c");
+        assert_eq!(buffer.evicted(), 2);
+        assert_eq!(buffer.pushed(), 3);
+        assert_eq!(MessageBuffer::with_capacity(0).capacity(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+// src/tui/command.rs
+//! Slash-command registry for the TUI input box
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Feedback lines of a command, shown as `[System]` messages; `Err` holds an error or usage
+pub type CommandOutput = Result<Vec<String>, String>;
+
+type CommandHandler = Box<dyn FnMut(&CommandArgs) -> CommandOutput + Send>;
+
+/// Arguments following a command name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandArgs<'a> {
+    raw: &'a str,
+    parts: Vec<&'a str>,
+}
+
+impl<'a> CommandArgs<'a> {
+    pub fn new(raw: &'a str) -> Self {
+        let raw = raw.trim();
+        Self {
+            raw,
+            parts: raw.split_whitespace().collect(),
+        }
+    }
+
+    /// The whole argument string, trimmed
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Whitespace-separated arguments
+    pub fn parts(&self) -> &[&'a str] {
+        &self.parts
+    }
+
+    /// Get the argument at `index`
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        self.parts.get(index).copied()
+    }
+
+    /// Parse the argument at `index`, returning `usage` if it is missing or invalid
+    pub fn parse<T: FromStr>(&self, index: usize, usage: &str) -> Result<T, String> {
+        self.get(index)
+            .and_then(|arg| arg.parse().ok())
+            .ok_or_else(|| usage.to_string())
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+}
+
+/// Name and help text of a registered command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Command name including the leading `/`
+    pub name: String,
+    /// Usage string, e.g. `/ban <uid> [hours]`
+    pub usage: String,
+    /// One-line description for `/help`
+    pub description: String,
+}
+
+/// Commands available from the TUI input box
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, (CommandSpec, CommandHandler)>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a command, replacing any existing command with the same name
+    pub fn register<F>(&mut self, name: &str, usage: &str, description: &str, handler: F)
+    where
+        F: FnMut(&CommandArgs) -> CommandOutput + Send + 'static,
+    {
+        let name = if name.starts_with('/') {
+            name.to_string()
+        } else {
+            format!("/{}", name)
+        };
+        let spec = CommandSpec {
+            name: name.clone(),
+            usage: usage.to_string(),
+            description: description.to_string(),
+        };
+        self.commands.insert(name, (spec, Box::new(handler)));
+    }
+
+    /// Whether a command with this name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    /// Registered commands in name order
+    pub fn specs(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.commands.values().map(|(spec, _)| spec)
+    }
+
+    /// Run `input` if it names a registered command; returns None otherwise
+    pub fn execute(&mut self, input: &str) -> Option<CommandOutput> {
+        let (name, args) = split_command(input);
+        let (_, handler) = self.commands.get_mut(name)?;
+        Some(handler(&CommandArgs::new(args)))
+    }
+}
+
+/// Split input into the command name and the rest of the line
+pub fn split_command(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    input.split_once(' ').unwrap_or((input, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_execute() {
+        let mut registry = CommandRegistry::new();
+        registry.register("echo", "/echo <text>", "Echo text", |args| {
+            if args.is_empty() {
+                Err("Usage: /echo <text>".to_string())
+            } else {
+                Ok(vec![args.raw().to_string()])
+            }
+        });
+        registry.register("/add", "/add <a> <b>", "Add numbers", |args| {
+            let a: u32 = args.parse(0, "Usage: /add <a> <b>")?;
+            let b: u32 = args.parse(1, "Usage: /add <a> <b>")?;
+            Ok(vec![(a + b).to_string()])
+        });
+
+        assert!(registry.contains("/echo"));
+        assert_eq!(
+            registry.execute("/echo  hello world "),
+            Some(Ok(vec!["hello world".to_string()]))
+        );
+        assert_eq!(
+            registry.execute("/echo"),
+            Some(Err("Usage: /echo <text>".to_string()))
+        );
+        assert_eq!(
+            registry.execute("/add 1 2"),
+            Some(Ok(vec!["3".to_string()]))
+        );
+        assert_eq!(
+            registry.execute("/add 1 x"),
+            Some(Err("Usage: /add <a> <b>".to_string()))
+        );
+        assert_eq!(registry.execute("/unknown"), None);
+        // Commands match the whole first token, so `/echoes` is not `/echo`
+        assert_eq!(registry.execute("/echoes hi"), None);
+        assert_eq!(split_command("/banana 1"), ("/banana", "1"));
+        assert_eq!(
+            registry
+                .specs()
+                .map(|spec| spec.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/add", "/echo"]
+        );
+    }
+}
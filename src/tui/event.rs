@@ -1,7 +1,9 @@
 // src/tui/event.rs
 //! Event handling and main TUI loop
 
-use crate::tui::app::TuiApp;
+use crate::tui::app::{Keymap, TuiApp};
+use crate::tui::i18n::trf;
+use crate::tui::rate::BUCKET_DURATION;
 use crate::tui::ui;
 use arboard::Clipboard;
 use crossterm::{
@@ -10,8 +12,11 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::io;
-use std::time::Duration;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How often to check shared state for changes when no input arrives
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 /// Run the TUI application
 pub fn run_tui<F>(mut app: TuiApp, mut on_message: F) -> io::Result<()>
@@ -26,6 +31,11 @@ where
 
     let result = run_app(&mut terminal, &mut app, &mut on_message);
 
+    if let Some(alerts) = &app.alerts {
+        terminal
+            .backend_mut()
+            .write_all(alerts.restore_title().as_bytes())?;
+    }
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
@@ -43,39 +53,116 @@ where
 {
     let mut needs_redraw = true;
     let mut clipboard = Clipboard::new().ok();
-    let mut last_message_count = app.message_count();
-    let mut last_log_count = app.log_message_count();
+    let mut last_message_id = app.latest_message_id();
+    let mut last_log = (app.log_message_count(), app.latest_log());
     let mut last_online_count = app.get_online_count();
+    let mut last_draw = Instant::now();
+    let mut last_online_rank = app.get_online_rank();
+    let mut last_images = (Vec::new(), 0);
+    let mut redraw_images = false;
 
     loop {
-        let message_count = app.message_count();
-        let log_count = app.log_message_count();
+        let message_id = app.latest_message_id();
+        let log = (app.log_message_count(), app.latest_log());
         let online_count = app.get_online_count();
 
         if !app.visual_mode
-            && (message_count != last_message_count
-                || log_count != last_log_count
+            && (message_id != last_message_id
+                || log != last_log
                 || online_count != last_online_count)
         {
             needs_redraw = true;
         }
 
-        last_message_count = message_count;
-        last_log_count = log_count;
+        // A downloaded image may now replace its placeholder
+        let images_generation = app
+            .graphics
+            .as_ref()
+            .map_or(0, |graphics| graphics.generation());
+        if images_generation != last_images.1 {
+            needs_redraw = true;
+            last_images.1 = images_generation;
+        }
+
+        if app.show_rank {
+            let online_rank = app.get_online_rank();
+            if online_rank != last_online_rank {
+                needs_redraw = true;
+                last_online_rank = online_rank;
+            }
+        }
+
+        // Keep pinned super chat countdowns and the uptime ticking, and let the rate
+        // sparkline roll over
+        let tick = if app.get_super_chats().is_empty() && app.connection_status.is_none() {
+            BUCKET_DURATION
+        } else {
+            Duration::from_secs(1)
+        };
+        if last_draw.elapsed() >= tick {
+            needs_redraw = true;
+        }
+
+        last_message_id = message_id;
+        last_log = log;
         last_online_count = online_count;
 
         if needs_redraw {
             terminal.draw(|f| ui::render(f, app))?;
+            if redraw_images || app.image_placements != last_images.0 {
+                // Images drawn as text cells stay until the screen is cleared
+                if !last_images.0.is_empty()
+                    && app
+                        .graphics
+                        .as_ref()
+                        .is_some_and(|graphics| graphics.needs_clear())
+                {
+                    terminal.clear()?;
+                    terminal.draw(|f| ui::render(f, app))?;
+                }
+                if let Some(graphics) = app.graphics.as_mut() {
+                    graphics.draw(terminal.backend_mut(), &app.image_placements)?;
+                }
+                last_images.0 = app.image_placements.clone();
+                redraw_images = false;
+            }
             needs_redraw = false;
+            last_draw = Instant::now();
         }
 
-        if event::poll(Duration::from_millis(16))? {
-            if let Event::Key(key) = event::read()? {
+        if let Some(alerts) = &app.alerts {
+            let output = alerts.take_output(Instant::now());
+            if !output.is_empty() {
+                let backend = terminal.backend_mut();
+                backend.write_all(output.as_bytes())?;
+                backend.flush()?;
+            }
+        }
+
+        // Keys wake the poll immediately; the timeout only bounds how late new messages show up
+        if event::poll(POLL_INTERVAL)? {
+            let event = event::read()?;
+            if let Event::Resize(..) = event {
+                needs_redraw = true;
+                redraw_images = true;
+            }
+            if let Event::Key(key) = event {
                 match key.code {
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         app.quit();
                         needs_redraw = true;
                     }
+                    _ if app.log_search_input.is_some() => {
+                        match key.code {
+                            KeyCode::Enter => app.submit_log_search(),
+                            KeyCode::Esc => app.cancel_log_search(),
+                            KeyCode::Backspace => app.log_search_pop(),
+                            KeyCode::Char(c) => app.log_search_push(c),
+                            _ => {}
+                        }
+
+                        needs_redraw = true;
+                    }
                     KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         app.toggle_visual_mode();
                         needs_redraw = true;
@@ -92,27 +179,92 @@ where
                             needs_redraw = true;
                         }
                     }
+                    KeyCode::Char('t')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.visual_mode =>
+                    {
+                        app.toggle_show_timestamps();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('p')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.visual_mode =>
+                    {
+                        app.toggle_pause();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('b')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.visual_mode =>
+                    {
+                        app.toggle_show_rank();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('s')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.visual_mode =>
+                    {
+                        app.toggle_split_panes();
+                        needs_redraw = true;
+                    }
                     KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         if !app.visual_mode {
                             app.toggle_show_logs();
                             needs_redraw = true;
                         }
                     }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if !app.visual_mode
+                            && let Some(user) = app.user_at_cursor()
+                        {
+                            app.open_user_popup(&user);
+                            needs_redraw = true;
+                        }
+                    }
+                    KeyCode::Char('o')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.visual_mode =>
+                    {
+                        app.open_inspector();
+                        needs_redraw = true;
+                    }
                     KeyCode::Esc => {
                         if app.visual_mode {
                             app.exit_visual_mode();
+                        } else if app.inspector.is_some() {
+                            app.close_inspector();
+                        } else if app.user_popup.is_some() {
+                            app.close_user_popup();
                         } else if app.show_help {
                             app.show_help = false;
+                        } else if app.is_vim_insert() {
+                            app.set_vim_insert(false);
+                        } else if app.log_search.is_some() {
+                            app.clear_log_search();
+                        } else if app.follow.is_some() {
+                            app.follow = None;
                         } else if app.show_logs {
                             app.toggle_show_logs();
-                        } else {
+                        } else if app.keymap != Keymap::Vim {
                             app.quit();
                         }
 
                         needs_redraw = true;
                     }
 
-                    _ if app.show_help => {}
+                    _ if app.show_help || app.user_popup.is_some() => {}
+
+                    _ if app.inspector.is_some() => {
+                        match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => app.inspector_up(1),
+                            KeyCode::Down | KeyCode::Char('j') => app.inspector_down(1),
+                            KeyCode::PageUp => app.inspector_up(10),
+                            KeyCode::PageDown => app.inspector_down(10),
+                            _ => {}
+                        }
+
+                        needs_redraw = true;
+                    }
+
+                    KeyCode::Tab if app.layout.split && !app.visual_mode && !app.show_logs => {
+                        app.focus_next_pane();
+                        needs_redraw = true;
+                    }
 
                     _ if app.visual_mode => {
                         match key.code {
@@ -123,9 +275,45 @@ where
                             KeyCode::Char('g') | KeyCode::Home => app.visual_top(),
                             KeyCode::Char('G') | KeyCode::End => app.visual_bottom(),
                             KeyCode::Char('y') => {
-                                copy_selection(app, clipboard.as_mut())?;
+                                let feedback = match copy_selection(app, clipboard.as_mut()) {
+                                    Ok(0) => None,
+                                    Ok(count) => Some(trf("Copied {} message(s)", &[&count])),
+                                    Err(e) => Some(trf("Copy failed: {}", &[&e])),
+                                };
                                 app.exit_visual_mode();
+                                if let Some(feedback) = feedback {
+                                    TuiApp::add_message(
+                                        &app.message_buffer,
+                                        format!("[System] {}", feedback),
+                                    );
+                                }
+                            }
+                            _ => {}
+                        }
+
+                        needs_redraw = true;
+                    }
+
+                    _ if app.is_vim_normal() => {
+                        let pending_g = std::mem::take(&mut app.vim_pending_g);
+                        match key.code {
+                            KeyCode::Char('j') | KeyCode::Down => app.pane_down(1),
+                            KeyCode::Char('k') | KeyCode::Up => app.pane_up(1),
+                            KeyCode::PageDown => app.pane_down(10),
+                            KeyCode::PageUp => app.pane_up(10),
+                            KeyCode::Char('g') if pending_g => app.pane_top(),
+                            KeyCode::Char('g') => app.vim_pending_g = true,
+                            KeyCode::Char('G') => app.pane_bottom(),
+                            KeyCode::Char('i') | KeyCode::Char('a') => app.set_vim_insert(true),
+                            KeyCode::Char(':') if !app.is_read_only() => {
+                                // Start a slash command, like an ex command line
+                                app.set_vim_insert(true);
+                                app.enter_char('/');
                             }
+                            KeyCode::Char('v') => app.enter_visual_mode(),
+                            KeyCode::Char('/') => app.start_log_search(),
+                            KeyCode::Char('n') => app.log_search_jump(true),
+                            KeyCode::Char('N') => app.log_search_jump(false),
                             _ => {}
                         }
 
@@ -165,9 +353,43 @@ where
                             app.pane_bottom();
                             needs_redraw = true;
                         }
+                        KeyCode::Char('/') => {
+                            app.start_log_search();
+                            needs_redraw = true;
+                        }
+                        KeyCode::Char('n') => {
+                            app.log_search_jump(true);
+                            needs_redraw = true;
+                        }
+                        KeyCode::Char('N') => {
+                            app.log_search_jump(false);
+                            needs_redraw = true;
+                        }
+                        KeyCode::Char(c) => {
+                            let level = match c {
+                                'e' => Some(log::LevelFilter::Error),
+                                'w' => Some(log::LevelFilter::Warn),
+                                'i' => Some(log::LevelFilter::Info),
+                                'd' => Some(log::LevelFilter::Debug),
+                                'a' => Some(log::LevelFilter::Trace),
+                                _ => None,
+                            };
+                            if let Some(level) = level {
+                                app.set_log_level_filter(level);
+                                needs_redraw = true;
+                            }
+                        }
                         _ => {}
                     },
 
+                    // Read-only layout: there is no input box to type into
+                    KeyCode::Char(_)
+                    | KeyCode::Backspace
+                    | KeyCode::Enter
+                    | KeyCode::Left
+                    | KeyCode::Right
+                        if app.is_read_only() => {}
+
                     KeyCode::Char(c) => {
                         app.enter_char(c);
                         needs_redraw = true;
@@ -178,16 +400,24 @@ where
                     }
                     KeyCode::Enter => {
                         let input = app.take_input();
-                        if !input.is_empty() {
-                            if input == "/quit" || input == "/exit" {
-                                app.quit();
-                            } else {
-                                on_message(input);
-                            }
+                        if !input.is_empty() && !app.run_command(&input) {
+                            on_message(input);
                         }
 
                         needs_redraw = true;
                     }
+                    KeyCode::Up
+                        if key.modifiers.contains(KeyModifiers::ALT) || !app.input.is_empty() =>
+                    {
+                        app.history_prev();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Down
+                        if key.modifiers.contains(KeyModifiers::ALT) || !app.input.is_empty() =>
+                    {
+                        app.history_next();
+                        needs_redraw = true;
+                    }
                     KeyCode::Up => {
                         app.pane_up(1);
                         needs_redraw = true;
@@ -241,14 +471,16 @@ where
     Ok(())
 }
 
-fn copy_selection(app: &TuiApp, clipboard: Option<&mut Clipboard>) -> io::Result<()> {
+/// Copy the selected messages and return how many were copied
+fn copy_selection(app: &TuiApp, clipboard: Option<&mut Clipboard>) -> io::Result<usize> {
     let Some(text) = app.selected_text() else {
-        return Ok(());
+        return Ok(0);
     };
 
     let Some(clipboard) = clipboard else {
         return Err(io::Error::other("clipboard is unavailable"));
     };
 
-    clipboard.set_text(text).map_err(io::Error::other)
+    clipboard.set_text(text).map_err(io::Error::other)?;
+    Ok(app.selected_message_count())
 }
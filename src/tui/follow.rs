@@ -0,0 +1,112 @@
+// src/tui/follow.rs
+//! Following one user's messages in a fast chat
+
+use crate::tui::message::TuiMessage;
+
+/// Messages shown before each message of the followed user
+pub const FOLLOW_CONTEXT: usize = 2;
+
+/// The user followed with `/follow`, by name or uid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Follow {
+    /// Entry as typed, for display
+    pub entry: String,
+    name: Option<String>,
+    uid: Option<u64>,
+}
+
+impl Follow {
+    /// Follow `uid:<uid>`, or a user name; a bare number matches either
+    pub fn parse(entry: &str) -> Self {
+        let (name, uid) = match entry.strip_prefix("uid:") {
+            Some(uid) => (None, uid.parse().ok()),
+            None => (Some(entry.to_string()), entry.parse().ok()),
+        };
+        Self {
+            entry: entry.to_string(),
+            name,
+            uid,
+        }
+    }
+
+    /// Whether `msg` was sent by the followed user, by the sender of its source message
+    pub fn matches(&self, msg: &TuiMessage) -> bool {
+        let Some((user, uid)) = msg.source.as_ref().and_then(|source| source.sender()) else {
+            return false;
+        };
+        let name_matches = self.name.as_deref() == Some(user);
+        let uid_matches = self
+            .uid
+            .is_some_and(|followed| followed != 0 && followed == uid);
+        name_matches || uid_matches
+    }
+}
+
+/// Which of the messages to show while following: `shown` holds `None` for
+/// messages hidden anyway and whether the others are from the followed user.
+/// The result is `Some(true)` for their messages, `Some(false)` for up to
+/// `context` shown messages before each of them, and `None` for the rest.
+pub fn follow_view(shown: &[Option<bool>], context: usize) -> Vec<Option<bool>> {
+    let mut view = vec![None; shown.len()];
+    let mut remaining = 0;
+    for (index, followed) in shown.iter().enumerate().rev() {
+        match followed {
+            Some(true) => {
+                view[index] = Some(true);
+                remaining = context;
+            }
+            Some(false) if remaining > 0 => {
+                view[index] = Some(false);
+                remaining -= 1;
+            }
+            _ => {}
+        }
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::models::BiliMessage;
+
+    #[test]
+    fn test_follow_matches_and_context() {
+        let danmu = |user: &str, uid: u64| {
+            TuiMessage::new(format!("[Danmu] {}: hi", user)).with_source(BiliMessage::Danmu {
+                user: user.to_string(),
+                text: "hi".to_string(),
+                uid,
+                guard_level: 0,
+                medal: None,
+            })
+        };
+        assert!(Follow::parse("alice").matches(&danmu("alice", 1)));
+        assert!(Follow::parse("uid:2").matches(&danmu("bob", 2)));
+        assert!(Follow::parse("2").matches(&danmu("bob", 2)));
+        assert!(!Follow::parse("uid:0").matches(&danmu("bob", 0)));
+        assert!(!Follow::parse("alice").matches(&TuiMessage::new("[System] alice".to_string())));
+
+        let shown = [
+            Some(false),
+            Some(false),
+            None,
+            Some(false),
+            Some(true),
+            Some(false),
+            Some(true),
+        ];
+        assert_eq!(
+            follow_view(&shown, 2),
+            [
+                None,
+                Some(false),
+                None,
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+// src/tui/graphics.rs
+//! Inline images for terminals with a graphics protocol
+//!
+//! Emotes in danmaku, and optionally the avatars of their senders, are drawn
+//! over blanked text cells after each frame, with the kitty graphics protocol,
+//! iTerm2 inline images or sixel (with the `sixel` feature). Images download in
+//! the background; until one is ready, and on other terminals, the text stays
+//! as its placeholder.
+
+use base64::{Engine as _, engine::general_purpose};
+use crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+use crossterm::queue;
+use lru::LruCache;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::client::images::{EmoteCatalog, fetch_avatar_url, fetch_image};
+
+/// Width of an image in terminal cells; images are one row high
+pub const IMAGE_CELLS: u16 = 2;
+/// Size of the downloaded images in pixels
+const IMAGE_PIXELS: u32 = 48;
+/// Largest base64 payload of one kitty graphics command
+const KITTY_CHUNK: usize = 4096;
+/// Images and escape sequences kept, the least recently drawn dropped first
+const CACHED_IMAGES: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+/// Image protocol of a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    /// kitty graphics protocol (kitty, Ghostty)
+    Kitty,
+    /// iTerm2 inline images (iTerm2, WezTerm)
+    Iterm2,
+    /// DEC sixel graphics (foot, mlterm, contour)
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// Protocol of the terminal running the TUI, `None` without one
+    pub fn detect() -> Option<Self> {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    fn detect_from(env: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        // Multiplexers do not pass the escape sequences through
+        if env("TMUX").is_some() || env("STY").is_some() {
+            return None;
+        }
+        let term = env("TERM").unwrap_or_default();
+        let program = env("TERM_PROGRAM").unwrap_or_default();
+        if env("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || program == "ghostty" {
+            Some(Self::Kitty)
+        } else if program == "iTerm.app"
+            || program == "WezTerm"
+            || env("LC_TERMINAL").as_deref() == Some("iTerm2")
+        {
+            Some(Self::Iterm2)
+        } else if cfg!(feature = "sixel")
+            && ["foot", "mlterm", "contour"]
+                .iter()
+                .any(|name| term.starts_with(name))
+        {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+
+    /// Protocol for the `tui.images` setting: "auto" (detect), "off", "kitty",
+    /// "iterm2" or "sixel"
+    pub fn from_setting(setting: &str) -> Result<Option<Self>, String> {
+        match setting.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::detect()),
+            "off" | "none" => Ok(None),
+            "kitty" => Ok(Some(Self::Kitty)),
+            "iterm2" => Ok(Some(Self::Iterm2)),
+            "sixel" if cfg!(feature = "sixel") => Ok(Some(Self::Sixel)),
+            "sixel" => Err("sixel images need the sixel feature".to_string()),
+            _ => Err(format!(
+                "Unknown image setting '{}' (expected auto, off, kitty, iterm2 or sixel)",
+                setting
+            )),
+        }
+    }
+
+    /// Escape sequence drawing the PNG `png` at the cursor, `cols` cells wide
+    /// and one row high; `cell` is the cell size in pixels
+    pub fn encode(self, png: &[u8], cols: u16, cell: (u16, u16)) -> Result<String, String> {
+        if png.is_empty() {
+            return Err("empty image".to_string());
+        }
+        match self {
+            Self::Kitty => {
+                let data = general_purpose::STANDARD.encode(png);
+                let mut sequence = String::new();
+                for start in (0..data.len()).step_by(KITTY_CHUNK) {
+                    let end = (start + KITTY_CHUNK).min(data.len());
+                    let more = u8::from(end < data.len());
+                    if start == 0 {
+                        // C=1 leaves the cursor where it was
+                        sequence.push_str(&format!(
+                            "\x1b_Gf=100,a=T,q=2,C=1,c={},r=1,m={};",
+                            cols, more
+                        ));
+                    } else {
+                        sequence.push_str(&format!("\x1b_Gm={};", more));
+                    }
+                    sequence.push_str(&data[start..end]);
+                    sequence.push_str("\x1b\\");
+                }
+                Ok(sequence)
+            }
+            Self::Iterm2 => Ok(format!(
+                "\x1b]1337;File=inline=1;size={};width={};height=1;preserveAspectRatio=1:{}\x07",
+                png.len(),
+                cols,
+                general_purpose::STANDARD.encode(png)
+            )),
+            #[cfg(feature = "sixel")]
+            Self::Sixel => {
+                let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+                    .map_err(|e| format!("invalid image: {}", e))?
+                    .resize_exact(
+                        u32::from(cols * cell.0),
+                        u32::from(cell.1),
+                        image::imageops::FilterType::Triangle,
+                    )
+                    .to_rgba8();
+                Ok(sixel(
+                    image.width() as usize,
+                    image.height() as usize,
+                    image.as_raw(),
+                ))
+            }
+            #[cfg(not(feature = "sixel"))]
+            Self::Sixel => {
+                let _ = cell;
+                Err("sixel images need the sixel feature".to_string())
+            }
+        }
+    }
+}
+
+/// Sixel image of RGBA pixels, in the colors of a 6×6×6 cube; transparent
+/// pixels are left unpainted
+#[cfg(feature = "sixel")]
+fn sixel(width: usize, height: usize, rgba: &[u8]) -> String {
+    use std::collections::BTreeSet;
+    use std::fmt::Write as _;
+
+    let level = |c: u8| (usize::from(c) * 5 + 127) / 255;
+    let colors: Vec<Option<usize>> = rgba
+        .chunks_exact(4)
+        .map(|p| (p[3] >= 128).then(|| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])))
+        .collect();
+
+    let mut out = format!("\x1bP0;1q\"1;1;{};{}", width, height);
+    let used: BTreeSet<usize> = colors.iter().flatten().copied().collect();
+    for color in &used {
+        let percent = |value: usize| value * 100 / 5;
+        let _ = write!(
+            out,
+            "#{};2;{};{};{}",
+            color,
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let band_colors: BTreeSet<usize> = (band..band + rows)
+            .flat_map(|y| colors[y * width..(y + 1) * width].iter().flatten().copied())
+            .collect();
+        for color in band_colors {
+            let _ = write!(out, "#{}", color);
+            let mut runs: Vec<(u8, usize)> = Vec::new();
+            for x in 0..width {
+                let bits = (0..rows)
+                    .filter(|dy| colors[(band + dy) * width + x] == Some(color))
+                    .fold(0u8, |bits, dy| bits | (1 << dy));
+                match runs.last_mut() {
+                    Some((sixel, count)) if *sixel == 63 + bits => *count += 1,
+                    _ => runs.push((63 + bits, 1)),
+                }
+            }
+            for (sixel, count) in runs {
+                if count > 3 {
+                    let _ = write!(out, "!{}{}", count, sixel as char);
+                } else {
+                    out.extend(std::iter::repeat_n(sixel as char, count));
+                }
+            }
+            // Back to the start of the band for the next color
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+/// Download state of an image
+enum Slot {
+    Pending,
+    Ready(Arc<Vec<u8>>),
+    Failed,
+}
+
+/// Images by key, an emote URL or `uid:<uid>` for an avatar, downloaded by a
+/// background thread
+struct ImageStore {
+    slots: Arc<Mutex<LruCache<String, Slot>>>,
+    generation: Arc<AtomicU64>,
+    requests: Sender<String>,
+}
+
+impl ImageStore {
+    fn new(capacity: NonZeroUsize) -> Self {
+        let (requests, pending) = mpsc::channel::<String>();
+        let slots = Arc::new(Mutex::new(LruCache::new(capacity)));
+        let generation = Arc::new(AtomicU64::new(0));
+        let (fetched, finished) = (Arc::clone(&slots), Arc::clone(&generation));
+        let spawned = thread::Builder::new()
+            .name("blivedm-images".to_string())
+            .spawn(move || {
+                for key in pending {
+                    let image = match key.strip_prefix("uid:") {
+                        Some(uid) => uid
+                            .parse()
+                            .map_err(|_| format!("invalid uid {}", uid))
+                            .and_then(fetch_avatar_url)
+                            .and_then(|url| fetch_image(&url, IMAGE_PIXELS)),
+                        None => fetch_image(&key, IMAGE_PIXELS),
+                    };
+                    let slot = match image {
+                        Ok(png) => Slot::Ready(Arc::new(png)),
+                        Err(e) => {
+                            log::debug!("{}", e);
+                            Slot::Failed
+                        }
+                    };
+                    fetched.lock().unwrap().put(key, slot);
+                    finished.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("failed to start image thread: {}", e);
+        }
+        Self {
+            slots,
+            generation,
+            requests,
+        }
+    }
+
+    /// The image of `key`, requested on first use
+    fn get(&self, key: &str) -> Option<Arc<Vec<u8>>> {
+        let mut slots = self.slots.lock().unwrap();
+        match slots.get(key) {
+            Some(Slot::Ready(png)) => Some(Arc::clone(png)),
+            Some(Slot::Pending | Slot::Failed) => None,
+            None => {
+                slots.put(key.to_string(), Slot::Pending);
+                let _ = self.requests.send(key.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// An image drawn over the cells from `x`, `y` after a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePlacement {
+    /// Emote URL, or `uid:<uid>` for an avatar
+    pub key: String,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// Images shown in the message list
+pub struct Graphics {
+    pub protocol: GraphicsProtocol,
+    /// Emotes of the room, filled once fetched
+    pub emotes: Arc<Mutex<EmoteCatalog>>,
+    /// Whether senders' avatars replace the `[Danmu]` tag
+    pub avatars: bool,
+    store: ImageStore,
+    /// Escape sequences by image key
+    encoded: LruCache<String, Arc<str>>,
+    /// Size of a cell in pixels
+    cell: (u16, u16),
+}
+
+impl Graphics {
+    pub fn new(
+        protocol: GraphicsProtocol,
+        emotes: Arc<Mutex<EmoteCatalog>>,
+        avatars: bool,
+    ) -> Self {
+        let cell = crossterm::terminal::window_size()
+            .ok()
+            .filter(|size| size.columns > 0 && size.rows > 0 && size.width > 0)
+            .map(|size| (size.width / size.columns, size.height / size.rows))
+            .unwrap_or((10, 20));
+        Self {
+            protocol,
+            emotes,
+            avatars,
+            store: ImageStore::new(CACHED_IMAGES),
+            encoded: LruCache::new(CACHED_IMAGES),
+            cell,
+        }
+    }
+
+    /// Whether the image of `key` can be drawn, downloading it on first use
+    pub fn is_ready(&self, key: &str) -> bool {
+        self.store.get(key).is_some()
+    }
+
+    /// Mark `key` as downloaded, or as failed without an image
+    #[cfg(test)]
+    pub(crate) fn set_image(&self, key: &str, png: Option<Vec<u8>>) {
+        let slot = png.map_or(Slot::Failed, |png| Slot::Ready(Arc::new(png)));
+        self.store.slots.lock().unwrap().put(key.to_string(), slot);
+    }
+
+    /// Number of finished downloads, to redraw when it changes
+    pub fn generation(&self) -> u64 {
+        self.store.generation.load(Ordering::Relaxed)
+    }
+
+    /// Whether images of the previous frame stay on screen until the screen is
+    /// cleared, as text cells do
+    pub fn needs_clear(&self) -> bool {
+        self.protocol != GraphicsProtocol::Kitty
+    }
+
+    /// Draw `placements` to `out`, replacing the images of the previous frame
+    /// on terminals that keep them apart from the text
+    pub fn draw<W: Write>(&mut self, out: &mut W, placements: &[ImagePlacement]) -> io::Result<()> {
+        queue!(out, SavePosition)?;
+        if self.protocol == GraphicsProtocol::Kitty {
+            out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        }
+        for placement in placements {
+            let Some(sequence) = self.sequence(&placement.key) else {
+                continue;
+            };
+            queue!(out, MoveTo(placement.x, placement.y))?;
+            out.write_all(sequence.as_bytes())?;
+        }
+        queue!(out, RestorePosition)?;
+        out.flush()
+    }
+
+    fn sequence(&mut self, key: &str) -> Option<Arc<str>> {
+        if let Some(sequence) = self.encoded.get(key) {
+            return Some(Arc::clone(sequence));
+        }
+        let png = self.store.get(key)?;
+        match self.protocol.encode(&png, IMAGE_CELLS, self.cell) {
+            Ok(sequence) => {
+                let sequence: Arc<str> = sequence.into();
+                self.encoded.put(key.to_string(), Arc::clone(&sequence));
+                Some(sequence)
+            }
+            Err(e) => {
+                log::debug!("image {}: {}", key, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_encode() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            GraphicsProtocol::detect_from(env(&[("TERM", "xterm-kitty")])),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            GraphicsProtocol::detect_from(env(&[("TERM_PROGRAM", "iTerm.app")])),
+            Some(GraphicsProtocol::Iterm2)
+        );
+        assert_eq!(
+            GraphicsProtocol::detect_from(env(&[("TERM", "xterm-kitty"), ("TMUX", "1")])),
+            None
+        );
+        assert_eq!(
+            GraphicsProtocol::detect_from(env(&[("TERM", "xterm-256color")])),
+            None
+        );
+        assert_eq!(GraphicsProtocol::from_setting("off"), Ok(None));
+        assert!(GraphicsProtocol::from_setting("ascii").is_err());
+
+        // Large images are sent in chunks, all but the last marked m=1
+        let png = vec![0u8; KITTY_CHUNK];
+        let kitty = GraphicsProtocol::Kitty.encode(&png, 2, (10, 20)).unwrap();
+        assert!(kitty.starts_with("\x1b_Gf=100,a=T,q=2,C=1,c=2,r=1,m=1;"));
+        assert!(kitty.ends_with("\x1b\\") && kitty.contains("\x1b_Gm=0;"));
+        let iterm = GraphicsProtocol::Iterm2
+            .encode(&[1, 2, 3], 2, (10, 20))
+            .unwrap();
+        assert_eq!(
+            iterm,
+            "\x1b]1337;File=inline=1;size=3;width=2;height=1;preserveAspectRatio=1:AQID\x07"
+        );
+        assert!(GraphicsProtocol::Iterm2.encode(&[], 2, (10, 20)).is_err());
+    }
+
+    #[test]
+    fn test_image_store_drops_least_recent() {
+        let store = ImageStore::new(NonZeroUsize::new(2).unwrap());
+        let ready = |key: &str| {
+            store
+                .slots
+                .lock()
+                .unwrap()
+                .put(key.to_string(), Slot::Ready(Arc::new(vec![1])));
+        };
+        ready("uid:1");
+        ready("uid:2");
+        // Drawing the first avatar keeps it over the second
+        assert!(store.get("uid:1").is_some());
+        ready("uid:3");
+        let slots = store.slots.lock().unwrap();
+        assert_eq!(slots.len(), 2);
+        assert!(slots.contains("uid:1") && slots.contains("uid:3"));
+    }
+
+    #[cfg(feature = "sixel")]
+    #[test]
+    fn test_sixel() {
+        // 5 red pixels over a transparent one, in one band
+        let mut rgba = [255, 0, 0, 255].repeat(5);
+        rgba.extend([0, 0, 0, 0]);
+        assert_eq!(
+            sixel(1, 6, &rgba),
+            "\x1bP0;1q\"1;1;1;6#180;2;100;0;0#180^$-\x1b\\"
+        );
+    }
+}
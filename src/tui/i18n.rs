@@ -0,0 +1,286 @@
+// src/tui/i18n.rs
+//! Translations of TUI titles, help text and system messages
+//!
+//! Strings are looked up by their English text, so the English locale needs no table
+//! and an untranslated string falls back to English.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Language of the TUI strings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lang = s.to_ascii_lowercase().replace('_', "-");
+        if lang == "en" || lang.starts_with("en-") || lang == "c" || lang == "posix" {
+            Ok(Locale::En)
+        } else if lang == "zh" || lang.starts_with("zh-") {
+            Ok(Locale::ZhCn)
+        } else {
+            Err(format!("Unknown locale '{}' (expected en or zh-CN)", s))
+        }
+    }
+}
+
+impl Locale {
+    /// Locale from the configured value, else from `LC_ALL`, `LC_MESSAGES` or `LANG`
+    pub fn detect(configured: Option<&str>) -> Result<Self, String> {
+        if let Some(configured) = configured {
+            return configured.parse();
+        }
+        Ok(Self::from_env())
+    }
+
+    /// Locale from the environment, defaulting to English
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| {
+                // Strip the encoding, e.g. `zh_CN.UTF-8`
+                let lang = value.split('.').next().unwrap_or_default();
+                lang.parse().ok()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Translate an English string into this locale
+    pub fn tr(self, text: &'static str) -> &'static str {
+        match self {
+            Locale::En => text,
+            Locale::ZhCn => zh_cn(text).unwrap_or(text),
+        }
+    }
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// Set the locale used by `tr` and `trf`
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// Locale currently used by `tr` and `trf`
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::ZhCn,
+        _ => Locale::En,
+    }
+}
+
+/// Translate an English string into the current locale
+pub fn tr(text: &'static str) -> &'static str {
+    locale().tr(text)
+}
+
+/// Translate an English format string and replace its `{}` placeholders with `args` in order
+pub fn trf(text: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    format_args_list(tr(text), args)
+}
+
+fn format_args_list(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut pieces = template.split("{}");
+    if let Some(first) = pieces.next() {
+        out.push_str(first);
+    }
+    for piece in pieces {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(piece);
+    }
+    out
+}
+
+fn zh_cn(text: &str) -> Option<&'static str> {
+    Some(match text {
+        // Titles and status
+        " Super Chats " => " 醒目留言 ",
+        " Super Chats ({} pinned, newest first) " => " 醒目留言（置顶 {} 条，最新在前） ",
+        "Waiting for rank..." => "等待排行榜...",
+        " Rank (Ctrl+B) " => " 排行榜 (Ctrl+B) ",
+        " Stats " => " 统计 ",
+        "Connected" => "已连接",
+        "Reconnecting (attempt {})" => "重连中（第 {} 次）",
+        "Reconnecting in {}s (attempt {}, /reconnect to retry now)" => {
+            "{} 秒后重连（第 {} 次，/reconnect 立即重试）"
+        }
+        " | /reconnect to retry" => " | /reconnect 重试",
+        "Disconnected" => "已断开",
+        "guest" => "游客",
+        " (cookies rejected)" => "（Cookie 已失效）",
+        " | RTT " => " | 延迟 ",
+        " | Uptime " => " | 运行 ",
+        " | Login " => " | 登录 ",
+        "Online: " => "在线: ",
+        "Chatters: " => "发言人数: ",
+        "First-time: " => "首次发言: ",
+        "Danmaku: " => "弹幕数: ",
+        "Top chatters" => "发言最多",
+        "No stats collected" => "暂无统计",
+        "VISUAL | j/k move | g/G jump | y copy | Esc cancel" => {
+            "选择 | j/k 移动 | g/G 跳转 | y 复制 | Esc 取消"
+        }
+        "CURSOR | Up/Down move | Ctrl+Y visual from cursor" => {
+            "光标 | Up/Down 移动 | Ctrl+Y 从光标开始选择"
+        }
+        "Auto-scroll" => "自动滚动",
+        "Paused - Press Up/Down to scroll" => "已暂停 - 按 Up/Down 滚动",
+        "Paused" => "已暂停",
+        " | Online: {}" => " | 在线: {}",
+        " | Buffer {}/{} ({} dropped)" => " | 缓冲 {}/{}（已丢弃 {}）",
+        " | PAUSED +{} new (Ctrl+P)" => " | 已暂停 +{} 条新消息 (Ctrl+P)",
+        "Raw:ON" => "原始:开",
+        "Raw:OFF" => "原始:关",
+        " Room {}{}{}{}{}{}{}{} | {} | {} " => " 房间 {}{}{}{}{}{}{}{} | {} | {} ",
+        "Chat" => "弹幕",
+        "Gifts" => "礼物",
+        "Events" => "事件",
+        " {} ({}) | {} | Tab " => " {}（{}）| {} | Tab ",
+        "ALL" => "全部",
+        " Logs ({} entries, {}){} | {} | e/w/i/d/a: level | /: search | Ctrl+Y: visual | Ctrl+H: help | Ctrl+L: close " => {
+            " 日志（{} 条，{}）{} | {} | e/w/i/d/a: 级别 | /: 搜索 | Ctrl+Y: 选择 | Ctrl+H: 帮助 | Ctrl+L: 关闭 "
+        }
+        " | Search: /{}_" => " | 搜索: /{}_",
+        " | /{} ({} matches, n/N)" => " | /{}（{} 处匹配，n/N）",
+        " -- NORMAL -- (i: insert | j/k: move | gg/G: jump | /: search | Ctrl+C: exit) " => {
+            " -- 普通 -- (i: 输入 | j/k: 移动 | gg/G: 跳转 | /: 搜索 | Ctrl+C: 退出) "
+        }
+        " -- INSERT -- (Esc: normal | Enter: send) " => " -- 输入 -- (Esc: 普通 | Enter: 发送) ",
+        " Input (Ctrl+H: help | Ctrl+Y: visual | Ctrl+C: exit) " => {
+            " 输入 (Ctrl+H: 帮助 | Ctrl+Y: 选择 | Ctrl+C: 退出) "
+        }
+        " Help - Logs " => " 帮助 - 日志 ",
+        " Help - Messages " => " 帮助 - 消息 ",
+        " User - {} (Esc: close) " => " 用户 - {} (Esc: 关闭) ",
+        "UID:      {}" => "UID:      {}",
+        "Medal:    {}" => "粉丝牌:   {}",
+        "Guard:    {}" => "舰队:     {}",
+        "Messages: {}" => "消息数:   {}",
+        "Gifts:    ¥{}" => "礼物:     ¥{}",
+        "No activity this session" => "本次会话暂无活动",
+        "No stored history" => "暂无历史记录",
+        "History" => "历史记录",
+        "First seen: {}" => "首次出现: {}",
+        "Total:      {} messages, ¥{}" => "累计:     {} 条消息, ¥{}",
+        "No payload recorded for this message" => "这条消息没有记录原始数据",
+        " Payload (Up/Down: scroll | Esc: close) " => " 原始数据 (Up/Down: 滚动 | Esc: 关闭) ",
+        // Help overlay
+        "Key Map" => "按键说明",
+        "Ctrl+H   Toggle this help" => "Ctrl+H   显示/隐藏帮助",
+        "Up/Down  Pick start line" => "Up/Down  选择起始行",
+        "Ctrl+Y   Enter visual mode from cursor" => "Ctrl+Y   从光标处进入选择模式",
+        "j/k      Move visual selection" => "j/k      移动选择范围",
+        "g / G    Jump to top or bottom" => "g / G    跳到顶部或底部",
+        "y        Copy selected messages" => "y        复制选中的消息",
+        "Esc      Close help, cancel visual, or close logs" => {
+            "Esc      关闭帮助、取消选择或关闭日志"
+        }
+        "Up/Down  Scroll logs normally" => "Up/Down  滚动日志",
+        "PgUp/Dn  Scroll faster" => "PgUp/Dn  快速滚动",
+        "Home/End Jump to top or bottom" => "Home/End 跳到顶部或底部",
+        "e/w/i/d  Show errors, warnings, info, or debug and above" => {
+            "e/w/i/d  显示错误、警告、信息或调试及以上级别"
+        }
+        "a        Show all levels" => "a        显示全部级别",
+        "/        Search logs (Enter apply, Esc clear)" => {
+            "/        搜索日志（Enter 应用，Esc 清除）"
+        }
+        "n / N    Next or previous match" => "n / N    下一个或上一个匹配",
+        "Ctrl+C   Exit app" => "Ctrl+C   退出程序",
+        "Enter    Send input" => "Enter    发送输入",
+        "Alt+Up/Dn Recall sent input (Up/Dn while typing)" => {
+            "Alt+Up/Dn 调出已发送的输入（输入时 Up/Dn）"
+        }
+        "/help    List slash commands" => "/help    列出斜杠命令",
+        "Ctrl+U   Show info for the user under cursor" => "Ctrl+U   显示光标处用户的信息",
+        "Ctrl+O   Inspect payload of the message under cursor" => {
+            "Ctrl+O   查看光标处消息的原始数据"
+        }
+        "Ctrl+B   Toggle online rank sidebar" => "Ctrl+B   显示/隐藏在线排行榜",
+        "Ctrl+P   Pause/resume incoming messages" => "Ctrl+P   暂停/恢复接收消息",
+        "Ctrl+S   Split chat, gifts and events panes" => "Ctrl+S   分栏显示弹幕、礼物和事件",
+        "Tab      Switch the pane scroll keys move" => "Tab      切换滚动的分栏",
+        "Ctrl+R   Toggle raw messages" => "Ctrl+R   显示/隐藏原始消息",
+        "Ctrl+T   Toggle message timestamps" => "Ctrl+T   显示/隐藏消息时间",
+        "Ctrl+L   Toggle logs panel" => "Ctrl+L   显示/隐藏日志面板",
+        "Up/Down  Scroll messages normally" => "Up/Down  滚动消息",
+        "Left/Right Move input cursor" => "Left/Right 移动输入光标",
+        "Home/End Move input cursor" => "Home/End 移动输入光标",
+        "Ctrl+Home Jump to top" => "Ctrl+Home 跳到顶部",
+        "Ctrl+End Jump to bottom" => "Ctrl+End 跳到底部",
+        "Esc      Close help, cancel visual, or quit" => "Esc      关闭帮助、取消选择或退出",
+        "Vim keymap (normal mode)" => "Vim 按键（普通模式）",
+        "j/k      Move cursor" => "j/k      移动光标",
+        "gg / G   Jump to top or bottom" => "gg / G   跳到顶部或底部",
+        "/ n N    Search messages, next or previous match" => {
+            "/ n N    搜索消息，下一个或上一个匹配"
+        }
+        "v        Enter visual mode" => "v        进入选择模式",
+        "i or :   Type a message or a /command (Esc back)" => {
+            "i 或 :   输入消息或 /命令（Esc 返回）"
+        }
+        // Commands and system messages
+        "List available commands" => "列出可用命令",
+        "Show user info" => "显示用户信息",
+        "Hide a user locally, or list muted users" => "在本地屏蔽用户，或列出已屏蔽用户",
+        "Show a muted user again" => "取消屏蔽用户",
+        "Only show a user's messages, with context" => "只显示某个用户的消息及其上文",
+        "Show all messages again" => "恢复显示所有消息",
+        "Show or hide a message category" => "显示或隐藏某类消息",
+        "Write visible messages to a text or .jsonl file" => "将可见消息写入文本或 .jsonl 文件",
+        "Exit app" => "退出程序",
+        "Commands:" => "命令：",
+        "Usage: {}" => "用法：{}",
+        "Unknown command: {} (try /help)" => "未知命令：{}（试试 /help）",
+        "Mute list is unavailable" => "屏蔽列表不可用",
+        "No muted users" => "没有已屏蔽的用户",
+        "Muted: {}" => "已屏蔽：{}",
+        "Muted {}" => "已屏蔽 {}",
+        "{} is already muted" => "{} 已被屏蔽",
+        "Unmuted {}" => "已取消屏蔽 {}",
+        "{} is not muted" => "{} 未被屏蔽",
+        "Following {} (/unfollow or Esc to stop)" => "正在关注 {}（/unfollow 或 Esc 停止）",
+        "Stopped following {}" => "已停止关注 {}",
+        "Not following anyone" => "没有正在关注的用户",
+        " | Following {}" => " | 关注 {}",
+        "Filters: {}" => "过滤：{}",
+        "{} messages shown" => "已显示 {} 消息",
+        "{} messages hidden" => "已隐藏 {} 消息",
+        "Saved {} message(s) to {}" => "已保存 {} 条消息到 {}",
+        "Failed to save messages to {}: {}" => "保存消息到 {} 失败：{}",
+        "Copied {} message(s)" => "已复制 {} 条消息",
+        "Copy failed: {}" => "复制失败：{}",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_translation() {
+        assert_eq!("zh_CN".parse::<Locale>(), Ok(Locale::ZhCn));
+        assert_eq!("en-US".parse::<Locale>(), Ok(Locale::En));
+        assert!("fr".parse::<Locale>().is_err());
+
+        assert_eq!(Locale::ZhCn.tr("Auto-scroll"), "自动滚动");
+        assert_eq!(Locale::ZhCn.tr("Not translated"), "Not translated");
+        assert_eq!(Locale::En.tr("Auto-scroll"), "Auto-scroll");
+        assert_eq!(
+            format_args_list(Locale::ZhCn.tr("Muted {}"), &[&"alice"]),
+            "已屏蔽 alice"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+// src/tui/layout.rs
+//! Arrangement and sizes of the TUI panes
+
+/// How the message list shares the screen with the stats pane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// Message list above the input box
+    #[default]
+    Vertical,
+    /// Message list on the left, viewer stats on the right, input box below both
+    Horizontal,
+}
+
+impl std::str::FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vertical" => Ok(Orientation::Vertical),
+            "horizontal" => Ok(Orientation::Horizontal),
+            _ => Err(format!(
+                "Unknown layout orientation '{}' (expected vertical or horizontal)",
+                s
+            )),
+        }
+    }
+}
+
+/// Pane of the split message view a message is shown in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Pane {
+    /// Danmaku
+    #[default]
+    Chat,
+    /// Gifts, super chats and guard purchases
+    Gifts,
+    /// Entrances, room changes, system and raw messages
+    Events,
+}
+
+impl Pane {
+    /// Panes in focus order
+    pub const ALL: [Pane; 3] = [Pane::Chat, Pane::Gifts, Pane::Events];
+
+    /// Pane of a message, by the category prefix of its text
+    pub fn of(text: &str) -> Self {
+        if text.starts_with("[Danmu]") {
+            Pane::Chat
+        } else if ["[Gift]", "[SuperChat]", "[Guard]"]
+            .iter()
+            .any(|prefix| text.starts_with(prefix))
+        {
+            Pane::Gifts
+        } else {
+            Pane::Events
+        }
+    }
+
+    /// Pane after this one in focus order, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|pane| *pane == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Pane arrangement and sizes of the message view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TuiLayout {
+    pub orientation: Orientation,
+    /// Height of the input box, in percent of the screen
+    pub input_percent: u16,
+    /// Whether the input box is shown; without it the TUI is read-only
+    pub show_input: bool,
+    /// Width of the stats pane in the horizontal orientation, in percent of the screen
+    pub stats_percent: u16,
+    /// Whether danmaku, gifts and events are shown in separate panes
+    pub split: bool,
+}
+
+impl TuiLayout {
+    /// Input box height, kept between 1% and 90%
+    pub fn input_percent(&self) -> u16 {
+        self.input_percent.clamp(1, 90)
+    }
+
+    /// Stats pane width, kept between 10% and 90%
+    pub fn stats_percent(&self) -> u16 {
+        self.stats_percent.clamp(10, 90)
+    }
+}
+
+impl Default for TuiLayout {
+    fn default() -> Self {
+        Self {
+            orientation: Orientation::Vertical,
+            input_percent: 10,
+            show_input: true,
+            stats_percent: 30,
+            split: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_parsing_and_limits() {
+        assert_eq!("Horizontal".parse(), Ok(Orientation::Horizontal));
+        assert!("diagonal".parse::<Orientation>().is_err());
+
+        let layout = TuiLayout {
+            input_percent: 0,
+            stats_percent: 100,
+            ..TuiLayout::default()
+        };
+        assert_eq!(layout.input_percent(), 1);
+        assert_eq!(layout.stats_percent(), 90);
+
+        assert_eq!(Pane::of("[Danmu] viewer: hi"), Pane::Chat);
+        assert_eq!(Pane::of("[SuperChat] viewer (¥30): hi"), Pane::Gifts);
+        assert_eq!(Pane::of("[Entry] viewer entered"), Pane::Events);
+        assert_eq!(Pane::Events.next(), Pane::Chat);
+    }
+}
@@ -3,39 +3,117 @@
 
 use log::{Log, Metadata, Record};
 use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 /// Maximum number of log messages to keep in buffer
 const MAX_LOG_MESSAGES: usize = 1000;
+/// Size at which the log file is rotated to `<path>.1`
+const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// A captured log record with its level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub level: log::Level,
+    /// Formatted line: `[elapsed] [LEVEL] [target] message`
+    pub text: String,
+}
+
+/// Log file that is rotated to `<path>.1` when it grows past `max_size`
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&self.path, rotated)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
 
 /// A logger that writes log messages to a shared buffer for TUI display.
 /// It also optionally forwards to env_logger for file/stderr output.
 pub struct TuiLogger {
-    buffer: Arc<Mutex<VecDeque<String>>>,
+    buffer: Arc<Mutex<VecDeque<LogEntry>>>,
     level: log::LevelFilter,
     start_time: Instant,
+    /// Optional file that also receives every record
+    file: Option<Mutex<LogFile>>,
 }
 
 impl TuiLogger {
     /// Create a new TuiLogger with the given shared buffer and level filter.
-    pub fn new(buffer: Arc<Mutex<VecDeque<String>>>, level: log::LevelFilter) -> Self {
+    pub fn new(buffer: Arc<Mutex<VecDeque<LogEntry>>>, level: log::LevelFilter) -> Self {
         Self {
             buffer,
             level,
             start_time: Instant::now(),
+            file: None,
         }
     }
 
+    /// Also append records to `path`, rotating it to `<path>.1` when it reaches 10 MiB
+    pub fn with_log_file(mut self, path: &Path) -> io::Result<Self> {
+        self.file = Some(Mutex::new(LogFile::open(path, MAX_LOG_FILE_SIZE)?));
+        Ok(self)
+    }
+
     /// Initialize this logger as the global logger.
     /// Returns the shared buffer so it can be passed to TuiApp.
-    pub fn init(level: log::LevelFilter) -> Arc<Mutex<VecDeque<String>>> {
+    pub fn init(level: log::LevelFilter) -> Arc<Mutex<VecDeque<LogEntry>>> {
         let buffer = Arc::new(Mutex::new(VecDeque::new()));
         let logger = TuiLogger::new(Arc::clone(&buffer), level);
         log::set_boxed_logger(Box::new(logger)).expect("Failed to set TuiLogger");
         log::set_max_level(level);
         buffer
     }
+
+    /// Initialize this logger as the global logger, also writing records to `path`.
+    /// Returns the shared buffer so it can be passed to TuiApp.
+    pub fn init_with_log_file(
+        level: log::LevelFilter,
+        path: &Path,
+    ) -> io::Result<Arc<Mutex<VecDeque<LogEntry>>>> {
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let logger = TuiLogger::new(Arc::clone(&buffer), level).with_log_file(path)?;
+        log::set_boxed_logger(Box::new(logger)).expect("Failed to set TuiLogger");
+        log::set_max_level(level);
+        Ok(buffer)
+    }
 }
 
 impl Log for TuiLogger {
@@ -61,13 +139,58 @@ impl Log for TuiLogger {
             record.args()
         );
 
+        // The file gets wall-clock time since it outlives the session
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.write_line(&format!(
+                "{} [{}] [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+
         if let Ok(mut buf) = self.buffer.lock() {
-            buf.push_back(msg);
+            buf.push_back(LogEntry {
+                level: record.level(),
+                text: msg,
+            });
             while buf.len() > MAX_LOG_MESSAGES {
                 buf.pop_front();
             }
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_file_rotation() {
+        let dir = std::env::temp_dir().join(format!("blivedm-log-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("blivedm.log");
+
+        let mut file = LogFile::open(&path, 16).unwrap();
+        file.write_line("first line").unwrap();
+        file.write_line("second line").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second line\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("blivedm.log.1")).unwrap(),
+            "first line\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,85 @@
+// src/tui/message.rs
+//! Messages stored in the TUI message buffer
+
+use crate::client::models::BiliMessage;
+use chrono::{DateTime, Local};
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Source of message ids, unique within the process
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A formatted message and the metadata recorded when it was received
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuiMessage {
+    /// Unique id, used to cache per-message render state
+    pub id: u64,
+    /// Display text, starting with a category prefix such as `[Danmu]`
+    pub text: String,
+    /// Local time the message was added to the buffer
+    pub received_at: DateTime<Local>,
+    /// Server JSON the message was parsed from, if recorded
+    pub payload: Option<Value>,
+    /// Parsed message the text was formatted from, if any
+    pub source: Option<BiliMessage>,
+}
+
+impl TuiMessage {
+    /// Create a message received now
+    pub fn new(text: String) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            text,
+            received_at: Local::now(),
+            payload: None,
+            source: None,
+        }
+    }
+
+    /// Attach the server JSON the message was parsed from
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Attach the parsed message the text was formatted from
+    pub fn with_source(mut self, source: BiliMessage) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Receive time formatted as `HH:MM:SS`
+    pub fn timestamp(&self) -> String {
+        self.received_at.format("%H:%M:%S").to_string()
+    }
+
+    /// JSON record of the message, as written by `/save` to `.jsonl` files
+    pub fn to_json(&self) -> Value {
+        json!({
+            "time": self.received_at.to_rfc3339(),
+            "text": self.text,
+            "payload": self.payload,
+        })
+    }
+
+    /// Text for display, optionally prefixed with the receive time
+    pub fn display_text(&self, show_timestamp: bool) -> String {
+        if show_timestamp {
+            format!("{} {}", self.timestamp(), self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+impl From<String> for TuiMessage {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<&str> for TuiMessage {
+    fn from(text: &str) -> Self {
+        Self::new(text.to_string())
+    }
+}
@@ -2,10 +2,31 @@
 //! TUI module for displaying messages and handling user input
 
 pub mod app;
+pub mod buffer;
+pub mod command;
 pub mod event;
+pub mod follow;
+pub mod graphics;
+pub mod i18n;
+pub mod layout;
 pub mod logger;
+pub mod message;
+pub mod mute;
+pub mod rate;
+pub mod superchat;
 pub mod ui;
+pub mod wrap;
 
-pub use app::TuiApp;
+pub use app::{Keymap, TuiApp};
+pub use buffer::MessageBuffer;
+pub use command::{CommandArgs, CommandOutput, CommandRegistry};
 pub use event::run_tui;
-pub use logger::TuiLogger;
+pub use follow::Follow;
+pub use graphics::{Graphics, GraphicsProtocol};
+pub use i18n::Locale;
+pub use layout::{Orientation, Pane, TuiLayout};
+pub use logger::{LogEntry, TuiLogger};
+pub use message::TuiMessage;
+pub use mute::MuteList;
+pub use rate::MessageRate;
+pub use superchat::SuperChatBoard;
@@ -0,0 +1,67 @@
+// src/tui/mute.rs
+//! Local mute list for hiding users from the message list
+
+use std::collections::BTreeSet;
+
+/// Users whose messages are hidden locally, by name or uid
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MuteList {
+    names: BTreeSet<String>,
+    uids: BTreeSet<u64>,
+}
+
+impl MuteList {
+    pub fn new<N, U>(names: N, uids: U) -> Self
+    where
+        N: IntoIterator<Item = String>,
+        U: IntoIterator<Item = u64>,
+    {
+        Self {
+            names: names.into_iter().collect(),
+            uids: uids.into_iter().collect(),
+        }
+    }
+
+    /// Mute an entry given as `uid:<uid>` or a user name; returns false if already muted
+    pub fn mute(&mut self, entry: &str) -> bool {
+        match parse_uid(entry) {
+            Some(uid) => self.uids.insert(uid),
+            None => self.names.insert(entry.to_string()),
+        }
+    }
+
+    /// Unmute an entry given as `uid:<uid>` or a user name; returns false if it was not muted
+    pub fn unmute(&mut self, entry: &str) -> bool {
+        match parse_uid(entry) {
+            Some(uid) => self.uids.remove(&uid),
+            None => self.names.remove(entry),
+        }
+    }
+
+    /// Whether a user is muted by name or by uid (0 = unknown uid)
+    pub fn is_muted(&self, name: &str, uid: u64) -> bool {
+        self.names.contains(name) || (uid != 0 && self.uids.contains(&uid))
+    }
+
+    /// Whether a user name is muted
+    pub fn is_name_muted(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.uids.is_empty()
+    }
+
+    /// All entries in `/mute` syntax
+    pub fn entries(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .cloned()
+            .chain(self.uids.iter().map(|uid| format!("uid:{}", uid)))
+            .collect()
+    }
+}
+
+fn parse_uid(entry: &str) -> Option<u64> {
+    entry.strip_prefix("uid:")?.parse().ok()
+}
@@ -0,0 +1,150 @@
+// src/tui/rate.rs
+//! Chat message rate tracking for the TUI title sparkline
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Width of one rate bucket
+pub const BUCKET_DURATION: Duration = Duration::from_secs(10);
+/// Number of buckets kept in the ring buffer
+const BUCKET_COUNT: usize = 12;
+/// Sparkline glyphs from lowest to highest
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Ring buffer of message counts per 10 seconds
+#[derive(Debug, Clone)]
+pub struct MessageRate {
+    started: Instant,
+    /// Index of the newest bucket since `started`
+    current_bucket: u64,
+    buckets: VecDeque<u64>,
+}
+
+impl MessageRate {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            current_bucket: 0,
+            buckets: VecDeque::from([0]),
+        }
+    }
+
+    /// Count one message received now
+    pub fn record(&mut self) {
+        self.record_at(Instant::now());
+    }
+
+    /// Count one message received at `now`
+    pub fn record_at(&mut self, now: Instant) {
+        self.advance(now);
+        if let Some(count) = self.buckets.back_mut() {
+            *count += 1;
+        }
+    }
+
+    /// Message count of the current (incomplete) bucket
+    pub fn current(&mut self) -> u64 {
+        self.current_at(Instant::now())
+    }
+
+    /// Message count of the bucket containing `now`
+    pub fn current_at(&mut self, now: Instant) -> u64 {
+        self.advance(now);
+        self.buckets.back().copied().unwrap_or(0)
+    }
+
+    /// Render the bucket history as a sparkline, oldest first
+    pub fn sparkline(&mut self) -> String {
+        self.sparkline_at(Instant::now())
+    }
+
+    /// Render the bucket history up to `now` as a sparkline, oldest first
+    pub fn sparkline_at(&mut self, now: Instant) -> String {
+        self.advance(now);
+        let max = self.buckets.iter().copied().max().unwrap_or(0);
+        let mut line: String = self
+            .buckets
+            .iter()
+            .map(|&count| {
+                if max == 0 {
+                    SPARK_CHARS[0]
+                } else {
+                    let level = (count * (SPARK_CHARS.len() as u64 - 1)).div_ceil(max);
+                    SPARK_CHARS[level as usize]
+                }
+            })
+            .collect();
+        // Pad on the left so the sparkline keeps its width from the start
+        for _ in self.buckets.len()..BUCKET_COUNT {
+            line.insert(0, ' ');
+        }
+        line
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.started);
+        let bucket = elapsed.as_secs() / BUCKET_DURATION.as_secs();
+        let gap = bucket.saturating_sub(self.current_bucket);
+        for _ in 0..gap.min(BUCKET_COUNT as u64) {
+            self.buckets.push_back(0);
+        }
+        while self.buckets.len() > BUCKET_COUNT {
+            self.buckets.pop_front();
+        }
+        self.current_bucket = bucket;
+    }
+}
+
+impl Default for MessageRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_rate_window() {
+        let mut rate = MessageRate::new();
+        let start = rate.started;
+        rate.record_at(start);
+        rate.record_at(start + Duration::from_secs(3));
+        rate.record_at(start + Duration::from_secs(9));
+        assert_eq!(rate.current_at(start + Duration::from_secs(9)), 3);
+
+        // The next bucket starts empty and the earlier one stays in the history
+        assert_eq!(rate.current_at(start + BUCKET_DURATION), 0);
+        rate.record_at(start + BUCKET_DURATION + Duration::from_secs(1));
+        assert_eq!(
+            rate.current_at(start + BUCKET_DURATION * 2 - Duration::from_secs(1)),
+            1
+        );
+        assert_eq!(rate.buckets, VecDeque::from([3, 1]));
+        let line = rate.sparkline_at(start + BUCKET_DURATION);
+        assert_eq!(line.chars().count(), BUCKET_COUNT);
+        assert!(line.ends_with("█▄"));
+    }
+
+    #[test]
+    fn test_message_rate_evicts_old_buckets() {
+        let mut rate = MessageRate::new();
+        let start = rate.started;
+        rate.record_at(start);
+        rate.record_at(start + BUCKET_DURATION);
+
+        // The first bucket falls out once BUCKET_COUNT newer ones exist
+        let last = start + BUCKET_DURATION * BUCKET_COUNT as u32;
+        assert_eq!(rate.current_at(last), 0);
+        assert_eq!(rate.buckets.len(), BUCKET_COUNT);
+        assert_eq!(rate.buckets.iter().sum::<u64>(), 1);
+        assert_eq!(rate.buckets[0], 1);
+
+        // A gap longer than the history leaves only empty buckets
+        rate.record_at(last + BUCKET_DURATION * 100);
+        assert_eq!(rate.buckets.len(), BUCKET_COUNT);
+        assert_eq!(rate.buckets.iter().sum::<u64>(), 1);
+        assert_eq!(rate.buckets.back(), Some(&1));
+    }
+}
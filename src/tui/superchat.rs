@@ -0,0 +1,105 @@
+// src/tui/superchat.rs
+//! Super chats pinned above the message list for their paid duration
+
+use std::time::{Duration, Instant};
+
+/// A super chat pinned until `expires_at`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedSuperChat {
+    pub id: u64,
+    pub user: String,
+    pub text: String,
+    /// Price in CNY
+    pub price: u64,
+    pub expires_at: Instant,
+}
+
+impl PinnedSuperChat {
+    /// Time left before the super chat is unpinned
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.expires_at.saturating_duration_since(now)
+    }
+}
+
+/// Super chats currently pinned, newest last
+#[derive(Debug, Clone, Default)]
+pub struct SuperChatBoard {
+    pinned: Vec<PinnedSuperChat>,
+}
+
+impl SuperChatBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin a super chat received now for `duration` seconds
+    pub fn pin(&mut self, id: u64, user: &str, text: &str, price: u64, duration: u64) {
+        self.pin_at(id, user, text, price, duration, Instant::now());
+    }
+
+    /// Pin a super chat received at `now`; a super chat with the same id is only pinned once
+    pub fn pin_at(
+        &mut self,
+        id: u64,
+        user: &str,
+        text: &str,
+        price: u64,
+        duration: u64,
+        now: Instant,
+    ) {
+        if id != 0 && self.pinned.iter().any(|sc| sc.id == id) {
+            return;
+        }
+        self.pinned.push(PinnedSuperChat {
+            id,
+            user: user.to_string(),
+            text: text.to_string(),
+            price,
+            expires_at: now + Duration::from_secs(duration),
+        });
+    }
+
+    /// Unpin a withdrawn or moderated super chat, returning whether it was pinned
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.pinned.len();
+        self.pinned.retain(|sc| sc.id != id);
+        self.pinned.len() != before
+    }
+
+    /// Super chats still pinned now, dropping expired ones
+    pub fn active(&mut self) -> Vec<PinnedSuperChat> {
+        self.active_at(Instant::now())
+    }
+
+    /// Super chats still pinned at `now`, dropping expired ones
+    pub fn active_at(&mut self, now: Instant) -> Vec<PinnedSuperChat> {
+        self.pinned.retain(|sc| sc.expires_at > now);
+        self.pinned.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_super_chat_board_expiry() {
+        let now = Instant::now();
+        let mut board = SuperChatBoard::new();
+        board.pin_at(1, "a", "short", 30, 60, now);
+        board.pin_at(2, "b", "long", 100, 300, now);
+        board.pin_at(2, "b", "long", 100, 300, now);
+
+        let active = board.active_at(now);
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].remaining(now), Duration::from_secs(60));
+
+        let active = board.active_at(now + Duration::from_secs(120));
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, 2);
+
+        assert!(board.remove(2));
+        assert!(!board.remove(2));
+        assert!(board.active_at(now).is_empty());
+    }
+}
@@ -1,31 +1,340 @@
 // src/tui/ui.rs
 //! UI rendering logic for the TUI
 
-use crate::tui::app::TuiApp;
+use crate::client::chat_store::UserHistory;
+use crate::client::gift_catalog::coins_to_rmb;
+use crate::client::models::guard_level_name;
+use crate::client::status::{ConnectionState, ConnectionStatus};
+use crate::tui::app::{Keymap, TuiApp};
+use crate::tui::follow::{FOLLOW_CONTEXT, follow_view};
+use crate::tui::graphics::{Graphics, IMAGE_CELLS, ImagePlacement};
+use crate::tui::i18n::{tr, trf};
+use crate::tui::layout::{Orientation, Pane};
+use crate::tui::message::TuiMessage;
+use crate::tui::superchat::PinnedSuperChat;
+use crate::tui::wrap::wrap_text;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use std::ops::Range;
+use std::time::{Duration, Instant};
 use unicode_width::UnicodeWidthStr;
 
+/// Maximum number of super chats shown in the pinned strip
+const MAX_PINNED_SUPER_CHATS: usize = 3;
+/// Width of the online rank sidebar, including borders
+const RANK_SIDEBAR_WIDTH: u16 = 30;
+
 pub fn render(f: &mut Frame, app: &mut TuiApp) {
+    app.image_placements.clear();
+    let mut screen = f.area();
+    if let Some(status) = app.get_connection_status() {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(screen);
+        render_status_bar(f, &status, rows[1]);
+        screen = rows[0];
+    }
+
     if app.show_logs {
-        render_logs_panel(f, app, f.area());
+        render_logs_panel(f, app, screen);
     } else {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(90), Constraint::Percentage(10)])
-            .split(f.area());
+        let super_chats = app.get_super_chats();
+        let mut area = screen;
+        if !super_chats.is_empty() {
+            // Newest super chats first, at most three rows plus borders
+            let height = super_chats.len().min(MAX_PINNED_SUPER_CHATS) as u16 + 2;
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(height), Constraint::Min(0)])
+                .split(area);
+            render_super_chats(f, &super_chats, chunks[0]);
+            area = chunks[1];
+        }
+
+        let layout = app.layout;
+        let mut message_area = area;
+        if layout.show_input {
+            let input_percent = layout.input_percent();
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Percentage(100 - input_percent),
+                    Constraint::Percentage(input_percent),
+                ])
+                .split(area);
+            message_area = chunks[0];
+            render_input_box(f, app, chunks[1]);
+        }
+
+        if layout.orientation == Orientation::Horizontal {
+            let stats_percent = layout.stats_percent();
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(100 - stats_percent),
+                    Constraint::Percentage(stats_percent),
+                ])
+                .split(message_area);
+            message_area = columns[0];
+            render_stats_panel(f, app, columns[1]);
+        }
+
+        if app.show_rank {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(RANK_SIDEBAR_WIDTH)])
+                .split(message_area);
+            message_area = columns[0];
+            render_rank_sidebar(f, app, columns[1]);
+        }
+
+        if layout.split {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(message_area);
+            message_area = columns[0];
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(columns[1]);
+            render_side_pane(f, app, Pane::Gifts, rows[0]);
+            render_side_pane(f, app, Pane::Events, rows[1]);
+        }
 
-        render_message_list(f, app, chunks[0]);
-        render_input_box(f, app, chunks[1]);
+        render_message_list(f, app, message_area);
     }
 
+    if app.show_help || app.inspector.is_some() || app.user_popup.is_some() {
+        // Images would be drawn over the overlay
+        app.image_placements.clear();
+    }
     if app.show_help {
         render_help_overlay(f, app);
+    } else if app.inspector.is_some() {
+        render_inspector(f, app);
+    } else if app.user_popup.is_some() {
+        render_user_popup(f, app);
+    }
+}
+
+fn render_super_chats(f: &mut Frame, super_chats: &[PinnedSuperChat], area: Rect) {
+    let now = Instant::now();
+    let lines = super_chats
+        .iter()
+        .rev()
+        .take(MAX_PINNED_SUPER_CHATS)
+        .map(|sc| {
+            let remaining = sc.remaining(now).as_secs();
+            let tier = super_chat_tier_color(sc.price);
+            Line::from(vec![
+                Span::styled(
+                    format!(
+                        " ¥{} {:02}:{:02} ",
+                        sc.price,
+                        remaining / 60,
+                        remaining % 60
+                    ),
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(tier)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(format!(" {}: ", sc.user), Style::default().fg(tier)),
+                Span::raw(sc.text.clone()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let title = if super_chats.len() > MAX_PINNED_SUPER_CHATS {
+        trf(
+            " Super Chats ({} pinned, newest first) ",
+            &[&super_chats.len()],
+        )
+    } else {
+        tr(" Super Chats ").to_string()
+    };
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(Color::LightRed)),
+    );
+
+    f.render_widget(paragraph, area);
+}
+
+fn render_status_bar(f: &mut Frame, status: &ConnectionStatus, area: Rect) {
+    let (state, color) = match status.state {
+        ConnectionState::Connected => (tr("Connected").to_string(), Color::Green),
+        ConnectionState::Reconnecting { attempt, retry_at } => {
+            let wait = retry_at.saturating_duration_since(Instant::now());
+            let state = if wait.is_zero() {
+                trf("Reconnecting (attempt {})", &[&attempt])
+            } else {
+                // Round up so the countdown never shows 0s while still waiting
+                let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                trf(
+                    "Reconnecting in {}s (attempt {}, /reconnect to retry now)",
+                    &[&secs, &attempt],
+                )
+            };
+            (state, Color::Yellow)
+        }
+        ConnectionState::Disconnected => (tr("Disconnected").to_string(), Color::Red),
+    };
+    let rtt = match status.heartbeat_rtt {
+        Some(rtt) => format!("{} ms", rtt.as_millis()),
+        None => "-".to_string(),
+    };
+    let identity = if status.uid > 0 {
+        format!("uid {}", status.uid)
+    } else {
+        tr("guest").to_string()
+    };
+
+    let label = Style::default().fg(Color::DarkGray);
+    let mut spans = vec![
+        Span::styled(format!(" ● {}", state), Style::default().fg(color)),
+        Span::styled(tr(" | RTT "), label),
+        Span::raw(rtt),
+        Span::styled(tr(" | Uptime "), label),
+        Span::raw(format_duration(status.uptime())),
+        Span::styled(tr(" | Login "), label),
+        Span::raw(identity),
+    ];
+    if status.auth_fallback.is_some() {
+        spans.push(Span::styled(
+            tr(" (cookies rejected)"),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if status.state == ConnectionState::Disconnected {
+        if let Some(error) = &status.last_error {
+            spans.push(Span::styled(
+                format!(" | {}", error),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        spans.push(Span::styled(tr(" | /reconnect to retry"), label));
+    }
+
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Format a duration as `HH:MM:SS`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn render_stats_panel(f: &mut Frame, app: &TuiApp, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = vec![Line::from(vec![
+        Span::styled(tr("Online: "), label),
+        Span::raw(app.get_online_count().to_string()),
+    ])];
+    match app
+        .viewer_stats
+        .as_ref()
+        .and_then(|stats| stats.lock().ok())
+    {
+        Some(stats) => {
+            lines.push(Line::from(vec![
+                Span::styled(tr("Chatters: "), label),
+                Span::raw(stats.unique_chatters().to_string()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled(tr("First-time: "), label),
+                Span::raw(stats.first_time_chatters().len().to_string()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled(tr("Danmaku: "), label),
+                Span::raw(stats.total_messages().to_string()),
+            ]));
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                tr("Top chatters"),
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            let rows = area.height.saturating_sub(2 + lines.len() as u16) as usize;
+            for (name, count) in stats.top_chatters(rows) {
+                lines.push(Line::from(vec![
+                    Span::raw(name.to_string()),
+                    Span::styled(format!(" {}", count), label),
+                ]));
+            }
+        }
+        None => lines.push(Line::from(Span::styled(tr("No stats collected"), label))),
+    }
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(tr(" Stats "))
+            .border_style(Style::default().fg(Color::Green)),
+    );
+
+    f.render_widget(paragraph, area);
+}
+
+fn render_rank_sidebar(f: &mut Frame, app: &TuiApp, area: Rect) {
+    let online_rank = app.get_online_rank();
+    let lines = if online_rank.is_empty() {
+        vec![Line::from(Span::styled(
+            tr("Waiting for rank..."),
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        online_rank
+            .iter()
+            .map(|entry| {
+                let rank_style = match entry.rank {
+                    1 => Style::default().fg(Color::Yellow),
+                    2 => Style::default().fg(Color::White),
+                    3 => Style::default().fg(Color::LightRed),
+                    _ => Style::default().fg(Color::Gray),
+                };
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>2} ", entry.rank),
+                        rank_style.add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(entry.name.clone()),
+                    Span::styled(
+                        format!(" {}", entry.score),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(tr(" Rank (Ctrl+B) "))
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    f.render_widget(paragraph, area);
+}
+
+/// Color of a super chat by price tier, following the live room player
+fn super_chat_tier_color(price: u64) -> Color {
+    match price {
+        0..50 => Color::Rgb(0x2a, 0x60, 0xb2),
+        50..100 => Color::Rgb(0x42, 0x7d, 0x9e),
+        100..500 => Color::Rgb(0xe2, 0xb5, 0x2b),
+        500..1000 => Color::Rgb(0xe0, 0x94, 0x43),
+        1000..2000 => Color::Rgb(0xe5, 0x4d, 0x4d),
+        _ => Color::Rgb(0xab, 0x1a, 0x32),
     }
 }
 
@@ -35,14 +344,50 @@ fn render_message_list(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     let visible_height = area.height.saturating_sub(2) as usize;
     let mut all_lines = Vec::new();
 
-    for msg in &messages {
-        if !app.show_raw && msg.starts_with("[Raw]") {
+    app.wrap_cache.prepare(inner_width, app.show_timestamps);
+    app.wrap_cache.retain(&messages);
+    let followed = app.follow.as_ref().map(|follow| {
+        let shown: Vec<Option<bool>> = messages
+            .iter()
+            .map(|msg| {
+                (app.is_message_visible(&msg.text) && app.is_main_pane_message(&msg.text))
+                    .then(|| follow.matches(msg))
+            })
+            .collect();
+        follow_view(&shown, FOLLOW_CONTEXT)
+    });
+    for (msg_idx, msg) in messages.iter().enumerate() {
+        if !app.is_message_visible(&msg.text) || !app.is_main_pane_message(&msg.text) {
             continue;
         }
 
-        let style = get_message_style(msg);
-        for line_text in wrap_text(msg, inner_width) {
-            all_lines.push((line_text, style));
+        let mut style = get_message_style(&msg.text);
+        match followed.as_ref().map(|followed| followed[msg_idx]) {
+            // Context before a message of the followed user
+            Some(Some(false)) => style = Style::default().fg(Color::DarkGray),
+            Some(None) => continue,
+            _ => {}
+        }
+        if app.is_highlighted(&msg.text) {
+            style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+        }
+        let text = msg.display_text(app.show_timestamps);
+        let wrapped = app.wrap_cache.get(msg).to_vec();
+        // Byte range of the sender name in the first line, colored by guard level or medal
+        let mut name_span = get_user_style(msg).and_then(|(user, user_style)| {
+            let start = text.len() - msg.text.len() + "[Danmu] ".len();
+            text[start..].starts_with(user.as_str()).then_some((
+                start,
+                start + user.len(),
+                user_style,
+            ))
+        });
+        for line_text in wrapped {
+            let span = name_span
+                .take()
+                .map(|(start, end, user_style)| (start, end.min(line_text.len()), user_style))
+                .filter(|(start, end, _)| start < end);
+            all_lines.push((line_text, style, msg_idx, span));
         }
     }
 
@@ -54,59 +399,290 @@ fn render_message_list(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     };
 
     let start_line = app.set_rendered_lines(
-        all_lines.iter().map(|(text, _)| text.clone()).collect(),
+        all_lines
+            .iter()
+            .map(|(text, _, _, _)| text.clone())
+            .collect(),
+        all_lines.iter().map(|(_, _, idx, _)| *idx).collect(),
         start_line,
         visible_height,
     );
 
+    if let Some(graphics) = &app.graphics {
+        // Blank the text under each image; text stays as the placeholder until the image is ready
+        let end = (start_line + visible_height).min(all_lines.len());
+        for idx in start_line..end {
+            let first = idx == 0 || all_lines[idx - 1].2 != all_lines[idx].2;
+            let (line_text, _, msg_idx, _) = &mut all_lines[idx];
+            let images = line_images(graphics, &messages[*msg_idx], line_text, first);
+            for (range, key) in images.into_iter().rev() {
+                let width = line_text[range.clone()].width();
+                if width < IMAGE_CELLS as usize {
+                    continue;
+                }
+                app.image_placements.push(ImagePlacement {
+                    key,
+                    x: area.x + 1 + line_text[..range.start].width() as u16,
+                    y: area.y + 1 + (idx - start_line) as u16,
+                });
+                line_text.replace_range(range, &" ".repeat(width));
+            }
+        }
+    }
+
+    let query = app
+        .log_search
+        .as_ref()
+        .map(|query| query.to_ascii_lowercase())
+        .filter(|query| !query.is_empty());
     let visible_lines = all_lines
         .into_iter()
         .enumerate()
         .skip(start_line)
         .take(visible_height)
-        .map(|(idx, (line_text, style))| {
-            Line::from(Span::styled(
+        .map(|(idx, (line_text, style, _, name_span))| match name_span {
+            _ if query
+                .as_ref()
+                .is_some_and(|query| line_text.to_ascii_lowercase().contains(query)) =>
+            {
+                let style = style_for_line(app, idx, style, Color::Blue);
+                Line::from(highlight_matches(
+                    &line_text,
+                    query.as_deref().unwrap_or_default(),
+                    style,
+                ))
+            }
+            Some((start, end, user_style)) => Line::from(vec![
+                Span::styled(
+                    line_text[..start].to_string(),
+                    style_for_line(app, idx, style, Color::Blue),
+                ),
+                Span::styled(
+                    line_text[start..end].to_string(),
+                    style_for_line(app, idx, style.patch(user_style), Color::Blue),
+                ),
+                Span::styled(
+                    line_text[end..].to_string(),
+                    style_for_line(app, idx, style, Color::Blue),
+                ),
+            ]),
+            None => Line::from(Span::styled(
                 line_text,
                 style_for_line(app, idx, style, Color::Blue),
-            ))
+            )),
         })
         .collect::<Vec<_>>();
 
     let scroll_indicator = if app.visual_mode {
-        "VISUAL | j/k move | g/G jump | y copy | Esc cancel"
+        tr("VISUAL | j/k move | g/G jump | y copy | Esc cancel")
     } else if app.pane_cursor().is_some() {
-        "CURSOR | Up/Down move | Ctrl+Y visual from cursor"
+        tr("CURSOR | Up/Down move | Ctrl+Y visual from cursor")
     } else if app.auto_scroll {
-        "Auto-scroll"
+        tr("Auto-scroll")
     } else {
-        "Paused - Press Up/Down to scroll"
+        tr("Paused - Press Up/Down to scroll")
     };
 
+    let room_display = [
+        app.get_room_id(),
+        app.get_room_streamer(),
+        app.get_room_title(),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(" - ");
+
     let online_count = app.get_online_count();
     let online_display = if online_count > 0 {
-        format!(" | Online: {}", online_count)
+        trf(" | Online: {}", &[&online_count])
+    } else {
+        String::new()
+    };
+
+    let rate_display = match app.get_message_rate() {
+        Some((sparkline, current)) => format!(" | {} {}/10s", sparkline, current),
+        None => String::new(),
+    };
+    let counters_display = match app.get_message_counters() {
+        Some(counters) => format!(" | {}", counters),
+        None => String::new(),
+    };
+
+    let (buffered, capacity, evicted) = app.buffer_stats();
+    let buffer_display = if evicted > 0 {
+        trf(
+            " | Buffer {}/{} ({} dropped)",
+            &[&buffered, &capacity, &evicted],
+        )
     } else {
         String::new()
     };
 
-    let raw_indicator = if app.show_raw { "Raw:ON" } else { "Raw:OFF" };
-    let title = format!(
-        " Room {}{} | {} | {} ",
-        app.room_id, online_display, scroll_indicator, raw_indicator
+    let follow_display = match &app.follow {
+        Some(follow) => trf(" | Following {}", &[&follow.entry]),
+        None => String::new(),
+    };
+
+    let pause_display = if app.is_paused() {
+        trf(" | PAUSED +{} new (Ctrl+P)", &[&app.paused_new_messages()])
+    } else {
+        String::new()
+    };
+
+    let raw_indicator = if app.show_raw {
+        tr("Raw:ON")
+    } else {
+        tr("Raw:OFF")
+    };
+    let title = trf(
+        " Room {}{}{}{}{}{}{}{} | {} | {} ",
+        &[
+            &room_display,
+            &online_display,
+            &rate_display,
+            &counters_display,
+            &buffer_display,
+            &follow_display,
+            &pause_display,
+            &search_display(app),
+            &scroll_indicator,
+            &raw_indicator,
+        ],
     );
 
     let paragraph = Paragraph::new(visible_lines)
-        .block(Block::default().borders(Borders::ALL).title(title))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(pane_border_style(app, Pane::Chat)),
+        )
         .wrap(Wrap { trim: false });
 
     f.render_widget(paragraph, area);
 }
 
+/// Byte ranges of a message list line to draw as images and their image keys:
+/// the `[Danmu]` tag as the sender's avatar and the emotes in the text. Only
+/// images already downloaded are returned; the others are requested.
+fn line_images(
+    graphics: &Graphics,
+    msg: &TuiMessage,
+    line: &str,
+    first: bool,
+) -> Vec<(Range<usize>, String)> {
+    let Some(danmu) = msg.source.as_ref().and_then(|source| source.as_danmaku()) else {
+        return Vec::new();
+    };
+    let mut images = Vec::new();
+    let mut text_start = 0;
+    if first {
+        let Some(tag) = line.find("[Danmu] ") else {
+            return Vec::new();
+        };
+        text_start = (tag + "[Danmu] ".len() + danmu.user.len() + ": ".len()).min(line.len());
+        if graphics.avatars && danmu.uid != 0 {
+            images.push((tag..tag + "[Danmu]".len(), format!("uid:{}", danmu.uid)));
+        }
+    }
+    if let Ok(emotes) = graphics.emotes.lock()
+        && let Some(text) = line.get(text_start..)
+    {
+        images.extend(emotes.find(text).into_iter().map(|(range, url)| {
+            (
+                range.start + text_start..range.end + text_start,
+                url.to_string(),
+            )
+        }));
+    }
+    images.retain(|(_, key)| graphics.is_ready(key));
+    images
+}
+
+/// Border of a pane, highlighted when it has the focus in the split view
+fn pane_border_style(app: &TuiApp, pane: Pane) -> Style {
+    if app.layout.split && app.focused_pane == pane {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    }
+}
+
+/// Gifts or events pane of the split view, scrolled by its own offset
+fn render_side_pane(f: &mut Frame, app: &mut TuiApp, pane: Pane, area: Rect) {
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let visible_height = area.height.saturating_sub(2) as usize;
+    let messages: Vec<TuiMessage> = app
+        .get_messages()
+        .into_iter()
+        .filter(|msg| Pane::of(&msg.text) == pane && app.is_message_visible(&msg.text))
+        .collect();
+
+    let mut all_lines = Vec::new();
+    for msg in &messages {
+        let mut style = get_message_style(&msg.text);
+        if app.is_highlighted(&msg.text) {
+            style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+        }
+        for line_text in wrap_text(&msg.display_text(app.show_timestamps), inner_width) {
+            all_lines.push(Line::from(Span::styled(line_text, style)));
+        }
+    }
+
+    let max_offset = all_lines.len().saturating_sub(visible_height);
+    let offset = app
+        .side_scroll
+        .get(&pane)
+        .copied()
+        .unwrap_or(0)
+        .min(max_offset);
+    app.side_scroll.insert(pane, offset);
+    let start_line = all_lines.len().saturating_sub(visible_height + offset);
+    let visible_lines: Vec<Line> = all_lines
+        .into_iter()
+        .skip(start_line)
+        .take(visible_height)
+        .collect();
+
+    let name = match pane {
+        Pane::Chat => tr("Chat"),
+        Pane::Gifts => tr("Gifts"),
+        Pane::Events => tr("Events"),
+    };
+    let scroll_indicator = if offset == 0 {
+        tr("Auto-scroll")
+    } else {
+        tr("Paused")
+    };
+    let title = trf(
+        " {} ({}) | {} | Tab ",
+        &[&name, &messages.len(), &scroll_indicator],
+    );
+    let paragraph = Paragraph::new(visible_lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(pane_border_style(app, pane)),
+    );
+
+    f.render_widget(paragraph, area);
+}
+
 fn get_message_style(msg: &str) -> Style {
     if msg.starts_with("[Danmu]") {
         Style::default().fg(Color::Cyan)
     } else if msg.starts_with("[Gift]") {
         Style::default().fg(Color::Yellow)
+    } else if msg.starts_with("[SuperChat]") {
+        Style::default().fg(Color::LightRed)
+    } else if msg.starts_with("[Guard]") {
+        Style::default().fg(Color::LightMagenta)
+    } else if msg.starts_with("[Entry]") {
+        // Entrances of guards and other privileged viewers
+        Style::default().fg(Color::LightBlue)
+    } else if msg.starts_with("[Room]") {
+        Style::default().fg(Color::LightGreen)
     } else if msg.starts_with("[Raw]") {
         Style::default().fg(Color::Magenta)
     } else if msg.starts_with("[Unsupported") {
@@ -118,37 +694,37 @@ fn get_message_style(msg: &str) -> Style {
     }
 }
 
-fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
-    if max_width == 0 {
-        return vec![text.to_string()];
-    }
-
-    let mut lines = Vec::new();
-    let mut current_line = String::new();
-    let mut current_width = 0;
-
-    for ch in text.chars() {
-        let char_width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
-
-        if current_width + char_width > max_width && !current_line.is_empty() {
-            lines.push(current_line);
-            current_line = String::new();
-            current_width = 0;
-        }
-
-        current_line.push(ch);
-        current_width += char_width;
-    }
-
-    if !current_line.is_empty() {
-        lines.push(current_line);
-    }
-
-    if lines.is_empty() {
-        lines.push(String::new());
-    }
+/// Sender of a danmaku and the style of their name, by guard level or else by medal tier
+fn get_user_style(msg: &TuiMessage) -> Option<(String, Style)> {
+    let danmu = msg.source.as_ref()?.as_danmaku()?;
+    let color = match danmu.guard_level {
+        1 => Color::Rgb(0xff, 0x6d, 0x3b),
+        2 => Color::Rgb(0xc8, 0x6b, 0xff),
+        3 => Color::Rgb(0x4c, 0xa6, 0xff),
+        _ => medal_tier_color(danmu.medal?.level)?,
+    };
+    Some((
+        danmu.user.to_string(),
+        Style::default().fg(color).add_modifier(Modifier::BOLD),
+    ))
+}
 
-    lines
+/// Color of a fan medal by level tier (every four levels), following the web player
+fn medal_tier_color(level: u8) -> Option<Color> {
+    let color = match level {
+        0 => return None,
+        1..=4 => Color::Rgb(0x5c, 0x96, 0x8e),
+        5..=8 => Color::Rgb(0x5d, 0x7b, 0x9e),
+        9..=12 => Color::Rgb(0x8d, 0x7c, 0xa6),
+        13..=16 => Color::Rgb(0xbe, 0x66, 0x86),
+        17..=20 => Color::Rgb(0xc7, 0x9d, 0x24),
+        21..=24 => Color::Rgb(0x1a, 0x54, 0x4b),
+        25..=28 => Color::Rgb(0x06, 0x15, 0x4c),
+        29..=32 => Color::Rgb(0x2d, 0x08, 0x55),
+        33..=36 => Color::Rgb(0x7a, 0x04, 0x23),
+        _ => Color::Rgb(0xff, 0x61, 0x0b),
+    };
+    Some(color)
 }
 
 fn render_logs_panel(f: &mut Frame, app: &mut TuiApp, area: Rect) {
@@ -157,10 +733,10 @@ fn render_logs_panel(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     let visible_height = area.height.saturating_sub(2) as usize;
     let mut all_lines = Vec::new();
 
-    for log_msg in &logs {
-        let style = get_log_style(log_msg);
-        for line_text in wrap_text(log_msg, inner_width) {
-            all_lines.push((line_text, style));
+    for (log_idx, entry) in logs.iter().enumerate() {
+        let style = get_log_style(entry.level);
+        for line_text in wrap_text(&entry.text, inner_width) {
+            all_lines.push((line_text, style, log_idx));
         }
     }
 
@@ -172,38 +748,52 @@ fn render_logs_panel(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     };
 
     let start_line = app.set_rendered_lines(
-        all_lines.iter().map(|(text, _)| text.clone()).collect(),
+        all_lines.iter().map(|(text, _, _)| text.clone()).collect(),
+        all_lines.iter().map(|(_, _, idx)| *idx).collect(),
         start_line,
         visible_height,
     );
 
+    let query = app
+        .log_search
+        .as_ref()
+        .map(|query| query.to_ascii_lowercase());
     let visible_lines = all_lines
         .into_iter()
         .enumerate()
         .skip(start_line)
         .take(visible_height)
-        .map(|(idx, (line_text, style))| {
-            Line::from(Span::styled(
-                line_text,
-                style_for_line(app, idx, style, Color::LightBlue),
-            ))
+        .map(|(idx, (line_text, style, _))| {
+            let style = style_for_line(app, idx, style, Color::LightBlue);
+            match &query {
+                Some(query) => Line::from(highlight_matches(&line_text, query, style)),
+                None => Line::from(Span::styled(line_text, style)),
+            }
         })
         .collect::<Vec<_>>();
 
     let scroll_indicator = if app.visual_mode {
-        "VISUAL | j/k move | g/G jump | y copy | Esc cancel"
+        tr("VISUAL | j/k move | g/G jump | y copy | Esc cancel")
     } else if app.pane_cursor().is_some() {
-        "CURSOR | Up/Down move | Ctrl+Y visual from cursor"
+        tr("CURSOR | Up/Down move | Ctrl+Y visual from cursor")
     } else if app.log_auto_scroll {
-        "Auto-scroll"
+        tr("Auto-scroll")
     } else {
-        "Paused"
+        tr("Paused")
     };
 
-    let title = format!(
-        " Logs ({} entries) | {} | Ctrl+Y: visual | Ctrl+H: help | Ctrl+L: close ",
-        logs.len(),
-        scroll_indicator
+    let level_display = match app.log_level_filter {
+        log::LevelFilter::Trace => tr("ALL").to_string(),
+        level => format!("{}+", level),
+    };
+    let title = trf(
+        " Logs ({} entries, {}){} | {} | e/w/i/d/a: level | /: search | Ctrl+Y: visual | Ctrl+H: help | Ctrl+L: close ",
+        &[
+            &logs.len(),
+            &level_display,
+            &search_display(app),
+            &scroll_indicator,
+        ],
     );
 
     let paragraph = Paragraph::new(visible_lines)
@@ -218,29 +808,71 @@ fn render_logs_panel(f: &mut Frame, app: &mut TuiApp, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
-fn get_log_style(msg: &str) -> Style {
-    if msg.contains("[ERROR]") {
-        Style::default().fg(Color::Red)
-    } else if msg.contains("[WARN]") {
-        Style::default().fg(Color::Yellow)
-    } else if msg.contains("[INFO]") {
-        Style::default().fg(Color::Green)
-    } else if msg.contains("[DEBUG]") || msg.contains("[TRACE]") {
-        Style::default().fg(Color::DarkGray)
-    } else {
-        Style::default()
+/// Title segment for the search prompt or the active search
+fn search_display(app: &TuiApp) -> String {
+    match (&app.log_search_input, &app.log_search) {
+        (Some(input), _) => trf(" | Search: /{}_", &[input]),
+        (None, Some(query)) => trf(
+            " | /{} ({} matches, n/N)",
+            &[query, &app.log_search_matches()],
+        ),
+        (None, None) => String::new(),
+    }
+}
+
+/// Split a line into spans, highlighting ASCII case-insensitive matches of a lowercased query
+fn highlight_matches(line: &str, query: &str, style: Style) -> Vec<Span<'static>> {
+    let highlight = style.bg(Color::Yellow).fg(Color::Black);
+    let lower = line.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while !query.is_empty()
+        && let Some(found) = lower[pos..].find(query)
+    {
+        let start = pos + found;
+        let end = start + query.len();
+        if start > pos {
+            spans.push(Span::styled(line[pos..start].to_string(), style));
+        }
+        spans.push(Span::styled(line[start..end].to_string(), highlight));
+        pos = end;
+    }
+    if pos < line.len() || spans.is_empty() {
+        spans.push(Span::styled(line[pos..].to_string(), style));
+    }
+    spans
+}
+
+fn get_log_style(level: log::Level) -> Style {
+    match level {
+        log::Level::Error => Style::default().fg(Color::Red),
+        log::Level::Warn => Style::default().fg(Color::Yellow),
+        log::Level::Info => Style::default().fg(Color::Green),
+        log::Level::Debug | log::Level::Trace => Style::default().fg(Color::DarkGray),
     }
 }
 
 fn render_input_box(f: &mut Frame, app: &TuiApp, area: Rect) {
     let input_text = format!("> {}", app.input);
 
+    let title = if app.is_vim_normal() {
+        tr(" -- NORMAL -- (i: insert | j/k: move | gg/G: jump | /: search | Ctrl+C: exit) ")
+    } else if app.is_vim_insert() {
+        tr(" -- INSERT -- (Esc: normal | Enter: send) ")
+    } else {
+        tr(" Input (Ctrl+H: help | Ctrl+Y: visual | Ctrl+C: exit) ")
+    };
+    let border_color = if app.is_vim_normal() {
+        Color::DarkGray
+    } else {
+        Color::Green
+    };
     let paragraph = Paragraph::new(input_text.as_str())
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Input (Ctrl+H: help | Ctrl+Y: visual | Ctrl+C: exit) ")
-                .border_style(Style::default().fg(Color::Green)),
+                .title(title)
+                .border_style(Style::default().fg(border_color)),
         )
         .style(Style::default());
 
@@ -251,57 +883,85 @@ fn render_input_box(f: &mut Frame, app: &TuiApp, area: Rect) {
     let cursor_x = area.x + 1 + 2 + display_width as u16;
     let cursor_y = area.y + 1;
 
-    if !app.visual_mode && cursor_x < area.x + area.width.saturating_sub(1) {
+    if !app.visual_mode && !app.is_vim_normal() && cursor_x < area.x + area.width.saturating_sub(1)
+    {
         f.set_cursor_position((cursor_x, cursor_y));
     }
 }
 
 fn render_help_overlay(f: &mut Frame, app: &TuiApp) {
     let area = centered_rect(72, 72, f.area());
-    let lines = if app.show_logs {
+    let mut lines = if app.show_logs {
         vec![
-            Line::from("Key Map"),
+            Line::from(tr("Key Map")),
             Line::from(""),
-            Line::from("Ctrl+H   Toggle this help"),
-            Line::from("Up/Down  Pick start line"),
-            Line::from("Ctrl+Y   Enter visual mode from cursor"),
-            Line::from("j/k      Move visual selection"),
-            Line::from("g / G    Jump to top or bottom"),
-            Line::from("y        Copy selected lines"),
-            Line::from("Esc      Close help, cancel visual, or close logs"),
-            Line::from("Up/Down  Scroll logs normally"),
-            Line::from("PgUp/Dn  Scroll faster"),
-            Line::from("Home/End Jump to top or bottom"),
-            Line::from("Ctrl+C   Exit app"),
+            Line::from(tr("Ctrl+H   Toggle this help")),
+            Line::from(tr("Up/Down  Pick start line")),
+            Line::from(tr("Ctrl+Y   Enter visual mode from cursor")),
+            Line::from(tr("j/k      Move visual selection")),
+            Line::from(tr("g / G    Jump to top or bottom")),
+            Line::from(tr("y        Copy selected messages")),
+            Line::from(tr("Esc      Close help, cancel visual, or close logs")),
+            Line::from(tr("Up/Down  Scroll logs normally")),
+            Line::from(tr("PgUp/Dn  Scroll faster")),
+            Line::from(tr("Home/End Jump to top or bottom")),
+            Line::from(tr(
+                "e/w/i/d  Show errors, warnings, info, or debug and above",
+            )),
+            Line::from(tr("a        Show all levels")),
+            Line::from(tr("/        Search logs (Enter apply, Esc clear)")),
+            Line::from(tr("n / N    Next or previous match")),
+            Line::from(tr("Ctrl+C   Exit app")),
         ]
     } else {
         vec![
-            Line::from("Key Map"),
+            Line::from(tr("Key Map")),
             Line::from(""),
-            Line::from("Enter    Send input"),
-            Line::from("Ctrl+H   Toggle this help"),
-            Line::from("Up/Down  Pick start line"),
-            Line::from("Ctrl+Y   Enter visual mode from cursor"),
-            Line::from("j/k      Move visual selection"),
-            Line::from("g / G    Jump to top or bottom"),
-            Line::from("y        Copy selected lines"),
-            Line::from("Ctrl+R   Toggle raw messages"),
-            Line::from("Ctrl+L   Toggle logs panel"),
-            Line::from("Up/Down  Scroll messages normally"),
-            Line::from("PgUp/Dn  Scroll faster"),
-            Line::from("Left/Right Move input cursor"),
-            Line::from("Home/End Move input cursor"),
-            Line::from("Ctrl+Home Jump to top"),
-            Line::from("Ctrl+End Jump to bottom"),
-            Line::from("Esc      Close help, cancel visual, or quit"),
-            Line::from("Ctrl+C   Exit app"),
+            Line::from(tr("Enter    Send input")),
+            Line::from(tr("Alt+Up/Dn Recall sent input (Up/Dn while typing)")),
+            Line::from(tr("/help    List slash commands")),
+            Line::from(tr("Ctrl+H   Toggle this help")),
+            Line::from(tr("Up/Down  Pick start line")),
+            Line::from(tr("Ctrl+Y   Enter visual mode from cursor")),
+            Line::from(tr("Ctrl+U   Show info for the user under cursor")),
+            Line::from(tr("Ctrl+O   Inspect payload of the message under cursor")),
+            Line::from(tr("Ctrl+B   Toggle online rank sidebar")),
+            Line::from(tr("Ctrl+P   Pause/resume incoming messages")),
+            Line::from(tr("Ctrl+S   Split chat, gifts and events panes")),
+            Line::from(tr("Tab      Switch the pane scroll keys move")),
+            Line::from(tr("j/k      Move visual selection")),
+            Line::from(tr("g / G    Jump to top or bottom")),
+            Line::from(tr("y        Copy selected messages")),
+            Line::from(tr("Ctrl+R   Toggle raw messages")),
+            Line::from(tr("Ctrl+T   Toggle message timestamps")),
+            Line::from(tr("Ctrl+L   Toggle logs panel")),
+            Line::from(tr("Up/Down  Scroll messages normally")),
+            Line::from(tr("PgUp/Dn  Scroll faster")),
+            Line::from(tr("Left/Right Move input cursor")),
+            Line::from(tr("Home/End Move input cursor")),
+            Line::from(tr("Ctrl+Home Jump to top")),
+            Line::from(tr("Ctrl+End Jump to bottom")),
+            Line::from(tr("Esc      Close help, cancel visual, or quit")),
+            Line::from(tr("Ctrl+C   Exit app")),
         ]
     };
 
+    if app.keymap == Keymap::Vim && !app.show_logs {
+        lines.extend([
+            Line::from(""),
+            Line::from(tr("Vim keymap (normal mode)")),
+            Line::from(tr("j/k      Move cursor")),
+            Line::from(tr("gg / G   Jump to top or bottom")),
+            Line::from(tr("/ n N    Search messages, next or previous match")),
+            Line::from(tr("v        Enter visual mode")),
+            Line::from(tr("i or :   Type a message or a /command (Esc back)")),
+        ]);
+    }
+
     let title = if app.show_logs {
-        " Help - Logs "
+        tr(" Help - Logs ")
     } else {
-        " Help - Messages "
+        tr(" Help - Messages ")
     };
 
     let paragraph = Paragraph::new(lines)
@@ -317,6 +977,141 @@ fn render_help_overlay(f: &mut Frame, app: &TuiApp) {
     f.render_widget(paragraph, area);
 }
 
+fn render_user_popup(f: &mut Frame, app: &TuiApp) {
+    let Some((name, stats)) = app.user_popup_info() else {
+        return;
+    };
+
+    let area = match app.user_history {
+        Some(_) => centered_rect(60, 70, f.area()),
+        None => centered_rect(50, 40, f.area()),
+    };
+    let mut lines = match stats {
+        Some(stats) => {
+            let uid = if stats.uid > 0 {
+                stats.uid.to_string()
+            } else {
+                "-".to_string()
+            };
+            let medal = match &stats.medal {
+                Some(medal) => format!("{} Lv.{}", medal.name, medal.level),
+                None => "-".to_string(),
+            };
+            let guard = match guard_level_name(stats.guard_level) {
+                "" => "-",
+                level => level,
+            };
+            vec![
+                Line::from(trf("UID:      {}", &[&uid])),
+                Line::from(trf("Medal:    {}", &[&medal])),
+                Line::from(trf("Guard:    {}", &[&guard])),
+                Line::from(trf("Messages: {}", &[&stats.messages])),
+                Line::from(trf(
+                    "Gifts:    ¥{}",
+                    &[&format!("{:.1}", coins_to_rmb(stats.gift_value))],
+                )),
+            ]
+        }
+        None => vec![Line::from(tr("No activity this session"))],
+    };
+    if let Some(history) = &app.user_history {
+        lines.extend(user_history_lines(history));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(trf(" User - {} (Esc: close) ", &[&name]))
+                .border_style(Style::default().fg(Color::Cyan)),
+        )
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Lines of the stored history of a user in the user info popup
+fn user_history_lines(history: &UserHistory) -> Vec<Line<'static>> {
+    let time = |timestamp: u64, format: &str| {
+        chrono::DateTime::from_timestamp(timestamp as i64, 0)
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format(format)
+                    .to_string()
+            })
+            .unwrap_or_default()
+    };
+    let summary = &history.summary;
+    let Some(first_seen) = summary.first_seen else {
+        return vec![Line::from(""), Line::from(tr("No stored history"))];
+    };
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            tr("History"),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(trf(
+            "First seen: {}",
+            &[&time(first_seen, "%Y-%m-%d %H:%M")],
+        )),
+        Line::from(trf(
+            "Total:      {} messages, ¥{}",
+            &[
+                &summary.danmu_count,
+                &format!("{:.1}", coins_to_rmb(summary.gift_value)),
+            ],
+        )),
+    ];
+    // Super chats are in both lists
+    let gifts = history
+        .gifts
+        .iter()
+        .filter(|gift| gift.kind != "super_chat");
+    for message in history.messages.iter().chain(gifts) {
+        lines.push(Line::from(format!(
+            "  {} {}",
+            time(message.timestamp, "%m-%d %H:%M"),
+            message.text
+        )));
+    }
+    lines
+}
+
+fn render_inspector(f: &mut Frame, app: &TuiApp) {
+    let Some(message) = &app.inspector else {
+        return;
+    };
+
+    let area = centered_rect(80, 80, f.area());
+    let body = match &message.payload {
+        Some(payload) => serde_json::to_string_pretty(payload).unwrap_or_default(),
+        None => tr("No payload recorded for this message").to_string(),
+    };
+    let mut lines = vec![
+        Line::from(Span::styled(
+            message.display_text(true),
+            get_message_style(&message.text),
+        )),
+        Line::from(""),
+    ];
+    lines.extend(body.lines().map(|line| Line::from(line.to_string())));
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Payload (Up/Down: scroll | Esc: close) "))
+                .border_style(Style::default().fg(Color::Magenta)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((app.inspector_scroll, 0));
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let vertical = Layout::default()
         .direction(Direction::Vertical)
@@ -354,3 +1149,141 @@ fn style_for_line(app: &TuiApp, idx: usize, base: Style, cursor_color: Color) ->
 
     base
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::images::EmoteCatalog;
+    use crate::client::models::BiliMessage;
+    use crate::tui::buffer::MessageBuffer;
+    use crate::tui::graphics::GraphicsProtocol;
+    use ratatui::{Terminal, backend::TestBackend};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    const DOG: &str = "https://i0.hdslb.com/dog.png";
+
+    fn graphics() -> Graphics {
+        let emotes = EmoteCatalog::from_data(&json!({
+            "data": [{"emoticons": [{"emoji": "[dog]", "url": DOG}]}]
+        }));
+        Graphics::new(GraphicsProtocol::Kitty, Arc::new(Mutex::new(emotes)), true)
+    }
+
+    fn danmu(uid: u64, text: &str) -> TuiMessage {
+        TuiMessage::new(format!("[Danmu] alice: {}", text)).with_source(BiliMessage::Danmu {
+            user: "alice".to_string(),
+            text: text.to_string(),
+            uid,
+            guard_level: 0,
+            medal: None,
+        })
+    }
+
+    #[test]
+    fn test_line_images() {
+        let graphics = graphics();
+        graphics.set_image("uid:7", Some(vec![1]));
+        graphics.set_image(DOG, Some(vec![1]));
+        let msg = danmu(7, "hi [dog]");
+        assert_eq!(
+            line_images(&graphics, &msg, &msg.text, true),
+            vec![(0..7, "uid:7".to_string()), (18..23, DOG.to_string())]
+        );
+        // A wrapped line has no tag; its emotes start at the line start
+        assert_eq!(
+            line_images(&graphics, &msg, "[dog] more", false),
+            vec![(0..5, DOG.to_string())]
+        );
+        // An emote in the user name is not one
+        let named =
+            TuiMessage::new("[Danmu] [dog]: hi".to_string()).with_source(BiliMessage::Danmu {
+                user: "[dog]".to_string(),
+                text: "hi".to_string(),
+                uid: 0,
+                guard_level: 0,
+                medal: None,
+            });
+        assert!(line_images(&graphics, &named, &named.text, true).is_empty());
+    }
+
+    #[test]
+    fn test_line_images_fallback() {
+        let graphics = graphics();
+        // Failed downloads stay as text, as do messages without a source
+        graphics.set_image("uid:7", None);
+        graphics.set_image(DOG, None);
+        let msg = danmu(7, "hi [dog]");
+        assert!(line_images(&graphics, &msg, &msg.text, true).is_empty());
+        let plain = TuiMessage::new("[Danmu] alice: hi [dog]".to_string());
+        assert!(line_images(&graphics, &plain, &plain.text, true).is_empty());
+
+        // Without avatars the tag stays
+        let mut graphics = graphics;
+        graphics.avatars = false;
+        graphics.set_image("uid:7", Some(vec![1]));
+        assert!(line_images(&graphics, &msg, &msg.text, true).is_empty());
+    }
+
+    #[test]
+    fn test_message_list_image_placement() {
+        let buffer = Arc::new(Mutex::new(MessageBuffer::new()));
+        TuiApp::add_message(&buffer, danmu(7, "hi [dog]"));
+        TuiApp::add_message(&buffer, danmu(8, "[dog]"));
+        let mut app = TuiApp::new(Arc::clone(&buffer), "1".to_string());
+        app.show_timestamps = false;
+        let graphics = graphics();
+        graphics.set_image("uid:7", Some(vec![1]));
+        graphics.set_image(DOG, Some(vec![1]));
+        graphics.set_image("uid:8", None);
+        app.set_graphics(graphics);
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 6)).unwrap();
+        terminal
+            .draw(|f| render_message_list(f, &mut app, f.area()))
+            .unwrap();
+        let key = |placement: &ImagePlacement| (placement.key.clone(), placement.x, placement.y);
+        let mut placements: Vec<_> = app.image_placements.iter().map(key).collect();
+        placements.sort();
+        // The second sender's avatar failed to download and keeps its tag
+        assert_eq!(
+            placements,
+            vec![
+                (DOG.to_string(), 16, 2),
+                (DOG.to_string(), 19, 1),
+                ("uid:7".to_string(), 1, 1),
+            ]
+        );
+        let row = |y: u16| {
+            (1..39)
+                .map(|x| terminal.backend().buffer()[(x, y)].symbol().to_string())
+                .collect::<String>()
+        };
+        assert!(row(1).starts_with("        alice: hi      "));
+        assert!(row(2).starts_with("[Danmu] alice:      "));
+    }
+
+    #[test]
+    fn test_highlight_matches() {
+        let style = Style::default();
+        let highlight = style.bg(Color::Yellow).fg(Color::Black);
+        let spans = highlight_matches("WARN retry, warn again", "warn", style);
+        assert_eq!(
+            spans,
+            vec![
+                Span::styled("WARN", highlight),
+                Span::styled(" retry, ", style),
+                Span::styled("warn", highlight),
+                Span::styled(" again", style),
+            ]
+        );
+        assert_eq!(
+            highlight_matches("", "warn", style),
+            vec![Span::styled("", style)]
+        );
+        assert_eq!(
+            highlight_matches("info", "", style),
+            vec![Span::styled("info", style)]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// src/tui/wrap.rs
+//! Text wrapping for the message and log panes
+
+use crate::tui::message::TuiMessage;
+use std::collections::{HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Wrapped lines of buffered messages, so each message is only wrapped once per width
+#[derive(Debug, Clone, Default)]
+pub struct WrapCache {
+    width: usize,
+    show_timestamps: bool,
+    lines: HashMap<u64, Vec<String>>,
+}
+
+impl WrapCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the wrap width and timestamp display, dropping the cache if either changed
+    pub fn prepare(&mut self, width: usize, show_timestamps: bool) {
+        if width != self.width || show_timestamps != self.show_timestamps {
+            self.lines.clear();
+            self.width = width;
+            self.show_timestamps = show_timestamps;
+        }
+    }
+
+    /// Forget messages that are no longer in `messages`
+    pub fn retain(&mut self, messages: &[TuiMessage]) {
+        if self.lines.len() > messages.len() {
+            let ids: HashSet<u64> = messages.iter().map(|msg| msg.id).collect();
+            self.lines.retain(|id, _| ids.contains(id));
+        }
+    }
+
+    /// Wrapped lines of a message, wrapping it on first use
+    pub fn get(&mut self, msg: &TuiMessage) -> &[String] {
+        let (width, show_timestamps) = (self.width, self.show_timestamps);
+        self.lines
+            .entry(msg.id)
+            .or_insert_with(|| wrap_text(&msg.display_text(show_timestamps), width))
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// Wrap text to `max_width` terminal columns
+pub fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
+    if max_width == 0 {
+        return vec![text.to_string()];
+    }
+
+    let mut lines = Vec::new();
+    let mut current_line = String::new();
+    let mut current_width = 0;
+
+    // Break between grapheme clusters so emoji sequences and combining marks stay whole
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+
+        if current_width + grapheme_width > max_width && !current_line.is_empty() {
+            lines.push(current_line);
+            current_line = String::new();
+            current_width = 0;
+        }
+
+        current_line.push_str(grapheme);
+        current_width += grapheme_width;
+    }
+
+    if !current_line.is_empty() {
+        lines.push(current_line);
+    }
+
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_text_keeps_graphemes_whole() {
+        // Family emoji (ZWJ sequence), flag, and a letter with a combining accent
+        let text = "[Danmu] a: 👨‍👩‍👧🇨🇳e\u{301}好";
+        let lines = wrap_text(text, 12);
+        assert_eq!(lines.concat(), text);
+        for line in &lines {
+            assert!(line.width() <= 12, "{:?} is too wide", line);
+        }
+        assert!(lines.iter().any(|line| line.contains("👨‍👩‍👧")));
+        assert!(lines.iter().any(|line| line.contains("e\u{301}")));
+    }
+
+    #[test]
+    fn test_wrap_cache() {
+        let first = TuiMessage::from("[Danmu] a: hello world");
+        let second = TuiMessage::from("[Danmu] b: hi");
+        let mut cache = WrapCache::new();
+        cache.prepare(10, false);
+        assert_eq!(cache.get(&first), ["[Danmu] a:", " hello wor", "ld"]);
+        assert_eq!(cache.get(&second).len(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // Evicted messages are dropped, a new width re-wraps everything
+        cache.retain(std::slice::from_ref(&second));
+        assert_eq!(cache.len(), 1);
+        cache.prepare(20, false);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&first), ["[Danmu] a: hello wor", "ld"]);
+    }
+
+    #[test]
+    fn test_wrap_text_emoji_heavy() {
+        let lines = wrap_text("😀😀😀😀😀", 4);
+        assert_eq!(lines, vec!["😀😀", "😀😀", "😀"]);
+        assert_eq!(wrap_text("", 4), vec![String::new()]);
+    }
+}